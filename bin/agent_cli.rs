@@ -0,0 +1,174 @@
+//! Binary part for running as a remote agent, so another machine can drive benchmarks on this one.
+//!
+//! Connections are handled one at a time, so two clients never benchmark at the same time and skew each other's results. See [`benchmarker::bencher::remote`] for the protocol.
+//!
+//! **NB:** the agent runs whatever binary it is asked to, so only listen on addresses you trust, and use a `--token`.
+
+use benchmarker::bencher::{
+    remote::{RemoteMessage, RemoteRequest, DEFAULT_AGENT_PORT, STOP_LINE},
+    timing::SelfReportedTiming,
+    Runner,
+};
+use clap::Parser;
+use color_eyre::eyre::WrapErr;
+use owo_colors::OwoColorize;
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{Shutdown, TcpListener, TcpStream},
+    sync::mpsc::channel,
+};
+
+/// The CLI args for running an agent
+#[derive(Clone, Debug, Parser)] //struct for CLI args which can be parsed/cloned/printed
+pub struct AgentCLIArgs {
+    ///The address to listen on - use `0.0.0.0` to allow other machines to connect
+    #[arg(short, long, default_value_t = String::from("127.0.0.1"))]
+    address: String,
+    ///The port to listen on
+    #[arg(short, long, default_value_t = DEFAULT_AGENT_PORT)]
+    port: u16,
+    ///A token that clients have to send to be allowed to run anything
+    #[arg(short, long)]
+    token: Option<String>,
+}
+
+///Run the agent - this only stops when the process gets killed
+#[instrument(skip(token))]
+pub fn run(
+    AgentCLIArgs {
+        address,
+        port,
+        token,
+    }: AgentCLIArgs,
+) -> color_eyre::Result<()> {
+    let listener =
+        TcpListener::bind((address.as_str(), port)).wrap_err_with(|| format!("unable to listen on {address}:{port}"))?;
+    println!("{} on {address}:{port}", "Agent listening".bold());
+    if token.is_none() {
+        println!(
+            "{}: no token set, so anyone who can connect can run anything",
+            "Warning".yellow().bold()
+        );
+    }
+
+    for stream in listener.incoming() {
+        //handle connections one at a time, so benchmarks don't overlap
+        match stream {
+            Ok(stream) => {
+                if let Err(e) = handle_connection(stream, token.as_deref()) {
+                    warn!(%e, "Error handling connection");
+                }
+            }
+            Err(e) => warn!(%e, "Error accepting connection"),
+        }
+    }
+    Ok(())
+}
+
+///Sends one message down the stream as a line of JSON
+fn send(stream: &mut TcpStream, message: &RemoteMessage) -> io::Result<()> {
+    writeln!(stream, "{}", serde_json::to_string(message)?)
+}
+
+///Serves one connection, then shuts the socket down - the stop listener holds a clone of the stream, so just dropping it wouldn't close the connection and the client would never see the end of the results
+fn handle_connection(mut stream: TcpStream, token: Option<&str>) -> io::Result<()> {
+    let result = serve_request(&mut stream, token);
+    let shutdown = stream.shutdown(Shutdown::Both); //this also wakes the stop listener up, so its thread finishes
+    result.and(shutdown)
+}
+
+///Reads a request from the stream, runs it, and streams back the results
+fn serve_request(stream: &mut TcpStream, token: Option<&str>) -> io::Result<()> {
+    let peer = stream.peer_addr()?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let RemoteRequest {
+        token: request_token,
+        binary,
+        cli_args,
+        runs,
+        warmup,
+        timeout,
+        randomize_environment,
+        self_reported_timing,
+        pipeline,
+        ram_copy,
+        shell,
+        env,
+    } = match serde_json::from_str(&request_line) {
+        Ok(r) => r,
+        Err(e) => return send(stream, &RemoteMessage::Error(format!("Invalid request: {e}"))),
+    };
+
+    if token.is_some() && request_token.as_deref() != token {
+        warn!(%peer, "Rejected request with wrong token");
+        return send(stream, &RemoteMessage::Error("Invalid token".into()));
+    }
+
+    let self_reported_timing = match self_reported_timing
+        .map(|(pattern, unit)| SelfReportedTiming::new(&pattern, unit))
+        .transpose()
+    {
+        Ok(t) => t,
+        Err(e) => return send(stream, &RemoteMessage::Error(format!("Invalid timing regex: {e}"))),
+    };
+
+    println!(
+        "{} {} {} for {peer}",
+        "Benchmark:".bold(),
+        binary.display().italic(),
+        cli_args.join(" ").italic()
+    );
+
+    let (stop_tx, stop_rx) = channel();
+    std::thread::Builder::new()
+        .name("agent_stop_listener".into())
+        .spawn(move || {
+            //stop if the client asks us to, or disconnects - either way, this thread ends when the connection does
+            let mut line = String::new();
+            loop {
+                line.clear();
+                match reader.read_line(&mut line) {
+                    Ok(0) | Err(_) => break,
+                    Ok(_) if line.trim() == STOP_LINE => break,
+                    Ok(_) => {}
+                }
+            }
+            let _ = stop_tx.send(()); //if the runner has already finished, we don't care
+        })?;
+
+    let (timeout_tx, timeout_rx) = channel();
+    let runner = Runner::new(binary, cli_args, runs, Some(stop_rx), warmup, false)
+        .timeout(timeout)
+        .randomize_environment(randomize_environment)
+        .self_reported_timing(self_reported_timing)
+        .pipeline(pipeline)
+        .ram_copy(ram_copy)
+        .shell(shell)
+        .env(env)
+        .timeout_sender(Some(timeout_tx));
+    let (handle, rx, error_rx) = match runner.start() {
+        Ok(r) => r,
+        Err(e) => return send(stream, &RemoteMessage::Error(format!("Unable to start runner: {e}"))),
+    };
+
+    for measurement in rx {
+        //this finishes when the runner does, as that drops the sender - and if we fail to send, the client is gone so dropping rx stops the runner
+        send(stream, &RemoteMessage::Measurement(measurement))?;
+    }
+    if handle.join().is_err() {
+        return send(stream, &RemoteMessage::Error("The runner thread panicked".into())); //tell the client rather than taking the whole agent down
+    }
+
+    for elapsed in timeout_rx.try_iter() {
+        send(stream, &RemoteMessage::TimedOut(elapsed))?; //the client only counts these, so they can all go at the end
+    }
+    for error in error_rx.try_iter() {
+        send(stream, &RemoteMessage::Error(error.to_string()))?;
+    }
+
+    println!("{} for {peer}", "Finished".bold());
+    Ok(())
+}
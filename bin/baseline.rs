@@ -0,0 +1,106 @@
+//! Shows a finished session against a baseline in an in-app plot, so the GUI gives a before/after comparison without opening a browser.
+//!
+//! The baseline is the first trace from a CSV or Google Benchmark JSON file. Both get drawn as density curves (like `--plot-kind density` in the exporter), with a line at each mean and the change between them written on the plot.
+
+use benchmarker::{
+    compare::{compare, DEFAULT_SIGNIFICANCE_LEVEL},
+    diagnostics::KernelDensity,
+    io::import_traces_with_tags,
+};
+use crate::theme::Theme;
+use color_eyre::eyre::{eyre, WrapErr};
+use eframe::egui::{
+    plot::{Legend, Line, LineStyle, Plot, PlotPoint, PlotPoints, Text, VLine},
+    Ui,
+};
+use std::path::PathBuf;
+
+///How many points to draw each density curve with
+const CURVE_POINTS: usize = 200;
+
+///A trace loaded from a file to compare a session against
+#[derive(Clone, Debug)]
+pub struct Baseline {
+    ///The file the trace came from
+    pub file: PathBuf,
+    ///The name of the trace
+    pub name: String,
+    ///The runs in microseconds
+    pub runs: Vec<u128>,
+}
+
+impl Baseline {
+    ///Loads the first trace from a file to use as the baseline
+    ///
+    /// # Errors
+    ///
+    /// Can fail if we fail to read the file, or it doesn't have any traces in it
+    pub fn load(file: PathBuf) -> color_eyre::Result<Self> {
+        let (name, runs, _) = import_traces_with_tags(&file)
+            .wrap_err_with(|| format!("unable to load a baseline from {}", file.display()))?
+            .into_iter()
+            .next()
+            .ok_or_else(|| eyre!("{} doesn't have any traces in it", file.display()))?;
+
+        Ok(Self { file, name, runs })
+    }
+
+    ///Shows how the session's runs compare to the baseline, with both distributions in a plot - the baseline is dashed, so the two can be told apart without their colours
+    #[allow(clippy::cast_precision_loss)]
+    pub fn display(&self, ui: &mut Ui, runs: &[u128], theme: Theme) {
+        ui.label(format!("Baseline: {} from {}", self.name, self.file.display()));
+        match compare(&self.runs, runs, DEFAULT_SIGNIFICANCE_LEVEL) {
+            Some((verdict, test)) => ui.label(format!("Against the baseline: {verdict} (p = {:.4}, t = {:.3})", test.p_value, test.t)),
+            None => ui.label("Not enough runs to test against the baseline."),
+        };
+
+        let (Some(baseline_density), Some(session_density)) = (KernelDensity::new(&self.runs), KernelDensity::new(runs)) else {
+            ui.label("Not enough distinct runs to plot.");
+            return;
+        };
+        //one range for both curves, going a few kernels past the ends so they get back down to 0
+        let low = [&baseline_density, &session_density]
+            .into_iter()
+            .map(|density| 3.0f64.mul_add(-density.bandwidth(), density.percentile(0.01)))
+            .fold(f64::INFINITY, f64::min)
+            .max(0.0);
+        let high = [&baseline_density, &session_density]
+            .into_iter()
+            .map(|density| 3.0f64.mul_add(density.bandwidth(), density.percentile(0.99)))
+            .fold(f64::NEG_INFINITY, f64::max);
+        let baseline_curve = baseline_density.curve(low, high, CURVE_POINTS);
+        let session_curve = session_density.curve(low, high, CURVE_POINTS);
+        let peak = baseline_curve
+            .iter()
+            .chain(&session_curve)
+            .map(|(_, density)| *density)
+            .fold(0.0, f64::max);
+
+        let mean = |runs: &[u128]| runs.iter().map(|run| *run as f64).sum::<f64>() / runs.len() as f64;
+        let (baseline_mean, session_mean) = (mean(&self.runs), mean(runs));
+        let delta = format!("{:+.2}%", (session_mean - baseline_mean) / baseline_mean * 100.0);
+
+        let text_colour = ui.visuals().strong_text_color();
+        Plot::new("baseline_plot")
+            .legend(Legend::default())
+            .height(250.0)
+            .allow_scroll(false)
+            .show(ui, |plot_ui| {
+                let to_points = |curve: Vec<(f64, f64)>| curve.into_iter().map(<[f64; 2]>::from).collect::<PlotPoints>();
+                plot_ui.line(
+                    Line::new(to_points(baseline_curve))
+                        .color(theme.baseline())
+                        .style(LineStyle::dashed_loose())
+                        .name(format!("Baseline ({})", self.name)),
+                );
+                plot_ui.line(Line::new(to_points(session_curve)).color(theme.session()).name("This session"));
+                plot_ui.vline(VLine::new(baseline_mean).color(theme.baseline()).style(LineStyle::dashed_loose()).name("Baseline mean"));
+                plot_ui.vline(VLine::new(session_mean).color(theme.session()).name("Session mean"));
+                plot_ui.text(
+                    Text::new(PlotPoint::new(f64::midpoint(baseline_mean, session_mean), peak * 1.05), delta) //between the means, just above the curves
+                        .color(text_colour),
+                );
+            });
+        ui.label("Time (µs) against density - each curve has an area of 1, so sessions with different numbers of runs are still easy to compare.");
+    }
+}
@@ -0,0 +1,67 @@
+//! Binary part for running as a Cargo subcommand, like `cargo precipice --bin my_program -- args`.
+//!
+//! Cargo runs `cargo-precipice precipice ...` for `cargo precipice ...`, which passes everything on to `precipice cargo ...`. The Cargo-style arguments (`--bin` and `--manifest-path`) get turned into the runner CLI's `--cargo-bin` and `--cargo-project`, and everything else goes to the runner CLI as it is - so the binary gets built in release mode and benchmarked, just like `runner-cli --cargo-bin`.
+
+use crate::{
+    failure,
+    runner_cli::{self, FullCLIArgs},
+};
+use benchmarker::exit_reason::ExitReason;
+use clap::Parser;
+use std::path::Path;
+
+/// The CLI args for running as a Cargo subcommand
+#[derive(Clone, Debug, Parser)] //struct for CLI args which can be parsed/cloned/printed
+pub struct CargoCLIArgs {
+    ///`--bin` for the binary to build and benchmark, `--manifest-path` for the project if it isn't in the current directory, then any runner CLI options, then the binary's arguments after a `--`
+    #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+    args: Vec<String>,
+}
+
+///Turns Cargo-style arguments into runner CLI arguments, leaving anything after a `--` alone
+fn runner_args(args: Vec<String>) -> color_eyre::Result<Vec<String>> {
+    let mut runner_args = vec!["precipice cargo".to_string()]; //the name clap shows in errors
+    let mut found_bin = false;
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        let (flag, value) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag.to_string(), Some(value.to_string())),
+            _ => (arg.clone(), None),
+        };
+
+        match flag.as_str() {
+            "--" => {
+                runner_args.push(arg);
+                runner_args.extend(args.by_ref()); //the binary's own args, which could be anything
+            }
+            "--bin" | "--manifest-path" => {
+                let Some(value) = value.or_else(|| args.next()) else {
+                    return Err(failure(ExitReason::InvalidArguments, format!("{flag} needs a value")));
+                };
+                if flag == "--bin" {
+                    found_bin = true;
+                    runner_args.extend(["--cargo-bin".to_string(), value]);
+                } else {
+                    let project = Path::new(&value).parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or_else(|| Path::new("."));
+                    runner_args.extend(["--cargo-project".to_string(), project.display().to_string()]);
+                }
+            }
+            _ => runner_args.push(arg),
+        }
+    }
+
+    if !found_bin {
+        return Err(failure(
+            ExitReason::InvalidArguments,
+            "need a binary to build and benchmark, like `cargo precipice --bin my_program`",
+        ));
+    }
+    Ok(runner_args)
+}
+
+///Run the Cargo subcommand
+#[instrument]
+pub fn run(CargoCLIArgs { args }: CargoCLIArgs) -> color_eyre::Result<()> {
+    runner_cli::run(FullCLIArgs::parse_from(runner_args(args)?))
+}
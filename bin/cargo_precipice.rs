@@ -0,0 +1,34 @@
+#![warn(
+    clippy::all,
+    clippy::pedantic,
+    clippy::nursery,
+    clippy::missing_docs_in_private_items
+)]
+//! This is `cargo-precipice` - it lets Cargo find precipice as `cargo precipice`, and hands everything on to `precipice cargo`
+
+use std::{
+    env::{args_os, consts::EXE_SUFFIX, current_exe},
+    path::PathBuf,
+    process::{exit, Command},
+};
+
+fn main() {
+    let precipice = current_exe()
+        .ok()
+        .map(|exe| exe.with_file_name(format!("precipice{EXE_SUFFIX}")))
+        .filter(|precipice| precipice.exists()) //installed next to us by `cargo install`
+        .unwrap_or_else(|| PathBuf::from("precipice")); //or else hopefully on the PATH
+
+    let mut args = args_os().skip(1).peekable();
+    if args.peek().is_some_and(|arg| arg == "precipice") {
+        args.next(); //cargo passes the subcommand name first
+    }
+
+    match Command::new(&precipice).arg("cargo").args(args).status() {
+        Ok(status) => exit(status.code().unwrap_or(1)),
+        Err(e) => {
+            eprintln!("Unable to run {}: {e}", precipice.display());
+            exit(1);
+        }
+    }
+}
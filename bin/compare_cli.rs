@@ -0,0 +1,274 @@
+//! Binary part for comparing two builds of the same binary in a CLI - eg. to see whether an optimisation helped.
+//!
+//! The runs of both binaries are interleaved (alternating which goes first each round), so anything that changes over the session - like the machine heating up, or something starting in the background - affects both equally. At the end, we run a significance test and export both traces to one file.
+
+use benchmarker::{
+    bencher::{calculate_mean_standard_deviation, multi::MultiRunner, RunMeasurement, RunnerError, DEFAULT_RUNS},
+    compare::{compare, effect_size, Verdict, DEFAULT_SIGNIFICANCE_LEVEL},
+    debug_build::debug_build_warning,
+    environment::check_environment,
+    exit_reason::ExitReason,
+    format::DurationFormat,
+    io::ExportType,
+    webhook::{Webhook, WebhookEvent},
+};
+use crate::{
+    failure,
+    runner_cli::{DurationFormatArgs, LockArgs},
+};
+use clap::Parser;
+use color_eyre::eyre::{eyre, WrapErr};
+use indicatif::{ProgressBar, ProgressStyle};
+use owo_colors::OwoColorize;
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::channel,
+        Arc,
+    },
+    time::Duration,
+};
+
+///The trace name for the `--before` binary
+const BEFORE_TRACE_NAME: &str = "before";
+///The trace name for the `--after` binary
+const AFTER_TRACE_NAME: &str = "after";
+
+/// The CLI args for comparing two binaries
+#[derive(Clone, Debug, Parser)] //struct for CLI args which can be parsed/cloned/printed
+pub struct CompareCLIArgs {
+    ///The binary from before the change
+    #[arg(long)]
+    before: PathBuf,
+    ///The binary from after the change
+    #[arg(long)]
+    after: PathBuf,
+    ///The CLI arguments to pass to both binaries, after a `--` - these get passed on exactly as they are, like with the runner
+    #[arg(last = true)]
+    cli_args: Vec<String>,
+    ///The number of runs for each binary (excluding warm-up runs)
+    #[arg(short, long, default_value_t = DEFAULT_RUNS)]
+    runs: usize,
+    ///The number of warmup runs for each binary before the measured ones
+    #[arg(short = 'w', long = "warmup", alias = "no-warmup-runs", value_name = "N", default_value_t = 0)]
+    no_warmup_runs: u8,
+    ///The significance level for deciding whether there's a difference
+    #[arg(long, default_value_t = DEFAULT_SIGNIFICANCE_LEVEL)]
+    significance_level: f64,
+    ///How long a single run can take in milliseconds, before it gets killed and skipped
+    #[arg(long)]
+    timeout_ms: Option<u64>,
+    ///How to export the data - a csv with the microsecond values, an HTML graph, or a Vega-Lite spec
+    #[arg(value_enum, short = 't', long, default_value_t = ExportType::CSV)]
+    export_ty: ExportType,
+    ///The file to export both traces to, without extension
+    #[arg(short = 'f', long, default_value_t = String::from("comparison"))]
+    export_out_file: String,
+    ///Whether or not we should refuse to start if the machine looks unfit for benchmarking (eg. on battery, or with frequency scaling)
+    #[arg(long, default_value_t = false)]
+    abort_on_environment_warnings: bool,
+    ///A webhook URL (eg. for Slack, Discord or Matrix) to notify when the comparison finishes, with the `regression` event if the after binary is slower. This needs `curl`
+    #[arg(long)]
+    webhook_url: Option<String>,
+    ///The JSON payload for the webhook, with placeholders like `{message}` and `{event}` - see the README
+    #[arg(long, requires = "webhook_url")]
+    webhook_template: Option<String>,
+    ///Only fire the webhook if the after binary is slower
+    #[arg(long, requires = "webhook_url", default_value_t = false)]
+    webhook_only_on_regression: bool,
+    ///How to write the times for each binary
+    #[command(flatten)]
+    duration_format: DurationFormatArgs,
+    ///Whether to stop other sessions running at the same time
+    #[command(flatten)]
+    lock: LockArgs,
+}
+
+///Run the comparison CLI
+#[instrument]
+#[allow(clippy::needless_pass_by_value)]
+pub fn run(
+    CompareCLIArgs {
+        before,
+        after,
+        cli_args,
+        runs,
+        no_warmup_runs,
+        significance_level,
+        timeout_ms,
+        export_ty,
+        export_out_file,
+        abort_on_environment_warnings,
+        webhook_url,
+        webhook_template,
+        webhook_only_on_regression,
+        duration_format,
+        lock,
+    }: CompareCLIArgs,
+) -> color_eyre::Result<()> {
+    let duration_format = DurationFormat::from(duration_format);
+
+    println!(
+        "{} {} vs {}",
+        "Comparing:".bold(),
+        before.display().italic(),
+        after.display().italic()
+    );
+
+    for binary in [&before, &after] {
+        if let Some(warning) = debug_build_warning(binary) {
+            println!("{}: {warning}", "Warning".yellow().bold());
+        }
+    }
+
+    let environment_warnings = check_environment(); //check that the machine is fit for benchmarking
+    for warning in &environment_warnings {
+        println!("{}: {warning}", "Warning".yellow().bold());
+    }
+    if abort_on_environment_warnings && !environment_warnings.is_empty() {
+        //if the user wants to be strict about it, stop before we start
+        return Err(failure(ExitReason::AssertionFailed, "aborting due to environment warnings"));
+    }
+    let _lock = lock.acquire()?; //held until we're done, so nothing else benchmarks alongside us
+
+    let (stop_tx, stop_rx) = channel();
+    let (handle, rx, error_rx) = MultiRunner::new(
+        vec![(before, cli_args.clone()), (after, cli_args)],
+        runs,
+        Some(stop_rx),
+        no_warmup_runs,
+    )
+    .timeout(timeout_ms.map(Duration::from_millis))
+    .start()
+    .wrap_err("unable to start the runner")?; //interleaves the runs, alternating which goes first each round
+
+    let stopped = Arc::new(AtomicBool::new(false));
+    {
+        let stopped = stopped.clone();
+        ctrlc::set_handler(move || {
+            if stopped.swap(true, Ordering::SeqCst) {
+                //if the user has already asked nicely, they want out right now
+                std::process::exit(ExitReason::Interrupted.code().into());
+            }
+            let _ = stop_tx.send(()); //the runner kills the in-flight run - if it has already finished, we don't care
+        })
+        .wrap_err("unable to set the Ctrl-C handler")?;
+    }
+
+    let progress_bar = ProgressBar::new((runs * 2) as u64);
+    progress_bar.set_style(
+        ProgressStyle::with_template(
+            "{spinner} Elapsed: [{elapsed_precise}], ETA: [{eta_precise}] {bar:40.cyan/blue} {pos:>7}/{len:7}",
+        )
+        .unwrap()
+        .progress_chars("##-"),
+    );
+
+    let mut before_runs = vec![];
+    let mut after_runs = vec![];
+    let mut failed_runs = 0;
+    let mut record = |measurement: RunMeasurement| {
+        let found_runs = if measurement.command == 0 { &mut before_runs } else { &mut after_runs };
+        found_runs.push(measurement.wall.as_micros());
+        if measurement.exit_status != Some(0) {
+            failed_runs += 1;
+        }
+    };
+    for measurement in &rx {
+        //block until each run comes in rather than spinning on the machine being measured - this finishes when the runner is done and drops its sender
+        record(measurement);
+        progress_bar.inc(1);
+    }
+    handle.join().map_err(|_| eyre!("the runner thread panicked"))?;
+    let errors: Vec<RunnerError> = error_rx.try_iter().collect();
+
+    if stopped.load(Ordering::SeqCst) {
+        progress_bar.abandon_with_message("Stopped by User");
+    } else {
+        progress_bar.finish_and_clear();
+    }
+    println!();
+
+    for error in &errors {
+        eprintln!("{}: {error}", "Error".red().bold());
+    }
+
+    for (name, found_runs) in [(BEFORE_TRACE_NAME, &before_runs), (AFTER_TRACE_NAME, &after_runs)] {
+        if let Some((mean, standard_deviation)) = calculate_mean_standard_deviation(found_runs) {
+            println!(
+                "{}: {} ± {} : {}",
+                format!("{name:<6}").bold(),
+                duration_format.duration(mean).bright_green(),
+                duration_format.duration(standard_deviation).bright_green(),
+                found_runs.len().bright_white(),
+            );
+        }
+    }
+
+    let comparison = compare(&before_runs, &after_runs, significance_level);
+    match comparison {
+        Some((verdict, test)) => {
+            let verdict_text = format!("After is {verdict}");
+            let verdict_text = match verdict {
+                Verdict::Faster(_) => verdict_text.bright_green().to_string(),
+                Verdict::Slower(_) => verdict_text.bright_red().to_string(),
+                Verdict::NoDifference => verdict.to_string().bright_white().to_string(),
+            };
+            println!(
+                "{}: {verdict_text} (p = {:.4}, t = {:.3})",
+                "Verdict".bold(),
+                test.p_value,
+                test.t
+            );
+        }
+        None => println!("{}", "Not enough runs to compare.".yellow()),
+    }
+    if let Some(effect) = effect_size(&before_runs, &after_runs) {
+        //a tiny change can still be significant with enough runs, so show how big it actually is
+        println!("{}: {effect}", "Effect size".bold());
+    }
+
+    if let Some(url) = webhook_url {
+        let (event, message) = match comparison {
+            Some((verdict @ Verdict::Slower(_), test)) => (
+                WebhookEvent::Regression,
+                format!("Regression in {export_out_file}: after is {verdict} (p = {:.4})", test.p_value),
+            ),
+            Some((verdict, test)) => (
+                WebhookEvent::Finished,
+                format!("{export_out_file} finished: after is {verdict} (p = {:.4})", test.p_value),
+            ),
+            None => (WebhookEvent::Finished, format!("{export_out_file} finished without enough runs to compare")),
+        };
+        if event == WebhookEvent::Regression || !webhook_only_on_regression {
+            if let Err(e) = Webhook::new(url, webhook_template).fire(event, &export_out_file, &message, &after_runs) {
+                eprintln!("{}: unable to fire webhook: {e}", "Warning".yellow().bold());
+            }
+        }
+    }
+
+    let n = export_ty
+        .export_traces(
+            export_out_file.clone(),
+            vec![
+                (BEFORE_TRACE_NAME.to_string(), before_runs),
+                (AFTER_TRACE_NAME.to_string(), after_runs),
+            ],
+        )
+        .wrap_err(ExitReason::ExportFailed)
+        .wrap_err_with(|| format!("unable to export to {export_out_file} as {export_ty:?}"))?;
+    trace!(?n, "Finished exporting");
+
+    //everything has still been exported, but scripts should know that something went wrong
+    if stopped.load(Ordering::SeqCst) {
+        return Err(ExitReason::Interrupted.into());
+    }
+    if !errors.is_empty() {
+        return Err(failure(ExitReason::BenchmarkFailed, "the runner stopped early"));
+    }
+    if failed_runs > 0 {
+        return Err(failure(ExitReason::BenchmarkFailed, format!("{failed_runs} runs exited unsuccessfully")));
+    }
+    Ok(())
+}
@@ -1,23 +1,46 @@
 //! Makes an optionally ordered list of things to be displayed in an EGUI window
 
-use eframe::egui::{ScrollArea, Ui};
+use eframe::egui::{Color32, ScrollArea, Ui};
 use std::{
     fmt::Debug,
-    ops::{Deref, DerefMut},
+    ops::{Deref, DerefMut, Range},
     vec::IntoIter,
 };
 
-///An enum to represent a change in a list item
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
-pub enum ChangeType<T> {
-    ///An item was removed
-    Removed(T),
-    ///An item was reordered
-    Reordered,
+///An enum to represent a change in a list item. The removed items themselves can be got from [`EguiList::take_removed`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ChangeType {
+    ///The item at this index was removed
+    Removed(usize),
+    ///Several items were removed at once, from the bulk actions - these are their indices from before the removal, in order
+    RemovedMultiple(Vec<usize>),
+    ///The items at `from` and `to` swapped places
+    Reordered {
+        ///The index the item was moved from
+        from: usize,
+        ///The index the item was moved to, where the item that was there came from
+        to: usize,
+    },
+    ///The items at these indices (in order) were moved to the top, keeping the same order as each other
+    MovedToTop(Vec<usize>),
+    ///The item at this index was changed in place
+    Edited(usize),
+}
+
+///A colour, symbol and tooltip to make one item in an [`EguiList`] stand out - see [`EguiList::display_highlighted`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Highlight {
+    ///The colour to draw the item's label in
+    pub colour: Color32,
+    ///The symbol to put before the item's label, so it doesn't only stand out by its colour
+    pub marker: &'static str,
+    ///What to show when the label is hovered over
+    pub tooltip: String,
 }
 
 ///A struct to wrap around a [`Vec`], which has utilities related to displaying it in an [`egui`] window.
 #[derive(Debug, Clone)]
+#[allow(clippy::struct_excessive_bools)] //they're all independent builder options
 pub struct EguiList<T> {
     ///Whether or not the list is displayed in a vertical [`egui::ScrollArea`]. Defaults to `false`
     is_scrollable: bool,
@@ -25,8 +48,18 @@ pub struct EguiList<T> {
     is_editable: bool,
     ///Whether or not you can reorder items in the list. Defaults to `false`
     is_reorderable: bool,
+    ///Whether or not items have checkboxes to select several at once for bulk actions. Defaults to `false`
+    is_multi_selectable: bool,
+    ///Which items are selected - this is kept the same length as `backing` when displaying
+    selected: Vec<bool>,
+    ///The labels we've already made for each item, so we don't format them all again every frame. This gets cleared whenever the list might have changed
+    labels: Vec<Option<(String, Option<Highlight>)>>,
     ///A temporary variable for if we had an update
-    had_list_update: Option<ChangeType<T>>,
+    had_list_update: Option<ChangeType>,
+    ///Goes up whenever the list might have changed - see [`EguiList::generation`]
+    generation: u64,
+    ///The items from the last removal, until they get taken
+    removed: Vec<T>,
     ///The backing list that gets displayed.
     backing: Vec<T>,
 }
@@ -37,19 +70,51 @@ impl<T> Default for EguiList<T> {
             is_scrollable: false,
             is_editable: false,
             is_reorderable: false,
+            is_multi_selectable: false,
+            selected: vec![],
+            labels: vec![],
+            removed: vec![],
             backing: vec![],
             had_list_update: None,
+            generation: 0,
         }
     }
 }
 
+#[allow(clippy::wrong_self_convention)] //the builder methods are named after the fields they set
 impl<T> EguiList<T> {
     ///This uses [`std::mem::take`] on the temporary list update variable - it gets it, and if you poll after it will be [`None`] unless something changes
     #[must_use]
-    pub fn had_update(&mut self) -> Option<ChangeType<T>> {
+    pub fn had_update(&mut self) -> Option<ChangeType> {
         std::mem::take(&mut self.had_list_update)
     }
 
+    ///A number which goes up whenever the list might have changed (including through [`DerefMut`]), so anything worked out from the items can be kept until this changes, rather than being worked out again every frame
+    #[must_use]
+    pub const fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    ///Takes the items from the last removal, in the same order as their indices in the [`ChangeType`]. If you poll after, it will be empty unless more get removed
+    #[must_use]
+    pub fn take_removed(&mut self) -> Vec<T> {
+        std::mem::take(&mut self.removed)
+    }
+
+    ///Changes the item at `index` in place, and records it as an update
+    ///
+    /// # Panics
+    ///
+    /// If the index is out of bounds
+    pub fn edit(&mut self, index: usize, edit: impl FnOnce(&mut T)) {
+        edit(&mut self.backing[index]);
+        if let Some(label) = self.labels.get_mut(index) {
+            *label = None; //the label is the only one that could have changed
+        }
+        self.generation += 1;
+        self.had_list_update = Some(ChangeType::Edited(index));
+    }
+
     ///Changes whether or not we can scroll - builder pattern
     #[must_use]
     pub const fn is_scrollable(mut self, is_scrollable: bool) -> Self {
@@ -71,22 +136,99 @@ impl<T> EguiList<T> {
         self
     }
 
-    ///Inner method for displaying - this way we avoid code duplication around the scroll area.
-    fn display_inner(&mut self, ui: &mut Ui, label: impl Fn(&T, usize) -> String) {
+    ///Changes whether or not we can select several items for bulk actions - builder pattern
+    #[must_use]
+    pub const fn is_multi_selectable(mut self, is_multi_selectable: bool) -> Self {
+        self.is_multi_selectable = is_multi_selectable;
+        self
+    }
+
+    ///Makes sure there is one selection flag per item. Items get added and removed through [`DerefMut`] without us knowing which, so if the list got shorter we can't tell which flags to drop, and just clear them all
+    fn sync_selection(&mut self) {
+        if self.selected.len() > self.backing.len() {
+            self.selected.clear();
+        }
+        self.selected.resize(self.backing.len(), false); //new items start off unselected
+    }
+
+    ///Displays the buttons for the bulk actions, if any items are selected
+    fn display_bulk_actions(&mut self, ui: &mut Ui) {
+        let no_selected = self.selected.iter().filter(|selected| **selected).count();
+
+        ui.horizontal(|ui| {
+            if ui.button("Select All").clicked() {
+                self.selected.fill(true);
+            }
+            if no_selected > 0 {
+                if ui.button("Select None").clicked() {
+                    self.selected.fill(false);
+                }
+                if self.had_list_update.is_some() {
+                    return; //same as the individual buttons - only one change at a time
+                }
+
+                let indices: Vec<usize> = self
+                    .selected
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, selected)| selected.then_some(i))
+                    .collect();
+
+                if self.is_editable && ui.button(format!("Remove {no_selected} Selected")).clicked() {
+                    let (removed, kept): (Vec<_>, Vec<_>) = std::mem::take(&mut self.backing)
+                        .into_iter()
+                        .zip(std::mem::take(&mut self.selected))
+                        .partition(|(_, selected)| *selected);
+                    self.backing = kept.into_iter().map(|(item, _)| item).collect();
+                    self.selected = vec![false; self.backing.len()];
+                    self.removed = removed.into_iter().map(|(item, _)| item).collect();
+                    self.had_list_update = Some(ChangeType::RemovedMultiple(indices));
+                } else if self.is_reorderable && ui.button("Move Selected to Top").clicked() {
+                    let (mut moved, kept): (Vec<_>, Vec<_>) = std::mem::take(&mut self.backing)
+                        .into_iter()
+                        .zip(std::mem::take(&mut self.selected))
+                        .partition(|(_, selected)| *selected); //partition keeps the order, so the selected items stay in the same order as each other
+                    moved.extend(kept);
+                    (self.backing, self.selected) = moved.into_iter().unzip(); //keep them selected, so they can be moved again
+                    self.had_list_update = Some(ChangeType::MovedToTop(indices));
+                }
+            }
+        });
+    }
+
+    ///Inner method for displaying the items in `rows` - this way we avoid code duplication around the scroll area, which only shows the rows that are visible.
+    fn display_inner(
+        &mut self,
+        ui: &mut Ui,
+        rows: Range<usize>,
+        label: &impl Fn(&T, usize) -> String,
+        highlight: &impl Fn(&T, usize) -> Option<Highlight>,
+    ) {
         if self.backing.is_empty() {
             //If we don't have any arguments, then we don't need any of this and some of the logic gets screwed because of 0s
             return;
         }
+        if self.is_multi_selectable {
+            self.sync_selection();
+        }
+        self.labels.resize(self.backing.len(), None);
 
         //we could have multiple (as in vecs rather than options), but immediate mode, so unlikely to affect UX but much easier for me
         let mut need_to_remove = None; //we need to remove this index
         let mut up = None; //move this index up a position
         let mut down = None; //move this index down a position
 
-        for (i, arg) in self.backing.iter().enumerate() {
+        for (i, arg) in self.backing.iter().enumerate().take(rows.end).skip(rows.start) {
             ui.horizontal(|ui| {
                 //for each of our CLI args, make a new horizontal environment (to almost mimic a table without alignment), and add buttons for remove/up/down, and if we get input then set relevant variables
-                ui.label(label(arg, i)); //we don't break to ensure that everything always gets drawn, but we still skip over lots of logic if we have a change
+                if self.is_multi_selectable {
+                    ui.checkbox(&mut self.selected[i], "");
+                }
+                match self.labels[i].get_or_insert_with(|| (label(arg, i), highlight(arg, i))) {
+                    //we don't break to ensure that everything always gets drawn, but we still skip over lots of logic if we have a change
+                    (text, None) => ui.label(text.as_str()),
+                    (text, Some(Highlight { colour, marker, tooltip })) => ui.colored_label(*colour, format!("{marker} {text}")).on_hover_text(tooltip.as_str()),
+                };
 
                 if self.had_list_update.is_none() {
                     if self.is_editable && ui.button("Remove?").clicked() {
@@ -108,35 +250,68 @@ impl<T> EguiList<T> {
         }
 
         let len_minus_one = self.backing.len() - 1;
-        if let Some(need_to_remove) = need_to_remove {
-            self.had_list_update = Some(ChangeType::Removed(self.backing.remove(need_to_remove)));
+        let swap = if let Some(need_to_remove) = need_to_remove {
+            self.removed = vec![self.backing.remove(need_to_remove)];
+            self.had_list_update = Some(ChangeType::Removed(need_to_remove));
+            if self.is_multi_selectable {
+                self.selected.remove(need_to_remove); //keep the selection lined up with the items
+            }
+            None
         } else if let Some(up) = up {
-            self.had_list_update = Some(ChangeType::Reordered);
             //extra code with checking <> 0 for wrapping around rather than just normal swapping
-            if up > 0 {
-                self.backing.swap(up, up - 1);
-            } else {
-                self.backing.swap(0, len_minus_one);
-            }
-        } else if let Some(down) = down {
-            self.had_list_update = Some(ChangeType::Reordered);
-            if down < len_minus_one {
-                self.backing.swap(down, down + 1);
-            } else {
-                self.backing.swap(len_minus_one, 0);
+            Some(if up > 0 { (up, up - 1) } else { (0, len_minus_one) })
+        } else {
+            down.map(|down| if down < len_minus_one { (down, down + 1) } else { (len_minus_one, 0) })
+        };
+
+        if let Some((from, to)) = swap {
+            self.had_list_update = Some(ChangeType::Reordered { from, to });
+            self.backing.swap(from, to);
+            if self.is_multi_selectable {
+                self.selected.swap(from, to);
             }
         }
     }
 
     ///Actually displays the items, taking in a closure for how to display the items.
+    ///
+    /// The labels get cached until the list changes (including through [`DerefMut`]), so the closure should only depend on the item and its index. If the list is scrollable, only the visible rows get laid out, so even very long lists stay quick.
     pub fn display(&mut self, ui: &mut Ui, label: impl Fn(&T, usize) -> String) {
+        self.display_highlighted(ui, label, |_, _| None);
+    }
+
+    ///Displays the items like [`EguiList::display`], with a second closure to pick out items which should stand out - these get cached along with the labels
+    pub fn display_highlighted(
+        &mut self,
+        ui: &mut Ui,
+        label: impl Fn(&T, usize) -> String,
+        highlight: impl Fn(&T, usize) -> Option<Highlight>,
+    ) {
+        let had_update_before = self.had_list_update.is_some();
+
+        if self.is_multi_selectable && !self.backing.is_empty() {
+            //the bulk actions go outside the scroll area, so they're always visible
+            self.sync_selection();
+            self.display_bulk_actions(ui);
+        }
+
         if self.is_scrollable {
             //need to have 2 methods to allow one to be inside the vertical scroll
-            ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
-                self.display_inner(ui, label);
-            });
+            let row_height = ui.spacing().interact_size.y; //each row is as tall as its buttons
+            let len = self.backing.len();
+            ScrollArea::vertical()
+                .max_height(300.0)
+                .show_rows(ui, row_height, len, |ui, rows| {
+                    self.display_inner(ui, rows, &label, &highlight);
+                });
         } else {
-            self.display_inner(ui, label);
+            let len = self.backing.len();
+            self.display_inner(ui, 0..len, &label, &highlight);
+        }
+
+        if !had_update_before && self.had_list_update.is_some() {
+            self.labels.clear(); //the indices have changed, so the labels could be wrong
+            self.generation += 1;
         }
     }
 }
@@ -151,6 +326,8 @@ impl<T> Deref for EguiList<T> {
 
 impl<T> DerefMut for EguiList<T> {
     fn deref_mut(&mut self) -> &mut Self::Target {
+        self.labels.clear(); //we can't tell what gets changed, so all of the labels could be wrong
+        self.generation += 1;
         &mut self.backing
     }
 }
@@ -190,6 +367,8 @@ impl<T> AsRef<[T]> for EguiList<T> {
 }
 impl<T> AsMut<[T]> for EguiList<T> {
     fn as_mut(&mut self) -> &mut [T] {
+        self.labels.clear();
+        self.generation += 1;
         &mut self.backing
     }
 }
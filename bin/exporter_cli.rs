@@ -1,25 +1,79 @@
 //! Binary for dealing with exporting traces to a file.
 //! 
-//! Firstly, you need a `main` function which calls this. Arguments are dealt with in [`ExporterCLIArgs`] via `clap`, and take in a list input files, an extension for the final export (either HTML or CSV right now via [`ExportType`]) and the file name for that export.
+//! Firstly, you need a `main` function which calls this. Arguments are dealt with in [`ExporterCLIArgs`] via `clap`, and take in a list input files, an extension for the final export (HTML, CSV or Vega-Lite right now via [`ExportType`]) and the file name for that export.
 //! 
 //! The `run` function collects the arguments, gets traces and then exports.
 
-use benchmarker::io::{export_csv_no_file_input, export_html_no_file_input, get_traces, ExportType};
+use benchmarker::{
+    exit_reason::ExitReason,
+    format::DurationFormat,
+    io::{
+        expand_inputs, export_csv_with_tags, export_hyperfine_json, export_json_with_tags,
+        summary::{summarise_traces, TraceSummary}, export_html_with_options, export_vega_lite_no_file_input, import_traces_with_tags, import_traces_with_tags_from_reader,
+        parse_trace_colour, resolve_duplicates, split_tags, DuplicatePolicy, ExportType, PlotKind, PlotOptions, TraceColour,
+    },
+    parameters::{parameters, PARAMETER_TAG_PREFIX},
+    downsample::DEFAULT_MAX_PLOT_POINTS,
+    tags::{group_by_tag, matches_filter, parse_tag},
+};
+use crate::{failure, runner_cli::DurationFormatArgs};
 use clap::Parser;
-use std::path::PathBuf;
+use color_eyre::eyre::WrapErr;
+use owo_colors::OwoColorize;
+use std::path::{Path, PathBuf};
+
+///The input which means reading from stdin, rather than a file
+const STDIN_INPUT: &str = "-";
 
 #[derive(Clone, Debug, Parser)] //struct for exporter cli args that can be cloned/printed/parsed from cli
 ///CLI Arguments for the Exporter, designed to be collected via `clap`
+#[allow(clippy::struct_excessive_bools)] //they're all independent flags
 pub struct ExporterCLIArgs {
-    ///List of input CSV files to pull from
+    ///List of input CSV files to pull from - these can be glob patterns, like `results/*.csv`, or `-` to read CSV or JSON (precipice, hyperfine or Google Benchmark) from stdin
     #[arg(long, short)]
     pub input: Vec<PathBuf>,
+    ///Directories to pull every CSV and JSON file from
+    #[arg(long)]
+    pub input_dir: Vec<PathBuf>,
     ///The file name to export to, without extension
     #[arg(long, short, default_value_t = String::from("precipice_bench"))]
     pub output_without_extension: String,
-    ///How to export the data - a csv with the microsecond values, or an HTML graph
+    ///How to export the data - a csv with the microsecond values, an HTML graph, or a Vega-Lite spec
     #[arg(value_enum, short = 't', long, default_value_t = ExportType::HTML)]
     pub output_ty: ExportType,
+    ///How to plot the traces in a HTML or Vega-Lite export - histograms, density curves which are easier to compare when the traces have very different numbers of runs, or every run against its index
+    #[arg(value_enum, long, default_value_t = PlotKind::Histogram)]
+    pub plot_kind: PlotKind,
+    ///Use a logarithmic scale for the time axis in a HTML or Vega-Lite export
+    #[arg(long)]
+    pub log_scale: bool,
+    ///Plot every run in a HTML export - by default, traces with lots of runs get thinned out so the plot stays quick in a browser
+    #[arg(long)]
+    pub full_resolution: bool,
+    ///What to do when two traces have the same name - add a suffix to the later ones, merge their runs, skip the later ones, or fail
+    #[arg(value_enum, long, default_value_t = DuplicatePolicy::Suffix)]
+    pub duplicates: DuplicatePolicy,
+    ///The colour for a trace in a HTML export, like `name=#rrggbb` (or `#rrggbbaa` to make it see-through) - can be given more than once
+    #[arg(long, value_parser = parse_trace_colour)]
+    pub trace_color: Vec<(String, TraceColour)>,
+    ///Only export the traces with this tag, like `machine=ryzen-box` - can be given more than once, and traces need all of them
+    #[arg(long, value_parser = parse_tag)]
+    pub filter_tag: Vec<(String, String)>,
+    ///Merge the traces with the same value for this tag into one trace each, called `tag=value`
+    #[arg(long)]
+    pub group_by_tag: Option<String>,
+    ///Plot the mean and 95% confidence interval of each trace against this parameter from a sweep (eg. `threads` for traces tagged `param.threads=4`) in a HTML or Vega-Lite export, with a line for each set of the other parameters
+    #[arg(long)]
+    pub by_parameter: Option<String>,
+    ///Read huge files without loading every run - print a summary of each trace, and plot a sample of its runs. CSV exports need every run, so they can't be used with this
+    #[arg(long)]
+    pub low_memory: bool,
+    ///Open the export in the default browser once it has been written - only for HTML exports
+    #[arg(long)]
+    pub open: bool,
+    ///How to write the times in the `--low-memory` summaries
+    #[command(flatten)]
+    pub duration_format: DurationFormatArgs,
 }
 
 ///Run the CLI exporter
@@ -28,15 +82,109 @@ pub fn run(
     ExporterCLIArgs {
         //here, we pattern match on the args to just get all of the member variables, without having to clone anything. probably not needed for a one time run, without much memory behind it (hopefully), but a nice convenience for LOCs
         input,
+        input_dir,
         output_without_extension,
         output_ty,
+        plot_kind,
+        log_scale,
+        full_resolution,
+        duplicates,
+        trace_color,
+        filter_tag,
+        group_by_tag: group_by,
+        by_parameter,
+        low_memory,
+        open,
+        duration_format,
     }: ExporterCLIArgs,
-) {
-    let traces = get_traces(input, None).expect("unable to get traces");
-    match output_ty {
-        ExportType::HTML => export_html_no_file_input(output_without_extension, traces)
-            .expect("unable to export files to html"),
-        ExportType::CSV => export_csv_no_file_input(output_without_extension, traces)
-            .expect("unable to export files to csv"),
+) -> color_eyre::Result<()> {
+    let duration_format = DurationFormat::from(duration_format);
+    let input = expand_inputs(&input, &input_dir).wrap_err("unable to find the input files")?;
+    if input.is_empty() {
+        return Err(failure(ExitReason::InvalidArguments, "no input files - pass some with -i or --input-dir"));
+    }
+    if open && !matches!(output_ty, ExportType::HTML) {
+        eprintln!("{}: --open only opens HTML exports, so nothing will be opened", "Warning".yellow().bold());
+    }
+    if low_memory && matches!(output_ty, ExportType::CSV | ExportType::JSON | ExportType::Hyperfine) {
+        return Err(failure(
+            ExitReason::InvalidArguments,
+            format!("--low-memory only keeps a sample of each trace, so it can't export them to {output_ty:?} - use -t html or -t vega-lite"),
+        ));
+    }
+    let mut traces = vec![];
+    for file in input {
+        //import them one by one, so we can say which one was wrong
+        if low_memory && file != Path::new(STDIN_INPUT) {
+            let summaries = summarise_traces(&file, DEFAULT_MAX_PLOT_POINTS).wrap_err_with(|| format!("unable to summarise the traces in {}", file.display()))?;
+            for TraceSummary { name, tags, sample, .. } in summaries.into_iter().inspect(|summary| println!("{}", summary.format(&duration_format))) {
+                traces.push((name, sample, tags));
+            }
+        } else if low_memory {
+            for (name, runs, tags) in import_traces_with_tags_from_reader(std::io::stdin().lock()).wrap_err("unable to get traces from stdin")? {
+                let summary = TraceSummary::new(name, &runs, tags, DEFAULT_MAX_PLOT_POINTS); //stdin might be JSON, which can't be read a cell at a time
+                println!("{}", summary.format(&duration_format));
+                traces.push((summary.name, summary.sample, summary.tags));
+            }
+        } else if file == Path::new(STDIN_INPUT) {
+            traces.extend(import_traces_with_tags_from_reader(std::io::stdin().lock()).wrap_err("unable to get traces from stdin")?);
+        } else {
+            traces.extend(
+                import_traces_with_tags(&file).wrap_err_with(|| format!("unable to get traces from {}", file.display()))?,
+            );
+        }
+    }
+    traces.retain(|(_, _, tags)| matches_filter(tags, &filter_tag));
+    if let Some(key) = group_by {
+        traces = group_by_tag(traces, &key);
+    }
+    if let Some(parameter) = &by_parameter {
+        if matches!(output_ty, ExportType::CSV | ExportType::JSON | ExportType::Hyperfine) {
+            eprintln!("{}: --by-parameter only changes HTML and Vega-Lite plots, so the {output_ty:?} will have every trace as usual", "Warning".yellow().bold());
+        }
+        let (with, without): (Vec<_>, Vec<_>) = traces.iter().partition(|(_, _, tags)| parameters(tags).contains_key(parameter));
+        if with.is_empty() {
+            return Err(failure(
+                ExitReason::InvalidArguments,
+                format!("none of the traces have the parameter {parameter} - it should be a tag like {PARAMETER_TAG_PREFIX}{parameter}=value"),
+            ));
+        }
+        for (name, _, _) in without {
+            eprintln!("{}: {name} doesn't have the parameter {parameter}, so it won't be plotted", "Warning".yellow().bold());
+        }
+    }
+    let (traces, tags) = split_tags(traces); //filter and group before dealing with duplicates, as grouping can get rid of them
+    let traces = resolve_duplicates(traces, duplicates)
+        .wrap_err(ExitReason::AssertionFailed) //only --duplicates error can fail
+        .wrap_err("unable to resolve the duplicate traces")?;
+    for (name, _) in &trace_color {
+        if !traces.iter().any(|(trace_name, _)| trace_name == name) {
+            eprintln!("{}: there isn't a trace called {name} to colour", "Warning".yellow().bold());
+        }
+    }
+    let plot_options = PlotOptions {
+        kind: plot_kind,
+        log_scale,
+        colours: trace_color.into_iter().collect(),
+        full_resolution,
+        tags,
+        parameter: by_parameter,
     };
+    match output_ty {
+        ExportType::HTML => export_html_with_options(&output_without_extension, traces, &plot_options),
+        ExportType::CSV => export_csv_with_tags(&output_without_extension, traces, &plot_options.tags),
+        ExportType::VegaLite => export_vega_lite_no_file_input(&output_without_extension, traces, &plot_options),
+        ExportType::JSON => export_json_with_tags(&output_without_extension, traces, &plot_options.tags),
+        ExportType::Hyperfine => export_hyperfine_json(&output_without_extension, traces, &plot_options.tags),
+    }
+    .wrap_err(ExitReason::ExportFailed)
+    .wrap_err_with(|| format!("unable to export to {output_without_extension} as {output_ty:?}"))?;
+    if open && matches!(output_ty, ExportType::HTML) {
+        let file = format!("{output_without_extension}.html");
+        if let Err(e) = open::that(&file) {
+            eprintln!("{}: exported, but unable to open {file}: {e}", "Warning".yellow().bold()); //the export itself still worked
+        }
+    }
+
+    Ok(())
 }
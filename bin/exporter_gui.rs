@@ -2,151 +2,532 @@
 //! 
 //! It caches which files were picked last save, and then allows you to pick the files to take from (adding their traces to a list), the export name, and whether or not we totally clear out a file when we write to it.
 //! 
-//! The file reading is done on a separate thread to avoid UI slowing down whilst the file is read.
+//! The file reading is done on a small pool of separate threads to avoid UI slowing down whilst the files are read, and so adding lots of big files at once doesn't mean waiting for each one in turn.
+//!
+//! Every imported file is watched, and gets re-imported when it changes on disk, so the window can sit open whilst new sessions get added to it.
 
 //imports
 use benchmarker::{
-    io::{export_csv_no_file_input, export_html_no_file_input, import_csv},
-    EGUI_STORAGE_SEPARATOR,
+    compare::{compare, effect_size, DEFAULT_SIGNIFICANCE_LEVEL},
+    io::{
+        export_csv_with_progress, export_html_with_progress, export_json_with_progress, import_csv_iter, import_traces_with_tags, resolve_duplicates, split_tags, DuplicatePolicy, ExportProgress,
+        PlotKind, PlotOptions, TraceColour,
+    },
+    tags::{group_by_tag, matches_filter, parse_tags, tags_to_string, TaggedTrace, Tags, TraceTags},
 };
-use eframe::{App, Frame, Storage, egui::{Context, CentralPanel}};
+use eframe::{App, Frame, Storage, egui::{Button, ComboBox, Context, CentralPanel}};
 use egui_file::FileDialog;
 use itertools::Itertools;
+use notify::{event::ModifyKind, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
 use std::{
+    collections::HashMap,
     ffi::OsStr,
+    fs::File,
+    io::BufReader,
     path::{Path, PathBuf},
-    sync::mpsc::{channel, Receiver, Sender},
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
 };
-use crate::egui_utils::{ChangeType, EguiList};
+use crate::{
+    egui_utils::{ChangeType, EguiList},
+    gui_export::RunningExport,
+    native_dialog,
+    gui_storage::{load_exporter, save_json, ExporterStorage, EXPORTER_KEY},
+    theme::{theme_picker, Theme, ERROR_MARKER, WARNING_MARKER},
+    ui_scale::{ui_scale_buttons, update_ui_scale, DEFAULT_UI_SCALE},
+};
+
+///How long a file has to go without changing before we re-import it, so we don't read it half-written
+const RELOAD_DEBOUNCE: Duration = Duration::from_millis(250);
+///How many recent files to remember
+const RECENT_FILES_LIMIT: usize = 10;
+
+///How many unreadable cells to list for each file, before just saying how many more there were
+const LISTED_PROBLEMS_LIMIT: usize = 5;
+
+///The most threads to load files on - each one can hold a big trace in memory, so this stays small even on machines with lots of cores
+const MAX_LOADER_THREADS: usize = 4;
+
+///A trace we've read, with the file it came from and its tags
+type LoadedTrace = (PathBuf, String, Vec<u128>, Tags);
+
+///The comparison shown between two traces, kept until the pair or their runs change
+struct CachedComparison {
+    ///The indices of the two traces in `traces`, and the [`EguiList::generation`] of `traces` when they got compared
+    key: (usize, usize, u64),
+    ///The verdict and the effect size, or [`None`] if there weren't enough runs to compare
+    lines: Option<(String, String)>,
+}
+
+impl CachedComparison {
+    ///Compares the traces at the indices in the `key`, which need to be in bounds
+    fn new(traces: &EguiList<LoadedTrace>, key: (usize, usize, u64)) -> Self {
+        let (_, _, before, _) = &traces[key.0];
+        let (_, _, after, _) = &traces[key.1];
+        let lines = compare(before, after, DEFAULT_SIGNIFICANCE_LEVEL)
+            .zip(effect_size(before, after))
+            .map(|((verdict, test), effect)| {
+                (
+                    format!("{verdict} (p = {:.4}, t = {:.3})", test.p_value, test.t),
+                    effect.to_string(),
+                )
+            });
+        Self { key, lines }
+    }
+}
 
 ///Struct for an [`eframe::App`] for exports.
+#[allow(clippy::struct_excessive_bools)] //they're all independent settings from checkboxes
 pub struct ExporterApp {
     ///Current list of files we've read from - used for storing to load for next time
     files: Vec<PathBuf>,
+    ///The files which were added most recently, newest first - these are kept even once their traces get removed, so they can be added again
+    recent_files: Vec<PathBuf>,
     ///List of traces we've read from the above files
-    traces: EguiList<(PathBuf, String, Vec<u128>)>,
+    traces: EguiList<LoadedTrace>,
     ///File dialog for adding new files for traces
     add_file_dialog: Option<FileDialog>,
+    ///Whether to pick files with the operating system's dialog rather than the [`egui_file`] one
+    native_dialogs: bool,
+    ///How much to scale the whole GUI by
+    ui_scale: f32,
+    ///Which colours to draw the GUI in
+    theme: Theme,
     ///Sender for files to the loader thread
     file_tx: Sender<PathBuf>,
     ///Receiver to get back traces from the loader thread
-    trace_rx: Receiver<(PathBuf, String, Vec<u128>)>,
+    trace_rx: Receiver<LoadedTrace>,
+    ///Receiver to get back what went wrong loading each file from the loader thread
+    load_problem_rx: Receiver<String>,
+    ///What went wrong loading files, until the user dismisses them
+    load_problems: Vec<String>,
     ///The name to export the resulting file to, excluding extensions
     export_name: String,
     ///Whether or not we clear all traces associated with a file, when we re-import that file
     remove_existing_files_on_add_existing_file: bool,
+    ///Watches the files for changes - [`None`] if we couldn't make one
+    watcher: Option<RecommendedWatcher>,
+    ///Receiver to get back files which changed from the watcher
+    changed_rx: Receiver<PathBuf>,
+    ///Whether or not we re-import files when they change
+    reload_changed_files: bool,
+    ///Files which changed, with when they last changed
+    pending_reloads: HashMap<PathBuf, Instant>,
+    ///The index of the trace to compare from, in `traces`
+    compare_before: usize,
+    ///The index of the trace to compare to, in `traces`
+    compare_after: usize,
+    ///The last comparison between the two traces, as the effect sizes are too slow to work out every frame
+    comparison: Option<CachedComparison>,
+    ///How to plot the traces when exporting to HTML
+    plot_kind: PlotKind,
+    ///Whether to use a logarithmic time axis when exporting to HTML
+    log_scale: bool,
+    ///Whether to plot every run when exporting to HTML, rather than thinning out huge traces
+    full_resolution: bool,
+    ///The parameter from a sweep to plot the traces against when exporting to HTML, if it isn't empty
+    by_parameter: String,
+    ///The index of the trace to rename or colour, in `traces`
+    edit_index: usize,
+    ///The new name for that trace
+    rename_to: String,
+    ///What to do with traces which have the same name when exporting
+    duplicates: DuplicatePolicy,
+    ///The colours picked for traces, by name
+    colours: HashMap<String, TraceColour>,
+    ///Only export the traces with all of these tags, as they were typed in
+    tag_filter: String,
+    ///Merge the traces with the same value for this tag when exporting, if it isn't empty
+    group_by_tag: String,
+    ///Why the last export failed, if it did - or why a file couldn't be loaded
+    export_error: Option<String>,
+    ///The export running in the background, if there is one - big exports can take a while, so they don't block the window
+    running_export: Option<RunningExport>,
+    ///Whether to open HTML exports in the default browser once they've finished
+    open_after_export: bool,
 }
 
 impl ExporterApp {
-    ///Constructor - uses the `storage` to get the files
-    #[instrument(skip(storage))]
-    pub fn new(storage: Option<&dyn Storage>) -> Self {
+    ///Constructor - uses the `storage` to get the files, and the `ctx` to wake the UI up when a file changes
+    #[instrument(skip(storage, ctx))]
+    pub fn new(storage: Option<&dyn Storage>, ctx: Context) -> Self {
         trace!(has_storage=?storage.is_some(), "Starting new Exporter App");
         
-        let files: Vec<PathBuf> = storage
-            .and_then(|s| s.get_string("files")) //if we have storage, then get out the content from the key "files"
-            .map(|s| {
-                //if we got the content, then map it to the contents because it is a list
-                trace!(?s, "Got files");
-                if s.is_empty() {
-                    //"".split(/* anything */) returns vec![""], which we don't want, so we check if the string is empty first, before splitting it
-                    vec![]
-                } else {
-                    s.split(EGUI_STORAGE_SEPARATOR)
-                        .map(ToString::to_string) //since we get borrowed strings, and its easier to deal with owned strings (as we can't return a borrow of something from inside the function), we make them all into owned strings
-                        .collect() //and we make it into
-                }
-            })
-            .unwrap_or_default() //if we didn't get the content, we just make an empty vector
-            .into_iter() //which we turn into an iterator
-            .unique() //and only get the unique items to avoid duplicates
-            .filter_map(|s| {
-                //then, we filter through them and map items
-                let path = Path::new(&s); //we make a path from the strinh
+        let stored = load_exporter(storage); //if we didn't have anything stored, there aren't any files
+        let recent_files = stored.recent_files.into_iter().map(PathBuf::from).collect();
+        let files: Vec<PathBuf> = stored
+            .files
+            .into_iter()
+            .map(PathBuf::from)
+            .unique() //only get the unique items to avoid duplicates
+            .filter(|path| {
                 if path.exists() {
-                    let pb = path.to_path_buf();
-                    trace!(?pb, "Found path");
-                    Some(pb) //if it exists, we return it, and if not we don't
+                    trace!(?path, "Found path");
+                    true //if it exists, we keep it, and if not we don't
                 } else {
-                    warn!(?s, ?path, "File not found");
-                    None
+                    warn!(?path, "File not found");
+                    false
                 }
             })
             .collect();
 
         let (file_tx, file_rx) = channel();
         let (trace_tx, trace_rx) = channel(); //here we make 2 channels for where we can send files to the thread and receive traces from the thread
+        let (load_problem_tx, load_problem_rx) = channel(); //and one for anything that goes wrong loading them
 
-        std::thread::Builder::new() //make a new thread for handling the loading of new files
-            .name("exporter_file_loader".into()) //we send files to the thread
-            .spawn(move || {
-                //then it sends traces back to us
-                handle_loading(file_rx, trace_tx); //and stops if we tell it
-            })
-            .expect("error creating thread");
+        let file_rx = Arc::new(Mutex::new(file_rx)); //every loader thread takes the next file from the same queue
+        let loader_threads = std::thread::available_parallelism().map_or(1, |threads| threads.get().min(MAX_LOADER_THREADS));
+        let mut loader_error = None;
+        let mut started_loaders = 0;
+        for i in 0..loader_threads {
+            let (file_rx, trace_tx, load_problem_tx) = (file_rx.clone(), trace_tx.clone(), load_problem_tx.clone());
+            match std::thread::Builder::new() //make new threads for handling the loading of new files
+                .name(format!("exporter_file_loader_{i}")) //we send files to the threads
+                .spawn(move || {
+                    //then they send traces back to us
+                    handle_loading(&file_rx, &trace_tx, &load_problem_tx); //and stop once the window has gone
+                }) {
+                Ok(_) => started_loaders += 1,
+                Err(e) => loader_error = Some(e),
+            }
+        }
+        let loader_error = loader_error.filter(|_| started_loaders == 0).map(|e| {
+            //we only need one of them to have started
+            let e = color_eyre::Report::new(e).wrap_err("unable to start the file loader threads, so no files can be loaded");
+            error!(?e, "Unable to start loader threads");
+            format!("{e:#}")
+        }); //show it to the user rather than crashing, as they can still see the window
 
-        for file in &files {
-            //for each file we got from the storage, we try and get it from the thread
-            file_tx //we don't need to worry about them being invalid, as they get checked above
-                .send(file.clone())
-                .expect("unable to send files from init load");
+        if loader_error.is_none() {
+            for file in &files {
+                //for each file we got from the storage, we try and get it from the thread
+                let _ = file_tx.send(file.clone()); //we don't need to worry about them being invalid, as they get checked above - and the loader can't have gone yet
+            }
+        }
+
+        let (changed_tx, changed_rx) = channel();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| match event {
+            Ok(event) => {
+                if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(ModifyKind::Data(_) | ModifyKind::Any)) {
+                    for path in event.paths {
+                        let _ = changed_tx.send(path); //if the app has gone, there's nobody to tell
+                    }
+                    ctx.request_repaint(); //egui only repaints on input, so we need to wake it up
+                }
+            }
+            Err(e) => error!(?e, "Error watching files"),
+        })
+        .map_err(|e| warn!(?e, "Unable to watch files for changes"))
+        .ok();
+        if let Some(watcher) = &mut watcher {
+            for file in &files {
+                if let Err(e) = watcher.watch(file, RecursiveMode::NonRecursive) {
+                    warn!(?e, ?file, "Unable to watch file for changes");
+                }
+            }
         }
 
         Self {
             files,
-            traces: EguiList::default().is_scrollable(true).is_editable(true),
+            recent_files,
+            traces: EguiList::default()
+                .is_scrollable(true)
+                .is_editable(true)
+                .is_reorderable(true)
+                .is_multi_selectable(true),
             add_file_dialog: None,
+            native_dialogs: stored.native_dialogs,
+            ui_scale: stored.ui_scale.unwrap_or(DEFAULT_UI_SCALE),
+            theme: stored.theme,
             file_tx,
             trace_rx,
+            load_problem_rx,
+            load_problems: vec![],
             export_name: String::default(),
             remove_existing_files_on_add_existing_file: false,
+            watcher,
+            changed_rx,
+            reload_changed_files: true,
+            pending_reloads: HashMap::new(),
+            compare_before: 0,
+            compare_after: 1,
+            comparison: None,
+            plot_kind: PlotKind::default(),
+            log_scale: false,
+            full_resolution: false,
+            by_parameter: String::default(),
+            edit_index: 0,
+            rename_to: String::default(),
+            duplicates: DuplicatePolicy::default(),
+            colours: HashMap::new(),
+            tag_filter: String::default(),
+            group_by_tag: String::default(),
+            export_error: loader_error,
+            running_export: None,
+            open_after_export: false,
+        }
+    }
+
+    ///Gets the traces to export with their tags, filtering and grouping them by their tags and then dealing with any duplicate names using the chosen policy. If that fails, the error gets shown and we give back [`None`]
+    #[allow(clippy::type_complexity)] //the same as split_tags gives back
+    fn traces_to_export(&mut self) -> Option<(Vec<(String, Vec<u128>)>, TraceTags)> {
+        let filter: Vec<(String, String)> = match parse_tags(&self.tag_filter) {
+            Ok(filter) => filter.into_iter().collect(),
+            Err(e) => {
+                self.export_error = Some(e);
+                return None;
+            }
+        };
+
+        let mut traces: Vec<TaggedTrace> = self
+            .traces
+            .iter()
+            .filter(|(_, _, _, tags)| matches_filter(tags, &filter))
+            .map(|(_file, name, list, tags)| (name.clone(), list.clone(), tags.clone()))
+            .collect();
+        if !self.group_by_tag.trim().is_empty() {
+            traces = group_by_tag(traces, self.group_by_tag.trim());
+        }
+        let (traces, tags) = split_tags(traces);
+
+        match resolve_duplicates(traces, self.duplicates) {
+            Ok(traces) => {
+                self.export_error = None;
+                Some((traces, tags))
+            }
+            Err(e) => {
+                error!(?e, "Unable to export traces");
+                self.export_error = Some(e.to_string());
+                None
+            }
+        }
+    }
+
+    ///Adds a file the user picked, remembering it as a recent file
+    fn add_file(&mut self, file: PathBuf) {
+        self.recent_files.retain(|recent| recent != &file);
+        self.recent_files.insert(0, file.clone()); //newest first
+        self.recent_files.truncate(RECENT_FILES_LIMIT);
+
+        self.import_file(file);
+    }
+
+    ///Starts an export on its own thread, showing the error if the thread couldn't be started. If `html_file` is given and "Open after export" is ticked, it gets opened once the export has worked
+    fn start_export(
+        &mut self,
+        name: &str,
+        html_file: Option<PathBuf>,
+        export: impl FnOnce(&ExportProgress) -> color_eyre::Result<usize> + Send + 'static,
+    ) {
+        match RunningExport::start(name, export) {
+            Ok(export) => {
+                self.running_export = Some(match html_file {
+                    Some(file) if self.open_after_export => export.open_when_done(file),
+                    _ => export,
+                });
+            }
+            Err(e) => {
+                error!(?e, "Unable to start export thread");
+                self.export_error = Some(format!("unable to start exporting: {e}"));
+            }
+        }
+    }
+
+    ///Sends a file to the loader thread. If we already have it, then we remove its old traces if the user wants them gone, and if not we start watching it
+    fn import_file(&mut self, file: PathBuf) {
+        if self.files.contains(&file) {
+            //we already have it
+            if self.remove_existing_files_on_add_existing_file {
+                //if we need to remove the old traces from that file
+                self.traces.retain(|(trace_file, _, _, _)| trace_file != &file);
+            }
+        } else {
+            if let Some(watcher) = &mut self.watcher {
+                if let Err(e) = watcher.watch(&file, RecursiveMode::NonRecursive) {
+                    warn!(?e, ?file, "Unable to watch file for changes");
+                }
+            }
+            self.files.push(file.clone()); //if we don't already have it, we add it
+        }
+
+        if let Err(e) = self.file_tx.send(file) {
+            //send it to the loader thread, which only stops if it couldn't start or panicked
+            error!(?e, "Loader thread has stopped");
+            self.export_error = Some(format!("unable to load {}, as the file loader has stopped", e.0.display()));
+        }
+    }
+
+    ///Re-imports any files which changed and have since settled down, and makes sure we get woken up for the ones which haven't yet
+    fn poll_changed_files(&mut self, ctx: &Context) {
+        while let Ok(file) = self.changed_rx.try_recv() {
+            if self.reload_changed_files && self.files.contains(&file) {
+                self.pending_reloads.insert(file, Instant::now()); //a write can come in lots of events, so we wait for them to stop
+            }
+        }
+
+        let settled: Vec<PathBuf> = self
+            .pending_reloads
+            .iter()
+            .filter(|(_, changed_at)| changed_at.elapsed() >= RELOAD_DEBOUNCE)
+            .map(|(file, _)| file.clone())
+            .collect();
+        for file in settled {
+            self.pending_reloads.remove(&file);
+            trace!(?file, "Reloading changed file");
+            self.import_file(file);
+        }
+
+        if !self.pending_reloads.is_empty() {
+            ctx.request_repaint_after(RELOAD_DEBOUNCE);
         }
     }
 }
 
-///This is the meat and potatoes of the loader thread - it basically just waits for files to arrive and parses all of them, and then repeats. If it sees the `stop_rx` complaining, then it stops.
-#[allow(clippy::needless_pass_by_value)]
-#[instrument]
-fn handle_loading(file_rx: Receiver<PathBuf>, trace_tx: Sender<(PathBuf, String, Vec<u128>)>) {
+///This is the meat and potatoes of the loader threads - each one waits for a file to arrive in the shared queue and parses it, and then repeats. Once the window has gone (and so the queue has gone), it stops.
+///
+/// Traces get sent back as soon as they're read, so the traces from the files being loaded at once can arrive mixed together - but the traces from each file still arrive in order.
+#[instrument(skip_all)]
+fn handle_loading(file_rx: &Mutex<Receiver<PathBuf>>, trace_tx: &Sender<LoadedTrace>, load_problem_tx: &Sender<String>) {
     println!("Handling loading");
 
-    while let Ok(file) = file_rx.recv() {
+    loop {
+        let file = match file_rx.lock() {
+            Ok(file_rx) => file_rx.recv(), //only hold the lock whilst waiting, so the other threads can take the next file whilst we load this one
+            Err(_) => return, //another loader thread panicked
+        };
+        let Ok(file) = file else {
+            return; //the window has closed, so nothing is going to send any more files
+        };
         println!("Polled");
 
-        match import_csv(file.clone()) {
+        if !load_file(&file, trace_tx, load_problem_tx) {
+            return; //the window has closed, so nothing wants the traces
+        }
+
+        std::thread::yield_now();
+    }
+}
+
+///Loads the traces from one file, sending each one back as it gets read. Gives back `false` if the window has closed, and so nothing wants the traces any more.
+///
+/// Anything that goes wrong gets sent down `load_problem_tx` to show to the user - whether a whole file couldn't be read, or some cells in a CSV file got skipped
+fn load_file(file: &Path, trace_tx: &Sender<LoadedTrace>, load_problem_tx: &Sender<String>) -> bool {
+    if file.extension().is_some_and(|extension| extension == "json") {
+        match import_traces_with_tags(file) {
             Ok(traces) => {
-                for (name, list) in traces {
-                    trace_tx
-                        .send((file.clone(), name, list))
-                        .expect("unable to send new trace");
+                for (name, list, tags) in traces {
+                    if trace_tx.send((file.to_path_buf(), name, list, tags)).is_err() {
+                        return false;
+                    }
                 }
             }
             Err(e) => {
                 error!(?e, "Error reading traces");
+                let _ = load_problem_tx.send(format!("unable to read {}: {e}", file.display()));
             }
-        };
+        }
+        return true;
+    }
+
+    //stream CSV files in one trace at a time, so huge files don't need to fit in memory twice
+    let reader = match File::open(file) {
+        Ok(reader) => reader,
+        Err(e) => {
+            error!(?e, "Error reading traces");
+            let _ = load_problem_tx.send(format!("unable to open {}: {e}", file.display()));
+            return true;
+        }
+    };
+    let mut traces = import_csv_iter(BufReader::new(reader));
+    for trace in traces.by_ref() {
+        match trace {
+            Ok((name, list, tags)) => {
+                if trace_tx.send((file.to_path_buf(), name, list, tags)).is_err() {
+                    return false;
+                }
+            }
+            Err(e) => {
+                error!(?e, "Error reading traces");
+                let _ = load_problem_tx.send(format!("stopped reading {}: {e}", file.display()));
+                break;
+            }
+        }
+    }
 
-        std::thread::yield_now()
+    let problems = traces.problems();
+    if !problems.is_empty() {
+        let more = problems.len().saturating_sub(LISTED_PROBLEMS_LIMIT);
+        let _ = load_problem_tx.send(format!(
+            "skipped {} unreadable cell(s) in {}: {}{}",
+            problems.len(),
+            file.display(),
+            problems.iter().take(LISTED_PROBLEMS_LIMIT).join("; "),
+            if more > 0 { format!("; and {more} more") } else { String::new() },
+        ));
     }
+    true
 }
 impl App for ExporterApp {
-    fn update(&mut self, ctx: &Context, _frame: &mut Frame) {
+    fn update(&mut self, ctx: &Context, frame: &mut Frame) {
+        update_ui_scale(ctx, frame, &mut self.ui_scale);
+        self.theme.apply(ctx);
+
         CentralPanel::default().show(ctx, |ui| {
             ui.label("Benchmarker Imports/Exports"); //add a title
             ui.separator();
 
+            ui.horizontal(|ui| {
+                ui_scale_buttons(ui, &mut self.ui_scale);
+                theme_picker(ui, &mut self.theme);
+            });
+
             ui.checkbox(
                 //make a checkbox
                 &mut self.remove_existing_files_on_add_existing_file,
                 "Remove old traces when re-adding files?",
             );
-            if self.add_file_dialog.is_none() && ui.button("Add new file").clicked() {
+            ui.checkbox(&mut self.reload_changed_files, "Re-add files when they change?");
+            ui.checkbox(&mut self.native_dialogs, "Use the system file dialog?");
+            if self.native_dialogs {
+                if ui.button("Add new file").clicked() {
+                    let start = self.files.last().map(PathBuf::as_path); //start next to the last file, same as the egui dialog
+                    if let Some(file) = native_dialog::pick_file(start, Some(("Results", &["csv", "json"]))) {
+                        self.add_file(file); //the filter means it is a CSV or JSON file
+                    }
+                }
+            } else if self.add_file_dialog.is_none() && ui.button("Add new file").clicked() {
                 //if we don't have a dialog currently open AND we click the new file button
                 let mut dialog = FileDialog::open_file(self.files.last().cloned()); //make a new file dialog with the last file currently open to save the person reopening the directories. since the constructor takes an option, if we don't have any files, it just is None and we don't have to worry about it
                 dialog.open(); //open the dialog
 
                 self.add_file_dialog = Some(dialog); //and add it to the member variable
             }
+            if !self.recent_files.is_empty() {
+                ui.menu_button("Recent Files", |ui| {
+                    let mut picked = None;
+                    for recent in &self.recent_files {
+                        let exists = recent.exists(); //it could have been moved or deleted since
+                        let button = ui.add_enabled(exists, Button::new(recent.display().to_string()));
+                        if button.on_disabled_hover_text("File not found").clicked() {
+                            picked = Some(recent.clone());
+                        }
+                    }
+                    ui.separator();
+                    if ui.button("Clear Recent Files").clicked() {
+                        self.recent_files.clear();
+                        ui.close_menu();
+                    }
+
+                    if let Some(picked) = picked {
+                        self.add_file(picked);
+                        ui.close_menu();
+                    }
+                });
+            }
+
             let mut needs_to_close = false; //variable for if we need to close it to avoid ownership faffery
             if let Some(dialog) = &mut self.add_file_dialog {
                 //if we have a dialog, take a mutable reference
@@ -156,38 +537,11 @@ impl App for ExporterApp {
                         //and there is a path
                         needs_to_close = true; //we need to now close the dialog
 
-                        if file.extension() == Some(OsStr::new("csv")) {
-                            //if it is a CSV file
-                            if self.files.contains(&file) {
-                                //and we already have it
-                                if self.remove_existing_files_on_add_existing_file {
-                                    //if we need to remove the old traces from that file
-                                    #[allow(clippy::needless_collect)]
-                                    let inidicies: Vec<usize> = self
-                                        .traces
-                                        .iter()
-                                        .enumerate()
-                                        .filter_map(|(i, (trace_file, _, _))| {
-                                            if trace_file == &file {
-                                                Some(i)
-                                            } else {
-                                                None
-                                            } //get all the indicies of traces associated with that file
-                                        })
-                                        .collect(); //ignore clippy error - we need this to avoid borrow checker stuff. afaik its cheaper to collect and into_iter here, than it is to clone all the traces
-                                    for (offset, i) in inidicies.into_iter().enumerate() {
-                                        self.traces.remove(i - offset); //and remove them
-                                    }
-                                }
-                            } else {
-                                self.files.push(file.clone()); //if we don't already have it, we add it
-                            }
-
-                            self.file_tx
-                                .send(file) //send it to the loader thread
-                                .expect("unable to send pathbuf to file tx");
+                        if [Some(OsStr::new("csv")), Some(OsStr::new("json"))].contains(&file.extension()) {
+                            //if it is a CSV file, or JSON from us, hyperfine or Google Benchmark
+                            self.add_file(file);
                         } else {
-                            error!(?file, "File doesn't end in CSV"); //if we don't get a CSV file, error out
+                            error!(?file, "File doesn't end in CSV or JSON"); //if we don't get a file we can read, error out
                         }
                     }
                 }
@@ -198,12 +552,89 @@ impl App for ExporterApp {
             }
             ui.separator();
 
+            if !self.load_problems.is_empty() {
+                //show what went wrong loading files, until the user has seen it
+                for problem in &self.load_problems {
+                    ui.colored_label(self.theme.warning(), format!("{WARNING_MARKER} {problem}"));
+                }
+                if ui.button("Dismiss").clicked() {
+                    self.load_problems.clear();
+                }
+                ui.separator();
+            }
+
             if !self.traces.is_empty() {
                 //if we have any traces
                 ui.label("Traces to use:");
-                self.traces.display(ui, |(file, name, list), _i| {
-                    format!("File: {file:?}, {name} with {} elements.", list.len())
-                }); //display each trace with their file names, trace names and number of elements
+                self.traces.display(ui, |(file, name, list, tags), _i| {
+                    if tags.is_empty() {
+                        format!("File: {}, {name} with {} elements.", file.display(), list.len())
+                    } else {
+                        format!("File: {}, {name} with {} elements, tagged {}.", file.display(), list.len(), tags_to_string(tags))
+                    }
+                }); //display each trace with their file names, trace names, number of elements and tags
+
+                let len = self.traces.len();
+                self.edit_index = self.edit_index.min(len - 1); //traces could have been removed since we picked
+                ui.horizontal(|ui| {
+                    ui.label("Edit Trace:");
+                    ComboBox::from_id_source("edit_trace").show_index(ui, &mut self.edit_index, len, |i| {
+                        self.traces.get(i).map(|(_, name, _, _)| name.clone()).unwrap_or_default()
+                    });
+                    ui.text_edit_singleline(&mut self.rename_to);
+                    if ui.button("Rename").clicked() && !self.rename_to.is_empty() {
+                        let new_name = std::mem::take(&mut self.rename_to);
+                        if let Some(colour) = self.colours.get(&self.traces[self.edit_index].1).copied() {
+                            self.colours.insert(new_name.clone(), colour); //keep the colour it had - we leave the old one, in case other traces have the same name
+                        }
+                        self.traces.edit(self.edit_index, |(_, name, _, _)| *name = new_name);
+                    }
+
+                    let name = self.traces[self.edit_index].1.clone();
+                    let mut colour = self
+                        .colours
+                        .get(&name)
+                        .map_or([128, 128, 128, 255], |c| [c.red, c.green, c.blue, c.alpha]); //grey until one gets picked
+                    if ui.color_edit_button_srgba_unmultiplied(&mut colour).changed() {
+                        let [red, green, blue, alpha] = colour;
+                        self.colours.insert(name.clone(), TraceColour { red, green, blue, alpha });
+                    }
+                    if self.colours.contains_key(&name) && ui.button("Default Colour").clicked() {
+                        self.colours.remove(&name);
+                    }
+                });
+                ui.separator();
+            }
+
+            if self.traces.len() >= 2 {
+                //if we have at least two traces, we can compare them
+                let len = self.traces.len();
+                self.compare_before = self.compare_before.min(len - 1); //traces could have been removed since we picked
+                self.compare_after = self.compare_after.min(len - 1);
+                let trace_name = |i: usize| self.traces.get(i).map(|(_, name, _, _)| name.clone()).unwrap_or_default();
+
+                ui.label("Compare Traces:");
+                ui.horizontal(|ui| {
+                    ComboBox::from_id_source("compare_before").show_index(ui, &mut self.compare_before, len, trace_name);
+                    ui.label("vs");
+                    ComboBox::from_id_source("compare_after").show_index(ui, &mut self.compare_after, len, trace_name);
+                });
+
+                let key = (self.compare_before, self.compare_after, self.traces.generation());
+                let comparison = match self.comparison.take() {
+                    Some(comparison) if comparison.key == key => comparison,
+                    _ => CachedComparison::new(&self.traces, key), //the pair or their runs changed - we can index as they got clamped above
+                };
+                match &comparison.lines {
+                    Some((verdict, effect)) => {
+                        ui.label(verdict);
+                        ui.label(effect); //so a significant but tiny change doesn't look like a big win
+                    }
+                    None => {
+                        ui.label("Not enough runs to compare.");
+                    }
+                }
+                self.comparison = Some(comparison);
                 ui.separator();
             }
 
@@ -212,34 +643,89 @@ impl App for ExporterApp {
                 ui.text_edit_singleline(&mut self.export_name);
 
                 ui.vertical(|ui| {
-                    if ui.button("Export to CSV").clicked() {
+                    ui.horizontal(|ui| {
+                        ui.label("Only export traces tagged:");
+                        ui.text_edit_singleline(&mut self.tag_filter);
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Merge traces by tag:");
+                        ui.text_edit_singleline(&mut self.group_by_tag);
+                    });
+                    ComboBox::from_label("Duplicate Names")
+                        .selected_text(self.duplicates.to_string())
+                        .show_ui(ui, |ui| {
+                            for policy in [DuplicatePolicy::Suffix, DuplicatePolicy::Merge, DuplicatePolicy::Skip, DuplicatePolicy::Error] {
+                                ui.selectable_value(&mut self.duplicates, policy, policy.to_string());
+                            }
+                        });
+                    if ui.add_enabled(self.running_export.is_none(), Button::new("Export to CSV")).clicked() {
                         //export to CSV button with all our traces
-                        export_csv_no_file_input(
-                            &self.export_name,
-                            self.traces
-                                .clone()
-                                .into_iter()
-                                .map(|(_file, name, list)| (name, list))
-                                .collect(),
-                        )
-                        .expect("unable to export files to csv");
+                        if let Some((traces, tags)) = self.traces_to_export() {
+                            let export_name = self.export_name.clone();
+                            self.start_export("csv_exporter", None, move |progress| {
+                                Ok(export_csv_with_progress(&export_name, traces, &tags, progress)?) //the error says which file it was
+                            });
+                        }
+                    }
+                    if ui.add_enabled(self.running_export.is_none(), Button::new("Export to JSON")).clicked() {
+                        //export to JSON button with all our traces and their statistics
+                        if let Some((traces, tags)) = self.traces_to_export() {
+                            let export_name = self.export_name.clone();
+                            self.start_export("json_exporter", None, move |progress| {
+                                Ok(export_json_with_progress(&export_name, traces, &tags, progress)?) //the error says which file it was
+                            });
+                        }
                     }
-                    if ui.button("Export to HTML").clicked() {
+                    ComboBox::from_label("HTML Plot")
+                        .selected_text(self.plot_kind.to_string())
+                        .show_ui(ui, |ui| {
+                            for kind in [PlotKind::Histogram, PlotKind::Density, PlotKind::Scatter] {
+                                ui.selectable_value(&mut self.plot_kind, kind, kind.to_string());
+                            }
+                        });
+                    ui.checkbox(&mut self.log_scale, "Logarithmic time axis");
+                    ui.checkbox(&mut self.full_resolution, "Plot every run (slow for huge traces)");
+                    ui.horizontal(|ui| {
+                        ui.label("Plot against parameter:");
+                        ui.text_edit_singleline(&mut self.by_parameter);
+                    });
+                    ui.checkbox(&mut self.open_after_export, "Open after export");
+                    if ui.add_enabled(self.running_export.is_none(), Button::new("Export to HTML")).clicked() {
                         //export to HTML button with all our traces
-                        export_html_no_file_input(
-                            &self.export_name,
-                            self.traces
-                                .clone()
-                                .into_iter()
-                                .map(|(_file, name, list)| (name, list))
-                                .collect(),
-                        )
-                        .expect("unable to export files to csv");
+                        if let Some((traces, tags)) = self.traces_to_export() {
+                            let export_name = self.export_name.clone();
+                            let options = PlotOptions {
+                                kind: self.plot_kind,
+                                log_scale: self.log_scale,
+                                colours: self.colours.clone(),
+                                full_resolution: self.full_resolution,
+                                tags,
+                                parameter: Some(self.by_parameter.trim().to_string()).filter(|parameter| !parameter.is_empty()),
+                            };
+                            let html_file = PathBuf::from(format!("{export_name}.html"));
+                            self.start_export("html_exporter", Some(html_file), move |progress| {
+                                Ok(export_html_with_progress(&export_name, traces, &options, progress)?) //the error says which file it was
+                            });
+                        }
+                    }
+                    if let Some(export) = &mut self.running_export {
+                        export.display(ui); //show how far we've got, with a button to cancel
+                    }
+                    if let Some(export_error) = &self.export_error {
+                        ui.colored_label(self.theme.error(), format!("{ERROR_MARKER} {export_error}"));
                     }
                 });
             });
         });
 
+        self.poll_changed_files(ctx);
+
+        if let Some(export) = self.running_export.take_if(|export| export.is_finished()) {
+            self.export_error = export.join();
+        }
+
+        self.load_problems.extend(self.load_problem_rx.try_iter());
+
         while let Ok(new_trace) = self.trace_rx.try_recv() {
             //poll our trace receiver for new traces. use try_recv to avoid blocking on a UI thread
             self.traces.push(new_trace); //and add all of them
@@ -248,18 +734,19 @@ impl App for ExporterApp {
         if let Some(change) = self.traces.had_update() {
             match change {
                 //if our traces EguiList had an update, match on it
-                ChangeType::Removed(_) => {
-                    //here, we go through every trace in our traces EguiList, keep the unique ones, and then get owned versions of the remaining ones
-                    self.files = self
-                        .traces
-                        .iter()
-                        .map(|(file, _, _)| file)
-                        .unique()
-                        .cloned()
-                        .collect(); //we can't just remove the item, as we might still have other traces from that file
+                ChangeType::Removed(_) | ChangeType::RemovedMultiple(_) => {
+                    for (file, _, _, _) in self.traces.take_removed() {
+                        //we can't just remove the file, as we might still have other traces from it
+                        if !self.traces.iter().any(|(other, _, _, _)| other == &file) {
+                            if let Some(watcher) = &mut self.watcher {
+                                let _ = watcher.unwatch(&file); //if it got deleted, it isn't being watched anyway
+                            }
+                            self.files.retain(|f| f != &file);
+                        }
+                    }
                 }
-                _ => {
-                    trace!(?change, "list change in exporter traces list");
+                ChangeType::Reordered { .. } | ChangeType::MovedToTop(_) | ChangeType::Edited(_) => {
+                    trace!(?change, "list change in exporter traces list"); //none of these change which files we have
                 }
             }
         }
@@ -267,19 +754,14 @@ impl App for ExporterApp {
 
     #[instrument(skip(self, storage))]
     fn save(&mut self, storage: &mut dyn Storage) {
-        let files_to_save = self
-            .files
-            .clone()
-            .into_iter() //for each file
-            .filter_map(|pb| match pb.into_os_string().into_string() {
-                Ok(p) => Some(p), //if we can turn it into a string, do it
-                Err(e) => {
-                    error!(?e, "Error getting String of PathBuf for saving.");
-                    None //if not, we just don't save it
-                }
-            })
-            .join(EGUI_STORAGE_SEPARATOR); //join with the separator into a String
+        let state = ExporterStorage {
+            files: self.files.iter().map(|file| file.as_path().into()).collect(),
+            recent_files: self.recent_files.iter().map(|file| file.as_path().into()).collect(),
+            native_dialogs: self.native_dialogs,
+            ui_scale: Some(self.ui_scale),
+            theme: self.theme,
+        };
         trace!("Saving current files");
-        storage.set_string("files", files_to_save); //and save them
+        save_json(storage, EXPORTER_KEY, &state); //and save them
     }
 }
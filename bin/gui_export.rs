@@ -0,0 +1,116 @@
+//! Runs an export on its own thread for the GUIs, so big sessions don't freeze the window, and shows how far it has got with a button to cancel it.
+
+use benchmarker::io::ExportProgress;
+use eframe::egui::{ProgressBar, Ui, Widget};
+use std::{
+    io,
+    path::PathBuf,
+    sync::mpsc::{channel, Receiver, Sender},
+    thread::JoinHandle,
+    time::Duration,
+};
+
+///How often to repaint whilst an export is running, so the progress bar moves without any input
+const REPAINT_INTERVAL: Duration = Duration::from_millis(100);
+
+///An export running on its own thread
+#[derive(Debug)]
+pub struct RunningExport {
+    ///The thread doing the export, which gives back how many bytes it wrote
+    handle: JoinHandle<color_eyre::Result<usize>>,
+    ///Where the thread sends `(bytes written, total bytes)`
+    progress_rx: Receiver<(usize, usize)>,
+    ///Send anything down this to cancel the export
+    cancel_tx: Sender<()>,
+    ///The latest progress we've heard about - [`None`] until it starts writing, as working out what to write can take a while too
+    progress: Option<(usize, usize)>,
+    ///A file to open in the default browser if the export works
+    open_when_done: Option<PathBuf>,
+}
+
+impl RunningExport {
+    ///Starts exporting on a new thread called `name`, with an [`ExportProgress`] to pass on to the export function
+    ///
+    /// # Errors
+    ///
+    /// Can fail if we fail to spawn the thread
+    pub fn start(
+        name: &str,
+        export: impl FnOnce(&ExportProgress) -> color_eyre::Result<usize> + Send + 'static,
+    ) -> io::Result<Self> {
+        let (progress_tx, progress_rx) = channel();
+        let (cancel_tx, cancel_rx) = channel();
+
+        let handle = std::thread::Builder::new().name(name.into()).spawn(move || {
+            export(&ExportProgress {
+                progress_tx,
+                cancel_rx,
+            })
+        })?;
+
+        Ok(Self {
+            handle,
+            progress_rx,
+            cancel_tx,
+            progress: None,
+            open_when_done: None,
+        })
+    }
+
+    ///Opens `file` in the default browser once the export has worked, eg. for the "Open after export" checkbox
+    #[must_use]
+    pub fn open_when_done(mut self, file: PathBuf) -> Self {
+        self.open_when_done = Some(file);
+        self
+    }
+
+    ///Shows a progress bar for the export, with a button to cancel it
+    pub fn display(&mut self, ui: &mut Ui) {
+        if let Some(progress) = self.progress_rx.try_iter().last() {
+            self.progress = Some(progress); //we only care about the latest
+        }
+
+        match self.progress {
+            Some((written, total)) => {
+                ProgressBar::new(if total == 0 { 1.0 } else { written as f32 / total as f32 })
+                    .text(format!("Exported {} of {} KiB", written / 1024, total / 1024))
+                    .ui(ui);
+            }
+            None => {
+                ui.label("Preparing the export...");
+            }
+        }
+        if ui.button("Cancel Export").clicked() {
+            info!("Cancelling export");
+            let _ = self.cancel_tx.send(()); //if it has already finished, there's nothing to cancel
+        }
+
+        ui.ctx().request_repaint_after(REPAINT_INTERVAL);
+    }
+
+    ///Whether the export has finished, one way or another
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    ///Waits for the export to finish, giving back why it failed if it did - to show to the user
+    #[must_use]
+    pub fn join(self) -> Option<String> {
+        match self.handle.join() {
+            Ok(Ok(n)) => {
+                trace!(?n, "Finished export");
+                let file = self.open_when_done?;
+                open::that(&file).err().map(|e| {
+                    error!(?e, ?file, "Unable to open export");
+                    format!("exported, but unable to open {}: {e}", file.display())
+                })
+            }
+            Ok(Err(e)) => {
+                error!(?e, "Unable to export");
+                Some(format!("{e:#}")) //show the whole chain, so the user knows which file was the problem
+            }
+            Err(_) => Some("the export thread panicked".into()),
+        }
+    }
+}
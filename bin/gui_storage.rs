@@ -0,0 +1,195 @@
+//! Module for persisting the state of the GUI programs between shutdowns.
+//!
+//! Each program's state gets stored as one JSON blob in eframe's [`Storage`], under its own key. Older versions stored lists joined with [`EGUI_STORAGE_SEPARATOR`], which broke when a path or argument had the separator in it and silently dropped any path which wasn't UTF-8 - so paths get stored as raw bytes when they aren't UTF-8, and the old keys are only read to migrate from them.
+
+use crate::theme::Theme;
+use benchmarker::{format::DurationFormat, EGUI_STORAGE_SEPARATOR};
+use eframe::Storage;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+///The key for the [`SetupStorage`]
+pub const SETUP_KEY: &str = "setup_state";
+///The key for the [`ExporterStorage`]
+pub const EXPORTER_KEY: &str = "exporter_state";
+
+///A path which can be stored without losing anything, even if it isn't UTF-8
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum StoredPath {
+    ///A UTF-8 path, stored as a string so the JSON is easy to read
+    Utf8(String),
+    ///The raw bytes of a path which isn't UTF-8
+    #[cfg(unix)]
+    Bytes(Vec<u8>),
+    ///The raw wide characters of a path which isn't valid unicode
+    #[cfg(windows)]
+    Wide(Vec<u16>),
+}
+
+impl From<&Path> for StoredPath {
+    #[allow(clippy::option_if_let_else)] //the cfgs don't fit into a map_or_else
+    fn from(path: &Path) -> Self {
+        match path.to_str() {
+            Some(path) => Self::Utf8(path.to_string()),
+            #[cfg(unix)]
+            None => Self::Bytes(std::os::unix::ffi::OsStrExt::as_bytes(path.as_os_str()).to_vec()),
+            #[cfg(windows)]
+            None => Self::Wide(std::os::windows::ffi::OsStrExt::encode_wide(path.as_os_str()).collect()),
+            #[cfg(not(any(unix, windows)))]
+            None => Self::Utf8(path.to_string_lossy().to_string()), //nowhere else to put it, so this is the best we can do
+        }
+    }
+}
+
+impl From<StoredPath> for PathBuf {
+    fn from(path: StoredPath) -> Self {
+        match path {
+            StoredPath::Utf8(path) => Self::from(path),
+            #[cfg(unix)]
+            StoredPath::Bytes(bytes) => Self::from(<std::ffi::OsString as std::os::unix::ffi::OsStringExt>::from_vec(bytes)),
+            #[cfg(windows)]
+            StoredPath::Wide(wide) => Self::from(<std::ffi::OsString as std::os::windows::ffi::OsStringExt>::from_wide(&wide)),
+        }
+    }
+}
+
+///What gets stored for the runner GUI - everything from the setup screen, apart from the agent token
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SetupStorage {
+    ///The binary to bench
+    pub binary: Option<StoredPath>,
+    ///The CLI arguments to pass to it
+    pub cli_args: Vec<String>,
+    ///The number of runs, as it was typed in
+    pub runs: Option<String>,
+    ///Whether or not to do a warmup run, from older versions which only had a checkbox - this is only read to migrate to `warmup_runs`
+    pub warmup: Option<bool>,
+    ///The address of the agent to run on
+    pub remote_address: Option<String>,
+    ///The tags for the session, as they were typed in
+    #[serde(default)]
+    pub tags: Option<String>,
+    ///Whether to use the operating system's file dialog rather than the in-window one
+    #[serde(default)]
+    pub native_dialogs: bool,
+    ///How to write times in the labels
+    #[serde(default)]
+    pub duration_format: DurationFormat,
+    ///How much to scale the whole GUI by
+    #[serde(default)]
+    pub ui_scale: Option<f32>,
+    ///Which colours to draw the GUI in
+    #[serde(default)]
+    pub theme: Theme,
+    ///The whole commands to compare the binary against, as they were typed in
+    #[serde(default)]
+    pub compare_commands: Vec<String>,
+    ///The number of warmup runs
+    #[serde(default)]
+    pub warmup_runs: Option<u8>,
+    ///Whether to run a command line through the shell instead of the binary
+    #[serde(default)]
+    pub shell: bool,
+    ///The command line to run through the shell, as it was typed in
+    #[serde(default)]
+    pub shell_command: Option<String>,
+}
+
+impl SetupStorage {
+    ///Reads the setup from the separator-joined keys that older versions used
+    fn from_old_keys(storage: &dyn Storage) -> Option<Self> {
+        let cli_args = storage.get_string("cli_args");
+        let binary = storage.get_string("binary_path");
+        if cli_args.is_none() && binary.is_none() {
+            return None; //nothing was ever saved
+        }
+
+        Some(Self {
+            binary: binary.map(StoredPath::Utf8),
+            cli_args: split_old_list(cli_args),
+            runs: storage.get_string("runs"),
+            warmup: storage.get_string("warmup").and_then(|s| s.parse().ok()),
+            remote_address: storage.get_string("remote_address"),
+            tags: None,
+            native_dialogs: false,
+            duration_format: DurationFormat::default(),
+            ui_scale: None,
+            theme: Theme::default(),
+            compare_commands: vec![],
+            warmup_runs: None,
+            shell: false,
+            shell_command: None,
+        })
+    }
+}
+
+///What gets stored for the exporter GUI
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ExporterStorage {
+    ///The files we had traces from
+    pub files: Vec<StoredPath>,
+    ///The files which were added most recently, newest first
+    #[serde(default)]
+    pub recent_files: Vec<StoredPath>,
+    ///Whether to use the operating system's file dialog rather than the in-window one
+    #[serde(default)]
+    pub native_dialogs: bool,
+    ///How much to scale the whole GUI by
+    #[serde(default)]
+    pub ui_scale: Option<f32>,
+    ///Which colours to draw the GUI in
+    #[serde(default)]
+    pub theme: Theme,
+}
+
+impl ExporterStorage {
+    ///Reads the files from the separator-joined key that older versions used
+    fn from_old_keys(storage: &dyn Storage) -> Option<Self> {
+        storage.get_string("files").map(|files| Self {
+            files: split_old_list(Some(files)).into_iter().map(StoredPath::Utf8).collect(),
+            recent_files: vec![],
+            native_dialogs: false,
+            ui_scale: None,
+            theme: Theme::default(),
+        })
+    }
+}
+
+///Splits a list stored by older versions
+fn split_old_list(list: Option<String>) -> Vec<String> {
+    match list {
+        Some(list) if !list.is_empty() => list.split(EGUI_STORAGE_SEPARATOR).map(ToString::to_string).collect(),
+        _ => vec![], //"".split(/* anything */) returns vec![""], which we don't want
+    }
+}
+
+///Reads a JSON value from the storage, if it is there and valid
+fn load_json<T: DeserializeOwned>(storage: &dyn Storage, key: &str) -> Option<T> {
+    let json = storage.get_string(key)?;
+    serde_json::from_str(&json)
+        .map_err(|e| warn!(?e, %key, "Unable to read stored state"))
+        .ok()
+}
+
+///Writes a value to the storage as JSON
+pub fn save_json(storage: &mut dyn Storage, key: &str, value: &impl Serialize) {
+    match serde_json::to_string(value) {
+        Ok(json) => storage.set_string(key, json),
+        Err(e) => error!(?e, %key, "Unable to store state"),
+    }
+}
+
+///Loads the runner GUI's setup, falling back to the keys from older versions
+pub fn load_setup(storage: Option<&dyn Storage>) -> SetupStorage {
+    storage
+        .and_then(|s| load_json(s, SETUP_KEY).or_else(|| SetupStorage::from_old_keys(s)))
+        .unwrap_or_default()
+}
+
+///Loads the exporter GUI's state, falling back to the key from older versions
+pub fn load_exporter(storage: Option<&dyn Storage>) -> ExporterStorage {
+    storage
+        .and_then(|s| load_json(s, EXPORTER_KEY).or_else(|| ExporterStorage::from_old_keys(s)))
+        .unwrap_or_default()
+}
@@ -0,0 +1,124 @@
+//! Binary part for viewing the history of a command in a CLI.
+//!
+//! Every session of the runner and suite appends its summary to the per-user history file. This pulls out every session for one command, prints them as a table with the change from the previous session, and exports a plot of the mean over time. Sessions can be filtered by their tags, and split up into a table and a line for each value of a tag.
+
+use benchmarker::{
+    exit_reason::ExitReason,
+    format::DurationFormat,
+    history::{command_hash, export_grouped_history_html, group_by_tag, history_file, read_history, HistoryEntry},
+    tags::{matches_filter, parse_tag, tags_to_string},
+};
+use crate::{failure, runner_cli::DurationFormatArgs};
+use clap::Parser;
+use color_eyre::eyre::WrapErr;
+use owo_colors::OwoColorize;
+use std::{ffi::OsStr, path::PathBuf, time::Duration};
+
+/// The CLI args for viewing the history of a command
+#[derive(Clone, Debug, Parser)] //struct for CLI args which can be parsed/cloned/printed
+pub struct HistoryCLIArgs {
+    ///The command to view, in the same form as the runner CLI - the binary, then any arguments separated by spaces
+    command: String,
+    ///The file to export the plot to, without extension. This defaults to the binary's name with `_history`
+    #[arg(short = 'f', long)]
+    export_out_file: Option<String>,
+    ///Only show sessions with this tag, like `machine=ryzen-box`. Can be given more than once, and sessions need all of them
+    #[arg(long = "tag", value_parser = parse_tag)]
+    tags: Vec<(String, String)>,
+    ///Split the sessions up by the value of this tag, with a table and a line on the plot for each value
+    #[arg(long)]
+    group_by_tag: Option<String>,
+    ///How to write the times in the table
+    #[command(flatten)]
+    duration_format: DurationFormatArgs,
+}
+
+///Run the history CLI
+#[instrument]
+pub fn run(
+    HistoryCLIArgs {
+        command,
+        export_out_file,
+        tags,
+        group_by_tag: group_by,
+        duration_format,
+    }: HistoryCLIArgs,
+) -> color_eyre::Result<()> {
+    let duration_format = DurationFormat::from(duration_format);
+    let mut parts = command.split(' ').filter(|s| !s.is_empty()); //split the same way as the runner CLI args
+    let Some(binary) = parts.next().map(PathBuf::from) else {
+        return Err(failure(ExitReason::InvalidArguments, "need a command to view the history of"));
+    };
+    let cli_args: Vec<String> = parts.map(ToString::to_string).collect();
+
+    let entries = read_history(command_hash(&binary, &cli_args))
+        .wrap_err_with(|| format!("unable to read the history for {command} from {:?}", history_file()))?;
+
+    let entries: Vec<HistoryEntry> = entries.into_iter().filter(|entry| matches_filter(&entry.tags, &tags)).collect();
+
+    if entries.is_empty() {
+        println!(
+            "No history found for {} in {:?}",
+            command.italic(),
+            history_file()
+        );
+        return Ok(());
+    }
+
+    println!("{} {}", "History:".bold(), entries[0].command.italic());
+
+    let groups = match &group_by {
+        Some(key) => group_by_tag(entries, key),
+        None => vec![("Mean".to_string(), entries)],
+    };
+    for (name, entries) in &groups {
+        if group_by.is_some() {
+            println!("\n{}", name.bold());
+        }
+        print_entries(entries, &duration_format);
+    }
+
+    let export_out_file = export_out_file.unwrap_or_else(|| {
+        let bin_name = binary
+            .file_name()
+            .and_then(OsStr::to_str)
+            .unwrap_or("bench_results");
+        format!("{bin_name}_history")
+    });
+    let n = export_grouped_history_html(&groups, &export_out_file)
+        .wrap_err(ExitReason::ExportFailed)
+        .wrap_err_with(|| format!("unable to export the history plot to {export_out_file}.html"))?;
+    trace!(?n, "Finished exporting");
+    println!("Exported plot to {export_out_file}.html");
+
+    Ok(())
+}
+
+///Prints a table of sessions, with the change in the mean from the previous one
+fn print_entries(entries: &[HistoryEntry], duration_format: &DurationFormat) {
+    let mut previous_mean = None;
+    for entry in entries {
+        let mean = Duration::from_secs_f64(entry.mean_micros / 1_000_000.0);
+        let standard_deviation = Duration::from_secs_f64(entry.standard_deviation_micros / 1_000_000.0);
+
+        let change = previous_mean.map_or_else(String::new, |previous: f64| {
+            let change = (entry.mean_micros - previous) / previous * 100.0; //percentage change from the last session
+            let change = format!("{change:+.2}%");
+            if change.starts_with('+') {
+                change.bright_red().to_string()
+            } else {
+                change.bright_green().to_string()
+            }
+        });
+        previous_mean = Some(entry.mean_micros);
+
+        println!(
+            "{}: {} ± {} : {} {change} {}",
+            entry.time.format("%Y-%m-%d %H:%M:%S"),
+            duration_format.duration(mean).bright_green(),
+            duration_format.duration(standard_deviation).bright_green(),
+            entry.runs.bright_white(),
+            tags_to_string(&entry.tags).dimmed(),
+        );
+    }
+}
@@ -9,17 +9,34 @@
 
 //imports
 use crate::{
-    exporter_cli::ExporterCLIArgs, exporter_gui::ExporterApp, runner_cli::FullCLIArgs,
-    runner_gui::BencherApp,
+    agent_cli::AgentCLIArgs, cargo_cli::CargoCLIArgs, compare_cli::CompareCLIArgs, exporter_cli::ExporterCLIArgs, exporter_gui::ExporterApp, history_cli::HistoryCLIArgs, man_cli::ManCLIArgs,
+    runner_cli::FullCLIArgs, runner_gui::BencherApp, serve_cli::ServeCLIArgs,
+    suite_cli::SuiteCLIArgs,
 };
+use benchmarker::exit_reason::ExitReason;
 use clap::Parser;
+use color_eyre::{eyre::eyre, Report};
+use std::{fmt::Display, process::ExitCode};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Registry};
 use tracing_tree::HierarchicalLayer;
 
+mod agent_cli;
+mod baseline;
+mod cargo_cli;
+mod compare_cli;
 mod exporter_cli;
 mod exporter_gui;
+mod gui_export;
+mod gui_storage;
+mod native_dialog;
+mod history_cli;
+mod man_cli;
 mod runner_cli;
 mod runner_gui;
+mod serve_cli;
+mod suite_cli;
+mod theme;
+mod ui_scale;
 mod egui_utils;
 
 //allow me to use tracing macros (eg. info! etc) without needing to import all of them.
@@ -29,6 +46,7 @@ extern crate tracing;
 #[derive(Clone, Debug, strum::Display, Parser)] //allow me to print/clone the enum, as well as to parse it as CLI args
 #[command(author, version, about, long_about = None)] //use the author/version/about from the Cargo.toml file
 ///CLI arguments
+#[allow(clippy::large_enum_variant)] //only ever made once, so the size doesn't matter
 pub enum Args {
     ///Collate together different runs in a GUI
     ExporterGUI,
@@ -38,9 +56,35 @@ pub enum Args {
     ExporterCLI(ExporterCLIArgs),
     ///Make runs and quickly export them in a CLI
     RunnerCLI(FullCLIArgs),
+    ///Run a whole suite of benchmarks from a TOML file in a CLI
+    Suite(SuiteCLIArgs),
+    ///Compare two builds of the same binary, interleaving their runs, and decide whether the change helped
+    Compare(CompareCLIArgs),
+    ///View the history of a command, and plot the mean over time
+    History(HistoryCLIArgs),
+    ///Serve a directory of results over HTTP, with interactive plots
+    Serve(ServeCLIArgs),
+    ///Run benchmarks for other machines, which connect with `--remote`
+    Agent(AgentCLIArgs),
+    ///Write man pages for precipice and all of its subcommands
+    Man(ManCLIArgs),
+    ///Build a binary from a Cargo project in release mode and benchmark it, taking Cargo-style arguments - this is what `cargo precipice` runs
+    Cargo(CargoCLIArgs),
 }
 
-fn main() {
+///Makes an error with a message, which makes us exit with the code for `reason`
+#[track_caller] //so the error's location is where it failed, rather than here
+pub fn failure(reason: ExitReason, message: impl Display + Send + Sync + 'static) -> Report {
+    Report::new(reason).wrap_err(message)
+}
+
+fn main() -> ExitCode {
+    if let Err(e) = color_eyre::install() {
+        //so errors come out with their context, rather than as raw panics
+        eprintln!("Error: {e:?}");
+        return ExitReason::Error.into();
+    }
+
     //setup tracing and tracing-tree via tracing-subscriber from the environment variables
     Registry::default()
         .with(EnvFilter::from_default_env())
@@ -52,18 +96,25 @@ fn main() {
         )
         .init();
 
-    match Args::parse() {
+    let result = match Args::parse() {
         //switch statement on the arguments, parsed from the CLI, which is an enum, so we switch on that enum
         Args::ExporterCLI(args) => exporter_cli::run(args),
         Args::RunnerCLI(args) => runner_cli::run(args),
+        Args::Suite(args) => suite_cli::run(args),
+        Args::Compare(args) => compare_cli::run(args),
+        Args::History(args) => history_cli::run(args),
+        Args::Serve(args) => serve_cli::run(args),
+        Args::Agent(args) => agent_cli::run(args),
+        Args::Man(args) => man_cli::run(args),
+        Args::Cargo(args) => cargo_cli::run(args),
         Args::ExporterGUI => {
             eframe::run_native(
                 //Run a new native window with default options, and the ExporterApp
                 "Precipice Exporter",
                 eframe::NativeOptions::default(),
-                Box::new(|cc| Box::new(ExporterApp::new(cc.storage))),
+                Box::new(|cc| Box::new(ExporterApp::new(cc.storage, cc.egui_ctx.clone()))),
             )
-            .expect("Error with eframe");
+            .map_err(|e| eyre!("unable to open the exporter window: {e}")) //eframe's errors aren't always Send, so we can't just wrap them
         }
         Args::RunnerGUI => {
             eframe::run_native(
@@ -72,7 +123,15 @@ fn main() {
                 eframe::NativeOptions::default(),
                 Box::new(|cc| Box::new(BencherApp::new(cc))),
             )
-            .expect("Error with eframe");
+            .map_err(|e| eyre!("unable to open the runner window: {e}"))
+        }
+    };
+
+    match result {
+        Ok(()) => ExitReason::Success.into(),
+        Err(e) => {
+            eprintln!("Error: {e:?}"); //the same as returning the error from main would print
+            e.downcast_ref::<ExitReason>().copied().unwrap_or(ExitReason::Error).into() //so scripts can tell what went wrong
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,34 @@
+//! Binary part for generating man pages.
+//!
+//! This renders a roff man page for `precipice` and one for each subcommand (eg. `precipice-runner-cli.1`) straight from the `clap` definitions, so packages can ship manuals which never go out of date with the real arguments.
+
+use crate::Args;
+use clap::{CommandFactory, Parser};
+use color_eyre::eyre::WrapErr;
+use owo_colors::OwoColorize;
+use std::path::PathBuf;
+
+///The name to give the command in the man pages - otherwise `clap` would use the package name
+const BINARY_NAME: &str = "precipice";
+
+/// The CLI args for generating man pages
+#[derive(Clone, Debug, Parser)] //struct for CLI args which can be parsed/cloned/printed
+pub struct ManCLIArgs {
+    ///The directory to write the man pages to - it gets made if it doesn't exist
+    #[arg(short, long, default_value = "man")]
+    out_dir: PathBuf,
+}
+
+///Run the man page generator
+#[instrument]
+pub fn run(ManCLIArgs { out_dir }: ManCLIArgs) -> color_eyre::Result<()> {
+    std::fs::create_dir_all(&out_dir).wrap_err_with(|| format!("unable to make {}", out_dir.display()))?;
+
+    let command = Args::command().name(BINARY_NAME).bin_name(BINARY_NAME);
+    let no_pages = command.get_subcommands().count() + 1; //one for each subcommand, and one for precipice itself
+    clap_mangen::generate_to(command, &out_dir)
+        .wrap_err_with(|| format!("unable to write man pages to {}", out_dir.display()))?;
+
+    println!("{} {no_pages} man pages to {}", "Wrote".bold(), out_dir.display().italic());
+    Ok(())
+}
@@ -0,0 +1,21 @@
+//! Module for picking files with the operating system's own file dialog, as an alternative to the in-window [`egui_file`] dialog.
+//!
+//! The native dialog has the OS's favourites and network drives, and stays quick in huge directories. It blocks until it gets closed, which is fine as nothing else can happen in the window whilst picking a file anyway.
+
+use std::path::{Path, PathBuf};
+
+///Picks a file with the native dialog, starting next to `start` if we have it. The `filter` is a name and the extensions to show, eg. `("Results", &["csv", "json"])`
+#[instrument]
+pub fn pick_file(start: Option<&Path>, filter: Option<(&str, &[&str])>) -> Option<PathBuf> {
+    let mut dialog = rfd::FileDialog::new();
+    if let Some(directory) = start.and_then(|start| if start.is_dir() { Some(start) } else { start.parent() }) {
+        dialog = dialog.set_directory(directory); //open in the same place as last time, like the egui dialog does
+    }
+    if let Some((name, extensions)) = filter {
+        dialog = dialog.add_filter(name, extensions);
+    }
+
+    let picked = dialog.pick_file();
+    trace!(?picked, "Native dialog closed");
+    picked
+}
@@ -1,27 +1,72 @@
 //! Binary part for running stuff in a CLI
 
-use benchmarker::{bencher::{calculate_mean_standard_deviation, Runner, DEFAULT_RUNS}, io::ExportType};
-use clap::Parser;
-use indicatif::{ProgressBar, ProgressStyle};
+use benchmarker::{bencher::{multi::MultiRunner, calculate_mean_standard_deviation, shell_command_line, calculate_median_mad_iqr, calculate_percentiles, calculate_trimmed_mean, calculate_winsorized_mean_standard_deviation, ramdisk::RamCopyOptions, remote::RemoteRunner, timing::{SelfReportedTiming, TimingUnit}, PipelineStage, RunMeasurement, RunOutcomes, Runner, DEFAULT_RUNS, SUMMARY_PERCENTILES}, build_command::run_build_command, cargo::{build_release, git_commit}, compare::{compare, speedups, Verdict, DEFAULT_SIGNIFICANCE_LEVEL}, debug_build::debug_build_warning, diagnostics::diagnose, environment::check_environment, events::{EventPublisher, SessionEvent}, exit_reason::ExitReason, export_command::{run_export_command, ResultDocument}, format::{DurationFormat, TimeFormat, DEFAULT_DECIMALS}, github::write_github_summary, history::{append_to_history, command_hash, last_session, read_history, HistoryEntry}, io::ExportType, lock::{default_lock_path, lock_holder, BenchmarkLock, LockMode}, memory::{export_memory, peak_memory}, metrics::{export_metrics, MetricsSampler, DEFAULT_SAMPLE_INTERVAL}, parameters::parameter_tags, profile::{export_flamegraph, profile_runs, DEFAULT_SAMPLE_FREQUENCY}, provenance::{quote_command, Provenance}, prometheus::{export_prometheus, prometheus_text, push_to_gateway}, report::SessionReport, stats::Statistic, syscalls::{count_syscalls, export_syscalls}, tags::{parse_tag, Tags, TraceTags}, terminal::{histogram_lines, sparkline, DEFAULT_HISTOGRAM_BINS, DEFAULT_HISTOGRAM_WIDTH, DEFAULT_SPARKLINE_RUNS}, upload::{upload_session, UploadFormat, UploadTarget}, webhook::{Webhook, WebhookEvent}};
+use clap::{Args, Parser};
+use color_eyre::eyre::{bail, eyre, WrapErr};
+use crate::failure;
+use indicatif::{ProgressBar, ProgressState, ProgressStyle};
 use owo_colors::OwoColorize;
-use std::{ffi::OsStr, path::PathBuf, sync::mpsc::channel, time::Duration};
+use std::{
+    ffi::OsStr,
+    num::NonZeroUsize,
+    collections::VecDeque,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::channel,
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+///Prints a line for the user - to stdout normally, but to stderr when stdout is taken up by `--json-lines`
+macro_rules! say {
+    ($json_lines:expr) => {
+        if $json_lines {
+            eprintln!()
+        } else {
+            println!()
+        }
+    };
+    ($json_lines:expr, $($arg:tt)*) => {
+        if $json_lines {
+            eprintln!($($arg)*)
+        } else {
+            println!($($arg)*)
+        }
+    };
+}
 
 /// The CLI args for running stuff
 #[derive(Clone, Debug, Parser)] //struct for CLI args which can be parsed/cloned/printed
+#[allow(clippy::struct_excessive_bools)] //they're all independent flags
 pub struct FullCLIArgs {
     ///The actual binary to run
-    #[arg(short, long)]
-    binary: PathBuf,
-    ///The CLI arguments to pass to the binary
-    #[arg(short, long)]
-    cli_args: Option<String>,
+    #[arg(short, long, required_unless_present_any = ["cargo_bin", "commands"])]
+    binary: Option<PathBuf>,
+    ///A whole command to compare, like `--command "./old_build --fast"`. Give this more than once instead of `--binary` to compare the commands in one session - their runs get interleaved, and each one gets its own trace in the export. Arguments can be quoted like in a shell
+    #[arg(long = "command", value_name = "COMMAND", conflicts_with_all = [
+        "binary", "cargo_bin", "cli_args", "export_trace_name", "timing_regex", "pipe_into", "copy_to_ram", "sample_metrics", "export_memory", "profile_runs",
+        "syscall_runs", "print_initial", "json_lines", "show_output_every", "no_histogram", "trim_percent", "stats", "report", "no_compare_last", "remote",
+        "upload_url", "github_summary", "prometheus", "pushgateway", "webhook_url", "events", "export_commands",
+    ])]
+    commands: Vec<String>,
+    ///Run the binary and its arguments as a command line through `sh -c` (or `cmd /C` on Windows), so it can be a whole shell pipeline like `--shell -b "grep foo big.txt | wc -l"`. The time includes starting the shell, and any arguments after `--` get added to the command line as they are, so they need quoting for the shell. With `--command`, each command gets run through the shell instead of being split
+    #[arg(long, default_value_t = false, conflicts_with_all = ["cargo_bin", "copy_to_ram", "profile_runs", "syscall_runs"])]
+    shell: bool,
+    ///How to build the binary before the session, if at all
+    #[command(flatten)]
+    build: BuildArgs,
+    ///The CLI arguments to pass to the binary, after a `--` - these get passed on exactly as they are, so they can have spaces or start with `-`
+    #[arg(last = true)]
+    cli_args: Vec<String>,
     ///The number of runs (excluding warm-up runs)
     #[arg(short, long, default_value_t = DEFAULT_RUNS)]
     runs: usize,
-    ///Whether or not we should have a warmup run where the results aren't sent to get the program into the cache
-    #[arg(short = 'w', long, default_value_t = 0)]
+    ///The number of warmup runs before the measured ones - their results aren't kept, they just get the program into the cache
+    #[arg(short = 'w', long = "warmup", alias = "no-warmup-runs", value_name = "N", default_value_t = 0)]
     no_warmup_runs: u8,
-    ///How to export the data - a csv with the microsecond values, or an HTML graph
+    ///How to export the data - a csv with the microsecond values, an HTML graph, or a Vega-Lite spec
     #[arg(value_enum, short = 't', long, default_value_t = ExportType::CSV)]
     export_ty: ExportType,
     ///The file to export to, without extension. This defaults to the binary's name
@@ -30,33 +75,343 @@ pub struct FullCLIArgs {
     ///The trace name to export as. This is the name of the line in the HTML graph and defaults to the binary's name
     #[arg(short = 'n', long)]
     export_trace_name: Option<String>,
+    ///How long a single run can take in milliseconds, before it (and anything it spawned) gets killed and skipped
+    #[arg(long)]
+    timeout_ms: Option<u64>,
+    ///Whether or not we should pad the environment by a random amount for each run, to average out any bias from the memory layout
+    #[arg(long, default_value_t = false)]
+    randomize_environment: bool,
+    ///A regex to find the binary's own timing in its stdout, to use instead of timing the whole process. The number is the capture group called `time`, or the first capture group, or the whole match
+    #[arg(long)]
+    timing_regex: Option<String>,
+    ///The unit of the timing found with `--timing-regex`
+    #[arg(value_enum, long, default_value_t = TimingUnit::Nanoseconds, requires = "timing_regex")]
+    timing_unit: TimingUnit,
+    ///A command to pipe the output of the binary into, eg. `wc -l` - quote any arguments with spaces, like `grep "two words"`. This can be given more than once to make a longer pipeline, and each run is timed until every command has finished
+    #[arg(long)]
+    pipe_into: Vec<String>,
+    ///Whether or not we should copy the binary to a RAM-backed directory (eg. `/dev/shm`) and run it from there, to avoid any disk reads. The runs then use that directory as their working directory
+    #[arg(long, default_value_t = false)]
+    copy_to_ram: bool,
+    ///The RAM-backed directory to copy to, if the platform doesn't have a default
+    #[arg(long, requires = "copy_to_ram")]
+    ram_dir: Option<PathBuf>,
+    ///A data file to copy next to the binary in the RAM-backed directory. This can be given more than once
+    #[arg(long, requires = "copy_to_ram")]
+    ram_data_file: Vec<PathBuf>,
+    ///Whether or not we should sample the CPU utilisation, memory and CPU temperature during the session, exporting them to `<export_out_file>_metrics`
+    #[arg(long, default_value_t = false)]
+    sample_metrics: bool,
+    ///How often to sample the metrics, in milliseconds
+    #[arg(long, default_value_t = DEFAULT_SAMPLE_INTERVAL.as_millis() as u64, requires = "sample_metrics")]
+    metrics_interval_ms: u64,
+    ///Whether or not we should also export the peak memory (resident set size) of each run to `<export_out_file>_memory`, in the same format as the times
+    #[arg(long, default_value_t = false)]
+    export_memory: bool,
+    ///The number of extra runs to profile with `perf` (or `dtrace` on macOS) once the benchmark is done, exporting a flamegraph to `<export_out_file>_flamegraph.svg`. These runs aren't timed
+    #[arg(long)]
+    profile_runs: Option<usize>,
+    ///How many samples the profiler takes every second
+    #[arg(long, default_value_t = DEFAULT_SAMPLE_FREQUENCY, requires = "profile_runs")]
+    profile_frequency: u32,
+    ///The number of extra runs to count the syscalls of with `strace -c` once the benchmark is done, exporting them to `<export_out_file>_syscalls`. These runs aren't timed, and this only works on Linux
+    #[arg(long)]
+    syscall_runs: Option<usize>,
     ///Whether or not we should print the inital run.
     #[arg(short, long, default_value_t = false)]
     print_initial: bool,
+    ///Whether or not we should print each run to stdout as a line of JSON with its index, duration in microseconds and exit code, as soon as it finishes. Everything else we print goes to stderr instead, so stdout can be piped straight into `jq`
+    #[arg(long, default_value_t = false)]
+    json_lines: bool,
+    ///Print the output of every nth measured run to stderr, to check the binary is still doing real work deep into a long session. Those runs are still measured, but have to hand their output over to us
+    #[arg(long)]
+    show_output_every: Option<NonZeroUsize>,
+    ///Whether or not we should skip printing a histogram of the runs in the summary
+    #[arg(long, default_value_t = false)]
+    no_histogram: bool,
+    ///Also print the trimmed mean and the winsorized mean ± standard deviation, dropping or clamping this percentage of the fastest and slowest runs (eg. `5` for 5% from each end). These are less affected by a fat tail of slow runs than the plain mean, and go in the report too
+    #[arg(long, value_parser = parse_trim_percent)]
+    trim_percent: Option<f64>,
+    ///Only print these statistics in the summary (and the report), rather than the usual ones - eg. `mean,median,p99,max`. The options are runs, mean, sd, median, mad, iqr, min, max, percentiles like p99 or p99.9, and trimmed or winsorized means (with 5% trimmed from each end, or eg. trimmed10)
+    #[arg(long, value_delimiter = ',')]
+    stats: Vec<Statistic>,
+    ///Whether or not we should also export a full HTML report, with the command, summary statistics, warnings, a histogram and a run-sequence chart, to `<export_out_file>_report.html`
+    #[arg(long, default_value_t = false)]
+    report: bool,
+    ///Whether or not we should open the HTML files we export (the HTML export and the report) in the default browser once the runs are done
+    #[arg(long, default_value_t = false)]
+    open: bool,
+    ///Whether or not we should refuse to start if the machine looks unfit for benchmarking (eg. on battery, or with frequency scaling)
+    #[arg(long, default_value_t = false)]
+    abort_on_environment_warnings: bool,
+    ///Whether or not we should skip adding this session to the per-user history
+    #[arg(long, default_value_t = false)]
+    no_history: bool,
+    ///Whether or not we should skip printing the change in the mean since the last session of the same command in the history
+    #[arg(long, default_value_t = false)]
+    no_compare_last: bool,
+    ///A tag for the session, like `machine=ryzen-box` or `commit=abc123`. This can be given more than once, and the tags go into the history, the report and CSV exports so sessions can be filtered and grouped by them later
+    #[arg(long = "tag", value_parser = parse_tag)]
+    tags: Vec<(String, String)>,
+    ///A parameter of this session if it's part of a sweep, like `threads=4`. This can be given more than once, and gets recorded as a `param.` tag so the exporter can plot sessions against it with `--by-parameter`
+    #[arg(long = "param", value_parser = parse_tag)]
+    parameters: Vec<(String, String)>,
+    ///The address of a `precipice agent` to run the benchmark on, eg. `quiet-box:8081`. The binary path is then on that machine
+    #[arg(long)]
+    remote: Option<String>,
+    ///The token the remote agent was started with
+    #[arg(long, requires = "remote")]
+    remote_token: Option<String>,
+    ///Where else to send the results, once the runs are done
+    #[command(flatten)]
+    integrations: IntegrationArgs,
+    ///How to write the times in the summary and the report
+    #[command(flatten)]
+    duration_format: DurationFormatArgs,
+    ///Whether to stop other sessions running at the same time
+    #[command(flatten)]
+    lock: LockArgs,
+}
+
+///CLI Arguments for locking the machine whilst benchmarking - shared with the other CLIs, so none of them run at the same time
+#[derive(Clone, Debug, Args)]
+pub struct LockArgs {
+    ///Take a machine-wide lock, so other sessions with `--lock` can't run at the same time and inflate each other's times. If another session has it, `--lock` (or `--lock wait`) waits for it to finish, and `--lock fail` stops straight away
+    #[arg(value_enum, long, num_args = 0..=1, default_missing_value = "wait")]
+    lock: Option<LockMode>,
+    ///The file to lock, if not `precipice.lock` in the temporary directory
+    #[arg(long, requires = "lock")]
+    lock_file: Option<PathBuf>,
+}
+
+impl LockArgs {
+    ///Takes the lock if it was asked for, waiting or failing if another session has it. The lock is held until the result gets dropped
+    ///
+    /// # Errors
+    ///
+    /// Fails if another session has the lock in [`LockMode::Fail`], or if we can't lock the file
+    pub fn acquire(self) -> color_eyre::Result<Option<BenchmarkLock>> {
+        let Some(mode) = self.lock else {
+            return Ok(None);
+        };
+        let path = self.lock_file.unwrap_or_else(default_lock_path);
+
+        if let Some(lock) = BenchmarkLock::try_acquire(&path).wrap_err_with(|| format!("unable to lock {}", path.display()))? {
+            return Ok(Some(lock));
+        }
+        let holder = lock_holder(&path).map_or_else(String::new, |pid| format!(" (PID {pid})"));
+        if mode == LockMode::Fail {
+            bail!("another session{holder} is already benchmarking - it has the lock on {}", path.display());
+        }
+
+        eprintln!("{} for another session{holder} to finish benchmarking...", "Waiting".yellow().bold());
+        Ok(Some(BenchmarkLock::acquire(&path).wrap_err_with(|| format!("unable to lock {}", path.display()))?))
+    }
+}
+
+///CLI Arguments for building the binary before the session, with a build command or Cargo
+#[derive(Clone, Debug, Args)]
+pub struct BuildArgs {
+    ///A command to build the binary with before the session, like `make release`. It runs before anything gets timed, and the session doesn't start if it fails. Arguments can be quoted like in a shell
+    #[arg(long)]
+    build: Option<String>,
+    ///The name of a binary in a Cargo project to build with `cargo build --release` and then benchmark, rather than giving `--binary`. The session gets tagged with the project's git `commit`
+    #[arg(long, conflicts_with_all = ["binary", "remote"])]
+    cargo_bin: Option<String>,
+    ///The Cargo project to build `--cargo-bin` in, if not the current directory
+    #[arg(long, requires = "cargo_bin")]
+    cargo_project: Option<PathBuf>,
+}
+
+impl BuildArgs {
+    ///Runs the build command, then builds the Cargo binary if one was asked for, giving back where it is and the commit it was built from
+    ///
+    /// # Errors
+    ///
+    /// Fails if either build does
+    pub fn build(self) -> color_eyre::Result<Option<(PathBuf, Option<String>)>> {
+        if let Some(command) = &self.build {
+            eprintln!("{} with {}", "Building".green().bold(), command.italic());
+            run_build_command(command).wrap_err_with(|| format!("unable to build with `{command}`"))?;
+        }
+
+        let Some(bin) = self.cargo_bin else {
+            return Ok(None);
+        };
+        let project = self.cargo_project.unwrap_or_else(|| PathBuf::from("."));
+
+        eprintln!("{} {bin} in {}", "Building".green().bold(), project.display());
+        let binary = build_release(&project, &bin).wrap_err_with(|| format!("unable to build {bin} in {}", project.display()))?;
+        Ok(Some((binary, git_commit(&project))))
+    }
+}
+
+///CLI Arguments for how times get written - shared with the other CLIs, so they all write times the same way
+#[derive(Clone, Debug, Args)]
+pub struct DurationFormatArgs {
+    ///How to write times - `human` picks a unit for each like 1.234ms, and `raw` always writes a plain number of microseconds for scripts
+    #[arg(value_enum, long, default_value_t = TimeFormat::Human)]
+    time_format: TimeFormat,
+    ///How many decimal places to write times with
+    #[arg(long, default_value_t = DEFAULT_DECIMALS)]
+    decimals: usize,
+    ///A character to group the thousands with, like `,` for `12,345.000s`, or `_` for raw times like `1_234_567.000`
+    #[arg(long)]
+    thousands_separator: Option<char>,
+}
+
+impl From<DurationFormatArgs> for DurationFormat {
+    fn from(DurationFormatArgs { time_format, decimals, thousands_separator }: DurationFormatArgs) -> Self {
+        Self::new(time_format)
+            .with_decimals(decimals)
+            .with_thousands_separator(thousands_separator)
+    }
+}
+
+///CLI Arguments for sending the results to other services - kept separate as there are lots of them
+#[derive(Clone, Debug, Args)]
+pub struct IntegrationArgs {
+    ///The URL of a continuous-benchmarking service (eg. bencher.dev) to post the session summary to once the runs are done. This needs `curl`
+    #[arg(long)]
+    upload_url: Option<String>,
+    ///The token for the upload service, sent as a bearer token. If this isn't given, it comes from `PRECIPICE_UPLOAD_TOKEN` if that is set
+    #[arg(long, requires = "upload_url")]
+    upload_token: Option<String>,
+    ///What to post to the upload service - the same summary as the history, or the Bencher Metric Format
+    #[arg(value_enum, long, default_value_t = UploadFormat::Summary)]
+    upload_format: UploadFormat,
+    ///Whether or not we should append a Markdown table of the results to the GitHub Actions job summary, and set the mean (and change from the last session in the history) as step outputs
+    #[arg(long, default_value_t = false)]
+    github_summary: bool,
+    ///Whether or not we should also export the summary in the Prometheus text format to `<export_out_file>.prom`, eg. for the node exporter's textfile collector
+    #[arg(long, default_value_t = false)]
+    prometheus: bool,
+    ///The address of a Prometheus Pushgateway to push the summary to, eg. `http://pushgateway:9091`. This needs `curl`
+    #[arg(long)]
+    pushgateway: Option<String>,
+    ///A webhook URL (eg. for Slack, Discord or Matrix) to notify when the session finishes. This needs `curl`
+    #[arg(long)]
+    webhook_url: Option<String>,
+    ///The JSON payload for the webhook, with placeholders like `{message}` and `{mean_us}` - see the README. Defaults to sending the message as `text` and `content`
+    #[arg(long, requires = "webhook_url")]
+    webhook_template: Option<String>,
+    ///A named pipe or Unix socket to publish the session's progress to as lines of JSON, for editor plugins or dashboards. A socket gets made if nothing is there yet, and pipes need something reading them already - see the README for the events
+    #[arg(long)]
+    events: Option<PathBuf>,
+    ///A command to send the results to once the runs are done, eg. `./my_exporter.sh`. It gets a JSON document with every run, the summary statistics and the tags on its stdin - see the README. This can be given more than once
+    #[arg(long = "export-cmd")]
+    export_commands: Vec<String>,
 }
 
 ///Run the runner CLI
-#[instrument]
+#[instrument(skip(commands, parameters, no_histogram, trim_percent, duration_format, lock, build, no_compare_last, open, should_export_memory))] //spans can only have 32 fields - the parameters get recorded with the tags anyway, and the rest only change what gets printed, opened or exported alongside
+#[allow(clippy::cast_possible_truncation)]
 pub fn run(
     FullCLIArgs {
         //destructure the struct right here to avoid having to do it in the function
         binary,
+        commands,
+        shell,
+        build,
         cli_args,
         runs,
         no_warmup_runs,
         export_ty,
         export_out_file,
         export_trace_name,
+        timeout_ms,
+        randomize_environment,
+        timing_regex,
+        timing_unit,
+        pipe_into,
+        copy_to_ram,
+        ram_dir,
+        ram_data_file,
+        sample_metrics,
+        metrics_interval_ms,
+        export_memory: should_export_memory,
+        profile_runs: no_profile_runs,
+        profile_frequency,
+        syscall_runs: no_syscall_runs,
         print_initial,
+        json_lines,
+        show_output_every,
+        no_histogram,
+        trim_percent,
+        stats,
+        report,
+        open,
+        abort_on_environment_warnings,
+        no_history,
+        no_compare_last,
+        tags,
+        parameters,
+        remote,
+        remote_token,
+        integrations,
+        duration_format,
+        lock,
     }: FullCLIArgs,
-) {
+) -> color_eyre::Result<()> {
+    let IntegrationArgs {
+        upload_url,
+        upload_token,
+        upload_format,
+        github_summary,
+        prometheus,
+        pushgateway,
+        webhook_url,
+        webhook_template,
+        events,
+        export_commands,
+    } = integrations;
+    let duration_format = DurationFormat::from(duration_format);
+    let mut tags: Tags = tags.into_iter().collect(); //if a key is given twice, the last one wins
+    tags.extend(parameter_tags(&parameters.into_iter().collect()));
+    if !commands.is_empty() {
+        build.build()?; //only the build command, as --cargo-bin can't go with --command
+        return run_comparison(ComparisonArgs {
+            commands,
+            shell,
+            runs,
+            no_warmup_runs,
+            export_ty,
+            export_out_file,
+            timeout_ms,
+            randomize_environment,
+            open,
+            abort_on_environment_warnings,
+            no_history,
+            tags,
+            duration_format,
+            lock,
+        });
+    }
+    let binary = match build.build()? {
+        Some((binary, commit)) => {
+            if let Some(commit) = commit {
+                tags.entry("commit".to_string()).or_insert(commit); //unless the user tagged it themselves
+            }
+            binary
+        }
+        None => binary.ok_or_else(|| failure(ExitReason::InvalidArguments, "need a binary to bench, or a Cargo binary to build"))?,
+    };
+    if open && !report && !matches!(export_ty, ExportType::HTML) {
+        eprintln!("{}: --open only opens HTML files, so add --report or -t html to get one", "Warning".yellow().bold());
+    }
+    let program = if shell {
+        //a command line isn't a path, so go by the program it starts with
+        binary.to_str().and_then(|line| line.split_whitespace().next()).map_or_else(|| binary.clone(), PathBuf::from)
+    } else {
+        binary.clone()
+    };
     let export_out_file = export_out_file.unwrap_or_else(|| {
         //shadow the export_out_file, and if we don't have it
         if export_trace_name.is_some() {
             //if we have the trace name, use that
             export_trace_name.clone().unwrap()
         } else {
-            let bin_name = binary
+            let bin_name = program
                 .file_name()
                 .and_then(OsStr::to_str) //if not, try to get the binary name
                 .unwrap_or("bench_results"); //falling back to bench_results
@@ -64,37 +419,132 @@ pub fn run(
         }
     });
     let export_trace_name = export_trace_name.unwrap_or_else(|| export_out_file.clone()); //shadow the export_trace_name, if we don't have it use the same name as the file
-    let cli_args = match cli_args {
-        Some(cli_args) if !cli_args.is_empty() => {
-            //if the CLI args aren't empty, split them by spaces
-            cli_args.split(' ').map(ToString::to_string).collect()
-        }
-        _ => vec![], //if it is empty or we didn't get anything, then return an empty vec. this way we avoid a vec![""]
-    };
 
-    let Some(file_name) = binary.file_name().map(OsStr::to_os_string) else {
-        panic!("need a binary to bench, not a folder");
+    let pipeline: Vec<_> = pipe_into
+        .iter()
+        .map(|command| {
+            //split like a shell would, the same as each --command, so a stage can have quoted arguments
+            let parts = shell_words::split(command)
+                .wrap_err(ExitReason::InvalidArguments)
+                .wrap_err_with(|| format!("unable to split the --pipe-into command {command:?}"))?;
+            Ok(parts
+                .split_first()
+                .map(|(binary, cli_args)| PipelineStage::new(binary.into(), cli_args.to_vec()))) //skip anything empty
+        })
+        .filter_map(Result::transpose)
+        .collect::<color_eyre::Result<_>>()?;
+
+    let Some(file_name) = program.file_name().map(OsStr::to_os_string) else {
+        return Err(failure(ExitReason::InvalidArguments, format!("need a binary to bench, not a folder - got {}", binary.display())));
     };
 
     {
         //scoped variables to print a message to the user to let them know what they are doing.
+        let shell_line = shell.then(|| shell_command_line(&binary, &cli_args)); //the args go to the shell as they are, so show them that way
         let binary = match file_name.into_string() {
             Ok(s) => s,
-            Err(s) => format!("{s:?}"),
-        };
-        let binary_and_args = if cli_args.is_empty() {
-            binary
-        } else {
-            binary + &cli_args.join(" ")
+            Err(s) => s.display().to_string(),
         };
+        let mut binary_and_args = shell_line.unwrap_or_else(|| {
+            if cli_args.is_empty() {
+                binary
+            } else {
+                binary + " " + &shell_words::join(&cli_args) //quote them, so it's clear where each one starts and ends
+            }
+        });
+        for command in &pipe_into {
+            binary_and_args += " | ";
+            binary_and_args += command;
+        }
+
+        say!(json_lines, "{} {}", "Benchmark:".bold(), binary_and_args.italic());
+    }
+
+    if remote.is_none() && !shell {
+        //the binary has to be on this machine to look inside it
+        if let Some(warning) = debug_build_warning(&binary) {
+            say!(json_lines, "{}: {warning}", "Warning".yellow().bold());
+        }
+    }
 
-        println!("{} {}", "Benchmark:".bold(), binary_and_args.italic());
+    let environment_warnings = check_environment(); //check that the machine is fit for benchmarking
+    for warning in &environment_warnings {
+        say!(json_lines, "{}: {warning}", "Warning".yellow().bold());
     }
+    if abort_on_environment_warnings && !environment_warnings.is_empty() {
+        //if the user wants to be strict about it, stop before we start
+        return Err(failure(ExitReason::AssertionFailed, "aborting due to environment warnings"));
+    }
+    let _lock = lock.acquire()?; //held until we're done, so nothing else benchmarks alongside us
+
+    let self_reported_timing = timing_regex
+        .map(|pattern| {
+            SelfReportedTiming::new(&pattern, timing_unit)
+                .wrap_err(ExitReason::InvalidArguments)
+                .wrap_err_with(|| format!("invalid timing regex {pattern:?}"))
+        })
+        .transpose()?;
+
+    let last_session = (!no_compare_last)
+        .then(|| last_session(command_hash(&binary, &cli_args)))
+        .flatten(); //before this session goes into the history
+    let history_command = (!no_history || upload_url.is_some() || github_summary).then(|| (binary.clone(), cli_args.clone())); //keep hold of the command for the history, uploading and GitHub, as the runner takes ownership
+    let upload_target = upload_url.map(|endpoint| UploadTarget {
+        endpoint,
+        token: upload_token.or_else(|| std::env::var("PRECIPICE_UPLOAD_TOKEN").ok()),
+        format: upload_format,
+    });
+    let profile_command = no_profile_runs.filter(|n| *n > 0).map(|n| (binary.clone(), cli_args.clone(), n)); //and for the profiler
+    let profile_command = if profile_command.is_some() && remote.is_some() {
+        eprintln!("{}: profiling isn't supported with a remote agent", "Warning".yellow().bold());
+        None
+    } else {
+        profile_command
+    };
+    let syscall_command = no_syscall_runs.filter(|n| *n > 0).map(|n| (binary.clone(), cli_args.clone(), n)); //and for counting syscalls
+    let syscall_command = if syscall_command.is_some() && remote.is_some() {
+        eprintln!("{}: counting syscalls isn't supported with a remote agent", "Warning".yellow().bold());
+        None
+    } else {
+        syscall_command
+    };
 
     let (stop_tx, stop_rx) = channel(); //make a channel for stopping
 
+    let full_command = std::iter::once(binary.display().to_string())
+        .chain(cli_args.iter().cloned())
+        .chain(pipe_into.iter().map(|command| format!("| {command}")))
+        .collect::<Vec<_>>()
+        .join(" "); //the same format as the history, so the report and exports say exactly what got run
+
+    let provenance = Provenance::start(
+        pipe_into.iter().fold(
+            if shell { shell_command_line(&binary, &cli_args) } else { quote_command(&binary, &cli_args) },
+            |command, pipe| format!("{command} | {pipe}"),
+        ),
+    );
+    let provenance = if remote.is_some() || shell {
+        provenance //the binary is on the agent (or is a whole command line), so we can't hash it
+    } else {
+        provenance.with_binary_hash(&binary)
+    };
+
+    let mut events = events
+        .map(|path| EventPublisher::open(&path).wrap_err_with(|| format!("unable to publish events to {}", path.display())))
+        .transpose()?; //before starting, so clients can connect in time for the first run
+    if let Some(events) = &mut events {
+        events.publish(&SessionEvent::Started {
+            name: export_trace_name.clone(),
+            command: full_command.clone(),
+            runs,
+            session_id: provenance.session_id.clone(),
+        });
+    }
+
     let mut found_runs = vec![]; //make a vec for runs we've received
-    let (handle, rx) = Runner::new(
+    let mut measurements: Vec<RunMeasurement> = vec![]; //and everything else we measured, for the report
+    let (timeout_tx, timeout_rx) = channel(); //to count the runs that time out, for the summary
+    let runner = Runner::new(
         binary,
         cli_args,
         runs,
@@ -102,80 +552,651 @@ pub fn run(
         no_warmup_runs,
         print_initial,
     )
-    .start(); //get a handle from a new runner, with the binary etc
+    .timeout(timeout_ms.map(Duration::from_millis))
+    .randomize_environment(randomize_environment)
+    .self_reported_timing(self_reported_timing)
+    .pipeline(pipeline)
+    .show_output_every(show_output_every.map(NonZeroUsize::get))
+    .timeout_sender(Some(timeout_tx))
+    .ram_copy(copy_to_ram.then_some(RamCopyOptions {
+        directory: ram_dir,
+        data_files: ram_data_file,
+    }))
+    .shell(shell); //make a new runner, with the binary etc
+    let metrics_sampler = sample_metrics
+        .then(|| {
+            //start sampling just before the runner, so the series covers the whole session
+            MetricsSampler::start(Duration::from_millis(metrics_interval_ms)).wrap_err("unable to start the metrics sampler")
+        })
+        .transpose()?;
+    let (handle, rx, error_rx) = match remote {
+        Some(address) => RemoteRunner::new(address.clone(), remote_token, runner) //if we have an agent, run it there
+            .start()
+            .wrap_err_with(|| format!("unable to start the runner on the agent at {address}"))?,
+        None => runner.start().wrap_err("unable to start the runner")?,
+    }; //and get a handle from it
 
     std::thread::sleep(Duration::from_millis(50)); //wait to make sure that we show the progress bar underneath the initial run
 
     let progress_bar = ProgressBar::new(runs as u64); //make a new progress bar with the number of runs we expect to do
+    let stopped = Arc::new(AtomicBool::new(false));
     {
         let progress_bar = progress_bar.clone();
+        let stopped = stopped.clone();
         ctrlc::set_handler(move || {
-            stop_tx.send(()).expect("Could not send signal on channel.");
+            if stopped.swap(true, Ordering::SeqCst) {
+                //if the user has already asked nicely, they want out right now
+                std::process::exit(ExitReason::Interrupted.code().into());
+            }
+            let _ = stop_tx.send(()); //the runner kills the in-flight run - if it has already finished, we don't care
             progress_bar.abandon_with_message("Stopped by User");
         })
-        .expect("Error setting Ctrl-C handler"); //if we receive a stop signal, stop the benching
+        .wrap_err("unable to set the Ctrl-C handler")?; //if we receive a stop signal, stop the benching
     }
 
+    let recent_runs = Arc::new(Mutex::new(VecDeque::with_capacity(DEFAULT_SPARKLINE_RUNS))); //the last few runs, for the sparkline
     progress_bar.set_style(
         ProgressStyle::with_template(
-            "{spinner} Elapsed: [{elapsed_precise}], ETA: [{eta_precise}] {bar:40.cyan/blue} {pos:>7}/{len:7}",
+            "{spinner} Elapsed: [{elapsed_precise}], ETA: [{eta_precise}] {bar:40.cyan/blue} {pos:>7}/{len:7} {sparkline:.yellow}",
         )
         .unwrap()
-        .progress_chars("##-"),
+        .progress_chars("##-")
+        .with_key("sparkline", {
+            let recent_runs = recent_runs.clone();
+            move |_: &ProgressState, w: &mut dyn std::fmt::Write| {
+                if let Ok(mut recent_runs) = recent_runs.lock() {
+                    let _ = w.write_str(&sparkline(recent_runs.make_contiguous()));
+                }
+            }
+        }),
     );
 
-    while !handle.is_finished() {
-        //while the handle isn't finished, that is whilst we've still got runs
-        let mut delta = 0;
-        for time in rx.try_iter() {
-            //use try_iter to avoid blocking so we keep on going and updating the progress bar
-            found_runs.push(time.as_micros()); //for every run we've got since the last poll, add it to our list
-            delta += 1; //and increment our delta
+    for measurement in &rx {
+        //block until each run comes in rather than spinning, so a stalled runner or agent doesn't pin a core - this finishes when the runner is done and drops its sender
+        found_runs.push(measurement.wall.as_micros());
+        if json_lines {
+            print_json_line(&measurement);
         }
-
-        if delta > 0 {
-            progress_bar.inc(delta); //update our progress bar with the delta
+        if let Some(events) = &mut events {
+            events.publish(&SessionEvent::run_completed(&measurement));
+        }
+        if let Ok(mut recent_runs) = recent_runs.lock() {
+            recent_runs.push_back(measurement.wall.as_micros());
+            let excess = recent_runs.len().saturating_sub(DEFAULT_SPARKLINE_RUNS);
+            recent_runs.drain(..excess); //only keep the newest ones
+        }
+        measurements.push(measurement);
+        progress_bar.inc(1);
+    }
+    handle.join().map_err(|_| eyre!("the runner thread panicked"))?; //join the handle
+    let metrics = metrics_sampler.map(MetricsSampler::finish); //and stop sampling as soon as the runs are done
+    let provenance = provenance.finish();
+    let last_runs: Vec<RunMeasurement> = rx.try_iter().collect(); //grab any runs that came in between the last poll and the thread finishing
+    if json_lines {
+        last_runs.iter().for_each(print_json_line);
+    }
+    if let Some(events) = &mut events {
+        for measurement in &last_runs {
+            events.publish(&SessionEvent::run_completed(measurement));
         }
-
-        std::thread::yield_now();
     }
-    handle
-        .join() //join the handle
-        .expect("unable to join handle")
-        .expect("error from inside handle");
+    measurements.extend(last_runs);
+    found_runs.extend(measurements[found_runs.len()..].iter().map(|measurement| measurement.wall.as_micros()));
 
     progress_bar.finish_and_clear();
-    println!();
+    let outcomes = RunOutcomes::new(
+        measurements.iter().map(|measurement| measurement.exit_status),
+        timeout_rx.try_iter().count(),
+    ); //count them all up here, rather than warning about each one as it happens
+    say!(json_lines);
+    if stopped.load(Ordering::SeqCst) {
+        say!(
+            json_lines,
+            "{} - exporting the {} runs that finished",
+            "Stopped by User".yellow(),
+            found_runs.len()
+        );
+    }
+
+    let mut runner_failed = false;
+    for error in error_rx.try_iter() {
+        //if the runner stopped because of an error, let the user know
+        eprintln!("{}: {error}", "Error".red().bold());
+        runner_failed = true;
+    }
 
-    let min_max_median: Option<(u128, u128, u128)> = found_runs
+    let min_max: Option<(u128, u128)> = found_runs
         .iter()
         .min()
         .copied()
-        .zip(found_runs.iter().max().copied())
-        .zip(found_runs.get(found_runs.len() / 2).copied())
-        .map(|((a, b), c)| (a, b, c));
+        .zip(found_runs.iter().max().copied());
     let mean_standard_deviation = calculate_mean_standard_deviation(&found_runs);
+    let median_mad_iqr = calculate_median_mad_iqr(&found_runs);
+    let percentiles = calculate_percentiles(&found_runs, &SUMMARY_PERCENTILES);
+    let trim_fraction = trim_percent.map(|percent| percent / 100.0);
+    let trimmed = trim_fraction.and_then(|fraction| {
+        calculate_trimmed_mean(&found_runs, fraction).zip(calculate_winsorized_mean_standard_deviation(&found_runs, fraction))
+    });
+    let diagnostics = diagnose(&found_runs); //check whether those stats can be trusted before found_runs gets exported
+    if let Some(mut events) = events {
+        events.publish(&SessionEvent::Finished {
+            runs: found_runs.len(),
+            mean_micros: mean_standard_deviation.map(|(mean, _)| mean.as_secs_f64() * 1_000_000.0),
+            standard_deviation_micros: mean_standard_deviation.map(|(_, standard_deviation)| standard_deviation.as_secs_f64() * 1_000_000.0),
+            stopped: stopped.load(Ordering::SeqCst),
+        });
+    } //and then close the socket
     let no_runs = found_runs.len();
 
-    let n = export_ty.export(export_trace_name, found_runs, export_out_file); //export
+    if let Some((binary, cli_args)) = history_command {
+        if let Some(entry) = HistoryEntry::new(&binary, &cli_args, &found_runs).map(|entry| entry.with_tags(tags.clone())) {
+            if github_summary {
+                //read the baseline before this session goes into the history
+                let baseline = read_history(entry.command_hash).ok().and_then(|mut entries| entries.pop());
+                if let Err(e) = write_github_summary(&export_trace_name, &entry, baseline.as_ref()) {
+                    eprintln!("{}: unable to write GitHub summary: {e}", "Warning".yellow().bold());
+                }
+            }
+            if !no_history {
+                if let Err(e) = append_to_history(&entry) {
+                    eprintln!("{}: unable to save to history: {e}", "Warning".yellow().bold());
+                }
+            }
+            if let Some(target) = &upload_target {
+                match upload_session(target, &export_trace_name, &entry) {
+                    Ok(()) => say!(json_lines, "{} to {}", "Uploaded".bold(), target.endpoint),
+                    Err(e) => eprintln!("{}: unable to upload: {e}", "Warning".yellow().bold()),
+                }
+            }
+        }
+    }
 
-    trace!(?n, "Finished exporting");
-    if let Some((mean, standard_deviation)) = mean_standard_deviation {
-        println!(
+    if let Some(metrics) = metrics {
+        if let Err(e) = export_metrics(&metrics, export_ty, format!("{export_out_file}_metrics")) {
+            eprintln!("{}: unable to export metrics: {e}", "Warning".yellow().bold());
+        }
+    }
+
+    if should_export_memory {
+        let memory = peak_memory(&measurements);
+        if memory.is_empty() {
+            eprintln!("{}: no memory usage was recorded, as it can't be read on this platform", "Warning".yellow().bold());
+        } else if let Err(e) = export_memory(&memory, export_ty, format!("{export_out_file}_memory")) {
+            eprintln!("{}: unable to export memory usage: {e}", "Warning".yellow().bold());
+        }
+    }
+
+    if let Some((binary, cli_args, no_profile_runs)) = profile_command {
+        if !stopped.load(Ordering::SeqCst) {
+            //profile after the measured runs, so the profiler doesn't slow any of them down
+            say!(json_lines, "{} {no_profile_runs} runs", "Profiling".bold());
+            match profile_runs(&binary, &cli_args, no_profile_runs, profile_frequency) {
+                Ok(stacks) => {
+                    if let Err(e) = export_flamegraph(&stacks, &export_trace_name, &format!("{export_out_file}_flamegraph")) {
+                        eprintln!("{}: unable to export flamegraph: {e}", "Warning".yellow().bold());
+                    }
+                }
+                Err(e) => eprintln!("{}: unable to profile: {e}", "Warning".yellow().bold()),
+            }
+        }
+    }
+
+    if let Some((binary, cli_args, no_syscall_runs)) = syscall_command {
+        if !stopped.load(Ordering::SeqCst) {
+            //like profiling, strace slows the runs down so do it after the measured runs
+            say!(json_lines, "{} for {no_syscall_runs} runs", "Counting syscalls".bold());
+            match count_syscalls(&binary, &cli_args, no_syscall_runs) {
+                Ok(summary) => {
+                    say!(
+                        json_lines,
+                        "{}: {}",
+                        "Syscalls per run".bold(),
+                        (summary.total_calls() / summary.runs.max(1) as u64).bright_white()
+                    );
+                    if let Err(e) = export_syscalls(&summary, export_ty, format!("{export_out_file}_syscalls")) {
+                        eprintln!("{}: unable to export syscalls: {e}", "Warning".yellow().bold());
+                    }
+                }
+                Err(e) => eprintln!("{}: unable to count syscalls: {e}", "Warning".yellow().bold()),
+            }
+        }
+    }
+
+    if prometheus || pushgateway.is_some() {
+        if let Some(text) = prometheus_text(&export_trace_name, &full_command, &found_runs) {
+            if prometheus {
+                if let Err(e) = export_prometheus(&text, &export_out_file) {
+                    eprintln!("{}: unable to export Prometheus metrics: {e}", "Warning".yellow().bold());
+                }
+            }
+            if let Some(gateway) = &pushgateway {
+                if let Err(e) = push_to_gateway(gateway, &export_trace_name, &text) {
+                    eprintln!("{}: unable to push to the Pushgateway: {e}", "Warning".yellow().bold());
+                }
+            }
+        }
+    }
+
+    if report {
+        let report = SessionReport::new(export_trace_name.clone(), full_command.clone(), measurements, environment_warnings)
+            .with_tags(tags.clone())
+            .with_provenance(provenance.clone())
+            .with_trim_fraction(trim_fraction)
+            .with_statistics(stats.clone())
+            .with_duration_format(duration_format);
+        if let Err(e) = report.export(format!("{export_out_file}_report")) {
+            eprintln!("{}: unable to export report: {e}", "Warning".yellow().bold());
+        } else if open {
+            open_in_browser(&format!("{export_out_file}_report.html"));
+        }
+    }
+
+    if let Some(url) = webhook_url {
+        let message = match mean_standard_deviation {
+            Some((mean, standard_deviation)) => format!(
+                "{export_trace_name} finished: {} ± {} over {no_runs} runs",
+                duration_format.duration(mean),
+                duration_format.duration(standard_deviation)
+            ),
+            None => format!("{export_trace_name} finished without any runs"),
+        };
+        if let Err(e) = Webhook::new(url, webhook_template).fire(WebhookEvent::Finished, &export_trace_name, &message, &found_runs) {
+            eprintln!("{}: unable to fire webhook: {e}", "Warning".yellow().bold());
+        }
+    }
+
+    tags.extend(provenance.to_tags()); //so the export can always be traced back to this session
+    let histogram = histogram_lines(&found_runs, DEFAULT_HISTOGRAM_BINS, DEFAULT_HISTOGRAM_WIDTH); //before found_runs gets moved into the export
+    let statistics: Vec<_> = stats
+        .iter()
+        .filter_map(|statistic| Some((statistic.label(), statistic.calculate(&found_runs)?)))
+        .collect();
+    let document = (!export_commands.is_empty())
+        .then(|| ResultDocument::new(export_trace_name.clone(), full_command, found_runs.clone(), tags.clone()));
+    let n = export_ty.export_with_tags(export_trace_name, found_runs, export_out_file.clone(), &tags); //export - any error gets bubbled once the stats have been printed
+    for (label, value) in statistics {
+        say!(json_lines, "{}: {}", format!("{label:<32}").bold(), value.format(&duration_format).bright_green());
+    }
+    if let (true, Some((mean, standard_deviation))) = (stats.is_empty(), mean_standard_deviation) {
+        say!(
+            json_lines,
             "{}: {} ± {} : {}",
             "Mean ± Standard Deviation : Runs".bold(),
-            format!("{mean:.3?}").bright_green(),
-            format!("{standard_deviation:.3?}").bright_green(),
+            duration_format.duration(mean).bright_green(),
+            duration_format.duration(standard_deviation).bright_green(),
             no_runs.bright_white(),
         );
     }
-    if let Some((min, max, median)) = min_max_median {
-        println!(
+    if let (true, Some(((min, max), (median, median_absolute_deviation, interquartile_range)))) =
+        (stats.is_empty(), min_max.zip(median_mad_iqr))
+    {
+        say!(
+            json_lines,
             "{}: {} … {} … {}",
             "Min … Median … Max              ".bold(),
-            format!("{:.3?}", Duration::from_micros(min as u64)).bright_blue(),
-            format!("{:.3?}", Duration::from_micros(median as u64)).bright_green(),
-            format!("{:.3?}", Duration::from_micros(max as u64)).bright_red()
+            duration_format.micros(min).bright_blue(),
+            duration_format.duration(median).bright_green(),
+            duration_format.micros(max).bright_red()
+        );
+        say!(
+            json_lines,
+            "{}: {} ± {} : {}",
+            "Median ± MAD : IQR              ".bold(),
+            duration_format.duration(median).bright_green(),
+            duration_format.duration(median_absolute_deviation).bright_green(),
+            duration_format.duration(interquartile_range).bright_green(),
+        );
+    }
+    if let (true, Some(percentiles)) = (stats.is_empty(), percentiles) {
+        say!(
+            json_lines,
+            "{}: {}",
+            format!("{:<32}", SUMMARY_PERCENTILES.iter().map(|percentile| format!("P{percentile}")).collect::<Vec<_>>().join(" … ")).bold(),
+            percentiles.into_iter().map(|percentile| duration_format.duration(percentile).bright_yellow().to_string()).collect::<Vec<_>>().join(" … "),
+        );
+    }
+    if let (true, Some((percent, (trimmed_mean, (winsorized_mean, winsorized_standard_deviation))))) = (stats.is_empty(), trim_percent.zip(trimmed)) {
+        say!(
+            json_lines,
+            "{}: {}",
+            format!("{:<32}", format!("Trimmed Mean ({percent}%)")).bold(),
+            duration_format.duration(trimmed_mean).bright_green(),
+        );
+        say!(
+            json_lines,
+            "{}: {} ± {}",
+            format!("{:<32}", format!("Winsorized Mean ± SD ({percent}%)")).bold(),
+            duration_format.duration(winsorized_mean).bright_green(),
+            duration_format.duration(winsorized_standard_deviation).bright_green(),
         );
     }
+    if outcomes.all_ok() {
+        say!(json_lines, "{}: {}", "Outcomes                         ".bold(), outcomes.bright_green());
+    } else {
+        say!(json_lines, "{}: {}", "Outcomes                         ".bold(), outcomes.yellow());
+    }
+    if let Some(change) = last_session
+        .as_ref()
+        .zip(mean_standard_deviation)
+        .and_then(|(last_session, (mean, _))| change_since(mean, last_session, &duration_format))
+    {
+        say!(json_lines, "{}: {change}", "Change Since Last Session        ".bold());
+    }
+    if !no_histogram && !histogram.is_empty() {
+        say!(json_lines);
+        for line in histogram {
+            say!(json_lines, "{}", line.bright_cyan());
+        }
+        say!(json_lines);
+    }
+    for diagnostic in diagnostics {
+        say!(json_lines, "{}: {diagnostic}", "Warning".yellow().bold());
+    }
+    if let Some(document) = document {
+        for command in &export_commands {
+            if let Err(e) = run_export_command(command, &document) {
+                eprintln!("{}: unable to export with `{command}`: {e}", "Warning".yellow().bold());
+            }
+        }
+    }
+
+    let n = n
+        .wrap_err(ExitReason::ExportFailed)
+        .wrap_err_with(|| format!("unable to export to {export_out_file} as {export_ty:?}"))?;
+    trace!(?n, "Finished exporting");
+    if open && matches!(export_ty, ExportType::HTML) {
+        open_in_browser(&format!("{export_out_file}.html"));
+    }
+
+    //everything has still been exported, but scripts should know that something went wrong
+    if stopped.load(Ordering::SeqCst) {
+        return Err(ExitReason::Interrupted.into());
+    }
+    if runner_failed {
+        return Err(failure(ExitReason::BenchmarkFailed, "the runner stopped early"));
+    }
+    if !outcomes.all_ok() {
+        return Err(failure(ExitReason::BenchmarkFailed, format!("not every run succeeded - {outcomes}")));
+    }
+    Ok(())
+}
+
+///The parts of [`FullCLIArgs`] which make sense when comparing several `--command`s
+#[allow(clippy::struct_excessive_bools)] //they're all independent flags
+struct ComparisonArgs {
+    ///The commands to compare, as they were given
+    commands: Vec<String>,
+    ///Whether to run each command through the shell rather than splitting it
+    shell: bool,
+    ///The number of runs of each command
+    runs: usize,
+    ///The number of warmup runs for each command
+    no_warmup_runs: u8,
+    ///How to export the traces
+    export_ty: ExportType,
+    ///The file to export every trace to, without extension
+    export_out_file: Option<String>,
+    ///How long a single run can take in milliseconds
+    timeout_ms: Option<u64>,
+    ///Whether to pad the environment by a random amount for each run
+    randomize_environment: bool,
+    ///Whether to open a HTML export once it has been written
+    open: bool,
+    ///Whether to refuse to start if the machine looks unfit for benchmarking
+    abort_on_environment_warnings: bool,
+    ///Whether to skip adding each command to the history
+    no_history: bool,
+    ///The tags for every command
+    tags: Tags,
+    ///How to write the times
+    duration_format: DurationFormat,
+    ///Whether to stop other sessions running at the same time
+    lock: LockArgs,
+}
+
+///Compares several `--command`s in one session with a [`MultiRunner`], interleaving their runs, then prints how each one did and how much faster the fastest one was
+#[instrument(skip(commands, duration_format, lock))]
+#[allow(clippy::needless_pass_by_value, clippy::cast_possible_truncation)]
+fn run_comparison(
+    ComparisonArgs {
+        commands,
+        shell,
+        runs,
+        no_warmup_runs,
+        export_ty,
+        export_out_file,
+        timeout_ms,
+        randomize_environment,
+        open,
+        abort_on_environment_warnings,
+        no_history,
+        tags,
+        duration_format,
+        lock,
+    }: ComparisonArgs,
+) -> color_eyre::Result<()> {
+    if commands.len() < 2 {
+        return Err(failure(
+            ExitReason::InvalidArguments,
+            "need at least two --commands to compare - use --binary to benchmark just one",
+        ));
+    }
+    let names: Vec<String> = commands.iter().map(|command| command.trim().to_string()).collect();
+    let commands = commands
+        .iter()
+        .map(|command| {
+            if shell {
+                return match command.trim() {
+                    "" => Err(failure(ExitReason::InvalidArguments, "one of the --commands is empty")),
+                    line => Ok((PathBuf::from(line), vec![])), //the shell splits it up
+                };
+            }
+            let parts = shell_words::split(command)
+                .wrap_err(ExitReason::InvalidArguments)
+                .wrap_err_with(|| format!("unable to split the command {command:?}"))?;
+            match parts.split_first() {
+                Some((binary, cli_args)) => Ok((PathBuf::from(binary), cli_args.to_vec())),
+                None => Err(failure(ExitReason::InvalidArguments, "one of the --commands is empty")),
+            }
+        })
+        .collect::<color_eyre::Result<Vec<_>>>()?;
+    let export_out_file = export_out_file.unwrap_or_else(|| String::from("comparison"));
+
+    println!("{} {}", "Comparing:".bold(), names.iter().map(|name| name.italic().to_string()).collect::<Vec<_>>().join(" vs "));
+    for (binary, _) in commands.iter().filter(|_| !shell) {
+        if let Some(warning) = debug_build_warning(binary) {
+            println!("{}: {warning}", "Warning".yellow().bold());
+        }
+    }
+
+    let environment_warnings = check_environment(); //check that the machine is fit for benchmarking
+    for warning in &environment_warnings {
+        println!("{}: {warning}", "Warning".yellow().bold());
+    }
+    if abort_on_environment_warnings && !environment_warnings.is_empty() {
+        return Err(failure(ExitReason::AssertionFailed, "aborting due to environment warnings"));
+    }
+    let _lock = lock.acquire()?; //held until we're done, so nothing else benchmarks alongside us
+
+    let provenances: Vec<Provenance> = commands
+        .iter()
+        .map(|(binary, cli_args)| {
+            if shell {
+                Provenance::start(shell_command_line(binary, cli_args))
+            } else {
+                Provenance::start(quote_command(binary, cli_args)).with_binary_hash(binary)
+            }
+        })
+        .collect();
+
+    let (stop_tx, stop_rx) = channel();
+    let (handle, rx, error_rx) = MultiRunner::new(commands.clone(), runs, Some(stop_rx), no_warmup_runs)
+        .timeout(timeout_ms.map(Duration::from_millis))
+        .randomize_environment(randomize_environment)
+        .shell(shell)
+        .start()
+        .wrap_err("unable to start the runner")?;
+
+    let progress_bar = ProgressBar::new((runs * commands.len()) as u64);
+    progress_bar.set_style(
+        ProgressStyle::with_template(
+            "{spinner} Elapsed: [{elapsed_precise}], ETA: [{eta_precise}] {bar:40.cyan/blue} {pos:>7}/{len:7}",
+        )
+        .unwrap()
+        .progress_chars("##-"),
+    );
+    let stopped = Arc::new(AtomicBool::new(false));
+    {
+        let progress_bar = progress_bar.clone();
+        let stopped = stopped.clone();
+        ctrlc::set_handler(move || {
+            if stopped.swap(true, Ordering::SeqCst) {
+                //if the user has already asked nicely, they want out right now
+                std::process::exit(ExitReason::Interrupted.code().into());
+            }
+            let _ = stop_tx.send(());
+            progress_bar.abandon_with_message("Stopped by User");
+        })
+        .wrap_err("unable to set the Ctrl-C handler")?;
+    }
+
+    let mut found_runs = vec![vec![]; commands.len()];
+    let mut exit_statuses = vec![];
+    let mut record = |measurement: RunMeasurement| {
+        found_runs[measurement.command].push(measurement.wall.as_micros());
+        exit_statuses.push(measurement.exit_status);
+    };
+    for measurement in &rx {
+        //block until each run comes in rather than spinning, like the single command - this finishes when the runner is done and drops its sender
+        record(measurement);
+        progress_bar.inc(1);
+    }
+    handle.join().map_err(|_| eyre!("the runner thread panicked"))?;
+    progress_bar.finish_and_clear();
+    println!();
+    let outcomes = RunOutcomes::new(exit_statuses, 0);
+
+    let mut runner_failed = false;
+    for error in error_rx.try_iter() {
+        eprintln!("{}: {error}", "Error".red().bold());
+        runner_failed = true;
+    }
+
+    let width = names.iter().map(String::len).max().unwrap_or_default();
+    for (name, found_runs) in names.iter().zip(&found_runs) {
+        if let Some(((mean, standard_deviation), (median, _, _))) = calculate_mean_standard_deviation(found_runs).zip(calculate_median_mad_iqr(found_runs)) {
+            println!(
+                "{}: {} ± {} (median {}) : {}",
+                format!("{name:<width$}").bold(),
+                duration_format.duration(mean).bright_green(),
+                duration_format.duration(standard_deviation).bright_green(),
+                duration_format.duration(median).bright_green(),
+                found_runs.len().bright_white(),
+            );
+        }
+    }
+    let run_slices: Vec<&[u128]> = found_runs.iter().map(Vec::as_slice).collect();
+    if let Some((fastest, relative)) = speedups(&run_slices) {
+        println!();
+        println!("{}: {}", "Fastest".bold(), names[fastest].bright_green());
+        for (i, relative) in relative.into_iter().enumerate() {
+            let Some(relative) = relative.filter(|_| i != fastest) else {
+                continue;
+            };
+            let significance = match compare(&found_runs[fastest], &found_runs[i], DEFAULT_SIGNIFICANCE_LEVEL) {
+                Some((Verdict::NoDifference, test)) => format!(" - {} (p = {:.4})", "not a significant difference".yellow(), test.p_value),
+                Some((_, test)) => format!(" (p = {:.4})", test.p_value),
+                None => String::new(),
+            };
+            println!("  {} faster than {}{significance}", format!("{relative:.2}x").bright_green(), names[i].italic());
+        }
+    }
+    if outcomes.all_ok() {
+        println!("{}: {}", "Outcomes".bold(), outcomes.bright_green());
+    } else {
+        println!("{}: {}", "Outcomes".bold(), outcomes.yellow());
+    }
+
+    let mut trace_tags = TraceTags::new();
+    for (((binary, cli_args), provenance), (name, found_runs)) in commands.iter().zip(provenances).zip(names.iter().zip(&found_runs)) {
+        if !no_history {
+            if let Some(entry) = HistoryEntry::new(binary, cli_args, found_runs).map(|entry| entry.with_tags(tags.clone())) {
+                if let Err(e) = append_to_history(&entry) {
+                    eprintln!("{}: unable to save to history: {e}", "Warning".yellow().bold());
+                }
+            }
+        }
+        let mut tags = tags.clone();
+        tags.extend(provenance.finish().to_tags()); //so each trace can be traced back to its command
+        trace_tags.insert(name.clone(), tags);
+    }
+
+    let n = export_ty
+        .export_traces_with_tags(export_out_file.clone(), names.into_iter().zip(found_runs).collect(), &trace_tags)
+        .wrap_err(ExitReason::ExportFailed)
+        .wrap_err_with(|| format!("unable to export to {export_out_file} as {export_ty:?}"))?;
+    trace!(?n, "Finished exporting");
+    if open && matches!(export_ty, ExportType::HTML) {
+        open_in_browser(&format!("{export_out_file}.html"));
+    }
+
+    //everything has still been exported, but scripts should know that something went wrong
+    if stopped.load(Ordering::SeqCst) {
+        return Err(ExitReason::Interrupted.into());
+    }
+    if runner_failed {
+        return Err(failure(ExitReason::BenchmarkFailed, "the runner stopped early"));
+    }
+    if !outcomes.all_ok() {
+        return Err(failure(ExitReason::BenchmarkFailed, format!("not every run succeeded - {outcomes}")));
+    }
+    Ok(())
+}
+
+///Describes the change in the mean since the last session of the same command, like `+3.21% (1.234ms → 1.274ms, 2024-01-02 13:45:00)` - red if it got slower, and green if it got faster
+#[must_use]
+pub fn change_since(mean: Duration, last_session: &HistoryEntry, duration_format: &DurationFormat) -> Option<String> {
+    if last_session.mean_micros <= 0.0 {
+        return None;
+    }
+    let mean_micros = mean.as_secs_f64() * 1_000_000.0;
+    let change = (mean_micros - last_session.mean_micros) / last_session.mean_micros * 100.0;
+    let percentage = format!("{change:+.2}%");
+    let percentage = if change > 0.0 {
+        percentage.bright_red().to_string()
+    } else {
+        percentage.bright_green().to_string()
+    };
+
+    Some(format!(
+        "{percentage} ({} → {}, {})",
+        duration_format.duration(Duration::from_secs_f64(last_session.mean_micros / 1_000_000.0)),
+        duration_format.duration(mean),
+        last_session.time.format("%Y-%m-%d %H:%M:%S")
+    ))
+}
+
+///Opens an exported file in the default browser for `--open`, warning if we can't - the export itself still worked
+fn open_in_browser(file: &str) {
+    if let Err(e) = open::that(file) {
+        eprintln!("{}: unable to open {file}: {e}", "Warning".yellow().bold());
+    }
+}
+
+///Prints one run to stdout as a line of JSON, for `--json-lines`
+fn print_json_line(measurement: &RunMeasurement) {
+    match measurement.to_json_line() {
+        Ok(line) => println!("{line}"), //stdout is line-buffered, so each run goes out as soon as it finishes
+        Err(e) => warn!(%e, "Unable to format run as JSON"),
+    }
+}
+
+///Parses a `--trim-percent`, which has to leave some runs in the middle
+fn parse_trim_percent(s: &str) -> Result<f64, String> {
+    let percent: f64 = s.trim().parse().map_err(|e| format!("{s} isn't a percentage: {e}"))?;
+    if (0.0..50.0).contains(&percent) {
+        Ok(percent)
+    } else {
+        Err(format!("{s} should be at least 0 and less than 50, as it gets trimmed from both ends"))
+    }
 }
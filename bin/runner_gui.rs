@@ -6,27 +6,61 @@
 //! Inside the app, we change state on update using an [`Option`] which stores a new state, which gets changed after the match statement on the internal state.
 
 use benchmarker::{
-    bencher::{calculate_mean_standard_deviation, Runner, DEFAULT_RUNS},
-    io::{export_csv, export_html},
-    EGUI_STORAGE_SEPARATOR,
+    bencher::{
+        calculate_mean_standard_deviation, calculate_median_mad_iqr, calculate_percentiles, multi::MultiRunner, remote::RemoteRunner, shell_command_line, RunMeasurement, Runner,
+        RunnerError, DEFAULT_RUNS, SHELL, SUMMARY_PERCENTILES,
+    },
+    compare::speedups,
+    diagnostics::{diagnose, Diagnostic},
+    environment::{check_environment, power_tag, EnvironmentWarning, SystemInfo, POWER_TAG},
+    format::{DurationFormat, TimeFormat},
+    history::{append_to_history, HistoryEntry},
+    io::{export_csv_with_progress, export_html_with_progress, export_json_with_progress, import_traces_with_tags, resolve_duplicates, split_tags, DuplicatePolicy, PlotOptions},
+    outliers::OutlierDetector,
+    provenance::{quote_command, Provenance},
+    tags::{parse_tags, tags_to_string, TaggedTrace, Tags, TraceTags},
 };
-use eframe::{App, CreationContext, Frame, Storage, egui::{CentralPanel, ProgressBar, Widget, Context}};
+use eframe::{App, CreationContext, Frame, Storage, egui::{plot::{Legend, Line, MarkerShape, Plot, PlotPoints, Points}, CentralPanel, ComboBox, DragValue, Grid, ProgressBar, RichText, TextEdit, Ui, Widget, Context}};
+use color_eyre::eyre::WrapErr;
 use egui_file::FileDialog;
 use itertools::Itertools;
-use crate::egui_utils::EguiList;
+use crate::{
+    baseline::Baseline,
+    egui_utils::{EguiList, Highlight},
+    gui_export::RunningExport,
+    native_dialog,
+    gui_storage::{load_setup, save_json, SetupStorage, SETUP_KEY},
+    theme::{theme_picker, Theme, ERROR_MARKER, FAST_MARKER, SLOW_MARKER, WARNING_MARKER},
+    ui_scale::{ui_scale_buttons, update_ui_scale, DEFAULT_UI_SCALE},
+};
 use std::{
     ffi::OsStr,
-    io,
+    iter::once,
     path::PathBuf,
     sync::mpsc::{channel, Receiver, Sender},
     thread::JoinHandle,
     time::Duration,
 };
 
+///How often to repaint whilst running, so new runs show up without any input
+const LIVE_REPAINT_INTERVAL: Duration = Duration::from_millis(100);
+
 ///[`egui`] app to actually run the benchmark - most of the state is stored inside [`State`]
 pub struct BencherApp {
     ///The number of runs we're going to do
     runs: usize,
+    ///Whether to pick files with the operating system's dialog rather than the [`egui_file`] one - this is kept between the states
+    native_dialogs: bool,
+    ///Whether to open HTML exports in the default browser once they've finished - this is kept between the states
+    open_after_export: bool,
+    ///How to write the times in the labels - this is kept between the states
+    duration_format: DurationFormat,
+    ///How much to scale the whole GUI by - this is kept between the states
+    ui_scale: f32,
+    ///Which colours to draw the GUI in - this is kept between the states
+    theme: Theme,
+    ///What the machine is, to show alongside the results - this is only collected once
+    system_info: SystemInfo,
     ///**The** [`State`]
     state: State,
 }
@@ -44,32 +78,78 @@ pub enum State {
         binary: Option<PathBuf>,
         /// `binary_dialog` stores an [`Option`] of a [`FileDialog`] which is the Dialog object from [`egui_file`] that lets a user pick a file - NB: no validation on whether or not it is a binary
         binary_dialog: Option<FileDialog>, //don't care if it is big - I'll only ever have one `State`
+        /// `shell` stores a [`bool`] on whether to run `shell_command` through the shell instead of running `binary`
+        shell: bool,
+        /// `shell_command` stores the command line (like `grep foo big.txt | wc -l`) to run through the shell if `shell` is set
+        shell_command: String,
         /// `cli_args` stores a [`EguiList`] of [`String`]s for all of the arguments we'll pass to `binary`
         cli_args: EguiList<String>,
         /// `current_cli_arg` stores a temporary [`String`] for user input of the next `cli_arg` to add to the list
         current_cli_arg: String,
         /// `runs_input` stores a temporary [`String`] for user input of the `runs`
         runs_input: String,
-        /// `warmup` stores a [`bool`] on whether or not to do a warmup run
-        warmup: bool,
+        /// `warmup` stores the number of warmup runs to do before the measured ones
+        warmup: u8,
+        /// `environment_warnings` stores a [`Vec`] of [`EnvironmentWarning`]s about the machine which could skew results
+        environment_warnings: Vec<EnvironmentWarning>,
+        /// `bench_anyway` stores a [`bool`] on whether or not the user wants to ignore the `environment_warnings` - we don't let them start until they do
+        bench_anyway: bool,
+        /// `remote_address` stores the address of a `precipice agent` to run on - if it is empty we run locally
+        remote_address: String,
+        /// `remote_token` stores the token for the agent at `remote_address`. This doesn't get saved
+        remote_token: String,
+        /// `tags_input` stores a temporary [`String`] for user input of the session's tags, like `machine=ryzen-box, commit=abc123`
+        tags_input: String,
+        /// `compare_commands` stores a [`EguiList`] of whole commands (like `./old_build --fast`) to compare `binary` against - if it is empty we just bench `binary`
+        compare_commands: EguiList<String>,
+        /// `current_compare_command` stores a temporary [`String`] for user input of the next command to add to `compare_commands`
+        current_compare_command: String,
+        /// `start_error` stores why the runner couldn't be started, if it couldn't
+        start_error: Option<String>,
     },
     /// [`State::Running`] represents the state whilst we're actively running the binary and keeps track of the runs and getting them.
     Running {
         /// `run_times` is a [`EguiList`] of [`Duration`]s that we've received so far from the [`Runner`]
         run_times: EguiList<Duration>,
+        /// `run_commands` stores which of `commands` each of `run_times` was from
+        run_commands: Vec<usize>,
+        /// `commands` stores the quoted command lines being run - there's only more than one when comparing
+        commands: Vec<String>,
+        /// `compared` stores the binaries and arguments of any commands being compared against `binary`, for the history
+        compared: Vec<(PathBuf, Vec<String>)>,
         /// `stop` is a unit tuple [`Sender`] which allows us to tell the [`Runner`] thread to stop execution as soon as it finishes with the current chunk.
         stop: Sender<()>,
-        /// `run_recv` is a [`Receiver`] for getting new [`Duration`]s to send to `run_times`.
-        run_recv: Receiver<Duration>,
+        /// `run_recv` is a [`Receiver`] for getting new [`RunMeasurement`]s, whose wall times get sent to `run_times`.
+        run_recv: Receiver<RunMeasurement>,
+        /// `error_recv` is a [`Receiver`] for getting any [`RunnerError`]s that stopped the [`Runner`] thread.
+        error_recv: Receiver<RunnerError>,
         /// `handle` stores a [`JoinHandle`] from [`Runner`], and is an [`Option`] to allow us to join the handle when it finishes as that requires ownership.
-        handle: Option<JoinHandle<io::Result<()>>>,
+        handle: Option<JoinHandle<()>>,
         ///`binary` stores a [`PathBuf`] with the binary we're running
         binary: PathBuf,
+        ///`cli_args` stores the arguments we passed to `binary`, for the history
+        cli_args: Vec<String>,
+        ///`shell` stores whether `binary` is really a command line being run through the shell, so the exports get named after the program it starts with
+        shell: bool,
+        ///`tags` stores the session's [`Tags`], for the history and the exports
+        tags: Tags,
+        ///`provenances` stores where the runs of each command are coming from, for the exports
+        provenances: Vec<Provenance>,
+        ///`outliers` stores an [`OutlierDetector`] for the runs of each command so far, to highlight the outliers in `run_times` - they get remade whenever new runs come in
+        outliers: Vec<Option<OutlierDetector>>,
+        ///`power_warnings` stores any [`EnvironmentWarning`]s about the power state when the session started (eg. being on battery), which are easy to forget about part-way through
+        power_warnings: Vec<EnvironmentWarning>,
     },
     /// [`State:PostContents`] represents what we're doing when we've finished - displaying results and stats as well as exporting.
     Finished {
         /// `run_times` is a [`EguiList`] of [`Duration`]s from the binary run times. If this changes - we need to update `min`, `max`, and `avg`
         run_times: EguiList<Duration>,
+        /// `run_commands` stores which of `commands` each of `run_times` was from
+        run_commands: Vec<usize>,
+        /// `commands` stores the quoted command lines that were run - there's only more than one when comparing, and then the stats below are for the first
+        commands: Vec<String>,
+        /// `comparison` stores a [`CommandSummary`] for each of `commands` when comparing, and is empty otherwise
+        comparison: Vec<CommandSummary>,
         /// `min` is the smallest [`Duration`] from `run_times`
         min: Duration,
         /// `max` is the biggest [`Duration`] from `run_times`
@@ -78,8 +158,18 @@ pub enum State {
         mean: Duration,
         /// `standard_deviation` is the population standard deviation [`Duration`] from `run_times`
         standard_deviation: Duration,
-        /// `export_handle`stores a [`JoinHandle`] from exporting `run_times` to a CSV to avoid blocking in immediate mode and is an [`Option`] to allow us to join the handle when it finishes as that requires ownership.
-        export_handle: Option<JoinHandle<io::Result<usize>>>,
+        /// `median` is the median [`Duration`] from `run_times`
+        median: Duration,
+        /// `median_absolute_deviation` is the median of how far each of `run_times` is from `median` - unlike `standard_deviation`, outliers barely move it
+        median_absolute_deviation: Duration,
+        /// `interquartile_range` is the gap between the first and third quartiles of `run_times`
+        interquartile_range: Duration,
+        /// `percentiles` stores the [`SUMMARY_PERCENTILES`] of `run_times`, for how slow the slowest runs were
+        percentiles: Vec<Duration>,
+        /// `diagnostics` stores anything about `run_times` which makes the stats above misleading
+        diagnostics: Vec<Diagnostic>,
+        /// `running_export` stores the [`RunningExport`] from exporting `run_times` on its own thread to avoid blocking in immediate mode, and is an [`Option`] to allow us to join it when it finishes as that requires ownership.
+        running_export: Option<RunningExport>,
         /// `file_name_input` stores a temporary variable to decide the name of the file name
         file_name_input: String,
         /// `trace_name_input` stores a temporary variable to decide the name of the trace
@@ -88,17 +178,59 @@ pub enum State {
         extra_trace_names_dialog: Option<FileDialog>,
         /// [`EguiList`] for trace names
         extra_files: EguiList<PathBuf>,
+        /// `errors` stores the messages from any [`RunnerError`]s that stopped the run early
+        errors: Vec<String>,
+        /// `tags` stores the session's [`Tags`], which go into the exports
+        tags: Tags,
+        /// `provenances` stores where the runs of each command came from, which also goes into the exports
+        provenances: Vec<Provenance>,
+        /// `export_error` stores why the last export failed, if it did
+        export_error: Option<String>,
+        /// `baseline` stores the [`Baseline`] to plot `run_times` against, if one has been loaded
+        baseline: Option<Baseline>,
+        /// File dialog for picking the baseline
+        baseline_dialog: Option<FileDialog>,
+        /// `baseline_error` stores why the last baseline failed to load, if it did
+        baseline_error: Option<String>,
+        /// `outliers` stores an [`OutlierDetector`] for the runs of each command, to highlight the outliers in `run_times`
+        outliers: Vec<Option<OutlierDetector>>,
     },
 }
 
+///The stats for one command of a comparison, to show in [`State::Finished`]
+pub struct CommandSummary {
+    ///The quoted command line
+    name: String,
+    ///How many of its runs finished
+    runs: usize,
+    ///The mean of its runs
+    mean: Duration,
+    ///The population standard deviation of its runs
+    standard_deviation: Duration,
+    ///The median of its runs
+    median: Duration,
+    ///How many times slower than the fastest command it was, so the fastest is `1.0` - [`None`] if it didn't have any runs
+    relative: Option<f64>,
+    ///Whether it was the fastest command
+    fastest: bool,
+    ///Anything about its runs which makes the stats above misleading
+    diagnostics: Vec<Diagnostic>,
+}
+
 impl State {
-    ///This creates a new State of [`Self::Setup`], with an empty `current_cli_arg`, no `binary_dialog` and unwrapping `runs_input` to itself or default and `warmup` to itself or false.
+    ///This creates a new State of [`Self::Setup`], with an empty `current_cli_arg`, no `binary_dialog` and unwrapping `runs_input` to itself or default and `warmup` to itself or no warmup runs.
     #[instrument]
+    #[allow(clippy::too_many_arguments)] //one for everything that gets stored
     fn new_from_args(
         binary: Option<PathBuf>,
+        shell: bool,
+        shell_command: Option<String>,
         cli_args: Vec<String>,
         runs_input: Option<String>,
-        warmup: Option<bool>,
+        warmup: Option<u8>,
+        remote_address: Option<String>,
+        tags_input: Option<String>,
+        compare_commands: Vec<String>,
     ) -> Self {
         Self::Setup {
             binary,
@@ -107,39 +239,44 @@ impl State {
                 .is_reorderable(true)
                 .is_editable(true),
             binary_dialog: None,
+            shell,
+            shell_command: shell_command.unwrap_or_default(),
             runs_input: runs_input.unwrap_or_else(|| DEFAULT_RUNS.to_string()),
-            warmup: warmup.unwrap_or(false),
+            warmup: warmup.unwrap_or(0),
+            environment_warnings: check_environment(),
+            bench_anyway: false,
+            remote_address: remote_address.unwrap_or_default(),
+            remote_token: String::default(),
+            tags_input: tags_input.unwrap_or_default(),
+            compare_commands: EguiList::from(compare_commands)
+                .is_reorderable(true)
+                .is_editable(true),
+            current_compare_command: String::default(),
+            start_error: None,
         }
     }
 }
 
-impl<'a> From<Option<&'a dyn Storage>> for State {
-    ///This uses the [`CreationContext`]'s persistent [`Storage`] to build a default state
-    #[instrument(skip(cc))]
-    fn from(cc: Option<&'a dyn Storage>) -> Self {
-        let binary = cc
-            .and_then(|s| s.get_string("binary_path")) //if we've got a creation context, grab the binary path
-            .map(PathBuf::from); //and turn it into a path
-        let cli_args: Vec<String> = cc
-            .and_then(|s| s.get_string("cli_args")) //if we've got a creation context, grab the cli args
-            .map(|s| {
-                if s.is_empty() {
-                    //"".split(/* anything */) returns vec![""], which we don't want, so we clear the vec if we see this
-                    vec![]
-                } else {
-                    s.split(EGUI_STORAGE_SEPARATOR) //if not, then split into a string by the separator
-                        .map(ToString::to_string)
-                        .collect()
-                }
-            })
-            .unwrap_or_default(); //if we didn't get any cli args, just set it to a new vector
-
-        let runs_input = cc.and_then(|s| s.get_string("runs")); //get the runs input
-        let warmup = cc
-            .and_then(|s| s.get_string("warmup"))
-            .and_then(|s| s.parse::<bool>().ok()); //if both warmup is a key, and that evaluates to a bool, then we use Some(that), if not we use None
-
-        Self::new_from_args(binary, cli_args, runs_input, warmup)
+impl From<SetupStorage> for State {
+    ///This uses what got stored in the [`CreationContext`]'s persistent [`Storage`] to build a default state
+    #[instrument]
+    fn from(
+        SetupStorage {
+            binary,
+            cli_args,
+            runs,
+            warmup,
+            remote_address,
+            tags,
+            compare_commands,
+            warmup_runs,
+            shell,
+            shell_command,
+            ..
+        }: SetupStorage,
+    ) -> Self {
+        let warmup = warmup_runs.or_else(|| warmup.map(u8::from)); //older versions only stored whether to do one warmup run
+        Self::new_from_args(binary.map(PathBuf::from), shell, shell_command, cli_args, runs, warmup, remote_address, tags, compare_commands)
     }
 }
 
@@ -147,10 +284,16 @@ impl BencherApp {
     ///This creates a new [`BencherApp`], using [`State::from`] from parsing stuff from the [`CreationContext`]'s [`Storage`], which is persistent between shutdowns
     #[instrument(skip(cc))]
     pub fn new(cc: &CreationContext) -> Self {
-        //turns the storage into a state
+        let setup = load_setup(cc.storage); //if we didn't have anything stored, everything is empty
         Self {
             runs: 0,
-            state: cc.storage.into(),
+            native_dialogs: setup.native_dialogs,
+            open_after_export: false,
+            duration_format: setup.duration_format,
+            ui_scale: setup.ui_scale.unwrap_or(DEFAULT_UI_SCALE),
+            theme: setup.theme,
+            system_info: SystemInfo::collect(),
+            state: setup.into(), //turns the storage into a state
         }
     }
 }
@@ -159,6 +302,8 @@ impl App for BencherApp {
     #[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
     fn update(&mut self, ctx: &Context, frame: &mut Frame) {
         //TODO: put this across different methods for each state
+        update_ui_scale(ctx, frame, &mut self.ui_scale); //before anything gets laid out, so the shortcuts work in every state
+        self.theme.apply(ctx);
         let mut change = None; //Variable to store a new State if we want to change
 
         //**Huge** match statement on our current state, mutably
@@ -167,25 +312,68 @@ impl App for BencherApp {
                 //if we are setting up
                 binary,
                 binary_dialog,
+                shell,
+                shell_command,
                 runs_input,
                 current_cli_arg,
                 cli_args,
                 warmup,
+                environment_warnings,
+                bench_anyway,
+                remote_address,
+                remote_token,
+                tags_input,
+                compare_commands,
+                current_compare_command,
+                start_error,
             } => {
                 CentralPanel::default().show(ctx, |ui| {
                     //new central panel
                     ui.label("Preparing to Bench"); //title label
                     ui.separator();
 
-                    //If we have a binary, display it, if not say we don't have one yet
-                    if let Some(binary) = binary {
-                        ui.label(format!("File to run: {binary:?}"));
-                    } else {
-                        ui.label("No file selected");
+                    if !environment_warnings.is_empty() {
+                        //banner for anything about the machine that could ruin the results
+                        for warning in environment_warnings.iter() {
+                            ui.colored_label(self.theme.warning(), format!("{WARNING_MARKER} Warning: {warning}"));
+                        }
+                        ui.horizontal(|ui| {
+                            ui.checkbox(bench_anyway, "Bench anyway?");
+                            if ui.button("Re-check").clicked() {
+                                *environment_warnings = check_environment();
+                            }
+                        });
+                        ui.separator();
                     }
 
-                    let clicked = ui.button("Change file").clicked(); //to avoid short-circuiting not showing the button
-                    if clicked && binary_dialog.is_none() {
+                    system_info_panel(ui, &self.system_info);
+                    ui.separator();
+
+                    ui.checkbox(shell, format!("Run a command line through the shell ({} {})?", SHELL.0, SHELL.1));
+                    let clicked = if *shell {
+                        ui.horizontal(|ui| {
+                            ui.label("Command line (eg. grep foo big.txt | wc -l): ");
+                            ui.text_edit_singleline(shell_command);
+                        });
+                        false
+                    } else {
+                        //If we have a binary, display it, if not say we don't have one yet
+                        if let Some(binary) = binary {
+                            ui.label(format!("File to run: {}", binary.display()));
+                        } else {
+                            ui.label("No file selected");
+                        }
+
+                        ui.checkbox(&mut self.native_dialogs, "Use the system file dialog?");
+                        ui.button("Change file").clicked() //to avoid short-circuiting not showing the button
+                    };
+                    if clicked && self.native_dialogs {
+                        if let Some(file) = native_dialog::pick_file(binary.as_deref(), None) {
+                            //no filter, as binaries don't have one extension
+                            *binary = Some(file);
+                            info!(binary=?binary.clone(), "Picked file");
+                        }
+                    } else if clicked && binary_dialog.is_none() {
                         //if we clicked it, and we don't currently have a dialog open
                         trace!(current_binary=?binary.clone(), "Showing File Dialog");
                         let mut dialog = FileDialog::open_file(binary.clone()); //open a dialog at the location of the current binary, and if we don't have one, its an option so we're all fine
@@ -201,12 +389,56 @@ impl App for BencherApp {
                         ui.text_edit_singleline(runs_input);
                     });
 
-                    ui.checkbox(warmup, "Do an initial warmup run."); //checkbox for whether or not we do a warmup run
+                    ui.horizontal(|ui| {
+                        ui.label("Warmup runs: ");
+                        ui.add(DragValue::new(warmup).clamp_range(0..=u8::MAX)); //the warmup runs don't get measured, they just get the binary into the cache
+                    });
+
+                    ui.horizontal(|ui| {
+                        //the run labels get cached, so this can only be changed before starting
+                        ComboBox::from_label("Times")
+                            .selected_text(self.duration_format.time_format.to_string())
+                            .show_ui(ui, |ui| {
+                                for time_format in [TimeFormat::Human, TimeFormat::Raw] {
+                                    ui.selectable_value(&mut self.duration_format.time_format, time_format, time_format.to_string());
+                                }
+                            });
+                        ui.add(DragValue::new(&mut self.duration_format.decimals).clamp_range(0..=9).suffix(" decimals"));
+                        let mut grouped = self.duration_format.thousands_separator.is_some();
+                        if ui.checkbox(&mut grouped, "Group thousands").changed() {
+                            self.duration_format.thousands_separator = grouped.then_some(',');
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        //the run labels get cached with their colours, so this can only be changed before starting
+                        ui_scale_buttons(ui, &mut self.ui_scale);
+                        theme_picker(ui, &mut self.theme);
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label("Remote agent (leave empty to run here): ");
+                        ui.text_edit_singleline(remote_address);
+                    });
+                    if !remote_address.is_empty() {
+                        ui.horizontal(|ui| {
+                            ui.label("Agent token: ");
+                            ui.add(TextEdit::singleline(remote_token).password(true));
+                        });
+                    }
+
+                    ui.horizontal(|ui| {
+                        ui.label("Tags (eg. machine=ryzen-box, commit=abc123): ");
+                        ui.text_edit_singleline(tags_input);
+                    });
+                    let tags = parse_tags(tags_input);
+                    if let Err(e) = &tags {
+                        ui.colored_label(self.theme.error(), format!("{ERROR_MARKER} {e}"));
+                    }
 
                     ui.separator();
 
                     ui.label("CLI Arguments");
-                    cli_args.display(ui, |arg, _i| arg.to_string()); //display all the CLI arguments, not displaying indicies
+                    cli_args.display(ui, |arg, _i| arg.clone()); //display all the CLI arguments, not displaying indicies
                     if let Some(change) = cli_args.had_update() {
                         trace!(?change, "CLI Args");
                     }
@@ -220,8 +452,37 @@ impl App for BencherApp {
                         cli_args.push(std::mem::take(current_cli_arg)); //take the current arg - this adds it to the list, and clears the input
                     }
 
-                    if binary.is_some() {
-                        //if we have a binary
+                    ui.separator();
+
+                    ui.label("Commands to Compare Against (eg. ./old_build --fast)");
+                    compare_commands.display(ui, |command, _i| command.clone());
+                    ui.horizontal(|ui| {
+                        ui.label("New Command");
+                        ui.text_edit_singleline(current_compare_command);
+                    });
+                    if ui.button("Submit new command!").clicked() && !current_compare_command.trim().is_empty() {
+                        compare_commands.push(std::mem::take(current_compare_command));
+                    }
+                    let compared = if *shell {
+                        Ok(compare_commands.iter().map(|command| (PathBuf::from(command.trim()), vec![])).collect()) //the shell splits them up
+                    } else {
+                        split_commands(compare_commands)
+                    };
+                    let binary_to_run = if *shell {
+                        Some(PathBuf::from(shell_command.trim())).filter(|command| !command.as_os_str().is_empty())
+                    } else {
+                        binary.clone()
+                    };
+                    if let Err(e) = &compared {
+                        ui.colored_label(self.theme.error(), format!("{ERROR_MARKER} {e}"));
+                    }
+                    let remote_comparison = !compare_commands.is_empty() && !remote_address.trim().is_empty();
+                    if remote_comparison {
+                        ui.colored_label(self.theme.error(), format!("{ERROR_MARKER} Comparisons can only be run here, not on an agent"));
+                    }
+
+                    if let Some(binary_to_run) = binary_to_run.filter(|_| tags.is_ok() && compared.is_ok() && !remote_comparison && (environment_warnings.is_empty() || *bench_anyway)) {
+                        //if we have a binary, valid tags and commands to compare against, and either the machine is fine or the user doesn't care
                         if let Ok(runs) = runs_input.parse::<usize>() {
                             //and we can successfully parse the runs
                             if runs > 0 {
@@ -230,36 +491,96 @@ impl App for BencherApp {
                                 if ui.button("Go!").clicked() {
                                     //and we click the go button
                                     trace!("Starting benchmarking");
-                                    self.runs = runs; //set the runner app variable for the runs
+                                    let compared = compared.unwrap_or_default(); //we only get here with valid commands
+                                    self.runs = runs * (compared.len() + 1); //set the runner app variable for the runs, of every command
                                     let (send_stop, recv_stop) = channel(); //Make a new channel for stopping/starting the Runner thread
 
-                                    let (handle, run_recv) = Runner::new(
-                                        binary.clone().unwrap(),
-                                        cli_args.backing_vec(),
-                                        runs,
-                                        Some(recv_stop),
-                                        match runs {
-                                            0..=10 => 0,
-                                            11..=25 => 1,
-                                            26..=50 => 2,
-                                            _ => 5,
-                                        },
-                                        true,
-                                    )
-                                    .start(); //make a new run and start it
-
-                                    change = Some(State::Running {
-                                        //make a new State with the relevant variables
-                                        run_times: EguiList::default().is_scrollable(true),
-                                        stop: send_stop,
-                                        run_recv,
-                                        handle: Some(handle),
-                                        binary: std::mem::take(binary).unwrap(),
-                                    });
+                                    let commands: Vec<_> = once((binary_to_run.clone(), cli_args.backing_vec())).chain(compared.iter().cloned()).collect();
+                                    let name_of = |(binary, cli_args): &(PathBuf, Vec<String>)| {
+                                        if *shell {
+                                            shell_command_line(binary, cli_args) //it goes to the shell as it is, so show it that way
+                                        } else {
+                                            quote_command(binary, cli_args)
+                                        }
+                                    };
+                                    let command_names = commands.iter().map(name_of).collect();
+
+                                    let provenances = commands
+                                        .iter()
+                                        .map(|command| {
+                                            let provenance = Provenance::start(name_of(command));
+                                            let (binary, _) = command;
+                                            if remote_address.trim().is_empty() && !*shell {
+                                                provenance.with_binary_hash(binary)
+                                            } else {
+                                                provenance //the binary is on the agent (or is a whole command line), so we can't hash it
+                                            }
+                                        })
+                                        .collect();
+
+                                    let started = if compared.is_empty() {
+                                        let runner = Runner::new(binary_to_run.clone(), cli_args.backing_vec(), runs, Some(recv_stop), *warmup, true).shell(*shell); //make a new run
+                                        if remote_address.trim().is_empty() {
+                                            runner.start()
+                                        } else {
+                                            //if we have an agent, run it there
+                                            RemoteRunner::new(
+                                                remote_address.trim().to_string(),
+                                                Some(remote_token.clone()).filter(|t| !t.is_empty()),
+                                                runner,
+                                            )
+                                            .start()
+                                        }
+                                    } else {
+                                        //interleave the runs of every command, so they all get the same conditions
+                                        MultiRunner::new(commands, runs, Some(recv_stop), *warmup).shell(*shell).start()
+                                    };
+
+                                    let power_warnings: Vec<_> = if remote_address.trim().is_empty() {
+                                        check_environment().into_iter().filter(EnvironmentWarning::is_power_state).collect() //checked again, as it might have been a while since the setup screen opened
+                                    } else {
+                                        vec![] //the agent's power state isn't ours to check
+                                    };
+                                    let mut tags = tags.unwrap_or_default(); //we only get here with valid tags
+                                    if let Some(power) = power_tag(&power_warnings) {
+                                        tags.entry(POWER_TAG.to_string()).or_insert(power); //so the exports say the runs were on battery
+                                    }
+
+                                    match started {
+                                        //and start it
+                                        Ok((handle, run_recv, error_recv)) => {
+                                            change = Some(State::Running {
+                                                //make a new State with the relevant variables
+                                                run_times: EguiList::default().is_scrollable(true),
+                                                run_commands: vec![],
+                                                commands: command_names,
+                                                compared,
+                                                stop: send_stop,
+                                                run_recv,
+                                                error_recv,
+                                                handle: Some(handle),
+                                                binary: binary_to_run,
+                                                cli_args: cli_args.backing_vec(),
+                                                shell: *shell,
+                                                tags,
+                                                provenances,
+                                                outliers: vec![],
+                                                power_warnings,
+                                            });
+                                        }
+                                        Err(e) => {
+                                            error!(%e, "Unable to start runner");
+                                            *start_error = Some(format!("unable to start the runner: {e}"));
+                                        }
+                                    }
                                 }
                             }
                         }
                     }
+
+                    if let Some(start_error) = start_error {
+                        ui.colored_label(self.theme.error(), format!("{ERROR_MARKER} {start_error}"));
+                    }
                 });
 
                 let mut should_close = false; //temp variable to avoid ownership faff
@@ -283,35 +604,82 @@ impl App for BencherApp {
             State::Running {
                 //if we are running runs
                 run_times,
+                run_commands,
+                commands,
+                compared,
                 stop,
                 run_recv,
+                error_recv,
                 handle,
                 binary,
+                cli_args,
+                shell,
+                tags,
+                provenances,
+                outliers,
+                power_warnings,
             } => {
-                for time in run_recv.try_iter() {
+                let mut got_runs = false;
+                for measurement in run_recv.try_iter() {
                     //for every message since we last checked, add it to the buffer
-                    run_times.push(time);
+                    run_times.push(measurement.wall);
+                    run_commands.push(measurement.command);
+                    got_runs = true;
+                }
+                if got_runs {
+                    *outliers = outliers_per_command(run_times, run_commands, commands.len()); //the labels get remade when new runs come in, so this only needs to be up to date then
                 }
 
-                if handle.as_ref().map_or(false, JoinHandle::is_finished) {
+                if handle.as_ref().is_some_and(JoinHandle::is_finished) {
                     //if we have a handle, and it is finished
-                    match std::mem::take(handle).unwrap().join() {
-                        //join the handle and report errors - we can unwrap here as we only go above if we have a handle
-                        Ok(Err(e)) => error!(%e, "Error from running handle"),
-                        Err(_e) => error!("Error joining running handle"),
-                        _ => {}
+                    let panicked = std::mem::take(handle).unwrap().join().is_err(); //join the handle - we can unwrap here as we only go above if we have a handle
+                    if panicked {
+                        error!("Error joining running handle");
+                    }
+                    for measurement in run_recv.try_iter() {
+                        //grab any runs that came in between the last poll and the thread finishing
+                        run_times.push(measurement.wall);
+                        run_commands.push(measurement.command);
+                    }
+                    *outliers = outliers_per_command(run_times, run_commands, commands.len());
+                    let mut errors: Vec<String> = error_recv.try_iter().map(|e| e.to_string()).collect(); //and any errors that stopped the thread
+                    if panicked {
+                        errors.push("the runner thread panicked, so some runs might be missing".into());
+                    }
+
+                    let first_runs = runs_of(run_times, run_commands, 0); //the first command is the only one, unless we're comparing
+                    let first_micros = first_runs.iter().map(Duration::as_micros).collect_vec(); //have to collect vec as we can't know the size of [u128] at compile-time
+                    let max = first_runs.iter().max().copied().unwrap_or_default(); //get the max and min
+                    let min = first_runs.iter().min().copied().unwrap_or_default();
+                    let (mean, standard_deviation) = calculate_mean_standard_deviation(&first_micros).unwrap_or_default(); //get the mean and standard deviation
+                    let (median, median_absolute_deviation, interquartile_range) = calculate_median_mad_iqr(&first_micros).unwrap_or_default(); //and the more robust stats
+                    let percentiles = calculate_percentiles(&first_micros, &SUMMARY_PERCENTILES).unwrap_or_default(); //and the slow tail
+                    let diagnostics = diagnose(&first_micros); //and whether to trust any of them
+                    let comparison = if commands.len() > 1 {
+                        command_summaries(run_times, run_commands, commands)
+                    } else {
+                        vec![]
                     };
 
-                    let max = run_times.iter().max().copied().unwrap_or_default(); //get the max and min
-                    let min = run_times.iter().min().copied().unwrap_or_default();
-                    let (mean, standard_deviation) = calculate_mean_standard_deviation(
-                        &run_times.iter().map(Duration::as_micros).collect_vec(), //have to collect vec as we can't know the size of [u128] at compile-time
-                    )
-                    .unwrap_or_default(); //get the mean and standard deviation
+                    for (command, (binary, cli_args)) in once((binary.clone(), cli_args.clone())).chain(compared.iter().cloned()).enumerate() {
+                        let runs = runs_of(run_times, run_commands, command).iter().map(Duration::as_micros).collect_vec();
+                        if let Some(entry) = HistoryEntry::new(&binary, &cli_args, &runs).map(|entry| entry.with_tags(tags.clone())) {
+                            //save the session to the per-user history, with each command getting its own entry
+                            if let Err(e) = append_to_history(&entry) {
+                                error!(%e, "Unable to save to history");
+                            }
+                        }
+                    }
 
+                    let program = if *shell {
+                        //a command line isn't a path, so go by the program it starts with
+                        binary.to_str().and_then(|line| line.split_whitespace().next()).map_or_else(|| binary.clone(), PathBuf::from)
+                    } else {
+                        binary.clone()
+                    };
                     let file_name = format!(
                         "{}_{}",
-                        binary
+                        program
                             .file_name()
                             .and_then(OsStr::to_str) //TODO: make this into its own method for consistency
                             .unwrap_or("bench_results"), //same as the runner cli default file name
@@ -321,15 +689,31 @@ impl App for BencherApp {
                         //make a new state
                         //new state
                         run_times: run_times.clone(),
+                        run_commands: std::mem::take(run_commands),
+                        commands: std::mem::take(commands),
+                        comparison,
                         min,
                         max,
                         mean,
                         standard_deviation,
-                        export_handle: None,
+                        median,
+                        median_absolute_deviation,
+                        interquartile_range,
+                        percentiles,
+                        diagnostics,
+                        running_export: None,
                         file_name_input: file_name.clone(),
                         trace_name_input: file_name, //same default trace name as file name
                         extra_trace_names_dialog: None,
                         extra_files: EguiList::default(),
+                        errors,
+                        tags: std::mem::take(tags),
+                        provenances: provenances.iter().cloned().map(Provenance::finish).collect(),
+                        export_error: None,
+                        baseline: None,
+                        baseline_dialog: None,
+                        baseline_error: None,
+                        outliers: std::mem::take(outliers),
                     });
                 } else {
                     //if we don't have a finished handle
@@ -337,51 +721,162 @@ impl App for BencherApp {
                         let runs_so_far = run_times.len();
 
                         ui.label("Running!");
+                        if commands.len() > 1 {
+                            ui.label(format!("Comparing {}", commands.join(" vs ")));
+                        }
                         ui.label(format!("{} runs left.", self.runs - runs_so_far));
+                        for warning in power_warnings.iter() {
+                            //big and red, as runs on battery can easily be a third slower
+                            ui.label(
+                                RichText::new(format!("{WARNING_MARKER} Warning: {warning} - these results will probably be slower than usual"))
+                                    .color(self.theme.error())
+                                    .heading(),
+                            );
+                        }
                         ui.separator();
 
-                        run_times.display(ui, |dur, i| format!("Run {} took {dur:?}", i + 1)); //display all runs
+                        live_plot(ui, run_times, run_commands, commands, outliers, self.theme);
                         ui.separator();
 
-                        ProgressBar::new((runs_so_far as f32) / (self.runs as f32)).ui(ui); //show all runs and add progress bar
+                        run_times.display_highlighted(
+                            ui,
+                            |dur, i| run_label(i + 1, *dur, commands, run_commands[i], self.duration_format),
+                            |dur, i| outlier_highlight(outliers.get(run_commands[i]).and_then(Option::as_ref), dur, self.theme),
+                        ); //display all runs, with the outliers standing out
+                        ui.separator();
+
+                        ProgressBar::new((runs_so_far as f32) / (self.runs as f32))
+                            .text(format!("{runs_so_far} of {} runs", self.runs))
+                            .ui(ui); //show all runs and add progress bar
 
                         if ui.button("Stop!").clicked() {
                             info!("Sending stop signal");
-                            stop.send(()).expect("Cannot send stop signal"); //if we want to stop, then send stop message on channel
+                            if stop.send(()).is_err() {
+                                //if we want to stop, then send stop message on channel - if the runner has already gone, it'll be finished on the next frame anyway
+                                warn!("Runner has already stopped");
+                            }
                         }
                     });
+                    ctx.request_repaint_after(LIVE_REPAINT_INTERVAL); //egui only repaints on input, so wake it up to show the new runs
                 }
             }
             State::Finished {
                 //if we've finished the runs
                 run_times,
+                run_commands,
+                commands,
+                comparison,
                 min,
                 max,
                 mean,
                 standard_deviation,
-                export_handle,
+                median,
+                median_absolute_deviation,
+                interquartile_range,
+                percentiles,
+                diagnostics,
+                running_export,
                 file_name_input,
                 trace_name_input,
                 extra_files,
                 extra_trace_names_dialog,
+                errors,
+                tags,
+                provenances,
+                export_error,
+                baseline,
+                baseline_dialog,
+                baseline_error,
+                outliers,
             } => {
                 CentralPanel::default().show(ctx, |ui| {
                     ui.label("All runs finished!");
-                    ui.label(format!(
-                        "{mean:?} ± {standard_deviation:?}, from {min:?} to {max:?}."
-                    ));
+                    let time = |duration: &Duration| self.duration_format.duration(*duration);
+                    if comparison.is_empty() {
+                        ui.label(format!(
+                            "{} ± {}, from {} to {}.",
+                            time(mean),
+                            time(standard_deviation),
+                            time(min),
+                            time(max)
+                        ));
+                        ui.label(format!(
+                            "Median {} ± {} (MAD), with an IQR of {}.",
+                            time(median),
+                            time(median_absolute_deviation),
+                            time(interquartile_range)
+                        ));
+                        if !percentiles.is_empty() {
+                            ui.label(
+                                SUMMARY_PERCENTILES
+                                    .iter()
+                                    .zip(percentiles.iter())
+                                    .map(|(percentile, value)| format!("P{percentile} {}", time(value)))
+                                    .join(", "),
+                            );
+                        }
+                    } else {
+                        comparison_panel(ui, comparison, self.duration_format, self.theme);
+                    }
+
+                    for (provenance, command) in provenances.iter().zip(commands.iter()) {
+                        if comparison.is_empty() {
+                            ui.label(format!("Session {}", provenance.session_id));
+                        } else {
+                            ui.label(format!("Session {} for {command}", provenance.session_id));
+                        }
+                    }
+                    if !tags.is_empty() {
+                        ui.label(format!("Tags: {}", tags_to_string(tags)));
+                    }
+                    system_info_panel(ui, &self.system_info);
+
+                    if comparison.is_empty() {
+                        for diagnostic in diagnostics.iter() {
+                            ui.colored_label(self.theme.warning(), format!("{WARNING_MARKER} {diagnostic}"));
+                        }
+                    }
+                    for error in errors.iter() {
+                        //if the runner stopped early, say why
+                        ui.colored_label(self.theme.error(), format!("{ERROR_MARKER} {error}"));
+                    }
 
                     ui.separator();
-                    run_times.display(ui, |dur, i| format!("Run {i} took {dur:?}"));
+                    run_times.display_highlighted(
+                        ui,
+                        |dur, i| run_label(i, *dur, commands, run_commands[i], self.duration_format),
+                        |dur, i| outlier_highlight(outliers.get(run_commands[i]).and_then(Option::as_ref), dur, self.theme),
+                    );
+                    ui.separator();
+
+                    let clicked = ui.button("Load Baseline").clicked(); //to avoid short-circuiting not showing the button
+                    if clicked && self.native_dialogs {
+                        let start = baseline.as_ref().map(|baseline| baseline.file.as_path());
+                        if let Some(file) = native_dialog::pick_file(start, Some(("Results", &["csv", "json"]))) {
+                            info!(baseline=?file, "Picked file for baseline");
+                            (*baseline, *baseline_error) = load_baseline(file);
+                        }
+                    } else if clicked && baseline_dialog.is_none() {
+                        let mut dialog = FileDialog::open_file(baseline.as_ref().map(|baseline| baseline.file.clone()));
+                        dialog.open();
+                        *baseline_dialog = Some(dialog);
+                    }
+                    if let Some(baseline_error) = baseline_error {
+                        ui.colored_label(self.theme.error(), format!("{ERROR_MARKER} {baseline_error}"));
+                    }
+                    if let Some(baseline) = baseline {
+                        //plot this session against the baseline, so the difference is obvious at a glance
+                        baseline.display(ui, &runs_of(run_times, run_commands, 0).iter().map(Duration::as_micros).collect_vec(), self.theme); //against the first command, if we're comparing
+                    }
                     ui.separator();
 
                     if ui.button("Go back to start").clicked() {
                         //if we need to go back to the start
                         trace!("Going back to start");
-                        change = Some(frame.storage().into()); //restart using the storage
+                        change = Some(load_setup(frame.storage()).into()); //restart using the storage
                     }
 
-                    if export_handle.is_none() {
+                    if running_export.is_none() {
                         //if we aren't currently exporting
                         ui.horizontal(|ui| {
                             ui.label("File name (w/o extension): ");
@@ -395,86 +890,97 @@ impl App for BencherApp {
 
                         ui.separator();
                         let clicked = ui.button("Add Extra Traces").clicked(); //to avoid short-circuiting not showing the button
-                        if clicked && extra_trace_names_dialog.is_none() {
+                        if clicked && self.native_dialogs {
+                            let start = extra_files.last().map(PathBuf::as_path);
+                            if let Some(file) = native_dialog::pick_file(start, Some(("Results", &["csv", "json"]))) {
+                                info!(trace=?file, "Picked file for extra traces");
+                                extra_files.push(file); //add the path to the extra files list
+                            }
+                        } else if clicked && extra_trace_names_dialog.is_none() {
                             let mut dialog = FileDialog::open_file(None);
                             dialog.open(); //make a new file dialog, open it, and then save it to the State
                             *extra_trace_names_dialog = Some(dialog);
                         }
 
-                        extra_files.display(ui, |file, _i| format!("{file:?}")); //display all of the extra trace file names
+                        extra_files.display(ui, |file, _i| file.display().to_string()); //display all of the extra trace file names
 
                         ui.vertical(|ui| {
                             if ui.button("Export to CSV").clicked() {
                                 //if we export to CSV
                                 info!("Exporting to CSV");
 
-                                let run_times = run_times.clone(); //thread-local clones to avoid move ownership faffery
-                                let file_name_input = file_name_input.clone();
-                                let trace_name_input = trace_name_input.clone();
+                                let traces = session_traces(run_times, run_commands, trace_name_input, tags, provenances); //with the provenance, so the export can always be traced back to this session
+                                let file_name_input = file_name_input.clone(); //thread-local clones to avoid move ownership faffery
                                 let extra_traces = extra_files.backing_vec();
 
-                                *export_handle = Some(
-                                    std::thread::Builder::new() //new thread for CSV export to avoid blocking on UI
-                                        .name("csv_exporter".into())
-                                        .spawn(move || {
-                                            export_csv(
-                                                //start a CSV export
-                                                Some((
-                                                    trace_name_input,
-                                                    run_times
-                                                        .backing_vec()
-                                                        .into_iter()
-                                                        .map(|d| d.as_micros())
-                                                        .collect(),
-                                                )),
-                                                file_name_input,
-                                                extra_traces,
-                                            )
-                                        })
-                                        .expect("error creating thread"),
-                                );
+                                let started = RunningExport::start("csv_exporter", move |progress| {
+                                    //new thread for CSV export to avoid blocking on UI, keeping the tags from the extra traces as well as ours
+                                    let (traces, tags) = traces_with_extras(traces, extra_traces)?;
+                                    Ok(export_csv_with_progress(&file_name_input, traces, &tags, progress)?) //the error says which file it was
+                                });
+                                match started {
+                                    Ok(export) => *running_export = Some(export),
+                                    Err(e) => *export_error = Some(format!("unable to start exporting: {e}")),
+                                }
+                            }
+
+                            if ui.button("Export to JSON").clicked() {
+                                //if we export to JSON
+                                info!("Exporting to JSON");
+
+                                let traces = session_traces(run_times, run_commands, trace_name_input, tags, provenances);
+                                let file_name_input = file_name_input.clone(); //thread-local clones to avoid move ownership faffery
+                                let extra_traces = extra_files.backing_vec();
+
+                                let started = RunningExport::start("json_exporter", move |progress| {
+                                    //new thread for JSON export to avoid blocking on UI, with the statistics for every trace
+                                    let (traces, tags) = traces_with_extras(traces, extra_traces)?;
+                                    Ok(export_json_with_progress(&file_name_input, traces, &tags, progress)?) //the error says which file it was
+                                });
+                                match started {
+                                    Ok(export) => *running_export = Some(export),
+                                    Err(e) => *export_error = Some(format!("unable to start exporting: {e}")),
+                                }
                             }
 
                             if ui.button("Export to HTML").clicked() {
                                 //if we export to HTML
                                 info!("Exporting to HTML");
 
-                                let run_times = run_times.clone(); //thread-local clones to avoid move ownership faffery
-                                let file_name_input = file_name_input.clone();
-                                let trace_name_input = trace_name_input.clone();
+                                let traces = session_traces(run_times, run_commands, trace_name_input, tags, provenances);
+                                let file_name_input = file_name_input.clone(); //thread-local clones to avoid move ownership faffery
                                 let extra_traces = extra_files.backing_vec();
+                                let html_file = PathBuf::from(format!("{file_name_input}.html"));
 
-                                *export_handle = Some(
-                                    std::thread::Builder::new() //new thread for HTML export to avoid blocking on UI
-                                        .name("html_exporter".into())
-                                        .spawn(move || {
-                                            export_html(
-                                                //start an HTML export
-                                                Some((
-                                                    trace_name_input,
-                                                    run_times
-                                                        .backing_vec()
-                                                        .into_iter()
-                                                        .map(|d| d.as_micros())
-                                                        .collect(),
-                                                )),
-                                                file_name_input,
-                                                extra_traces,
-                                            )
-                                        })
-                                        .expect("error creating thread"),
-                                );
+                                let started = RunningExport::start("html_exporter", move |progress| {
+                                    //new thread for HTML export to avoid blocking on UI, with the tags in its raw data link
+                                    let (traces, tags) = traces_with_extras(traces, extra_traces)?;
+                                    Ok(export_html_with_progress(&file_name_input, traces, &PlotOptions {
+                                        tags,
+                                        ..PlotOptions::default()
+                                    }, progress)?)
+                                });
+                                match started {
+                                    Ok(export) if self.open_after_export => {
+                                        *running_export = Some(export.open_when_done(html_file));
+                                    }
+                                    Ok(export) => *running_export = Some(export),
+                                    Err(e) => *export_error = Some(format!("unable to start exporting: {e}")),
+                                }
                             }
+                            ui.checkbox(&mut self.open_after_export, "Open after export");
                         });
-                    } else {
-                        ui.label("Exporting..."); //if we haven't finished, but have a handle then say we're exporting
+                    } else if let Some(export) = running_export {
+                        export.display(ui); //if we haven't finished, then show how far we've got
+                    }
+
+                    if let Some(export_error) = export_error {
+                        ui.colored_label(self.theme.error(), format!("{ERROR_MARKER} {export_error}"));
                     }
                 });
 
-                if export_handle.as_ref().map_or(false, |eh| eh.is_finished()) {
-                    let thread = std::mem::take(export_handle).expect("just checked that the thread was non-none");
-                    let n = thread.join().expect("unable to join export handle");
-                    trace!(?n, "Finished export");
+                if let Some(export) = running_export.take_if(|export| export.is_finished()) {
+                    *export_error = export.join();
                 }
 
                 let mut should_close = false; //temp variable for if we need to close stuff to avoid ownership faffery
@@ -494,6 +1000,16 @@ impl App for BencherApp {
                     info!("Closing File Dialog for traces");
                     *extra_trace_names_dialog = None;
                 }
+
+                if let Some(dialog) = baseline_dialog {
+                    if dialog.show(ctx).selected() {
+                        if let Some(file) = dialog.path() {
+                            info!(baseline=?file, "Picked file for baseline");
+                            (*baseline, *baseline_error) = load_baseline(file);
+                            *baseline_dialog = None;
+                        }
+                    }
+                }
             }
         }
 
@@ -513,19 +1029,34 @@ impl App for BencherApp {
         if let State::Setup {
             //we only need to save Pre stuff, so check if we've got that
             binary,
+            shell,
+            shell_command,
             cli_args,
             runs_input,
             warmup,
+            remote_address,
+            tags_input,
+            compare_commands,
             ..
         } = &self.state
         {
-            if let Some(Ok(binary)) = binary.clone().map(|b| b.into_os_string().into_string()) {
-                //only save binary if we can export it to a valid String - utf-8 issues can arise from PathBufs
-                storage.set_string("binary_path", binary);
-            }
-            storage.set_string("cli_args", cli_args.join(EGUI_STORAGE_SEPARATOR));
-            storage.set_string("runs", runs_input.to_string());
-            storage.set_string("warmup", warmup.to_string());
+            let setup = SetupStorage {
+                binary: binary.as_deref().map(Into::into),
+                cli_args: cli_args.backing_vec(),
+                runs: Some(runs_input.clone()),
+                warmup: None, //only kept to migrate from older versions
+                warmup_runs: Some(*warmup),
+                remote_address: Some(remote_address.clone()),
+                tags: Some(tags_input.clone()),
+                compare_commands: compare_commands.backing_vec(),
+                shell: *shell,
+                shell_command: Some(shell_command.clone()),
+                native_dialogs: self.native_dialogs,
+                duration_format: self.duration_format,
+                ui_scale: Some(self.ui_scale),
+                theme: self.theme,
+            };
+            save_json(storage, SETUP_KEY, &setup);
 
             info!("Saved stuff");
 
@@ -533,3 +1064,231 @@ impl App for BencherApp {
         }
     }
 }
+
+///Shows the [`SystemInfo`] in a collapsible panel, so screenshots of the results carry what they were run on
+fn system_info_panel(ui: &mut Ui, system_info: &SystemInfo) {
+    ui.collapsing("System Information", |ui| {
+        Grid::new("system_info").num_columns(2).striped(true).show(ui, |ui| {
+            for (label, value) in system_info.rows() {
+                ui.label(label);
+                ui.label(value);
+                ui.end_row();
+            }
+        });
+    });
+}
+
+///Shows the stats for every command of a comparison in a table, followed by which was fastest and how much faster it was than the others
+fn comparison_panel(ui: &mut Ui, comparison: &[CommandSummary], duration_format: DurationFormat, theme: Theme) {
+    let time = |duration: Duration| duration_format.duration(duration);
+    Grid::new("comparison").num_columns(5).striped(true).show(ui, |ui| {
+        for heading in ["Command", "Runs", "Mean ± σ", "Median", "Relative"] {
+            ui.label(RichText::new(heading).strong());
+        }
+        ui.end_row();
+
+        for summary in comparison {
+            ui.label(&summary.name);
+            ui.label(summary.runs.to_string());
+            ui.label(format!("{} ± {}", time(summary.mean), time(summary.standard_deviation)));
+            ui.label(time(summary.median));
+            ui.label(summary.relative.map_or_else(|| "-".to_string(), |relative| format!("{relative:.2}x")));
+            ui.end_row();
+        }
+    });
+
+    if let Some(fastest) = comparison.iter().find(|summary| summary.fastest) {
+        ui.label(RichText::new(format!("{} is the fastest", fastest.name)).strong());
+        for summary in comparison.iter().filter(|summary| !summary.fastest) {
+            if let Some(relative) = summary.relative {
+                ui.label(format!("  {relative:.2}x faster than {}", summary.name));
+            }
+        }
+    }
+
+    for summary in comparison {
+        for diagnostic in &summary.diagnostics {
+            ui.colored_label(theme.warning(), format!("{WARNING_MARKER} {}: {diagnostic}", summary.name));
+        }
+    }
+}
+
+///Plots the runs so far against their run number, with a line for each command, so any jitter or warm-up decay shows up whilst the session is still going.
+///
+/// Outliers get drawn on top as triangles pointing the same way as their markers in the list, so they stand out without telling colours apart.
+#[allow(clippy::cast_precision_loss)]
+fn live_plot(ui: &mut Ui, run_times: &[Duration], run_commands: &[usize], commands: &[String], outliers: &[Option<OutlierDetector>], theme: Theme) {
+    Plot::new("live_plot")
+        .legend(Legend::default())
+        .height(200.0)
+        .allow_scroll(false)
+        .show(ui, |plot_ui| {
+            for (command, name) in commands.iter().enumerate() {
+                let runs = runs_of(run_times, run_commands, command);
+                let points = |keep: &dyn Fn(&Duration) -> bool| {
+                    runs.iter()
+                        .enumerate()
+                        .filter(|(_, run)| keep(run))
+                        .map(|(i, run)| [(i + 1) as f64, run.as_micros() as f64])
+                        .collect::<PlotPoints>()
+                };
+
+                let line = Line::new(points(&|_| true)).name(name);
+                plot_ui.line(if commands.len() == 1 { line.color(theme.session()) } else { line }); //a comparison needs a colour for each command
+
+                if let Some(outliers) = outliers.get(command).and_then(Option::as_ref) {
+                    let is_outlier = |run: &Duration, slow: bool| outliers.is_outlier(run.as_micros()) && (outliers.z_score(run.as_micros()) > 0.0) == slow;
+                    plot_ui.points(
+                        Points::new(points(&|run| is_outlier(run, true)))
+                            .shape(MarkerShape::Up)
+                            .filled(true)
+                            .radius(4.0)
+                            .color(theme.slow())
+                            .name("Slow outliers"),
+                    );
+                    plot_ui.points(
+                        Points::new(points(&|run| is_outlier(run, false)))
+                            .shape(MarkerShape::Down)
+                            .filled(true)
+                            .radius(4.0)
+                            .color(theme.fast())
+                            .name("Fast outliers"),
+                    );
+                }
+            }
+        });
+    ui.label("Run number against time (µs).");
+}
+
+///Splits each of the commands to compare against into its binary and arguments, like a shell would
+fn split_commands(commands: &[String]) -> Result<Vec<(PathBuf, Vec<String>)>, String> {
+    commands
+        .iter()
+        .map(|command| {
+            let parts = shell_words::split(command).map_err(|e| format!("unable to split {command:?}: {e}"))?;
+            parts
+                .split_first()
+                .map(|(binary, cli_args)| (PathBuf::from(binary), cli_args.to_vec()))
+                .ok_or_else(|| "one of the commands to compare against is empty".to_string())
+        })
+        .collect()
+}
+
+///Loads a [`Baseline`], giving back either it or why it couldn't be loaded to show to the user
+fn load_baseline(file: PathBuf) -> (Option<Baseline>, Option<String>) {
+    match Baseline::load(file) {
+        Ok(baseline) => (Some(baseline), None),
+        Err(e) => {
+            error!(?e, "Unable to load baseline");
+            (None, Some(format!("{e:#}")))
+        }
+    }
+}
+
+///Gets the runs of one command from a session's runs, where `run_commands` says which command each run was of
+fn runs_of(run_times: &[Duration], run_commands: &[usize], command: usize) -> Vec<Duration> {
+    run_times
+        .iter()
+        .zip(run_commands)
+        .filter(|(_, run_command)| **run_command == command)
+        .map(|(run, _)| *run)
+        .collect()
+}
+
+///Makes an [`OutlierDetector`] for the runs of each command, as one command's runs aren't outliers just for being slower than another's
+fn outliers_per_command(run_times: &[Duration], run_commands: &[usize], commands: usize) -> Vec<Option<OutlierDetector>> {
+    (0..commands)
+        .map(|command| OutlierDetector::new(&runs_of(run_times, run_commands, command).iter().map(Duration::as_micros).collect_vec()))
+        .collect()
+}
+
+///Works out the [`CommandSummary`] for each command of a comparison
+fn command_summaries(run_times: &[Duration], run_commands: &[usize], commands: &[String]) -> Vec<CommandSummary> {
+    let runs = (0..commands.len())
+        .map(|command| runs_of(run_times, run_commands, command).iter().map(Duration::as_micros).collect_vec())
+        .collect_vec();
+    let (fastest, relative) = speedups(&runs.iter().map(Vec::as_slice).collect_vec()).map_or_else(|| (None, vec![None; commands.len()]), |(fastest, relative)| (Some(fastest), relative));
+
+    commands
+        .iter()
+        .zip(runs)
+        .zip(relative)
+        .enumerate()
+        .map(|(i, ((name, runs), relative))| {
+            let (mean, standard_deviation) = calculate_mean_standard_deviation(&runs).unwrap_or_default();
+            let (median, _, _) = calculate_median_mad_iqr(&runs).unwrap_or_default();
+            CommandSummary {
+                name: name.clone(),
+                runs: runs.len(),
+                mean,
+                standard_deviation,
+                median,
+                relative,
+                fastest: fastest == Some(i),
+                diagnostics: diagnose(&runs),
+            }
+        })
+        .collect()
+}
+
+///Gets the traces from a finished session - one called `trace_name` if there was only one command, or one called after each command if we were comparing - with the session's tags and where each one's runs came from
+fn session_traces(run_times: &[Duration], run_commands: &[usize], trace_name: &str, tags: &Tags, provenances: &[Provenance]) -> Vec<TaggedTrace> {
+    provenances
+        .iter()
+        .enumerate()
+        .map(|(command, provenance)| {
+            let name = if provenances.len() == 1 {
+                trace_name.to_string()
+            } else {
+                provenance.command.clone()
+            };
+            let runs = runs_of(run_times, run_commands, command).iter().map(Duration::as_micros).collect();
+            let mut tags = tags.clone();
+            tags.extend(provenance.to_tags());
+            (name, runs, tags)
+        })
+        .collect()
+}
+
+///Labels a run, with which command it was of if we're comparing
+fn run_label(number: usize, run: Duration, commands: &[String], command: usize, duration_format: DurationFormat) -> String {
+    if commands.len() > 1 {
+        format!("Run {number} of {} took {}", commands[command], duration_format.duration(run))
+    } else {
+        format!("Run {number} took {}", duration_format.duration(run))
+    }
+}
+
+///Gets the traces to export from a finished session, along with any extra trace files - keeping the tags for all of them, and suffixing any duplicate names
+#[allow(clippy::type_complexity)] //the same as split_tags gives back
+fn traces_with_extras(session_traces: Vec<TaggedTrace>, extra_traces: Vec<PathBuf>) -> color_eyre::Result<(Vec<(String, Vec<u128>)>, TraceTags)> {
+    let mut traces = vec![];
+    for file in extra_traces {
+        //import them one by one, so we can say which one was wrong
+        traces.extend(
+            import_traces_with_tags(&file).wrap_err_with(|| format!("unable to get extra traces from {}", file.display()))?,
+        );
+    }
+    traces.extend(session_traces);
+    let (traces, tags) = split_tags(traces);
+    Ok((resolve_duplicates(traces, DuplicatePolicy::default())?, tags))
+}
+
+///Picks out a run if it's an outlier - red with an up arrow if it was slow, and blue with a down arrow if it was fast - with its z-score as the tooltip
+fn outlier_highlight(outliers: Option<&OutlierDetector>, run: &Duration, theme: Theme) -> Option<Highlight> {
+    let outliers = outliers?;
+    let run = run.as_micros();
+    outliers.is_outlier(run).then(|| {
+        let z_score = outliers.z_score(run);
+        let (colour, marker, kind) = if z_score > 0.0 {
+            (theme.slow(), SLOW_MARKER, "Slow")
+        } else {
+            (theme.fast(), FAST_MARKER, "Fast")
+        };
+        Highlight {
+            colour,
+            marker,
+            tooltip: format!("{kind} outlier - modified z-score of {z_score:.1}"),
+        }
+    })
+}
@@ -0,0 +1,336 @@
+//! Binary part for serving a directory of results over HTTP, so results on a headless box can be looked at from a browser.
+//!
+//! This is a deliberately tiny HTTP/1.0 server on top of [`TcpListener`] - it only understands `GET`, and only serves pages generated from the CSV files directly inside the results directory:
+//! - `/` lists every CSV file, with links to their plots
+//! - `/plot/<file>` shows the traces from one file
+//! - `/all` shows the traces from every file on one plot
+//!
+//! Files are re-read on every request, so new results show up with a refresh. Files which can't be read don't stop the other files being served - they get listed on the index and under the plots, along with why.
+
+use crate::failure;
+use benchmarker::{
+    exit_reason::ExitReason,
+    io::{import_csv_iter, resolve_duplicates, traces_to_html, CsvProblem, DuplicatePolicy, PlotOptions},
+};
+use clap::Parser;
+use color_eyre::eyre::WrapErr;
+use itertools::Itertools;
+use owo_colors::OwoColorize;
+use std::{
+    ffi::OsStr,
+    fmt::Write as _,
+    fs::{read_dir, File},
+    io::{self, BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    path::{Path, PathBuf},
+};
+
+/// The CLI args for serving results
+#[derive(Clone, Debug, Parser)] //struct for CLI args which can be parsed/cloned/printed
+pub struct ServeCLIArgs {
+    ///The directory with the CSV results to serve
+    results_dir: PathBuf,
+    ///The address to listen on - use `0.0.0.0` to allow other machines to connect
+    #[arg(short, long, default_value_t = String::from("127.0.0.1"))]
+    address: String,
+    ///The port to listen on
+    #[arg(short, long, default_value_t = 8080)]
+    port: u16,
+}
+
+///How many unreadable cells get listed for each file, before the rest just get counted - the same as the exporter
+const LISTED_PROBLEMS_LIMIT: usize = 5;
+
+///Run the results server - this only stops when the process gets killed
+#[instrument]
+pub fn run(
+    ServeCLIArgs {
+        results_dir,
+        address,
+        port,
+    }: ServeCLIArgs,
+) -> color_eyre::Result<()> {
+    if !results_dir.is_dir() {
+        return Err(failure(ExitReason::InvalidArguments, format!("{} is not a directory", results_dir.display())));
+    }
+
+    let listener =
+        TcpListener::bind((address.as_str(), port)).wrap_err_with(|| format!("unable to listen on {address}:{port}"))?;
+    println!(
+        "{} {} on {}",
+        "Serving".bold(),
+        results_dir.display().italic(),
+        format!("http://{address}:{port}/").bright_blue()
+    );
+
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let results_dir = results_dir.clone();
+                if let Err(e) = std::thread::Builder::new()
+                    .name("results_server_connection".into()) //new thread for each connection so one slow plot doesn't hold everyone else up
+                    .spawn(move || {
+                        if let Err(e) = handle_connection(stream, &results_dir) {
+                            warn!(%e, "Error handling connection");
+                        }
+                    })
+                {
+                    error!(%e, "Unable to spawn connection thread");
+                }
+            }
+            Err(e) => warn!(%e, "Error accepting connection"),
+        }
+    }
+    Ok(())
+}
+
+///Reads one request from the stream, and writes back the response
+fn handle_connection(mut stream: TcpStream, results_dir: &Path) -> io::Result<()> {
+    let mut request_line = String::new();
+    BufReader::new(&mut stream).read_line(&mut request_line)?; //we only care about the first line - eg. `GET /plot/a.csv HTTP/1.1`
+
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+        return respond(&mut stream, "400 Bad Request", "Bad request");
+    };
+    trace!(%method, %path, "Got request");
+
+    if method != "GET" {
+        return respond(&mut stream, "405 Method Not Allowed", "Only GET is supported");
+    }
+
+    match page(path, results_dir) {
+        Ok((status, body)) => respond(&mut stream, status, &body),
+        Err(e) => {
+            warn!(%e, %path, "Error making page");
+            respond(&mut stream, "500 Internal Server Error", &html_escape(&e))
+        }
+    }
+}
+
+///Makes the page for a path, giving back its status and body - or the error text, if something went wrong with the whole request. Files which can't be read don't count as that, and just get listed with their errors
+fn page(path: &str, results_dir: &Path) -> Result<(&'static str, String), String> {
+    let files = csv_files(results_dir).map_err(|e| format!("unable to list {}: {e}", results_dir.display()))?;
+    if path == "/" {
+        let loaded = files.iter().map(|file| LoadedFile::load(file)).collect_vec();
+        Ok(("200 OK", index_page(results_dir, &loaded)))
+    } else if path == "/all" {
+        let loaded = files.iter().map(|file| LoadedFile::load(file)).collect_vec();
+        let traces = loaded
+            .iter()
+            .filter_map(|file| file.traces.as_ref().ok())
+            .flatten()
+            .cloned()
+            .collect();
+        let traces = resolve_duplicates(traces, DuplicatePolicy::default()).map_err(|e| e.to_string())?; //the same as get_traces, but without stopping at the first bad file
+        Ok((
+            "200 OK",
+            with_problems(traces_to_html("all", traces, &PlotOptions::default()), &problems_list(&loaded)),
+        ))
+    } else if let Some(name) = path.strip_prefix("/plot/") {
+        let name = percent_decode(name);
+        //only serve files we listed, so nobody can ask for ../../etc/passwd
+        let Some(file) = files
+            .into_iter()
+            .find(|f| f.file_name().and_then(OsStr::to_str) == Some(name.as_str()))
+        else {
+            return Ok(("404 Not Found", "No such results file".into()));
+        };
+
+        let loaded = LoadedFile::load(&file);
+        let problems = problems_list(std::slice::from_ref(&loaded));
+        let traces = loaded.traces.map_err(|e| format!("unable to read {name}: {e}"))?;
+        let stem = file.file_stem().and_then(OsStr::to_str).unwrap_or("results");
+        Ok((
+            "200 OK",
+            with_problems(traces_to_html(stem, traces, &PlotOptions::default()), &problems),
+        ))
+    } else {
+        Ok(("404 Not Found", "Not found".into()))
+    }
+}
+
+///The traces from one results file, along with anything which went wrong reading it
+struct LoadedFile {
+    ///The file name, without any directories
+    name: String,
+    ///The traces, or why the file couldn't be read
+    traces: Result<Vec<(String, Vec<u128>)>, String>,
+    ///Every cell which got skipped, because it wasn't a run
+    problems: Vec<CsvProblem>,
+}
+
+impl LoadedFile {
+    ///Reads all of the traces from a CSV file, keeping any error rather than bubbling it
+    fn load(file: &Path) -> Self {
+        let name = file
+            .file_name()
+            .map_or_else(|| file.display().to_string(), |name| name.to_string_lossy().into_owned());
+        let reader = match File::open(file) {
+            Ok(reader) => reader,
+            Err(e) => {
+                return Self {
+                    name,
+                    traces: Err(e.to_string()),
+                    problems: vec![],
+                }
+            }
+        };
+
+        let mut traces = import_csv_iter(BufReader::new(reader));
+        let contents = traces
+            .by_ref()
+            .map(|trace| trace.map(|(name, runs, _)| (name, runs)))
+            .collect::<io::Result<Vec<_>>>()
+            .map_err(|e| e.to_string());
+        if let Err(e) = &contents {
+            warn!(%e, %name, "Error reading results file");
+        }
+
+        Self {
+            name,
+            traces: contents,
+            problems: traces.problems().to_vec(),
+        }
+    }
+
+    ///Describes what went wrong reading the file, if anything did - in the same words as the exporter
+    fn problem(&self) -> Option<String> {
+        if let Err(e) = &self.traces {
+            return Some(format!("unable to read: {e}"));
+        }
+        if self.problems.is_empty() {
+            return None;
+        }
+
+        let more = self.problems.len().saturating_sub(LISTED_PROBLEMS_LIMIT);
+        Some(format!(
+            "skipped {} unreadable cell(s): {}{}",
+            self.problems.len(),
+            self.problems.iter().take(LISTED_PROBLEMS_LIMIT).join("; "),
+            if more > 0 { format!("; and {more} more") } else { String::new() },
+        ))
+    }
+}
+
+///Makes a HTML list of every file which couldn't be read properly, or an empty string if they all could
+fn problems_list(files: &[LoadedFile]) -> String {
+    let items = files
+        .iter()
+        .filter_map(|file| {
+            file.problem()
+                .map(|problem| format!("<li>{}: {}</li>", html_escape(&file.name), html_escape(&problem)))
+        })
+        .join("");
+    if items.is_empty() {
+        items
+    } else {
+        format!("<div style=\"font-family: sans-serif\"><p>Some results couldn't be read:</p><ul>{items}</ul></div>")
+    }
+}
+
+///Adds the [`problems_list`] to the end of a page from [`traces_to_html`]
+fn with_problems(html: String, problems: &str) -> String {
+    if problems.is_empty() {
+        html
+    } else {
+        html.replacen("</body>", &format!("{problems}\n</body>"), 1)
+    }
+}
+
+///Writes a HTML response with the given status
+fn respond(stream: &mut TcpStream, status: &str, body: &str) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.0 {status}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
+
+///Gets all of the CSV files directly inside the results directory, sorted by name
+fn csv_files(results_dir: &Path) -> io::Result<Vec<PathBuf>> {
+    Ok(read_dir(results_dir)?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.extension() == Some(OsStr::new("csv")))
+        .sorted()
+        .collect())
+}
+
+///Makes the index page, with a link to the plot for each file - files which couldn't be read get listed without a link, along with why
+fn index_page(results_dir: &Path, files: &[LoadedFile]) -> String {
+    let mut page = format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>Precipice Results</title></head><body><h1>Results in {}</h1>",
+        html_escape(&results_dir.display().to_string())
+    );
+
+    if files.is_empty() {
+        page += "<p>No CSV files found.</p>";
+    } else {
+        page += "<p><a href=\"/all\">All traces</a></p><ul>";
+        for file in files {
+            //writing to a String can't fail
+            if file.traces.is_ok() {
+                let _ = write!(
+                    page,
+                    "<li><a href=\"/plot/{}\">{}</a>",
+                    percent_encode(&file.name),
+                    html_escape(&file.name)
+                );
+            } else {
+                let _ = write!(page, "<li>{}", html_escape(&file.name));
+            }
+            if let Some(problem) = file.problem() {
+                let _ = write!(page, " - <em>{}</em>", html_escape(&problem));
+            }
+            page += "</li>";
+        }
+        page += "</ul>";
+    }
+
+    page + "</body></html>"
+}
+
+///Escapes the characters which have special meanings in HTML
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+///Percent-encodes everything apart from the unreserved URL characters
+fn percent_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| {
+            if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.' | b'~') {
+                char::from(b).to_string()
+            } else {
+                format!("%{b:02X}")
+            }
+        })
+        .collect()
+}
+
+///Decodes `%XX` escapes - anything which isn't a valid escape gets left alone
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| bytes.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        if let Some(b) = escaped {
+            decoded.push(b);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
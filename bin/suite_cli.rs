@@ -0,0 +1,311 @@
+//! Binary part for running a whole suite of benchmarks from one definition file in a CLI.
+//!
+//! The [`Suite`] is read from a TOML file, then each entry is run in order with its own progress bar. At the end, each entry gets its own export, and there's one combined export with every trace.
+
+use benchmarker::{
+    bencher::{calculate_mean_standard_deviation, RunOutcomes},
+    build_command::run_build_command,
+    debug_build::debug_build_warning,
+    environment::check_environment,
+    exit_reason::ExitReason,
+    export_command::{run_export_command, ResultDocument},
+    format::DurationFormat,
+    history::{append_to_history, command_hash, last_session, HistoryEntry},
+    io::ExportType,
+    matrix::MatrixFormat,
+    parameters::parameter_tags,
+    provenance::{quote_command, Provenance},
+    stats::Statistic,
+    suite::Suite,
+    tags::TraceTags,
+};
+use crate::{
+    failure,
+    runner_cli::{change_since, DurationFormatArgs, LockArgs},
+};
+use clap::Parser;
+use color_eyre::eyre::{eyre, WrapErr};
+use indicatif::{ProgressBar, ProgressStyle};
+use owo_colors::OwoColorize;
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, RecvTimeoutError},
+        Arc,
+    },
+    time::Duration,
+};
+
+///How long to wait for a run before checking whether the user has pressed Ctrl-C
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The CLI args for running a suite
+#[derive(Clone, Debug, Parser)] //struct for CLI args which can be parsed/cloned/printed
+pub struct SuiteCLIArgs {
+    ///The TOML file which describes the suite
+    #[arg(short, long)]
+    suite: PathBuf,
+    ///How to export the data - a csv with the microsecond values, an HTML graph, or a Vega-Lite spec. This defaults to the suite's `export`, or a CSV
+    #[arg(value_enum, short = 't', long)]
+    export_ty: Option<ExportType>,
+    ///A profile from the suite file to use on top of its defaults, like `quick` for `[profile.quick]`
+    #[arg(long)]
+    profile: Option<String>,
+    ///A command to build the binaries with before any entries run, like `make release`. It isn't timed, and the suite doesn't start if it fails
+    #[arg(long)]
+    build: Option<String>,
+    ///The file to export the combined results to, without extension. This defaults to the suite's name
+    #[arg(short = 'f', long)]
+    export_out_file: Option<String>,
+    ///Also export a comparison matrix, with a row for each command and a column for each set of parameters, to eg. `my_project_matrix.md`
+    #[arg(value_enum, long)]
+    matrix: Option<MatrixFormat>,
+    ///Only print these statistics for each entry, rather than the mean ± standard deviation and the number of runs - eg. `mean,p99`. See `runner-cli --help` for the options
+    #[arg(long, value_delimiter = ',')]
+    stats: Vec<Statistic>,
+    ///Whether or not we should refuse to start if the machine looks unfit for benchmarking (eg. on battery, or with frequency scaling)
+    #[arg(long, default_value_t = false)]
+    abort_on_environment_warnings: bool,
+    ///Whether or not we should skip adding these sessions to the per-user history
+    #[arg(long, default_value_t = false)]
+    no_history: bool,
+    ///Whether or not we should skip printing the change in the mean since the last session of each entry in the history
+    #[arg(long, default_value_t = false)]
+    no_compare_last: bool,
+    ///A command to send each entry's results to once it is done, eg. `./my_exporter.sh`. It gets the same JSON document as with `runner-cli --export-cmd` on its stdin. This can be given more than once
+    #[arg(long = "export-cmd")]
+    export_commands: Vec<String>,
+    ///How to write the times for each entry and in the matrix
+    #[command(flatten)]
+    duration_format: DurationFormatArgs,
+    ///Whether to stop other sessions running at the same time
+    #[command(flatten)]
+    lock: LockArgs,
+}
+
+///Run the suite CLI
+#[instrument]
+pub fn run(
+    SuiteCLIArgs {
+        suite,
+        export_ty,
+        profile,
+        build,
+        export_out_file,
+        matrix,
+        stats,
+        abort_on_environment_warnings,
+        no_history,
+        no_compare_last,
+        export_commands,
+        duration_format,
+        lock,
+    }: SuiteCLIArgs,
+) -> color_eyre::Result<()> {
+    let duration_format = DurationFormat::from(duration_format);
+    let mut suite = Suite::from_file(&suite).wrap_err_with(|| format!("unable to load the suite from {}", suite.display()))?;
+    if let Some(profile) = &profile {
+        suite = suite
+            .with_profile(profile)
+            .wrap_err(ExitReason::InvalidArguments)
+            .wrap_err_with(|| format!("unable to use the {profile} profile"))?;
+    }
+    let export_ty = export_ty.or(suite.defaults.export).unwrap_or(ExportType::CSV);
+    if let Some(command) = &build {
+        println!("{} with {}", "Building".green().bold(), command.italic());
+        run_build_command(command).wrap_err_with(|| format!("unable to build with `{command}`"))?;
+    }
+
+    match &profile {
+        Some(profile) => println!(
+            "{} {} ({} entries, {} profile)",
+            "Suite:".bold(),
+            suite.name.italic(),
+            suite.entries.len(),
+            profile.italic()
+        ),
+        None => println!(
+            "{} {} ({} entries)",
+            "Suite:".bold(),
+            suite.name.italic(),
+            suite.entries.len()
+        ),
+    }
+
+    for entry in &suite.entries {
+        if let Some(warning) = debug_build_warning(&entry.binary) {
+            println!("{}: {warning}", "Warning".yellow().bold());
+        }
+    }
+
+    let environment_warnings = check_environment(); //check that the machine is fit for benchmarking
+    for warning in &environment_warnings {
+        println!("{}: {warning}", "Warning".yellow().bold());
+    }
+    if abort_on_environment_warnings && !environment_warnings.is_empty() {
+        //if the user wants to be strict about it, stop before we start
+        return Err(failure(ExitReason::AssertionFailed, "aborting due to environment warnings"));
+    }
+    let _lock = lock.acquire()?; //held until we're done, so nothing else benchmarks alongside us
+
+    let stopped = Arc::new(AtomicBool::new(false)); //we can only set one Ctrl-C handler, so it sets this and we pass it on to whichever entry is running
+    {
+        let stopped = stopped.clone();
+        ctrlc::set_handler(move || {
+            if stopped.swap(true, Ordering::SeqCst) {
+                //if the user has already asked nicely, they want out right now
+                std::process::exit(ExitReason::Interrupted.code().into());
+            }
+        })
+        .wrap_err("unable to set the Ctrl-C handler")?;
+    }
+
+    let mut results = vec![];
+    let mut tags = TraceTags::new();
+    let mut failed_entries = vec![]; //the entries where a run failed, or the runner stopped early
+    for entry in &suite.entries {
+        if stopped.load(Ordering::SeqCst) {
+            //if the user stopped us, skip everything else
+            println!("{} {}", "Skipping".yellow(), entry.name);
+            continue;
+        }
+
+        let runs = entry.runs(&suite.defaults);
+        let last_session = (!no_compare_last)
+            .then(|| last_session(command_hash(&entry.binary, &entry.cli_args)))
+            .flatten(); //before this session goes into the history
+        let provenance = Provenance::start(quote_command(&entry.binary, &entry.cli_args)).with_binary_hash(&entry.binary);
+        let (stop_tx, stop_rx) = channel();
+        let (handle, rx, error_rx) = entry
+            .runner(&suite.defaults, Some(stop_rx))
+            .start()
+            .wrap_err_with(|| format!("unable to start the runner for {}", entry.name))?;
+
+        let progress_bar = ProgressBar::new(runs as u64);
+        progress_bar.set_style(
+            ProgressStyle::with_template(
+                "{spinner} {msg} Elapsed: [{elapsed_precise}], ETA: [{eta_precise}] {bar:40.cyan/blue} {pos:>7}/{len:7}",
+            )
+            .unwrap()
+            .progress_chars("##-"),
+        );
+        progress_bar.set_message(entry.name.clone());
+
+        let mut found_runs = vec![];
+        let mut exit_statuses = vec![];
+        let mut sent_stop = false;
+        loop {
+            //block on the runs rather than spinning on the machine being measured, but wake up now and then to pass on a Ctrl-C
+            match rx.recv_timeout(STOP_POLL_INTERVAL) {
+                Ok(measurement) => {
+                    found_runs.push(measurement.wall.as_micros());
+                    exit_statuses.push(measurement.exit_status);
+                    progress_bar.inc(1);
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break, //the runner is done, and has dropped its sender
+            }
+
+            if !sent_stop && stopped.load(Ordering::SeqCst) {
+                //pass on the Ctrl-C to the runner - we don't care if it has already gone
+                sent_stop = true;
+                let _ = stop_tx.send(());
+            }
+        }
+        handle
+            .join()
+            .map_err(|_| eyre!("the runner thread for {} panicked", entry.name))?;
+        let provenance = provenance.finish();
+
+        if sent_stop {
+            progress_bar.abandon_with_message(format!("{} - Stopped by User", entry.name));
+        } else {
+            progress_bar.finish_and_clear();
+        }
+
+        let mut runner_failed = false;
+        for error in error_rx.try_iter() {
+            eprintln!("{} in {}: {error}", "Error".red().bold(), entry.name);
+            runner_failed = true;
+        }
+        let outcomes = RunOutcomes::new(exit_statuses, 0);
+        if !outcomes.all_ok() {
+            eprintln!("{}: {} - {outcomes}", "Warning".yellow().bold(), entry.name);
+        }
+        if runner_failed || !outcomes.all_ok() {
+            failed_entries.push(entry.name.clone());
+        }
+
+        let change = last_session
+            .as_ref()
+            .zip(calculate_mean_standard_deviation(&found_runs))
+            .and_then(|(last_session, (mean, _))| change_since(mean, last_session, &duration_format))
+            .map_or_else(String::new, |change| format!(" {change}"));
+        if !stats.is_empty() {
+            let statistics: Vec<String> = stats
+                .iter()
+                .filter_map(|statistic| Some(format!("{} {}", statistic.label(), statistic.calculate(&found_runs)?.format(&duration_format).bright_green())))
+                .collect();
+            println!("{}: {}{change}", entry.name.bold(), statistics.join(", "));
+        } else if let Some((mean, standard_deviation)) = calculate_mean_standard_deviation(&found_runs) {
+            println!(
+                "{}: {} ± {} : {}{change}",
+                entry.name.bold(),
+                duration_format.duration(mean).bright_green(),
+                duration_format.duration(standard_deviation).bright_green(),
+                found_runs.len().bright_white(),
+            );
+        }
+
+        if !no_history {
+            if let Some(history_entry) = HistoryEntry::new(&entry.binary, &entry.cli_args, &found_runs) {
+                if let Err(e) = append_to_history(&history_entry) {
+                    eprintln!("{}: unable to save to history: {e}", "Warning".yellow().bold());
+                }
+            }
+        }
+
+        let mut entry_tags = provenance.to_tags(); //so each trace can be traced back to its session, even in the combined export
+        entry_tags.extend(parameter_tags(&entry.parameters));
+        if !export_commands.is_empty() {
+            let document = ResultDocument::new(entry.name.clone(), provenance.command.clone(), found_runs.clone(), entry_tags.clone());
+            for command in &export_commands {
+                if let Err(e) = run_export_command(command, &document) {
+                    eprintln!("{}: unable to export {} with `{command}`: {e}", "Warning".yellow().bold(), entry.name);
+                }
+            }
+        }
+        tags.insert(entry.name.clone(), entry_tags);
+        results.push((entry.name.clone(), found_runs));
+    }
+
+    let combined_file_name = export_out_file.unwrap_or_else(|| suite.name.clone());
+    if let Some(format) = matrix {
+        let matrix_file_name = format!("{combined_file_name}_matrix");
+        suite
+            .comparison_matrix(&results)
+            .with_duration_format(duration_format)
+            .export(&suite.name, format, &matrix_file_name)
+            .wrap_err(ExitReason::ExportFailed)
+            .wrap_err_with(|| format!("unable to export the comparison matrix to {matrix_file_name}.{}", format.extension()))?;
+    }
+
+    let n = suite
+        .export_with_tags(results, &tags, export_ty, Some(combined_file_name))
+        .wrap_err(ExitReason::ExportFailed)
+        .wrap_err_with(|| format!("unable to export the results of {} as {export_ty:?}", suite.name))?;
+    trace!(?n, "Finished exporting");
+
+    //everything has still been exported, but scripts should know that something went wrong
+    if stopped.load(Ordering::SeqCst) {
+        return Err(ExitReason::Interrupted.into());
+    }
+    if !failed_entries.is_empty() {
+        return Err(failure(
+            ExitReason::BenchmarkFailed,
+            format!("not every run succeeded in {}", failed_entries.join(", ")),
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,133 @@
+//! Module for the colour themes of the GUI programs, including a high-contrast one for presenting results or for anyone who finds the standard one hard to read.
+//!
+//! Anything which shows a state with a colour (warnings, errors, outliers, the baseline) gets its colour from the [`Theme`], and also marks it with a symbol or a label - so nothing depends on telling colours apart. The high-contrast colours come from the Okabe-Ito palette, which stays distinct with the common kinds of colour-blindness.
+
+use eframe::egui::{Color32, ComboBox, Context, Stroke, Ui, Visuals};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+///The symbol to put before warnings
+pub const WARNING_MARKER: &str = "⚠";
+///The symbol to put before errors
+pub const ERROR_MARKER: &str = "✖";
+///The symbol to put before runs which were outliers for being slow
+pub const SLOW_MARKER: &str = "⏶";
+///The symbol to put before runs which were outliers for being fast
+pub const FAST_MARKER: &str = "⏷";
+
+///Which colours the GUI gets drawn in
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Theme {
+    ///egui's own dark theme
+    #[default]
+    Standard,
+    ///White on black, with thick outlines and colour-blind friendly colours
+    HighContrast,
+}
+
+impl Display for Theme {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Standard => write!(f, "Standard"),
+            Self::HighContrast => write!(f, "High Contrast"),
+        }
+    }
+}
+
+impl Theme {
+    ///The colour for warnings
+    pub const fn warning(self) -> Color32 {
+        match self {
+            Self::Standard => Color32::YELLOW,
+            Self::HighContrast => Color32::from_rgb(240, 228, 66), //Okabe-Ito yellow
+        }
+    }
+
+    ///The colour for errors
+    pub const fn error(self) -> Color32 {
+        match self {
+            Self::Standard => Color32::RED,
+            Self::HighContrast => Color32::from_rgb(230, 159, 0), //Okabe-Ito orange, which is brighter on black than their vermillion
+        }
+    }
+
+    ///The colour for runs which were outliers for being slow
+    pub const fn slow(self) -> Color32 {
+        self.error()
+    }
+
+    ///The colour for runs which were outliers for being fast
+    pub const fn fast(self) -> Color32 {
+        match self {
+            Self::Standard => Color32::LIGHT_BLUE,
+            Self::HighContrast => Color32::from_rgb(86, 180, 233), //Okabe-Ito sky blue
+        }
+    }
+
+    ///The colour for a baseline's curve in plots
+    pub const fn baseline(self) -> Color32 {
+        match self {
+            Self::Standard => Color32::GRAY,
+            Self::HighContrast => Color32::WHITE,
+        }
+    }
+
+    ///The colour for the session's curve in plots
+    pub const fn session(self) -> Color32 {
+        self.fast()
+    }
+
+    ///Switches the [`Context`] over to this theme, if it isn't using it already - this should be called at the start of every frame
+    pub fn apply(self, ctx: &Context) {
+        let high_contrast = high_contrast_visuals();
+        let using_high_contrast = ctx.style().visuals == high_contrast;
+        match self {
+            Self::HighContrast if !using_high_contrast => ctx.set_visuals(high_contrast),
+            Self::Standard if using_high_contrast => ctx.set_visuals(Visuals::dark()), //eframe's default
+            _ => {}
+        }
+    }
+}
+
+///Makes egui's visuals for [`Theme::HighContrast`]
+fn high_contrast_visuals() -> Visuals {
+    let mut visuals = Visuals::dark();
+    visuals.override_text_color = Some(Color32::WHITE);
+    visuals.panel_fill = Color32::BLACK;
+    visuals.window_fill = Color32::BLACK;
+    visuals.extreme_bg_color = Color32::BLACK;
+    visuals.faint_bg_color = Color32::from_gray(40);
+    visuals.window_stroke = Stroke::new(2.0, Color32::WHITE);
+    visuals.hyperlink_color = Theme::HighContrast.fast();
+    visuals.warn_fg_color = Theme::HighContrast.warning();
+    visuals.error_fg_color = Theme::HighContrast.error();
+    visuals.selection.bg_fill = Color32::from_rgb(0, 114, 178); //Okabe-Ito blue
+    visuals.selection.stroke = Stroke::new(2.0, Color32::WHITE);
+
+    for widget in [&mut visuals.widgets.noninteractive, &mut visuals.widgets.inactive] {
+        widget.bg_stroke = Stroke::new(1.0, Color32::WHITE);
+        widget.fg_stroke = Stroke::new(1.5, Color32::WHITE);
+    }
+    visuals.widgets.inactive.bg_fill = Color32::from_gray(30);
+    visuals.widgets.inactive.weak_bg_fill = Color32::from_gray(30);
+    for widget in [&mut visuals.widgets.hovered, &mut visuals.widgets.active, &mut visuals.widgets.open] {
+        //a thick yellow outline, so it's obvious what is under the pointer
+        widget.bg_stroke = Stroke::new(2.0, Theme::HighContrast.warning());
+        widget.fg_stroke = Stroke::new(2.0, Color32::WHITE);
+        widget.bg_fill = Color32::from_gray(60);
+        widget.weak_bg_fill = Color32::from_gray(60);
+    }
+
+    visuals
+}
+
+///Shows a drop-down to pick the theme
+pub fn theme_picker(ui: &mut Ui, theme: &mut Theme) {
+    ComboBox::from_label("Theme")
+        .selected_text(theme.to_string())
+        .show_ui(ui, |ui| {
+            for option in [Theme::Standard, Theme::HighContrast] {
+                ui.selectable_value(theme, option, option.to_string());
+            }
+        });
+}
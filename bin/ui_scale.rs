@@ -0,0 +1,60 @@
+//! Module for scaling the GUI programs, as egui's default text is tiny on high-DPI monitors.
+//!
+//! The scale multiplies the monitor's own pixels-per-point, so `1.0` is egui's default size. Like in a browser, Ctrl+Plus and Ctrl+Minus (Cmd on macOS) make everything bigger or smaller, and Ctrl+0 resets it - each GUI also has buttons for it, and the scale gets stored with the rest of its state.
+
+use eframe::{
+    egui::{gui_zoom::kb_shortcuts, Context, Ui},
+    Frame,
+};
+
+///The scale to use if nothing was stored
+pub const DEFAULT_UI_SCALE: f32 = 1.0;
+///The smallest scale we allow, so everything stays readable
+const MIN_UI_SCALE: f32 = 0.5;
+///The biggest scale we allow, so everything still fits in the window
+const MAX_UI_SCALE: f32 = 3.0;
+///How much each shortcut or button press changes the scale by
+const UI_SCALE_STEP: f32 = 0.1;
+
+///Keeps a scale in range, rounded to the nearest step so repeated presses don't drift
+fn clamp_ui_scale(ui_scale: f32) -> f32 {
+    (ui_scale.clamp(MIN_UI_SCALE, MAX_UI_SCALE) / UI_SCALE_STEP).round() * UI_SCALE_STEP
+}
+
+///Handles the zoom shortcuts, and then sets the pixels-per-point for the scale - this should be called at the start of every frame
+pub fn update_ui_scale(ctx: &Context, frame: &Frame, ui_scale: &mut f32) {
+    if ctx.input_mut(|i| i.consume_shortcut(&kb_shortcuts::ZOOM_RESET)) {
+        *ui_scale = DEFAULT_UI_SCALE;
+    } else {
+        if ctx.input_mut(|i| i.consume_shortcut(&kb_shortcuts::ZOOM_IN)) {
+            *ui_scale += UI_SCALE_STEP;
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&kb_shortcuts::ZOOM_OUT)) {
+            *ui_scale -= UI_SCALE_STEP;
+        }
+    }
+    *ui_scale = clamp_ui_scale(*ui_scale);
+
+    let pixels_per_point = frame.info().native_pixels_per_point.unwrap_or(1.0) * *ui_scale;
+    if (ctx.pixels_per_point() - pixels_per_point).abs() > f32::EPSILON {
+        //only when it changes, as setting it makes egui redo all of the fonts
+        trace!(%ui_scale, %pixels_per_point, "Changing UI scale");
+        ctx.set_pixels_per_point(pixels_per_point);
+    }
+}
+
+///Shows the current scale, with buttons to change it - the new scale gets applied on the next frame
+pub fn ui_scale_buttons(ui: &mut Ui, ui_scale: &mut f32) {
+    ui.horizontal(|ui| {
+        ui.label(format!("UI Scale: {:.0}%", *ui_scale * 100.0));
+        if ui.button("-").on_hover_text("Ctrl+Minus").clicked() {
+            *ui_scale -= UI_SCALE_STEP;
+        }
+        if ui.button("+").on_hover_text("Ctrl+Plus").clicked() {
+            *ui_scale += UI_SCALE_STEP;
+        }
+        if ui.button("Reset").on_hover_text("Ctrl+0").clicked() {
+            *ui_scale = DEFAULT_UI_SCALE;
+        }
+    });
+}
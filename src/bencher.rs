@@ -1,29 +1,65 @@
 //! Module to contain the actual bencher, which runs on its own separate thread.
 //!
-//! A [`Runner`] is used to create the [`JoinHandle`] and [`Receiver`] where you will get the timing durations - when the [`JoinHandle`] is finished, you know you can safely drop the [`Receiver`], or you need to manually count.
+//! A [`Runner`] is used to create the [`JoinHandle`] and [`Receiver`]s where you will get a [`RunMeasurement`] for each run and any errors - when the [`JoinHandle`] is finished, you know you can safely drop the [`Receiver`]s, or you need to manually count.
+//!
+//! The runner thread never panics - if something goes wrong it sends a [`RunnerError`] over the error channel and stops, and if the measurements [`Receiver`] is dropped (eg. the GUI was closed mid-run) it just stops quietly.
 //!
 //! ## Example
 //! ```rust
 //! use std::path::PathBuf;
-//! use std::sync::mpsc::{channel, Receiver, Sender};
 //! use benchmarker::bencher::{DEFAULT_RUNS, Runner};
 //!
-//! let (handle, rx) = Runner::new(PathBuf::from("/bin/echo"), vec!["Hello".into()], DEFAULT_RUNS, None, true);
+//! let (handle, rx, error_rx) = Runner::new(PathBuf::from("/bin/echo"), vec!["Hello".into()], DEFAULT_RUNS, None, 0, true)
+//!     .start()
+//!     .unwrap();
 //!
 //! while !handle.is_finished() {
 //!     //add stuff from rx
 //! }
-//! handle.join().unwrap().unwrap();
+//! let times: Vec<u128> = rx.try_iter().map(|measurement| measurement.wall.as_micros()).collect();
+//! handle.join().unwrap();
+//!
+//! for error in error_rx.try_iter() {
+//!     eprintln!("{error}");
+//! }
 //! ```
+//!
+//! To run the benchmarks on another machine instead, see [`remote`], and to compare several commands in one session, see [`multi`]. The statistics for the runs are in [`stats`], and get re-exported here.
+
+pub mod multi;
+pub mod process;
+pub mod ramdisk;
+pub mod remote;
+pub mod stats;
+pub mod template;
+pub mod timing;
 
+use crate::error::Error;
+use chrono::{DateTime, Utc};
 use itertools::Itertools;
+use process::{ProcessKiller, ProcessTree, ResourceUsage};
+use rand::Rng;
+use ramdisk::{RamCopy, RamCopyOptions};
+use serde::{Deserialize, Serialize};
+pub use stats::{
+    calculate_mean_standard_deviation, calculate_median_mad_iqr, calculate_percentiles, calculate_trimmed_mean,
+    calculate_winsorized_mean_standard_deviation, DEFAULT_TRIM_FRACTION, SUMMARY_PERCENTILES,
+};
+use template::RunScope;
+use timing::SelfReportedTiming;
 use std::{
+    collections::BTreeMap,
     env::current_dir,
+    fmt::{Display, Formatter},
     io,
     io::Write,
-    path::PathBuf,
-    process::{Command, Output, Stdio},
-    sync::mpsc::{channel, Receiver, TryRecvError},
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus, Output, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, Receiver, RecvTimeoutError, Sender, TryRecvError},
+        Arc,
+    },
     thread::JoinHandle,
     time::{Duration, Instant},
 };
@@ -44,6 +80,237 @@ pub struct Runner {
     pub warmup: u8,
     ///Whether or not to print the initial run
     pub print_initial: bool,
+    ///How long a single run can take before it gets killed (along with anything it spawned) and skipped
+    pub timeout: Option<Duration>,
+    ///Whether to pad the environment by a random amount for each run, to randomise the memory layout - see [`ENVIRONMENT_PADDING_VARIABLE`]
+    pub randomize_environment: bool,
+    ///If set, the timings come from parsing stdout instead of timing the whole process
+    pub self_reported_timing: Option<SelfReportedTiming>,
+    ///Commands to pipe the output of the binary through, in order - each run is timed until all of them have finished
+    pub pipeline: Vec<PipelineStage>,
+    ///If set, the binary gets copied to a RAM-backed directory and run from there, with that directory as the working directory
+    pub ram_copy: Option<RamCopyOptions>,
+    ///If set, the stdout and stderr of every nth measured run get printed to stderr, to check the binary is still doing real work. Those runs have their output piped to us rather than thrown away, which can make them a little slower
+    pub show_output_every: Option<usize>,
+    ///If set, every run that times out sends how long it ran for down this, so frontends can say how many there were - they don't get a [`RunMeasurement`]
+    pub timeout_tx: Option<Sender<Duration>>,
+    ///Whether to run the binary and its args as a command line through the [`SHELL`], so it can be a whole shell pipeline - see [`PipelineStage::through_shell`]
+    pub shell: bool,
+    ///Extra environment variables to set for every command in the pipeline, on top of the ones we were started with
+    pub env: BTreeMap<String, String>,
+}
+
+///One command that gets piped into as part of a pipeline, eg. the `consumer` in `producer | consumer`
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PipelineStage {
+    ///The binary to run
+    pub binary: PathBuf,
+    ///The args to pass to the binary
+    #[serde(default)]
+    pub cli_args: Vec<String>,
+}
+
+impl PipelineStage {
+    ///Constructor
+    #[must_use]
+    pub const fn new(binary: PathBuf, cli_args: Vec<String>) -> Self {
+        Self { binary, cli_args }
+    }
+
+    ///Turns this stage into one which runs it as a command line through the [`SHELL`], so it can use pipes, redirects, globs and the like.
+    ///
+    /// The binary and args get joined with spaces as they are rather than being quoted, so an arg like `|` works as a pipe - anything with spaces in which should stay together needs quoting for the shell.
+    ///
+    /// ## Example
+    /// ```rust
+    /// use benchmarker::bencher::{PipelineStage, SHELL};
+    /// use std::path::PathBuf;
+    ///
+    /// let stage = PipelineStage::new(PathBuf::from("grep"), vec!["foo".into(), "big.txt".into(), "|".into(), "wc -l".into()]).through_shell();
+    /// assert_eq!(stage.binary, PathBuf::from(SHELL.0));
+    /// assert_eq!(stage.cli_args, [SHELL.1, "grep foo big.txt | wc -l"]);
+    /// ```
+    #[must_use]
+    pub fn through_shell(self) -> Self {
+        let command_line = shell_command_line(&self.binary, &self.cli_args);
+        Self::new(PathBuf::from(SHELL.0), vec![SHELL.1.to_string(), command_line])
+    }
+}
+
+///Joins a binary and its args into the command line that [`Runner::shell`] runs, with spaces and without quoting anything
+#[must_use]
+pub fn shell_command_line(binary: &Path, cli_args: &[String]) -> String {
+    std::iter::once(binary.to_string_lossy()).chain(cli_args.iter().map(Into::into)).join(" ")
+}
+
+///Everything we measured about one run, sent over the channel from [`Runner::start`]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RunMeasurement {
+    ///Which measured run this was, starting from 0 - runs that timed out don't get one
+    pub index: usize,
+    ///Which command this run was of, for a [`multi::MultiRunner`] comparing several - always 0 from a [`Runner`]
+    #[serde(default)]
+    pub command: usize,
+    ///How long the run took - this is the self-reported time if the runner is using [`Runner::self_reported_timing`]
+    pub wall: Duration,
+    ///Time spent running in user mode, across the whole pipeline
+    pub cpu_user: Option<Duration>,
+    ///Time spent running in the kernel, across the whole pipeline
+    pub cpu_sys: Option<Duration>,
+    ///The most memory any one command had resident at once, in bytes
+    pub max_rss: Option<u64>,
+    ///The exit code, or [`None`] if it got killed by a signal
+    pub exit_status: Option<i32>,
+    ///When the run started
+    pub started_at: DateTime<Utc>,
+}
+
+///The fields of a [`RunMeasurement`] that go into a JSON line - see [`RunMeasurement::to_json_line`]
+#[derive(Serialize)]
+struct JsonLine {
+    ///[`RunMeasurement::index`]
+    index: usize,
+    ///[`RunMeasurement::wall`], in microseconds
+    duration_micros: u128,
+    ///[`RunMeasurement::exit_status`]
+    exit_code: Option<i32>,
+}
+
+impl RunMeasurement {
+    ///Formats the run as one line of JSON with its index, how long it took in microseconds and its exit code - eg. to stream runs into `jq` or a dashboard as they happen
+    ///
+    /// ```rust
+    /// use benchmarker::bencher::RunMeasurement;
+    /// use chrono::Utc;
+    /// use std::time::Duration;
+    ///
+    /// let measurement = RunMeasurement {
+    ///     index: 3,
+    ///     command: 0,
+    ///     wall: Duration::from_micros(1500),
+    ///     cpu_user: None,
+    ///     cpu_sys: None,
+    ///     max_rss: None,
+    ///     exit_status: Some(0),
+    ///     started_at: Utc::now(),
+    /// };
+    /// assert_eq!(measurement.to_json_line().unwrap(), r#"{"index":3,"duration_micros":1500,"exit_code":0}"#);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Can't really fail, but `serde_json` gives back a [`Result`]
+    pub fn to_json_line(&self) -> serde_json::Result<String> {
+        serde_json::to_string(&JsonLine {
+            index: self.index,
+            duration_micros: self.wall.as_micros(),
+            exit_code: self.exit_status,
+        })
+    }
+}
+
+///How the runs in a session went - how many exited successfully, how many failed (by exit code), and how many timed out
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RunOutcomes {
+    ///The runs which exited successfully
+    pub ok: usize,
+    ///The runs which didn't, by exit code - [`None`] for runs killed by a signal
+    pub failed: BTreeMap<Option<i32>, usize>,
+    ///The runs which got killed for going past the timeout
+    pub timed_out: usize,
+}
+
+impl RunOutcomes {
+    ///Counts the outcomes from the exit statuses of the measured runs (see [`RunMeasurement::exit_status`]), and how many runs timed out
+    ///
+    /// ```rust
+    /// use benchmarker::bencher::RunOutcomes;
+    ///
+    /// let outcomes = RunOutcomes::new([Some(0), Some(0), Some(1), None, Some(1)], 4);
+    /// assert_eq!(outcomes.to_string(), "2 ok, 2 failed (exit 1), 1 failed (killed by a signal), 4 timed out");
+    /// assert!(!outcomes.all_ok());
+    /// assert_eq!(RunOutcomes::new([Some(0)], 0).to_string(), "1 ok");
+    /// ```
+    #[must_use]
+    pub fn new(exit_statuses: impl IntoIterator<Item = Option<i32>>, timed_out: usize) -> Self {
+        let mut outcomes = Self {
+            timed_out,
+            ..Self::default()
+        };
+        for exit_status in exit_statuses {
+            if exit_status == Some(0) {
+                outcomes.ok += 1;
+            } else {
+                *outcomes.failed.entry(exit_status).or_default() += 1;
+            }
+        }
+        outcomes
+    }
+
+    ///Whether every run exited successfully, without timing out
+    #[must_use]
+    pub fn all_ok(&self) -> bool {
+        self.failed.is_empty() && self.timed_out == 0
+    }
+}
+
+impl Display for RunOutcomes {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ok", self.ok)?;
+        for (exit_status, count) in self.failed.iter().sorted_by_key(|(exit_status, _)| exit_status.is_none()) {
+            //exit codes first, in order, and then the signals
+            match exit_status {
+                Some(code) => write!(f, ", {count} failed (exit {code})")?,
+                None => write!(f, ", {count} failed (killed by a signal)")?,
+            }
+        }
+        if self.timed_out > 0 {
+            write!(f, ", {} timed out", self.timed_out)?;
+        }
+        Ok(())
+    }
+}
+
+///What starting a runner gives back - the [`JoinHandle`] of its thread, a [`Receiver`] for the measurements, and a [`Receiver`] for any [`RunnerError`]s that stopped it
+pub type RunnerChannels = (JoinHandle<()>, Receiver<RunMeasurement>, Receiver<RunnerError>);
+
+///An error from inside the runner thread, sent over the error channel from [`Runner::start`]
+#[derive(Debug)]
+pub enum RunnerError {
+    ///We couldn't run the binary, or couldn't write its output to the console
+    Io(io::Error),
+    ///The initial (warmup) run of the binary didn't exit successfully, so we didn't bother with the rest
+    InitialRunFailed(ExitStatus),
+    ///Something went wrong on a remote agent - we only get the message
+    Remote(String),
+    ///We were looking for a self-reported timing, but a run didn't print one that matched
+    TimingNotFound,
+}
+
+impl Display for RunnerError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "IO error whilst benching: {e}"),
+            Self::InitialRunFailed(status) => write!(f, "Initial run failed with {status}"),
+            Self::Remote(e) => write!(f, "Error from remote agent: {e}"),
+            Self::TimingNotFound => write!(f, "Unable to find a timing in the output of a run"),
+        }
+    }
+}
+
+impl std::error::Error for RunnerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::InitialRunFailed(_) | Self::Remote(_) | Self::TimingNotFound => None,
+        }
+    }
+}
+
+impl From<io::Error> for RunnerError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
 }
 
 ///Runs a certain number of runs every time we see no stop signal, to avoid constantly polling the stop receiver
@@ -52,10 +319,36 @@ const CHUNK_SIZE: usize = 5;
 ///Useful constant for default runs
 pub const DEFAULT_RUNS: usize = 1_000;
 
+///The environment variable used to pad the environment when [`Runner::randomize_environment`] is set.
+///
+/// The environment gets copied onto the stack before `main`, so changing its size moves the stack (and anything allocated relative to it). A fixed layout can make a binary look consistently faster or slower than it really is - randomising it for each run averages that bias out rather than baking it into every result.
+pub const ENVIRONMENT_PADDING_VARIABLE: &str = "PRECIPICE_PADDING";
+
+///The most bytes [`ENVIRONMENT_PADDING_VARIABLE`] can be padded with - a few pages, so the stack moves across page boundaries too
+pub const MAX_ENVIRONMENT_PADDING: usize = 4096 * 4;
+
+///The shell that [`Runner::shell`] runs command lines through, and the flag that tells it to run the next argument
+#[cfg(windows)]
+pub const SHELL: (&str, &str) = ("cmd", "/C");
+///The shell that [`Runner::shell`] runs command lines through, and the flag that tells it to run the next argument
+#[cfg(not(windows))]
+pub const SHELL: (&str, &str) = ("sh", "-c");
+
+///How often the watchdog checks the stop channel whilst a run is going
+const WATCHDOG_POLL: Duration = Duration::from_millis(10);
+
+///Messages from the runner thread to the watchdog thread
+enum WatchdogMessage {
+    ///A run has started - kill it with this if we get stopped or it runs past the deadline
+    Watch(ProcessKiller, Option<Instant>),
+    ///The current run finished by itself
+    Done,
+}
+
 impl Runner {
     ///Constructor
     #[must_use]
-    pub fn new(
+    pub const fn new(
         binary: PathBuf,
         cli_args: Vec<String>,
         runs: usize,
@@ -70,13 +363,131 @@ impl Runner {
             stop_rx,
             warmup,
             print_initial,
+            timeout: None,
+            randomize_environment: false,
+            self_reported_timing: None,
+            pipeline: vec![],
+            ram_copy: None,
+            show_output_every: None,
+            timeout_tx: None,
+            shell: false,
+            env: BTreeMap::new(),
         }
     }
 
-    ///Starts the runner in a new thread
+    ///Changes how long a single run can take before it gets killed - builder pattern
+    #[must_use]
+    pub const fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    ///Changes whether to randomise the size of the environment for each run - builder pattern
+    #[must_use]
+    pub const fn randomize_environment(mut self, randomize_environment: bool) -> Self {
+        self.randomize_environment = randomize_environment;
+        self
+    }
+
+    ///Changes whether to read the timings from stdout instead of timing the whole process - builder pattern
     #[must_use]
+    #[allow(clippy::missing_const_for_fn)] //can't drop the old value in a const fn
+    pub fn self_reported_timing(mut self, self_reported_timing: Option<SelfReportedTiming>) -> Self {
+        self.self_reported_timing = self_reported_timing;
+        self
+    }
+
+    ///Changes the commands to pipe the output of the binary through - builder pattern
+    #[must_use]
+    #[allow(clippy::missing_const_for_fn)] //can't drop the old value in a const fn
+    pub fn pipeline(mut self, pipeline: Vec<PipelineStage>) -> Self {
+        self.pipeline = pipeline;
+        self
+    }
+
+    ///Changes whether to copy the binary to a RAM-backed directory and run it from there - builder pattern
+    #[must_use]
+    #[allow(clippy::missing_const_for_fn)] //can't drop the old value in a const fn
+    pub fn ram_copy(mut self, ram_copy: Option<RamCopyOptions>) -> Self {
+        self.ram_copy = ram_copy;
+        self
+    }
+
+    ///Changes how often to print the output of a measured run, if at all - builder pattern
+    #[must_use]
+    pub const fn show_output_every(mut self, show_output_every: Option<usize>) -> Self {
+        self.show_output_every = show_output_every;
+        self
+    }
+
+    ///Changes where to send the runs that time out, if anywhere - builder pattern
+    #[must_use]
+    pub fn timeout_sender(mut self, timeout_tx: Option<Sender<Duration>>) -> Self {
+        self.timeout_tx = timeout_tx;
+        self
+    }
+
+    ///Changes whether to run the binary and its args through the [`SHELL`] - builder pattern.
+    ///
+    /// The binary can then be a whole command line, like `grep foo big.txt | wc -l`, and the time includes starting the shell. There isn't a binary to copy, so this can't be used with [`Runner::ram_copy`] - if both are set, the command line runs where it is.
+    ///
+    /// ## Example
+    /// ```rust
+    /// use benchmarker::bencher::Runner;
+    /// use std::path::PathBuf;
+    ///
+    /// let (handle, rx, error_rx) = Runner::new(PathBuf::from("echo Hello | wc -c"), vec![], 3, None, 0, false)
+    ///     .shell(true)
+    ///     .start()
+    ///     .unwrap();
+    /// handle.join().unwrap();
+    ///
+    /// assert_eq!(rx.try_iter().count(), 3);
+    /// assert!(error_rx.try_recv().is_err());
+    /// ```
+    #[must_use]
+    pub const fn shell(mut self, shell: bool) -> Self {
+        self.shell = shell;
+        self
+    }
+
+    ///Changes the extra environment variables to set for every run - builder pattern
+    #[must_use]
+    #[allow(clippy::missing_const_for_fn)] //can't drop the old value in a const fn
+    pub fn env(mut self, env: BTreeMap<String, String>) -> Self {
+        self.env = env;
+        self
+    }
+
+    ///Starts the runner in a new thread.
+    ///
+    /// Gives back the [`JoinHandle`] of the thread, a [`Receiver`] for the measurements, and a [`Receiver`] for any [`RunnerError`]s that stopped the thread.
+    ///
+    /// # Errors
+    ///
+    /// Can fail with an [`Error::Spawn`] if we fail to spawn the thread
     #[instrument(skip(self))]
-    pub fn start(self) -> (JoinHandle<io::Result<()>>, Receiver<Duration>) {
+    pub fn start(self) -> Result<RunnerChannels, Error> {
+        let (measurement_sender, measurement_receiver) = channel(); //Here, we create a channel to send over the measurements
+        let (error_sender, error_receiver) = channel(); //and another to send over anything that went wrong
+
+        let handle = std::thread::Builder::new()
+            .name("benchmark_runner".into()) //new thread to run the benchmarks on
+            .spawn(move || {
+                if let Err(e) = self.run(&measurement_sender) {
+                    error!(%e, "Error whilst benching");
+                    if error_sender.send(e).is_err() {
+                        //if nobody is listening, we've already logged it so we can't do much else
+                        warn!("Error receiver closed before error could be sent");
+                    }
+                }
+            })
+            .map_err(Error::Spawn)?;
+        Ok((handle, measurement_receiver, error_receiver))
+    }
+
+    ///Actually runs the benchmarks on the current thread, sending measurements down `measurement_sender`. Stops early without an error if the measurements receiver gets dropped.
+    fn run(self, measurement_sender: &Sender<RunMeasurement>) -> Result<(), RunnerError> {
         let Self {
             runs,
             binary,
@@ -84,116 +495,305 @@ impl Runner {
             stop_rx,
             warmup,
             print_initial,
+            timeout,
+            randomize_environment,
+            self_reported_timing,
+            pipeline,
+            ram_copy,
+            show_output_every,
+            timeout_tx,
+            shell,
+            env,
         } = self; //destructure self - we can't do this in the method signature as I like using self to call methods, and you can't destructure self
 
-        let (duration_sender, duration_receiver) = channel(); //Here, we create a channel to send over the durations
+        info!(%runs, ?binary, ?cli_args, ?pipeline, ?warmup, %randomize_environment, %shell, ?env, "Starting benching.");
 
-        let handle = std::thread::Builder::new()
-            .name("benchmark_runner".into()) //new thread to run the benchmarks on
-            .spawn(move || {
-                info!(%runs, ?binary, ?cli_args, ?warmup, "Starting benching.");
+        if shell && ram_copy.is_some() {
+            warn!("Not copying to RAM, as a shell command line has no binary to copy");
+        }
+        let ram_copy = ram_copy
+            .filter(|_| !shell)
+            .map(|options| RamCopy::new(&binary, &options))
+            .transpose()?; //keep hold of this until we're done, as dropping it removes the copies
+        let binary = ram_copy
+            .as_ref()
+            .map_or(binary, |ram_copy| ram_copy.binary().to_path_buf()); //run the copy if we have one
+
+        let first_stage = PipelineStage::new(binary, cli_args);
+        let first_stage = if shell { first_stage.through_shell() } else { first_stage };
+        let stages: Vec<PipelineStage> = std::iter::once(first_stage)
+            .chain(pipeline)
+            .collect(); //the binary, and then anything it gets piped into
+        let needs_directory = stages
+            .iter()
+            .flat_map(|stage| &stage.cli_args)
+            .any(|arg| template::needs_directory(arg)); //only make a directory for each run if something uses it
 
-                let mut command = Command::new(binary);
-                command.args(cli_args); //Create a new Command and add our arguments
+        let current_dir = ram_copy.as_ref().map_or_else(
+            || current_dir().ok(),
+            |ram_copy| Some(ram_copy.directory().to_path_buf()),
+        ); //if we have copies, run next to them so data files can be found by name
+        let build_commands = |scope: &RunScope, show_output: bool| {
+            //Commands can't have their args reset, so we build them again for each run - before the clock starts, so it doesn't count
+            let mut commands: Vec<Command> = stages
+                .iter()
+                .map(|PipelineStage { binary, cli_args }| {
+                    let mut command = Command::new(binary);
+                    command.args(cli_args.iter().map(|arg| scope.expand(arg))); //Create a new Command and add our arguments
+                    command.envs(&env); //along with any environment variables we were given
+
+                    if let Some(cd) = &current_dir {
+                        command.current_dir(cd); //If we have a current directory, add that to the Command
+                    }
+                    command
+                })
+                .collect();
 
-                if let Ok(cd) = current_dir() {
-                    command.current_dir(cd); //If we have a current directory, add that to the Command
+            if let Some((last_command, earlier_commands)) = commands.split_last_mut() {
+                if show_output {
+                    last_command.stdout(Stdio::piped()).stderr(Stdio::piped()); //pipe the output of the warmup runs (and any measured runs we show), so we can print it
+                    for command in earlier_commands {
+                        command.stderr(Stdio::inherit()); //we only read the output from the end of a pipeline, so the rest go straight to the console
+                    }
+                } else {
+                    last_command.stderr(Stdio::null()); //the measured runs don't have a stderr
+                    for command in earlier_commands {
+                        command.stderr(Stdio::null());
+                    }
+                    if self_reported_timing.is_some() {
+                        last_command.stdout(Stdio::piped()); //keep the stdout if we need to read the timing from it
+                    } else {
+                        last_command.stdout(Stdio::null()); //and no stdout if we don't
+                    }
                 }
+            }
 
+            commands
+        };
 
-                let mut is_first = true;
-                for _ in 0..warmup {
-                    //either the first run, or the warmup run. if we print initial, we send the stdout, and we always send the stderr
-                    let Output {
-                        status,
-                        stdout,
-                        stderr,
-                    } = command.output()?;
+        let stopped = Arc::new(AtomicBool::new(false)); //set by the watchdog if we get a stop signal
+        let watchdog_tx = if stop_rx.is_some() || timeout.is_some() {
+            //we only need a watchdog if something could kill a run part-way through
+            let (watchdog_tx, watchdog_rx) = channel();
+            let stopped = stopped.clone();
+            std::thread::Builder::new()
+                .name("benchmark_watchdog".into())
+                .spawn(move || watchdog(&watchdog_rx, stop_rx, &stopped))?;
+            Some(watchdog_tx)
+        } else {
+            None
+        };
+        let watch = |tree: &ProcessTree, start: Instant| {
+            if let Some(watchdog_tx) = &watchdog_tx {
+                //let the watchdog know, so it can kill the run if needed - if it has gone, the run just can't be killed
+                let _ = watchdog_tx.send(WatchdogMessage::Watch(
+                    tree.killer(),
+                    timeout.map(|timeout| start + timeout),
+                ));
+            }
+        };
+        let unwatch = || {
+            if let Some(watchdog_tx) = &watchdog_tx {
+                let _ = watchdog_tx.send(WatchdogMessage::Done);
+            }
+        };
 
-                    if !status.success() {
-                        //if we don't have an initial success, stop!
-                        error!(?status, "Initial Command failed");
-                        return Ok(());
-                    }
+        let mut is_first = true;
+        for run_index in 0..warmup {
+            //either the first run, or the warmup run. if we print initial, we send the stdout, and we always send the stderr
+            let scope = RunScope::new(usize::from(run_index), needs_directory)?;
+            let tree = ProcessTree::spawn_pipeline(&mut build_commands(&scope, true))?;
+            watch(&tree, Instant::now()); //warmup runs can hang too, so they still need to be killable
+            let output = tree.wait_with_output();
+            unwatch();
+            let (
+                Output {
+                    status,
+                    stdout,
+                    stderr,
+                },
+                _,
+            ) = output?;
 
-                    if print_initial && is_first && !stdout.is_empty() {
-                        is_first = false;
-                        //if we have a stdout, print it
-                        io::stdout().lock().write_all(&stdout)?;
-                    }
-                    if !stderr.is_empty() {
-                        //if we have a stderr, print it
-                        io::stderr().lock().write_all(&stderr)?;
+            if stopped.load(Ordering::SeqCst) {
+                //if we got stopped during the warmup, there's nothing to report
+                info!("Stopped during warmup.");
+                return Ok(());
+            }
+            if !status.success() {
+                //if we don't have an initial success, stop!
+                return Err(RunnerError::InitialRunFailed(status));
+            }
+
+            if print_initial && is_first && !stdout.is_empty() {
+                is_first = false;
+                //if we have a stdout, print it
+                io::stdout().lock().write_all(&stdout)?;
+            }
+            if !stderr.is_empty() {
+                //if we have a stderr, print it
+                io::stderr().lock().write_all(&stderr)?;
+            }
+        }
+
+        let mut rng = rand::thread_rng();
+        let mut run_index = 0;
+        let mut measurement_index = 0;
+        let mut start;
+
+        for chunk_size in (0..runs)
+            .chunks(CHUNK_SIZE)
+            .into_iter()
+            .map(Iterator::count)
+        //run in chunks to avoid constantly checking whether we've been stopped
+        {
+            if stopped.load(Ordering::SeqCst) {
+                break;
+            }
+
+            trace!(%chunk_size, "Starting batch.");
+
+            for _ in 0..chunk_size {
+                let scope = RunScope::new(run_index, needs_directory)?; //cleans up the directory for the run when it gets dropped
+                run_index += 1;
+                let show_output = show_output_every.is_some_and(|n| n > 0 && run_index % n == 0); //run_index is already one-based here, so this is every nth run
+                let mut commands = build_commands(&scope, show_output);
+                if randomize_environment {
+                    //pad before starting the clock, so building the padding doesn't count
+                    let padding = "0".repeat(rng.gen_range(0..=MAX_ENVIRONMENT_PADDING));
+                    for command in &mut commands {
+                        command.env(ENVIRONMENT_PADDING_VARIABLE, &padding);
                     }
                 }
 
-                command.stdout(Stdio::null()).stderr(Stdio::null()); //now set the command to not have a stdout or stderr
-
-                let mut start;
-
-                for chunk_size in (0..runs)
-                    .chunks(CHUNK_SIZE)
-                    .into_iter()
-                    .map(Iterator::count)
-                //run in chunks to avoid constantly polling the stop_rx
-                {
-                    if stop_rx
-                        .as_ref()
-                        .map_or(true, |stop_recv| matches!(stop_recv.try_recv(), Err(TryRecvError::Empty)))
-                    //If we don't receive anything on the stop channel, or we don't have a stop channel
-                    {
-                        trace!(%chunk_size, "Starting batch.");
-
-                        for _ in 0..chunk_size {
-                            start = Instant::now(); //send the elapsed duration and reset it
-                            let status = command.status()?; //run the command
-                            let elapsed = start.elapsed(); //get how long it took
-
-                            duration_sender
-                                .send(elapsed)
-                                .expect("Error sending result");
-
-                            if status.success() {
-                                //log the status
-                                trace!(?status, "Finished command");
-                            } else {
-                                warn!(?status, "Command failed");
-                            }
-                        }
-                    } else {
-                        break; //if we did receive something on the stop channel, break the loop
+                let started_at = Utc::now();
+                start = Instant::now(); //send the elapsed duration and reset it
+                let mut tree = ProcessTree::spawn_pipeline(&mut commands)?; //run the command
+                watch(&tree, start);
+                let result = if self_reported_timing.is_some() || show_output {
+                    tree.wait_with_output()
+                        .map(|(Output { status, stdout, stderr }, usage)| (status, usage, stdout, stderr))
+                } else {
+                    tree.wait().map(|(status, usage)| (status, usage, vec![], vec![]))
+                };
+                let elapsed = start.elapsed(); //get how long it took
+                unwatch();
+                let (status, ResourceUsage { cpu_user, cpu_sys, max_rss }, stdout, stderr) = result?;
+
+                if show_output {
+                    //all to stderr, so it doesn't get mixed up with anything reading our stdout
+                    let mut console = io::stderr().lock();
+                    writeln!(console, "--- Output of run {run_index} ---")?;
+                    console.write_all(&stdout)?;
+                    console.write_all(&stderr)?;
+                }
+
+                if stopped.load(Ordering::SeqCst) {
+                    //if we got stopped part-way through, this run doesn't count
+                    info!("Stopped part-way through a run.");
+                    return Ok(());
+                }
+                if timeout.is_some_and(|timeout| elapsed >= timeout) {
+                    //if it got killed for taking too long, it doesn't count either
+                    warn!(?elapsed, "Run timed out");
+                    if let Some(timeout_tx) = &timeout_tx {
+                        let _ = timeout_tx.send(elapsed); //if nobody is counting them, that's fine
                     }
+                    continue;
                 }
 
-                Ok(())
-            })
-            .expect("error creating thread");
-        (handle, duration_receiver)
+                let elapsed = match &self_reported_timing {
+                    Some(self_reported_timing) => self_reported_timing
+                        .parse(&stdout)
+                        .ok_or(RunnerError::TimingNotFound)?, //if the binary times itself, use that instead
+                    None => elapsed,
+                };
+
+                let measurement = RunMeasurement {
+                    index: measurement_index,
+                    command: 0,
+                    wall: elapsed,
+                    cpu_user,
+                    cpu_sys,
+                    max_rss,
+                    exit_status: status.code(),
+                    started_at,
+                };
+                measurement_index += 1;
+                if measurement_sender.send(measurement).is_err() {
+                    //if nobody is listening anymore (eg. the GUI got closed), there's no point carrying on
+                    info!("Measurement receiver closed, stopping benching.");
+                    return Ok(());
+                }
+
+                if status.success() {
+                    //log the status
+                    trace!(?status, "Finished command");
+                } else {
+                    warn!(?status, "Command failed");
+                }
+            }
+        }
+
+        Ok(())
     }
 }
 
-///Calculate the mean and standard deviation from a list of microsecond run values
-#[allow(clippy::cast_precision_loss)]
-#[must_use]
-pub fn calculate_mean_standard_deviation(runs: &[u128]) -> Option<(Duration, Duration)> {
-    if runs.is_empty() {
-        return None;
-    }
+///Watches over each run, killing the whole process tree if we get a stop signal or it goes past the deadline. Stops when the runner drops its sender.
+#[allow(clippy::needless_pass_by_value)]
+fn watchdog(
+    watchdog_rx: &Receiver<WatchdogMessage>,
+    stop_rx: Option<Receiver<()>>,
+    stopped: &AtomicBool,
+) {
+    let should_stop = || {
+        //If we receive anything on the stop channel (or it gets disconnected), stop
+        stop_rx
+            .as_ref()
+            .is_some_and(|stop_rx| !matches!(stop_rx.try_recv(), Err(TryRecvError::Empty)))
+    };
 
-    let len = runs.len() as f64;
-    let mut sum = 0;
-    let mut sum_of_squares = 0;
+    while let Ok(message) = watchdog_rx.recv() {
+        let WatchdogMessage::Watch(killer, deadline) = message else {
+            continue;
+        };
 
-    for item in runs {
-        sum += item;
-        sum_of_squares += item.pow(2);
-    }
+        loop {
+            let wait = deadline.map_or(WATCHDOG_POLL, |deadline| {
+                WATCHDOG_POLL.min(deadline.saturating_duration_since(Instant::now()))
+            });
 
-    let mean = (sum as f64) / len;
-    let variance = mean.mul_add(-mean, (sum_of_squares as f64) / len); //(sum_of_squares as f64) / len - mean.powi(2); mean of squares - square of mean
+            match watchdog_rx.recv_timeout(wait) {
+                Ok(WatchdogMessage::Done) => break,
+                Ok(WatchdogMessage::Watch(..)) => {
+                    warn!("Watchdog got a new run before the last one finished");
+                    break;
+                }
+                Err(RecvTimeoutError::Disconnected) => return,
+                Err(RecvTimeoutError::Timeout) => {
+                    let timed_out = deadline.is_some_and(|deadline| Instant::now() >= deadline);
+                    if !stopped.load(Ordering::SeqCst) && should_stop() {
+                        stopped.store(true, Ordering::SeqCst);
+                    } else if !timed_out {
+                        continue;
+                    }
 
-    Some((
-        Duration::from_secs_f64(mean / 1_000_000.0),
-        Duration::from_secs_f64(variance.sqrt() / 1_000_000.0),
-    )) //divide by 1_000_000 to account for micros being stored
+                    trace!(%timed_out, "Killing run");
+                    if let Err(e) = killer.kill() {
+                        error!(%e, "Unable to kill run");
+                    }
+                    //wait for the runner to notice, so we don't kill anything twice
+                    if !matches!(watchdog_rx.recv(), Ok(WatchdogMessage::Done)) {
+                        return;
+                    }
+                    break;
+                }
+            }
+        }
+
+        if !stopped.load(Ordering::SeqCst) && should_stop() {
+            //also check between runs, so a stop signal doesn't have to wait for a run to start
+            stopped.store(true, Ordering::SeqCst);
+        }
+    }
 }
@@ -0,0 +1,211 @@
+//! Module to compare several commands in one session, eg. two builds of the same binary, or two tools that do the same job.
+//!
+//! A [`MultiRunner`] interleaves the runs of its commands - each round runs every command once, and the command that goes first moves along by one each round - so anything that changes over the session (like the machine heating up, or something starting in the background) affects them all equally. Each [`RunMeasurement`] it sends has the [`RunMeasurement::command`] it came from, and its [`RunMeasurement::index`] counts the runs of that command.
+//!
+//! Any warmup runs happen for each command before the measured ones start, and if any command's warmup fails, the session stops with a [`RunnerError::InitialRunFailed`] like a [`Runner`] would.
+//!
+//! ## Example
+//! ```rust
+//! use benchmarker::bencher::multi::MultiRunner;
+//! use std::path::PathBuf;
+//!
+//! let commands = vec![
+//!     (PathBuf::from("/bin/echo"), vec!["Hello".into()]),
+//!     (PathBuf::from("/bin/true"), vec![]),
+//! ];
+//! let (handle, rx, error_rx) = MultiRunner::new(commands, 3, None, 0).start().unwrap();
+//! handle.join().unwrap();
+//!
+//! let measurements: Vec<_> = rx.try_iter().collect();
+//! let order: Vec<usize> = measurements.iter().map(|measurement| measurement.command).collect();
+//! assert_eq!(order, [0, 1, 1, 0, 0, 1]); //whichever went second goes first next time
+//! assert_eq!(measurements.last().unwrap().index, 2); //the third run of /bin/true
+//! assert!(error_rx.try_recv().is_err());
+//! ```
+
+use super::{RunMeasurement, Runner, RunnerChannels, RunnerError, WATCHDOG_POLL};
+use crate::error::Error;
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, Receiver, RecvTimeoutError, Sender},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+///Struct to build a comparison of several commands - like a [`Runner`], it takes in arguments using a builder pattern, then you can start it
+pub struct MultiRunner {
+    ///The binaries to run, with the args to pass to each one
+    pub commands: Vec<(PathBuf, Vec<String>)>,
+    ///The number of runs of each command
+    pub runs: usize,
+    ///The channel to stop running - the run in progress gets killed, like with a [`Runner`]
+    pub stop_rx: Option<Receiver<()>>,
+    ///The number of warmup runs for each command
+    pub warmup: u8,
+    ///How long a single run can take before it gets killed and skipped
+    pub timeout: Option<Duration>,
+    ///Whether to pad the environment by a random amount for each run - see [`Runner::randomize_environment`]
+    pub randomize_environment: bool,
+    ///Whether to run each command as a command line through the shell - see [`Runner::shell`]
+    pub shell: bool,
+}
+
+impl MultiRunner {
+    ///Constructor
+    #[must_use]
+    pub const fn new(
+        commands: Vec<(PathBuf, Vec<String>)>,
+        runs: usize,
+        stop_rx: Option<Receiver<()>>,
+        warmup: u8,
+    ) -> Self {
+        Self {
+            commands,
+            runs,
+            stop_rx,
+            warmup,
+            timeout: None,
+            randomize_environment: false,
+            shell: false,
+        }
+    }
+
+    ///Changes how long a single run can take before it gets killed - builder pattern
+    #[must_use]
+    pub const fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    ///Changes whether to randomise the size of the environment for each run - builder pattern
+    #[must_use]
+    pub const fn randomize_environment(mut self, randomize_environment: bool) -> Self {
+        self.randomize_environment = randomize_environment;
+        self
+    }
+
+    ///Changes whether to run each command through the shell - builder pattern
+    #[must_use]
+    pub const fn shell(mut self, shell: bool) -> Self {
+        self.shell = shell;
+        self
+    }
+
+    ///Starts the comparison in a new thread.
+    ///
+    /// Gives back the same [`RunnerChannels`] as [`Runner::start`], with the [`RunMeasurement::command`] of each measurement saying which command it was.
+    ///
+    /// # Errors
+    ///
+    /// Can fail with an [`Error::Spawn`] if we fail to spawn the thread
+    #[instrument(skip(self))]
+    pub fn start(self) -> Result<RunnerChannels, Error> {
+        let (measurement_sender, measurement_receiver) = channel();
+        let (error_sender, error_receiver) = channel();
+
+        let handle = std::thread::Builder::new()
+            .name("benchmark_multi_runner".into())
+            .spawn(move || {
+                if let Err(e) = self.run(&measurement_sender) {
+                    error!(%e, "Error whilst comparing");
+                    if error_sender.send(e).is_err() {
+                        warn!("Error receiver closed before error could be sent");
+                    }
+                }
+            })
+            .map_err(Error::Spawn)?;
+        Ok((handle, measurement_receiver, error_receiver))
+    }
+
+    ///Actually runs the comparison on the current thread, with a [`Runner`] for every run so they can be interleaved
+    fn run(self, measurement_sender: &Sender<RunMeasurement>) -> Result<(), RunnerError> {
+        let Self {
+            commands,
+            runs,
+            stop_rx,
+            warmup,
+            timeout,
+            randomize_environment,
+            shell,
+        } = self;
+        info!(?commands, %runs, %warmup, "Starting comparison.");
+
+        let stopped = Arc::new(AtomicBool::new(false));
+        let current_stop: Arc<Mutex<Option<Sender<()>>>> = Arc::default(); //the stop channel for the run in progress, so a stop can kill it
+        let finished = Arc::new(AtomicBool::new(false));
+        if let Some(stop_rx) = stop_rx {
+            //each Runner needs its own stop channel, so pass the stop on to whichever one is going
+            let (stopped, current_stop, finished) = (stopped.clone(), current_stop.clone(), finished.clone());
+            std::thread::Builder::new()
+                .name("benchmark_multi_stopper".into())
+                .spawn(move || loop {
+                    match stop_rx.recv_timeout(WATCHDOG_POLL) {
+                        Ok(()) | Err(RecvTimeoutError::Disconnected) => {
+                            stopped.store(true, Ordering::SeqCst);
+                            if let Some(stop_tx) = current_stop.lock().ok().as_deref().and_then(Option::as_ref) {
+                                let _ = stop_tx.send(()); //if the run has just finished, that's fine
+                            }
+                            return;
+                        }
+                        Err(RecvTimeoutError::Timeout) if finished.load(Ordering::SeqCst) => return,
+                        Err(RecvTimeoutError::Timeout) => {}
+                    }
+                })?;
+        }
+
+        let run_command = |command: usize, runs: usize, warmup: u8| -> Result<Vec<RunMeasurement>, RunnerError> {
+            let (stop_tx, stop_rx) = channel();
+            if let Ok(mut current_stop) = current_stop.lock() {
+                *current_stop = Some(stop_tx); //before checking whether we've stopped, so a stop can't fall between the two
+            }
+            if stopped.load(Ordering::SeqCst) {
+                return Ok(vec![]);
+            }
+
+            let (binary, cli_args) = commands[command].clone();
+            let (tx, rx) = channel();
+            Runner::new(binary, cli_args, runs, Some(stop_rx), warmup, false)
+                .timeout(timeout)
+                .randomize_environment(randomize_environment)
+                .shell(shell)
+                .run(&tx)?;
+            Ok(rx.try_iter().collect())
+        };
+
+        let result = (|| {
+            if warmup > 0 {
+                for command in 0..commands.len() {
+                    run_command(command, 0, warmup)?;
+                }
+            }
+
+            let mut indices = vec![0; commands.len()];
+            for round in 0..runs {
+                for offset in 0..commands.len() {
+                    if stopped.load(Ordering::SeqCst) {
+                        info!("Stopped part-way through the comparison.");
+                        return Ok(());
+                    }
+
+                    let command = (round + offset) % commands.len(); //move along who goes first, so none of them always gets the same conditions
+                    for mut measurement in run_command(command, 1, 0)? {
+                        measurement.command = command;
+                        measurement.index = indices[command];
+                        indices[command] += 1;
+                        if measurement_sender.send(measurement).is_err() {
+                            info!("Measurement receiver closed, stopping comparison.");
+                            return Ok(());
+                        }
+                    }
+                }
+            }
+            Ok(())
+        })();
+
+        finished.store(true, Ordering::SeqCst); //let the stopper go
+        result
+    }
+}
@@ -0,0 +1,504 @@
+//! Module to spawn the benchmarked binary so that it, and anything it spawns, can all be killed together.
+//!
+//! If a binary spawns its own children, killing just the direct child leaves them running as orphans which then skew every run after. To avoid that:
+//! - On Unix, each run gets its own process group, and we kill the whole group.
+//! - On Windows, each run gets put in a Job Object, and we terminate the whole job. The job is also set to kill everything left in it when we close it.
+//!
+//! Pipelines (eg. `producer | consumer`) get spawned into the same group/job, so they can be killed together too.
+//!
+//! The [`ProcessKiller`] is separate from the [`ProcessTree`], so that one thread can wait on the process whilst another (eg. a watchdog for timeouts) decides to kill it.
+//!
+//! Waiting on a tree also gives back the [`ResourceUsage`] of the direct children - on Unix from `wait4`, which also counts anything they spawned and waited for, and on Windows from the process handles, which only counts the direct children.
+
+use std::{
+    io::{self, Read},
+    process::{Child, Command, ExitStatus, Output, Stdio},
+    time::Duration,
+};
+
+///The CPU time and memory used by the direct children of a [`ProcessTree`]. Anything we can't get on this platform is [`None`]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ResourceUsage {
+    ///Time spent running in user mode, summed across the children
+    pub cpu_user: Option<Duration>,
+    ///Time spent running in the kernel, summed across the children
+    pub cpu_sys: Option<Duration>,
+    ///The most memory any one of the children had resident at once, in bytes
+    pub max_rss: Option<u64>,
+}
+
+impl ResourceUsage {
+    ///Combines the usage of two commands in the same pipeline - the CPU times get added, and we keep the bigger peak memory
+    #[must_use]
+    pub fn combine(self, other: Self) -> Self {
+        ///Adds two optional times, as long as we have both
+        fn add(a: Option<Duration>, b: Option<Duration>) -> Option<Duration> {
+            a.zip(b).map(|(a, b)| a + b)
+        }
+
+        Self {
+            cpu_user: add(self.cpu_user, other.cpu_user),
+            cpu_sys: add(self.cpu_sys, other.cpu_sys),
+            max_rss: self.max_rss.zip(other.max_rss).map(|(a, b)| a.max(b)),
+        }
+    }
+}
+
+///A spawned binary (or pipeline of binaries), along with everything they spawn
+pub struct ProcessTree {
+    ///The direct children, in pipeline order - there is always at least one
+    children: Vec<Child>,
+    ///The killer for the whole tree
+    killer: ProcessKiller,
+}
+
+impl ProcessTree {
+    ///Spawns a command in a new process group/job. On Unix, this changes the process group of the command for all future spawns too
+    ///
+    /// # Errors
+    ///
+    /// Can fail if we fail to spawn the command, or fail to set up the job
+    pub fn spawn(command: &mut Command) -> io::Result<Self> {
+        Self::spawn_pipeline(std::slice::from_mut(command))
+    }
+
+    ///Spawns a pipeline of commands in one new process group/job, with the stdout of each command going to the stdin of the next.
+    ///
+    /// The stdin of the first command and the stdout of the last command are left as they were set up - everything else gets overwritten. Like [`Self::spawn`], this changes the commands for all future spawns too.
+    ///
+    /// # Errors
+    ///
+    /// Can fail if there are no commands, we fail to spawn any of them, or fail to set up the job
+    pub fn spawn_pipeline(commands: &mut [Command]) -> io::Result<Self> {
+        let last = commands
+            .len()
+            .checked_sub(1)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty pipeline"))?;
+
+        let mut children: Vec<Child> = Vec::with_capacity(commands.len());
+        let mut killer: Option<ProcessKiller> = None;
+        let mut previous_stdout = None;
+
+        for (i, command) in commands.iter_mut().enumerate() {
+            if let Some(stdout) = previous_stdout.take() {
+                command.stdin(Stdio::from(stdout)); //plug the last command into this one
+            }
+            if i != last {
+                command.stdout(Stdio::piped()); //and this one into the next
+            }
+
+            #[cfg(unix)]
+            std::os::unix::process::CommandExt::process_group(
+                command,
+                killer.as_ref().map_or(0, |k| k.process_group), //0 makes a new group with the same ID as the child, and the rest join it
+            );
+
+            let spawned = command.spawn().and_then(|mut child| {
+                if i != last {
+                    previous_stdout = child.stdout.take(); //leave the last stdout for wait_with_output
+                }
+                let added = match &killer {
+                    Some(killer) => killer.add(&child),
+                    None => ProcessKiller::new(&child).map(|k| killer = Some(k)),
+                };
+                match added {
+                    Ok(()) => Ok(child),
+                    Err(e) => {
+                        //if we can't track the child, we can't clean it up later so get rid of it now
+                        let _ = child.kill();
+                        let _ = child.wait();
+                        Err(e)
+                    }
+                }
+            });
+
+            match spawned {
+                Ok(child) => children.push(child),
+                Err(e) => {
+                    //get rid of the rest of the pipeline so nothing gets left behind
+                    if let Some(killer) = &killer {
+                        let _ = killer.kill();
+                    }
+                    for mut child in children {
+                        let _ = child.kill();
+                        let _ = child.wait();
+                    }
+                    return Err(e);
+                }
+            }
+        }
+
+        let killer = killer.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty pipeline"))?;
+        Ok(Self { children, killer })
+    }
+
+    ///Gets a killer for the whole tree, which can be sent to other threads
+    #[must_use]
+    pub fn killer(&self) -> ProcessKiller {
+        self.killer.clone()
+    }
+
+    ///Blocks until all of the direct children exit.
+    ///
+    /// Like `set -o pipefail`, this gives the status of the first command that failed, or the last command if they all succeeded.
+    ///
+    /// # Errors
+    ///
+    /// Can fail if the OS fails to wait on any of the children
+    pub fn wait(&mut self) -> io::Result<(ExitStatus, ResourceUsage)> {
+        let mut statuses = Vec::with_capacity(self.children.len());
+        let mut usage: Option<ResourceUsage> = None;
+        for child in self.children.iter_mut().rev() {
+            let (status, child_usage) = wait_child(child)?; //wait from the end, as the end of a pipeline is the last to finish
+            statuses.push(status);
+            usage = Some(usage.map_or(child_usage, |usage| usage.combine(child_usage)));
+        }
+        statuses.reverse();
+        Ok((pipeline_status(&statuses), usage.unwrap_or_default()))
+    }
+
+    ///Blocks until all of the direct children exit, collecting anything the last one wrote to piped stdout/stderr. The status and usage are the same as [`Self::wait`]
+    ///
+    /// # Errors
+    ///
+    /// Can fail if the OS fails to wait on any of the children, or to read the output
+    pub fn wait_with_output(mut self) -> io::Result<(Output, ResourceUsage)> {
+        let mut last = self
+            .children
+            .pop()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty pipeline"))?;
+
+        //read the output first, so the last command doesn't block on a full pipe - stderr on another thread, so neither pipe can fill up whilst we read the other
+        let stderr_reader = last.stderr.take().map(|mut stderr| {
+            std::thread::spawn(move || {
+                let mut buffer = vec![];
+                stderr.read_to_end(&mut buffer).map(|_| buffer)
+            })
+        });
+        let mut stdout = vec![];
+        if let Some(mut last_stdout) = last.stdout.take() {
+            last_stdout.read_to_end(&mut stdout)?;
+        }
+        let stderr = match stderr_reader {
+            Some(reader) => reader
+                .join()
+                .map_err(|_| io::Error::other("stderr reader panicked"))??,
+            None => vec![],
+        };
+
+        let (last_status, mut usage) = wait_child(&mut last)?;
+        let mut statuses = Vec::with_capacity(self.children.len() + 1);
+        for child in &mut self.children {
+            let (status, child_usage) = wait_child(child)?;
+            statuses.push(status);
+            usage = usage.combine(child_usage);
+        }
+        statuses.push(last_status);
+
+        Ok((
+            Output {
+                status: pipeline_status(&statuses),
+                stdout,
+                stderr,
+            },
+            usage,
+        ))
+    }
+}
+
+#[cfg(unix)]
+///Waits for a child with `wait4`, which also gives us its resource usage
+#[allow(clippy::needless_pass_by_ref_mut)] //the other platforms need it to be mutable
+fn wait_child(child: &mut Child) -> io::Result<(ExitStatus, ResourceUsage)> {
+    use std::os::unix::process::ExitStatusExt;
+
+    ///Converts the `timeval`s from `rusage`
+    fn to_duration(time: libc::timeval) -> Option<Duration> {
+        Some(Duration::from_secs(u64::try_from(time.tv_sec).ok()?)
+            + Duration::from_micros(u64::try_from(time.tv_usec).ok()?))
+    }
+
+    let pid = libc::pid_t::try_from(child.id()).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let mut status = 0;
+    // SAFETY: rusage is plain data, so all zeroes is valid
+    let mut rusage: libc::rusage = unsafe { std::mem::zeroed() };
+    loop {
+        // SAFETY: both pointers are to valid locals, and the child hasn't been reaped yet as std only reaps in wait/try_wait
+        let result = unsafe { libc::wait4(pid, std::ptr::addr_of_mut!(status), 0, std::ptr::addr_of_mut!(rusage)) };
+        if result != -1 {
+            break;
+        }
+        let error = io::Error::last_os_error();
+        if error.kind() != io::ErrorKind::Interrupted {
+            return Err(error);
+        }
+    }
+
+    let max_rss = u64::try_from(rusage.ru_maxrss).ok().map(|max_rss| {
+        if cfg!(any(target_os = "macos", target_os = "ios")) {
+            max_rss //these give bytes
+        } else {
+            max_rss * 1024 //everything else gives kilobytes
+        }
+    });
+    Ok((
+        ExitStatus::from_raw(status),
+        ResourceUsage {
+            cpu_user: to_duration(rusage.ru_utime),
+            cpu_sys: to_duration(rusage.ru_stime),
+            max_rss,
+        },
+    ))
+}
+
+#[cfg(windows)]
+///Waits for a child, and then asks for its times and memory whilst we still have the handle
+fn wait_child(child: &mut Child) -> io::Result<(ExitStatus, ResourceUsage)> {
+    use std::os::windows::io::AsRawHandle;
+    use windows_sys::Win32::{
+        Foundation::FILETIME,
+        System::{
+            ProcessStatus::{GetProcessMemoryInfo, PROCESS_MEMORY_COUNTERS},
+            Threading::GetProcessTimes,
+        },
+    };
+
+    ///Converts the `FILETIME`s, which count in 100ns ticks
+    fn to_duration(time: FILETIME) -> Duration {
+        let ticks = (u64::from(time.dwHighDateTime) << 32) | u64::from(time.dwLowDateTime);
+        Duration::from_nanos(ticks * 100)
+    }
+
+    let status = child.wait()?;
+    let handle = child.as_raw_handle() as isize;
+
+    let empty = FILETIME {
+        dwLowDateTime: 0,
+        dwHighDateTime: 0,
+    };
+    let (mut creation, mut exit, mut kernel, mut user) = (empty, empty, empty, empty);
+    // SAFETY: the handle is valid for as long as we have the child, and all of the pointers are to valid locals
+    let got_times = unsafe {
+        GetProcessTimes(
+            handle,
+            std::ptr::addr_of_mut!(creation),
+            std::ptr::addr_of_mut!(exit),
+            std::ptr::addr_of_mut!(kernel),
+            std::ptr::addr_of_mut!(user),
+        )
+    } != 0;
+
+    // SAFETY: the struct is plain data, so all zeroes is valid
+    let mut counters: PROCESS_MEMORY_COUNTERS = unsafe { std::mem::zeroed() };
+    let size = u32::try_from(std::mem::size_of_val(&counters)).unwrap_or(u32::MAX);
+    counters.cb = size;
+    // SAFETY: the handle is valid, and the pointer and size are for the right struct
+    let got_memory = unsafe { GetProcessMemoryInfo(handle, std::ptr::addr_of_mut!(counters), size) } != 0;
+
+    Ok((
+        status,
+        ResourceUsage {
+            cpu_user: got_times.then(|| to_duration(user)),
+            cpu_sys: got_times.then(|| to_duration(kernel)),
+            max_rss: got_memory.then_some(counters.PeakWorkingSetSize as u64),
+        },
+    ))
+}
+
+#[cfg(not(any(unix, windows)))]
+///We don't have a way to get the usage here, so we just wait
+fn wait_child(child: &mut Child) -> io::Result<(ExitStatus, ResourceUsage)> {
+    Ok((child.wait()?, ResourceUsage::default()))
+}
+
+///Gets the status of the first failed command, or the last command if they all succeeded
+fn pipeline_status(statuses: &[ExitStatus]) -> ExitStatus {
+    statuses
+        .iter()
+        .find(|status| !status.success())
+        .or_else(|| statuses.last())
+        .copied()
+        .unwrap_or_default()
+}
+
+#[cfg(unix)]
+///Kills a whole process group
+#[derive(Clone, Debug)]
+pub struct ProcessKiller {
+    ///The ID of the process group, which is the same as the PID of the direct child
+    process_group: libc::pid_t,
+}
+
+#[cfg(unix)]
+impl ProcessKiller {
+    ///Makes a killer for the group that was made for this child
+    fn new(child: &Child) -> io::Result<Self> {
+        Ok(Self {
+            process_group: libc::pid_t::try_from(child.id())
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+        })
+    }
+
+    ///Does nothing, as the child joined the group when it was spawned
+    #[allow(clippy::unnecessary_wraps, clippy::unused_self)]
+    const fn add(&self, _child: &Child) -> io::Result<()> {
+        Ok(())
+    }
+
+    ///Kills everything in the process group. It isn't an error if they've all already exited
+    ///
+    /// # Errors
+    ///
+    /// Can fail if the OS fails to send the signal
+    pub fn kill(&self) -> io::Result<()> {
+        // SAFETY: kill has no memory safety requirements, and a negative PID means the whole group
+        let result = unsafe { libc::kill(-self.process_group, libc::SIGKILL) };
+        if result == 0 {
+            return Ok(());
+        }
+
+        let error = io::Error::last_os_error();
+        if error.raw_os_error() == Some(libc::ESRCH) {
+            Ok(()) //nothing left to kill
+        } else {
+            Err(error)
+        }
+    }
+}
+
+#[cfg(windows)]
+///Kills a whole Job Object
+#[derive(Clone, Debug)]
+pub struct ProcessKiller {
+    ///The job, shared between all of the clones so that it only gets closed once
+    job: std::sync::Arc<Job>,
+}
+
+#[cfg(windows)]
+///Owned handle to a Job Object, which gets closed (killing anything left) on drop
+#[derive(Debug)]
+struct Job(windows_sys::Win32::Foundation::HANDLE);
+
+// SAFETY: job handles can be used from any thread
+#[cfg(windows)]
+unsafe impl Send for Job {}
+// SAFETY: all of the job functions we use are thread-safe
+#[cfg(windows)]
+unsafe impl Sync for Job {}
+
+#[cfg(windows)]
+impl Drop for Job {
+    fn drop(&mut self) {
+        // SAFETY: we own the handle, and this is the only place it gets closed
+        unsafe {
+            windows_sys::Win32::Foundation::CloseHandle(self.0);
+        }
+    }
+}
+
+#[cfg(windows)]
+impl ProcessKiller {
+    ///Makes a new job, and puts this child in it.
+    ///
+    /// Anything the child spawned before we got it into the job won't be included, but that window is tiny
+    fn new(child: &Child) -> io::Result<Self> {
+        use windows_sys::Win32::System::JobObjects::{
+            CreateJobObjectW, JobObjectExtendedLimitInformation, SetInformationJobObject,
+            JOBOBJECT_EXTENDED_LIMIT_INFORMATION, JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
+        };
+
+        // SAFETY: null attributes and name are allowed, and we check the handle
+        let handle = unsafe { CreateJobObjectW(std::ptr::null(), std::ptr::null()) };
+        if handle == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let job = Job(handle); //make sure it gets closed if anything after this fails
+
+        // SAFETY: the struct is plain data, so all zeroes is valid
+        let mut info: JOBOBJECT_EXTENDED_LIMIT_INFORMATION = unsafe { std::mem::zeroed() };
+        info.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+        // SAFETY: the pointer and length are for the right struct for this info class
+        let ok = unsafe {
+            SetInformationJobObject(
+                job.0,
+                JobObjectExtendedLimitInformation,
+                std::ptr::addr_of!(info).cast(),
+                u32::try_from(std::mem::size_of_val(&info)).unwrap_or(u32::MAX),
+            )
+        };
+        if ok == 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let killer = Self {
+            job: std::sync::Arc::new(job),
+        };
+        killer.add(child)?;
+        Ok(killer)
+    }
+
+    ///Puts another child in the job
+    fn add(&self, child: &Child) -> io::Result<()> {
+        use std::os::windows::io::AsRawHandle;
+
+        // SAFETY: the child handle is valid for as long as we have the child, and the job handle until the last clone gets dropped
+        let ok = unsafe {
+            windows_sys::Win32::System::JobObjects::AssignProcessToJobObject(
+                self.job.0,
+                child.as_raw_handle() as isize,
+            )
+        };
+        if ok == 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    ///Terminates everything in the job
+    ///
+    /// # Errors
+    ///
+    /// Can fail if the OS fails to terminate the job
+    pub fn kill(&self) -> io::Result<()> {
+        // SAFETY: the job handle is valid until the last clone gets dropped
+        let ok = unsafe { windows_sys::Win32::System::JobObjects::TerminateJobObject(self.job.0, 1) };
+        if ok == 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+///Fallback which can only kill the direct child
+#[derive(Clone, Debug)]
+pub struct ProcessKiller;
+
+#[cfg(not(any(unix, windows)))]
+impl ProcessKiller {
+    ///We don't have a way to track the tree here
+    #[allow(clippy::unnecessary_wraps)]
+    const fn new(_child: &Child) -> io::Result<Self> {
+        Ok(Self)
+    }
+
+    ///We don't have a way to track the tree here
+    #[allow(clippy::unnecessary_wraps, clippy::unused_self)]
+    const fn add(&self, _child: &Child) -> io::Result<()> {
+        Ok(())
+    }
+
+    ///We can't kill anything here without the child
+    ///
+    /// # Errors
+    ///
+    /// Always fails, as this platform isn't supported
+    pub fn kill(&self) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "killing process trees isn't supported on this platform",
+        ))
+    }
+}
@@ -0,0 +1,135 @@
+//! Module to copy the binary (and any data files it needs) to a RAM-backed directory before benchmarking it.
+//!
+//! The first few runs of a binary can be slowed down by reading it (and its data) off the disk, and that can come back whenever the OS drops it from the page cache. Running from a tmpfs avoids the disk entirely.
+//!
+//! The copies live in a new directory inside the RAM-backed directory, which is also used as the working directory for the runs so data files can be found by name. It gets removed when the [`RamCopy`] is dropped.
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::{
+    env,
+    fs::{copy, create_dir, remove_dir_all},
+    io,
+    path::{Path, PathBuf},
+};
+
+///Where to copy things to, and what to copy alongside the binary
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RamCopyOptions {
+    ///The RAM-backed directory to copy into - if not provided, we use [`default_ram_directory`]
+    pub directory: Option<PathBuf>,
+    ///Files to copy into the same directory as the binary, keeping their file names
+    pub data_files: Vec<PathBuf>,
+}
+
+///Gets the normal RAM-backed directory for this platform, if it has one
+#[must_use]
+pub fn default_ram_directory() -> Option<PathBuf> {
+    if cfg!(target_os = "linux") {
+        Some(PathBuf::from("/dev/shm")).filter(|p| p.is_dir())
+    } else {
+        None //macOS and Windows don't have one without setting up a ramdisk, so the user has to give us one
+    }
+}
+
+///Finds the actual file for a binary, searching the `PATH` if it is just a name (like [`std::process::Command`] does)
+fn find_binary(binary: &Path) -> io::Result<PathBuf> {
+    if binary.components().count() > 1 || binary.is_file() {
+        return Ok(binary.to_path_buf());
+    }
+
+    env::var_os("PATH")
+        .iter()
+        .flat_map(env::split_paths)
+        .map(|dir| dir.join(binary))
+        .find(|candidate| candidate.is_file())
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("unable to find {} to copy", binary.display()),
+            )
+        })
+}
+
+///The copies of the binary and its data files. The directory with the copies gets removed on drop
+#[derive(Debug)]
+pub struct RamCopy {
+    ///The new directory with all of the copies in
+    directory: PathBuf,
+    ///The copy of the binary
+    binary: PathBuf,
+}
+
+impl RamCopy {
+    ///Copies the binary and data files to a new directory inside the RAM-backed directory
+    ///
+    /// # Errors
+    ///
+    /// Can fail if we don't have a RAM-backed directory, can't find the binary or data files, or fail to copy any of them
+    #[instrument]
+    pub fn new(binary: &Path, options: &RamCopyOptions) -> io::Result<Self> {
+        let base = options
+            .directory
+            .clone()
+            .or_else(default_ram_directory)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "no RAM-backed directory on this platform - one has to be provided",
+                )
+            })?;
+
+        let directory = base.join(format!(
+            "precipice_ram_{}_{:x}",
+            std::process::id(),
+            rand::thread_rng().gen::<u32>()
+        ));
+        create_dir(&directory)?;
+        let mut copy = Self {
+            binary: directory.clone(),
+            directory,
+        }; //make sure the directory gets cleaned up if anything after this fails
+
+        let source = find_binary(binary)?;
+        copy.binary = copy_into(&source, &copy.directory)?; //fs::copy keeps the permissions, so the copy is still executable
+        for data_file in &options.data_files {
+            copy_into(data_file, &copy.directory)?;
+        }
+
+        info!(binary = ?copy.binary, "Copied to RAM");
+        Ok(copy)
+    }
+
+    ///The copy of the binary, to run instead of the original
+    #[must_use]
+    pub fn binary(&self) -> &Path {
+        &self.binary
+    }
+
+    ///The directory with all of the copies, to use as the working directory
+    #[must_use]
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+}
+
+///Copies a file into a directory, keeping the file name. Returns where it got copied to
+fn copy_into(file: &Path, directory: &Path) -> io::Result<PathBuf> {
+    let name = file.file_name().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} isn't a file", file.display()),
+        )
+    })?;
+    let destination = directory.join(name);
+    copy(file, &destination)?;
+    Ok(destination)
+}
+
+impl Drop for RamCopy {
+    fn drop(&mut self) {
+        if let Err(e) = remove_dir_all(&self.directory) {
+            warn!(%e, directory = ?self.directory, "Unable to clean up RAM copy");
+        }
+    }
+}
@@ -0,0 +1,209 @@
+//! Module to run benchmarks on another machine, using `precipice agent` on that machine.
+//!
+//! The protocol is newline-separated JSON over TCP:
+//! 1. The client connects and sends one [`RemoteRequest`].
+//! 2. The agent sends back a [`RemoteMessage`] for every run, and for any errors, then closes the connection when it is done.
+//! 3. At any point, the client can send the line `stop` (or just close the connection) to stop the agent early.
+//!
+//! [`RemoteRunner::start`] gives back the same [`RunnerChannels`] as [`Runner::start`], so frontends can use either without caring where the benchmark actually happens.
+
+use super::{ramdisk::RamCopyOptions, timing::TimingUnit, PipelineStage, RunMeasurement, Runner, RunnerChannels, RunnerError};
+use crate::error::Error;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    io::{self, BufRead, BufReader, Write},
+    net::TcpStream,
+    path::PathBuf,
+    sync::mpsc::{channel, Sender},
+    time::Duration,
+};
+
+///The line a client sends to stop the agent early
+pub const STOP_LINE: &str = "stop";
+
+///Default port for the agent to listen on
+pub const DEFAULT_AGENT_PORT: u16 = 8081;
+
+///What the client asks the agent to run - the same as the serialisable parts of a [`Runner`]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RemoteRequest {
+    ///The token the agent was started with, if it has one
+    pub token: Option<String>,
+    ///The binary to run, as a path on the agent's machine
+    pub binary: PathBuf,
+    ///The args to pass to the binary
+    pub cli_args: Vec<String>,
+    ///The number of runs
+    pub runs: usize,
+    ///The number of warmup runs
+    pub warmup: u8,
+    ///How long a single run can take before it gets killed
+    #[serde(default)]
+    pub timeout: Option<Duration>,
+    ///Whether to randomise the size of the environment for each run
+    #[serde(default)]
+    pub randomize_environment: bool,
+    ///If the timings should come from stdout, the regex to find them with and the unit they're in
+    #[serde(default)]
+    pub self_reported_timing: Option<(String, TimingUnit)>,
+    ///Commands to pipe the output of the binary through, as paths on the agent's machine
+    #[serde(default)]
+    pub pipeline: Vec<PipelineStage>,
+    ///Whether to copy the binary to a RAM-backed directory on the agent's machine, and what to copy with it
+    #[serde(default)]
+    pub ram_copy: Option<RamCopyOptions>,
+    ///Whether to run the binary and its args as a command line through the agent's shell
+    #[serde(default)]
+    pub shell: bool,
+    ///Extra environment variables to set for every run
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+}
+
+///What the agent sends back to the client
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum RemoteMessage {
+    ///One run finished, with everything the agent measured about it
+    Measurement(RunMeasurement),
+    ///Something went wrong on the agent and it stopped
+    Error(String),
+    ///A run timed out after this long, so it didn't get a measurement
+    TimedOut(Duration),
+}
+
+///Runs a [`Runner`] on another machine with an agent, rather than on this one
+pub struct RemoteRunner {
+    ///The address of the agent, eg. `quiet-box:8081`
+    pub address: String,
+    ///The token the agent was started with, if it has one
+    pub token: Option<String>,
+    ///What to run - the binary path is on the agent's machine, and `print_initial` and `show_output_every` get ignored
+    pub runner: Runner,
+}
+
+impl RemoteRunner {
+    ///Constructor
+    #[must_use]
+    pub const fn new(address: String, token: Option<String>, runner: Runner) -> Self {
+        Self {
+            address,
+            token,
+            runner,
+        }
+    }
+
+    ///Connects to the agent, sends the request, and then starts a new thread to receive the measurements.
+    ///
+    /// # Errors
+    ///
+    /// Can fail with an [`Error::Io`] if we fail to connect to the agent or send the request, or an [`Error::Spawn`] if we fail to spawn the threads
+    #[instrument(skip(self), fields(address = %self.address))]
+    pub fn start(self) -> Result<RunnerChannels, Error> {
+        let Self {
+            address,
+            token,
+            runner:
+                Runner {
+                    binary,
+                    cli_args,
+                    runs,
+                    stop_rx,
+                    warmup,
+                    print_initial: _,
+                    timeout,
+                    randomize_environment,
+                    self_reported_timing,
+                    pipeline,
+                    ram_copy,
+                    show_output_every: _,
+                    timeout_tx,
+                    shell,
+                    env,
+                },
+        } = self;
+
+        let mut stream = TcpStream::connect(&address)?;
+        let request = RemoteRequest {
+            token,
+            binary,
+            cli_args,
+            runs,
+            warmup,
+            timeout,
+            randomize_environment,
+            self_reported_timing: self_reported_timing
+                .map(|timing| (timing.pattern.as_str().to_string(), timing.unit)),
+            pipeline,
+            ram_copy,
+            shell,
+            env,
+        };
+        writeln!(stream, "{}", serde_json::to_string(&request).map_err(io::Error::from)?)?; //send the request as one line
+
+        if let Some(stop_rx) = stop_rx {
+            //forward stop signals down the connection - this thread finishes when the stop sender gets dropped
+            let mut stop_stream = stream.try_clone()?;
+            std::thread::Builder::new()
+                .name("remote_stop_forwarder".into())
+                .spawn(move || {
+                    if stop_rx.recv().is_ok() {
+                        if let Err(e) = writeln!(stop_stream, "{STOP_LINE}") {
+                            warn!(%e, "Unable to send stop to agent");
+                        }
+                    }
+                })
+                .map_err(Error::Spawn)?;
+        }
+
+        let (measurement_sender, measurement_receiver) = channel();
+        let (error_sender, error_receiver) = channel();
+
+        let handle = std::thread::Builder::new()
+            .name("remote_benchmark_receiver".into())
+            .spawn(move || {
+                if let Err(e) = receive(stream, &measurement_sender, &error_sender, timeout_tx.as_ref()) {
+                    error!(%e, "Error receiving from agent");
+                    if error_sender.send(e).is_err() {
+                        warn!("Error receiver closed before error could be sent");
+                    }
+                }
+            })
+            .map_err(Error::Spawn)?;
+
+        Ok((handle, measurement_receiver, error_receiver))
+    }
+}
+
+///Reads messages from the agent until it closes the connection, passing them on to the channels
+fn receive(
+    stream: TcpStream,
+    measurement_sender: &Sender<RunMeasurement>,
+    error_sender: &Sender<RunnerError>,
+    timeout_sender: Option<&Sender<Duration>>,
+) -> Result<(), RunnerError> {
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        match serde_json::from_str(&line).map_err(io::Error::from)? {
+            RemoteMessage::Measurement(measurement) => {
+                if measurement_sender.send(measurement).is_err() {
+                    //if nobody is listening anymore, dropping the stream closes the connection which stops the agent
+                    info!("Measurement receiver closed, stopping remote benching.");
+                    return Ok(());
+                }
+            }
+            RemoteMessage::Error(e) => {
+                if error_sender.send(RunnerError::Remote(e)).is_err() {
+                    warn!("Error receiver closed before error could be sent");
+                }
+            }
+            RemoteMessage::TimedOut(elapsed) => {
+                if let Some(timeout_sender) = timeout_sender {
+                    let _ = timeout_sender.send(elapsed);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
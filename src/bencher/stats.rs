@@ -0,0 +1,193 @@
+//! Module for the statistics worked out from the runs - all of these take microsecond run values, like the ones from each [`super::RunMeasurement`], and give back [`Duration`]s.
+//!
+//! These all get re-exported from [`crate::bencher`]. To choose which ones go in a summary, see [`crate::stats`].
+//!
+//! ## Example
+//! ```rust
+//! use benchmarker::bencher::stats::{calculate_mean_standard_deviation, calculate_median_mad_iqr};
+//! use std::time::Duration;
+//!
+//! let runs = [10, 20, 30];
+//! assert_eq!(
+//!     calculate_mean_standard_deviation(&runs).map(|(mean, _)| mean),
+//!     Some(Duration::from_micros(20))
+//! );
+//! assert_eq!(
+//!     calculate_median_mad_iqr(&runs),
+//!     Some((Duration::from_micros(20), Duration::from_micros(10), Duration::from_micros(10)))
+//! );
+//! ```
+
+use std::time::Duration;
+
+///Calculate the mean and standard deviation from a list of microsecond run values
+#[allow(clippy::cast_precision_loss)]
+#[must_use]
+pub fn calculate_mean_standard_deviation(runs: &[u128]) -> Option<(Duration, Duration)> {
+    if runs.is_empty() {
+        return None;
+    }
+
+    let len = runs.len() as f64;
+    let mut sum = 0;
+    let mut sum_of_squares = 0;
+
+    for item in runs {
+        sum += item;
+        sum_of_squares += item.pow(2);
+    }
+
+    let mean = (sum as f64) / len;
+    let variance = mean.mul_add(-mean, (sum_of_squares as f64) / len); //(sum_of_squares as f64) / len - mean.powi(2); mean of squares - square of mean
+
+    Some((
+        Duration::from_secs_f64(mean / 1_000_000.0),
+        Duration::from_secs_f64(variance.sqrt() / 1_000_000.0),
+    )) //divide by 1_000_000 to account for micros being stored
+}
+
+///Gets the value at a quantile (eg. `0.25` for the first quartile) of some sorted values, interpolating between the two nearest values
+#[allow(
+    clippy::cast_precision_loss,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss
+)]
+pub(crate) fn quantile(sorted: &[f64], quantile: f64) -> f64 {
+    let position = quantile * (sorted.len() - 1) as f64;
+    let (lower, upper) = (position.floor() as usize, position.ceil() as usize);
+    let fraction = position - position.floor();
+    (sorted[upper] - sorted[lower]).mul_add(fraction, sorted[lower])
+}
+
+///Calculate the median, median absolute deviation, and interquartile range from a list of microsecond run values.
+///
+/// Run times are normally skewed with a few big outliers, which drag the standard deviation around - these are much less affected by them
+#[allow(clippy::cast_precision_loss)]
+#[must_use]
+pub fn calculate_median_mad_iqr(runs: &[u128]) -> Option<(Duration, Duration, Duration)> {
+    if runs.is_empty() {
+        return None;
+    }
+
+    let mut sorted: Vec<f64> = runs.iter().map(|run| *run as f64).collect();
+    sorted.sort_by(f64::total_cmp);
+    let median = quantile(&sorted, 0.5);
+    let interquartile_range = quantile(&sorted, 0.75) - quantile(&sorted, 0.25);
+
+    let mut deviations: Vec<f64> = sorted.iter().map(|run| (run - median).abs()).collect();
+    deviations.sort_by(f64::total_cmp);
+    let median_absolute_deviation = quantile(&deviations, 0.5);
+
+    Some((
+        Duration::from_secs_f64(median / 1_000_000.0),
+        Duration::from_secs_f64(median_absolute_deviation / 1_000_000.0),
+        Duration::from_secs_f64(interquartile_range / 1_000_000.0),
+    )) //divide by 1_000_000 to account for micros being stored
+}
+
+///The percentiles which go in the summaries by default, for the slow tail of the runs
+pub const SUMMARY_PERCENTILES: [f64; 3] = [90.0, 95.0, 99.0];
+
+///Calculate percentiles (between `0` and `100`) from a list of microsecond run values, interpolating between the two nearest runs like [`calculate_median_mad_iqr`] does.
+///
+/// The mean hides how slow the slowest runs are - the 99th percentile says that 1 run in 100 was at least that slow
+///
+///```rust
+/// use benchmarker::bencher::stats::{calculate_percentiles, SUMMARY_PERCENTILES};
+/// use std::time::Duration;
+///
+/// let runs: Vec<u128> = (0..=100).collect();
+/// assert_eq!(
+///     calculate_percentiles(&runs, &SUMMARY_PERCENTILES),
+///     Some(vec![Duration::from_micros(90), Duration::from_micros(95), Duration::from_micros(99)])
+/// );
+/// assert_eq!(calculate_percentiles(&[], &[50.0]), None);
+///```
+#[allow(clippy::cast_precision_loss)]
+#[must_use]
+pub fn calculate_percentiles(runs: &[u128], percentiles: &[f64]) -> Option<Vec<Duration>> {
+    if runs.is_empty() {
+        return None;
+    }
+
+    let mut sorted: Vec<f64> = runs.iter().map(|run| *run as f64).collect();
+    sorted.sort_by(f64::total_cmp);
+    Some(
+        percentiles
+            .iter()
+            .map(|percentile| Duration::from_secs_f64(quantile(&sorted, percentile / 100.0) / 1_000_000.0))
+            .collect(),
+    ) //divide by 1_000_000 to account for micros being stored
+}
+
+///How much of each end to trim or winsorize by default - 5% of the fastest runs and 5% of the slowest
+pub const DEFAULT_TRIM_FRACTION: f64 = 0.05;
+
+///How many runs get trimmed or winsorized from each end, for a fraction of the runs between `0` and `0.5`
+#[allow(
+    clippy::cast_precision_loss,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss
+)]
+fn runs_to_trim(len: usize, fraction: f64) -> usize {
+    let count = (len as f64 * fraction.clamp(0.0, 0.5)).floor() as usize;
+    count.min(len.saturating_sub(1) / 2) //always leave at least one run in the middle
+}
+
+///Calculate the trimmed mean from a list of microsecond run values, dropping `fraction` of the fastest runs and `fraction` of the slowest before taking the mean.
+///
+/// Process benchmarks often have a fat tail of slow runs from the rest of the system, which drags the plain mean away from a typical run
+///
+///```rust
+/// use benchmarker::bencher::stats::calculate_trimmed_mean;
+/// use std::time::Duration;
+///
+/// let runs = [10, 10, 11, 12, 11, 11, 10, 12, 11, 1_000];
+/// assert_eq!(calculate_trimmed_mean(&runs, 0.1), Some(Duration::from_micros(11))); //the 1ms run and one of the 10µs runs get dropped
+/// assert_eq!(calculate_trimmed_mean(&[], 0.1), None);
+///```
+#[allow(clippy::cast_precision_loss)]
+#[must_use]
+pub fn calculate_trimmed_mean(runs: &[u128], fraction: f64) -> Option<Duration> {
+    if runs.is_empty() {
+        return None;
+    }
+
+    let mut sorted = runs.to_vec();
+    sorted.sort_unstable();
+    let trim = runs_to_trim(sorted.len(), fraction);
+    let kept = &sorted[trim..sorted.len() - trim];
+    let mean = kept.iter().map(|run| *run as f64).sum::<f64>() / kept.len() as f64;
+
+    Some(Duration::from_secs_f64(mean / 1_000_000.0)) //divide by 1_000_000 to account for micros being stored
+}
+
+///Calculate the winsorized mean and standard deviation from a list of microsecond run values.
+///
+/// Rather than dropping them like [`calculate_trimmed_mean`], `fraction` of the fastest and slowest runs get clamped to the fastest and slowest runs left, so outliers still count but can't pull the statistics far
+///
+///```rust
+/// use benchmarker::bencher::stats::calculate_winsorized_mean_standard_deviation;
+/// use std::time::Duration;
+///
+/// let runs = [8, 10, 10, 10, 1_000];
+/// let (mean, standard_deviation) = calculate_winsorized_mean_standard_deviation(&runs, 0.2).unwrap();
+/// assert_eq!(mean, Duration::from_micros(10)); //the 8µs and 1ms runs both become 10µs
+/// assert_eq!(standard_deviation, Duration::ZERO);
+///```
+#[must_use]
+pub fn calculate_winsorized_mean_standard_deviation(runs: &[u128], fraction: f64) -> Option<(Duration, Duration)> {
+    if runs.is_empty() {
+        return None;
+    }
+
+    let mut sorted = runs.to_vec();
+    sorted.sort_unstable();
+    let trim = runs_to_trim(sorted.len(), fraction);
+    let (low, high) = (sorted[trim], sorted[sorted.len() - 1 - trim]);
+    for run in &mut sorted {
+        *run = (*run).clamp(low, high);
+    }
+
+    calculate_mean_standard_deviation(&sorted)
+}
@@ -0,0 +1,99 @@
+//! Module to expand run-scoped placeholders in CLI args, so each run can get its own outputs without a prepare script.
+//!
+//! The placeholders are:
+//! - `{run_index}` - the index of the run, starting from 0. Warmup runs count separately, also starting from 0
+//! - `{tmpdir}` - a new empty directory just for this run
+//! - `{tmpfile}` - a path inside `{tmpdir}` which doesn't exist yet, for the binary to write to
+//!
+//! The directory only gets made if an arg uses it, and is removed (with everything inside it) as soon as the run finishes.
+//!
+//! ## Example
+//! ```rust
+//! use benchmarker::bencher::template::RunScope;
+//!
+//! let scope = RunScope::new(3, false).unwrap();
+//! assert_eq!(scope.expand("out_{run_index}.txt"), "out_3.txt");
+//! ```
+
+use rand::Rng;
+use std::{
+    fs::{create_dir, remove_dir_all},
+    io,
+    path::PathBuf,
+};
+
+///Replaced with the index of the run
+pub const RUN_INDEX_PLACEHOLDER: &str = "{run_index}";
+///Replaced with a new empty directory for each run
+pub const TMPDIR_PLACEHOLDER: &str = "{tmpdir}";
+///Replaced with a path which doesn't exist yet, inside the directory for the run
+pub const TMPFILE_PLACEHOLDER: &str = "{tmpfile}";
+
+///The name of the file that [`TMPFILE_PLACEHOLDER`] points to inside the run directory
+const TMPFILE_NAME: &str = "tmpfile";
+
+///Whether an arg has any placeholders that need a directory made for the run
+#[must_use]
+pub fn needs_directory(arg: &str) -> bool {
+    arg.contains(TMPDIR_PLACEHOLDER) || arg.contains(TMPFILE_PLACEHOLDER)
+}
+
+///Everything needed to expand the placeholders for one run. The directory (if there is one) gets removed on drop
+#[derive(Debug)]
+pub struct RunScope {
+    ///The index of the run
+    run_index: usize,
+    ///The directory just for this run, if we needed to make one
+    directory: Option<PathBuf>,
+}
+
+impl RunScope {
+    ///Makes the scope for a run, making a new directory in the system temporary directory if `needs_directory` is set
+    ///
+    /// # Errors
+    ///
+    /// Can fail if we fail to make the directory
+    pub fn new(run_index: usize, needs_directory: bool) -> io::Result<Self> {
+        let directory = if needs_directory {
+            let directory = std::env::temp_dir().join(format!(
+                "precipice_{}_{run_index}_{:x}",
+                std::process::id(),
+                rand::thread_rng().gen::<u32>()
+            )); //random suffix so a leftover from a crashed session can't clash
+            create_dir(&directory)?;
+            Some(directory)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            run_index,
+            directory,
+        })
+    }
+
+    ///Replaces all of the placeholders in an arg. If there is no directory, the directory placeholders are left alone
+    #[must_use]
+    pub fn expand(&self, arg: &str) -> String {
+        let mut arg = arg.replace(RUN_INDEX_PLACEHOLDER, &self.run_index.to_string());
+        if let Some(directory) = &self.directory {
+            arg = arg
+                .replace(
+                    TMPFILE_PLACEHOLDER,
+                    &directory.join(TMPFILE_NAME).to_string_lossy(),
+                )
+                .replace(TMPDIR_PLACEHOLDER, &directory.to_string_lossy());
+        }
+        arg
+    }
+}
+
+impl Drop for RunScope {
+    fn drop(&mut self) {
+        if let Some(directory) = &self.directory {
+            if let Err(e) = remove_dir_all(directory) {
+                warn!(%e, ?directory, "Unable to clean up run directory");
+            }
+        }
+    }
+}
@@ -0,0 +1,126 @@
+//! Module to read timings that the benchmarked binary prints itself, rather than timing the whole process.
+//!
+//! For short workloads, starting the process can take far longer than the work itself, so the wall-clock time mostly measures the OS. If the binary prints its own timing (eg. `elapsed_ns=12345`), a [`SelfReportedTiming`] can pull that out of stdout instead.
+//!
+//! ## Example
+//! ```rust
+//! use std::time::Duration;
+//! use benchmarker::bencher::timing::{SelfReportedTiming, TimingUnit};
+//!
+//! let timing = SelfReportedTiming::new(r"elapsed_ns=(\d+)", TimingUnit::Nanoseconds).unwrap();
+//! assert_eq!(timing.parse(b"starting\nelapsed_ns=12345\n"), Some(Duration::from_nanos(12_345)));
+//! ```
+
+use clap::ValueEnum;
+use regex::bytes::Regex;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+///The unit that a binary prints its own timing in
+#[derive(Copy, Clone, Debug, ValueEnum, strum::Display, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimingUnit {
+    ///Nanoseconds
+    #[value(alias = "ns")]
+    Nanoseconds,
+    ///Microseconds
+    #[value(alias = "us")]
+    Microseconds,
+    ///Milliseconds
+    #[value(alias = "ms")]
+    Milliseconds,
+    ///Seconds
+    #[value(alias = "s")]
+    Seconds,
+}
+
+impl TimingUnit {
+    ///Converts a value in this unit to a [`Duration`]. Returns [`None`] if the value is negative, or too big for a [`Duration`]
+    #[must_use]
+    pub fn to_duration(self, value: f64) -> Option<Duration> {
+        let seconds = match self {
+            Self::Nanoseconds => value / 1_000_000_000.0,
+            Self::Microseconds => value / 1_000_000.0,
+            Self::Milliseconds => value / 1_000.0,
+            Self::Seconds => value,
+        };
+        Duration::try_from_secs_f64(seconds).ok()
+    }
+
+    ///How many microseconds there are in one of this unit
+    #[must_use]
+    pub const fn micros(self) -> f64 {
+        match self {
+            Self::Nanoseconds => 0.001,
+            Self::Microseconds => 1.0,
+            Self::Milliseconds => 1_000.0,
+            Self::Seconds => 1_000_000.0,
+        }
+    }
+
+    ///The short name for the unit, like `ms`, for labelling axes
+    #[must_use]
+    pub const fn abbreviation(self) -> &'static str {
+        match self {
+            Self::Nanoseconds => "ns",
+            Self::Microseconds => "µs",
+            Self::Milliseconds => "ms",
+            Self::Seconds => "s",
+        }
+    }
+
+    ///Picks the biggest unit that a typical time of `micros` microseconds is still at least 1 of, so it reads as eg. `1.2 s` rather than `1200000 µs`
+    ///
+    ///```rust
+    /// use benchmarker::bencher::timing::TimingUnit;
+    ///
+    /// let unit = TimingUnit::for_micros(1_200_000.0);
+    /// assert_eq!(unit.abbreviation(), "s");
+    /// assert_eq!(1_200_000.0 / unit.micros(), 1.2);
+    /// assert_eq!(TimingUnit::for_micros(999.0).abbreviation(), "µs");
+    /// assert_eq!(TimingUnit::for_micros(0.5).abbreviation(), "ns");
+    ///```
+    #[must_use]
+    pub fn for_micros(micros: f64) -> Self {
+        [Self::Seconds, Self::Milliseconds, Self::Microseconds]
+            .into_iter()
+            .find(|unit| micros >= unit.micros())
+            .unwrap_or(Self::Nanoseconds)
+    }
+}
+
+///How to find a timing in the stdout of a run
+#[derive(Clone, Debug)]
+pub struct SelfReportedTiming {
+    ///The pattern to look for - the number is the capture group called `time`, or the first capture group, or the whole match
+    pub pattern: Regex,
+    ///The unit the number is in
+    pub unit: TimingUnit,
+}
+
+impl SelfReportedTiming {
+    ///Constructor - compiles the pattern
+    ///
+    /// # Errors
+    ///
+    /// Can fail if the pattern isn't a valid regex
+    pub fn new(pattern: &str, unit: TimingUnit) -> Result<Self, regex::Error> {
+        Ok(Self {
+            pattern: Regex::new(pattern)?,
+            unit,
+        })
+    }
+
+    ///Finds the first timing in the output. Returns [`None`] if nothing matches, or the match isn't a valid number
+    #[must_use]
+    pub fn parse(&self, stdout: &[u8]) -> Option<Duration> {
+        let captures = self.pattern.captures(stdout)?;
+        let time = captures
+            .name("time")
+            .or_else(|| captures.get(1))
+            .or_else(|| captures.get(0))?; //get(0) is always the whole match
+
+        let value: f64 = std::str::from_utf8(time.as_bytes()).ok()?.trim().parse().ok()?;
+        self.unit.to_duration(value)
+    }
+}
@@ -0,0 +1,37 @@
+//! Module for building the binary before a session, so what gets measured is never a stale build.
+//!
+//! A build command (like `make release`) gets split up like a shell would, so arguments can be quoted, and run before any runs start - it isn't timed, and its output goes straight to the terminal. If it fails, the session shouldn't go ahead.
+//!
+//! ## Example
+//! ```rust
+//! use benchmarker::build_command::run_build_command;
+//!
+//! assert!(run_build_command("true").is_ok());
+//! assert!(run_build_command("false").is_err());
+//! assert!(run_build_command("sh -c 'exit 0'").is_ok());
+//! ```
+
+use std::{
+    io::{self, ErrorKind},
+    process::Command,
+};
+
+///Runs a build command (like `make release`) and waits for it to finish
+///
+/// # Errors
+///
+/// Can fail if the command is empty or has unmatched quotes, can't be started, or exits unsuccessfully
+#[instrument]
+pub fn run_build_command(command: &str) -> io::Result<()> {
+    let parts = shell_words::split(command).map_err(|e| io::Error::new(ErrorKind::InvalidInput, format!("unable to split the build command: {e}")))?;
+    let Some((binary, args)) = parts.split_first() else {
+        return Err(io::Error::new(ErrorKind::InvalidInput, "the build command is empty"));
+    };
+
+    let status = Command::new(binary).args(args).status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("{binary} exited unsuccessfully ({status})")))
+    }
+}
@@ -0,0 +1,93 @@
+//! Module for benchmarking binaries straight from a Cargo project, without having to go and find where the build ended up.
+//!
+//! [`build_release`] runs `cargo build --release` for one binary, and reads Cargo's JSON messages to find the executable it made - so it works with workspaces, custom target directories and cross-compiling. [`git_commit`] gets the commit the project is on, so sessions can be tagged with what they were built from.
+//!
+//! ## Example
+//! ```rust
+//! use benchmarker::cargo::artifact_from_message;
+//! use std::path::PathBuf;
+//!
+//! let message = r#"{"reason":"compiler-artifact","target":{"name":"sort","kind":["bin"]},"executable":"/project/target/release/sort"}"#;
+//! assert_eq!(artifact_from_message(message, "sort"), Some(PathBuf::from("/project/target/release/sort")));
+//! assert_eq!(artifact_from_message(message, "search"), None);
+//! assert_eq!(artifact_from_message(r#"{"reason":"build-finished","success":true}"#, "sort"), None);
+//! ```
+
+use serde_json::Value;
+use std::{
+    ffi::OsString,
+    io::{self, BufRead, BufReader, ErrorKind},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+///Gets the Cargo to build with - the one running us if we're a Cargo subcommand, or else whichever `cargo` is on the `PATH`
+fn cargo() -> OsString {
+    std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into())
+}
+
+///Reads one of Cargo's JSON messages, and gives back the executable if it's the artifact for the binary called `bin`
+#[must_use]
+pub fn artifact_from_message(message: &str, bin: &str) -> Option<PathBuf> {
+    let message: Value = serde_json::from_str(message).ok()?;
+    if message["reason"] != "compiler-artifact" || message["target"]["name"] != bin {
+        return None;
+    }
+    if !message["target"]["kind"].as_array()?.iter().any(|kind| kind == "bin") {
+        return None;
+    }
+    message["executable"].as_str().map(PathBuf::from)
+}
+
+///Builds the binary called `bin` in the Cargo project at `project` in release mode, and gives back where the executable is. Cargo's own output goes straight to the terminal
+///
+/// # Errors
+///
+/// Can fail if Cargo can't be started, the build fails, or the build didn't make a binary called `bin`
+#[instrument]
+pub fn build_release(project: &Path, bin: &str) -> io::Result<PathBuf> {
+    let mut child = Command::new(cargo())
+        .args(["build", "--release", "--bin", bin, "--message-format=json-render-diagnostics"])
+        .current_dir(project)
+        .stdout(Stdio::piped()) //the JSON messages, with the diagnostics already rendered to stderr
+        .spawn()?;
+
+    let mut executable = None;
+    if let Some(stdout) = child.stdout.take() {
+        for line in BufReader::new(stdout).lines() {
+            if let Some(artifact) = artifact_from_message(&line?, bin) {
+                executable = Some(artifact);
+            }
+        }
+    }
+
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(io::Error::other(format!("cargo build exited unsuccessfully ({status})")));
+    }
+    executable.ok_or_else(|| io::Error::new(ErrorKind::NotFound, format!("cargo build didn't make a binary called {bin}")))
+}
+
+///Gets the commit that the git repository containing `directory` is on, with `-dirty` on the end if there are uncommitted changes. Gives back [`None`] if it isn't in a git repository, or `git` isn't installed
+#[must_use]
+#[instrument]
+pub fn git_commit(directory: &Path) -> Option<String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "HEAD"])
+        .current_dir(directory)
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let commit = String::from_utf8(output.stdout).ok()?.trim().to_string();
+
+    let clean = Command::new("git")
+        .args(["diff", "--quiet", "HEAD"])
+        .current_dir(directory)
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success());
+    Some(if clean { commit } else { commit + "-dirty" })
+}
@@ -0,0 +1,382 @@
+//! Module to compare two sets of runs - eg. before and after an optimisation - and decide whether there's a real difference between them.
+//!
+//! This uses [Welch's t-test](https://en.wikipedia.org/wiki/Welch%27s_t-test), which doesn't assume both sets have the same variance. It only says whether the difference in the means is likely to be real - with enough runs, even a tiny difference can be significant, so it's worth looking at the size of the change too.
+//!
+//! For that, [`effect_size`] gives [Cohen's d](https://en.wikipedia.org/wiki/Effect_size#Cohen's_d) (how many standard deviations apart the means are) and [Cliff's delta](https://en.wikipedia.org/wiki/Effect_size#Effect_size_for_ordinal_data) (how often a run from one set beats a run from the other, which isn't thrown off by outliers).
+//!
+//! ## Example
+//! ```rust
+//! use benchmarker::compare::{compare, effect_size, Magnitude, Verdict, DEFAULT_SIGNIFICANCE_LEVEL};
+//!
+//! let before = [1_000, 1_010, 990, 1_005, 995];
+//! let after = [800, 810, 790, 805, 795];
+//! let (verdict, _test) = compare(&before, &after, DEFAULT_SIGNIFICANCE_LEVEL).unwrap();
+//! assert!(matches!(verdict, Verdict::Faster(_)));
+//!
+//! let effect = effect_size(&before, &after).unwrap();
+//! assert!(effect.cliffs_delta < 0.0); //negative means the second set is faster
+//! assert_eq!(effect.cliffs_magnitude(), Magnitude::Large);
+//! ```
+
+use std::fmt::{Display, Formatter};
+
+///The default significance level - the chance we're willing to take of saying there's a difference when there isn't one
+pub const DEFAULT_SIGNIFICANCE_LEVEL: f64 = 0.05;
+
+///The results of a Welch's t-test
+#[derive(Copy, Clone, Debug)]
+pub struct WelchTest {
+    ///The t statistic - negative if the second set has a lower mean
+    pub t: f64,
+    ///The (Welch-Satterthwaite) degrees of freedom
+    pub degrees_of_freedom: f64,
+    ///The two-tailed p value - the chance of seeing a difference this big if the means were actually the same
+    pub p_value: f64,
+}
+
+///What a comparison decided
+#[derive(Copy, Clone, Debug)]
+pub enum Verdict {
+    ///The second set is faster, by this fraction of the first mean (eg. `0.1` for 10%)
+    Faster(f64),
+    ///The second set is slower, by this fraction of the first mean
+    Slower(f64),
+    ///There isn't enough evidence of a difference
+    NoDifference,
+}
+
+impl Display for Verdict {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Faster(change) => write!(f, "{:.2}% faster", change * 100.0),
+            Self::Slower(change) => write!(f, "{:.2}% slower", change * 100.0),
+            Self::NoDifference => write!(f, "No significant difference"),
+        }
+    }
+}
+
+///The size of the difference between two sets, independent of how many runs there were
+#[derive(Copy, Clone, Debug)]
+pub struct EffectSize {
+    ///The difference in the means divided by the pooled standard deviation - negative if the second set is faster
+    pub cohens_d: f64,
+    ///How much more likely a run from the second set is to be slower than one from the first than faster, from -1 to 1 - negative if the second set is faster
+    pub cliffs_delta: f64,
+}
+
+///Roughly how big an effect is, using the usual thresholds for each measure
+#[derive(Copy, Clone, Debug, PartialEq, Eq, strum::Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum Magnitude {
+    ///Too small to matter
+    Negligible,
+    ///Small
+    Small,
+    ///Medium
+    Medium,
+    ///Large
+    Large,
+}
+
+impl EffectSize {
+    ///How big Cohen's d is, using Cohen's thresholds of 0.2, 0.5 and 0.8
+    #[must_use]
+    pub fn cohens_magnitude(&self) -> Magnitude {
+        match self.cohens_d.abs() {
+            d if d < 0.2 => Magnitude::Negligible,
+            d if d < 0.5 => Magnitude::Small,
+            d if d < 0.8 => Magnitude::Medium,
+            _ => Magnitude::Large,
+        }
+    }
+
+    ///How big Cliff's delta is, using Romano et al's thresholds of 0.147, 0.33 and 0.474
+    #[must_use]
+    pub fn cliffs_magnitude(&self) -> Magnitude {
+        match self.cliffs_delta.abs() {
+            d if d < 0.147 => Magnitude::Negligible,
+            d if d < 0.33 => Magnitude::Small,
+            d if d < 0.474 => Magnitude::Medium,
+            _ => Magnitude::Large,
+        }
+    }
+}
+
+impl Display for EffectSize {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Cohen's d = {:.3} ({}), Cliff's delta = {:.3} ({})",
+            self.cohens_d,
+            self.cohens_magnitude(),
+            self.cliffs_delta,
+            self.cliffs_magnitude()
+        )
+    }
+}
+
+///Gets the mean and (sample) variance of a list of microsecond run values
+#[allow(clippy::cast_precision_loss)]
+fn mean_variance(runs: &[u128]) -> (f64, f64) {
+    let len = runs.len() as f64;
+    let mean = runs.iter().map(|x| *x as f64).sum::<f64>() / len;
+    let variance = runs
+        .iter()
+        .map(|x| (*x as f64 - mean).powi(2))
+        .sum::<f64>()
+        / (len - 1.0);
+    (mean, variance)
+}
+
+///Runs Welch's t-test on two lists of microsecond run values. Returns [`None`] if either list has fewer than two runs
+#[allow(clippy::cast_precision_loss)]
+#[must_use]
+pub fn welch_t_test(first: &[u128], second: &[u128]) -> Option<WelchTest> {
+    if first.len() < 2 || second.len() < 2 {
+        return None;
+    }
+
+    let (first_mean, first_variance) = mean_variance(first);
+    let (second_mean, second_variance) = mean_variance(second);
+    let first_error = first_variance / first.len() as f64; //squared standard errors
+    let second_error = second_variance / second.len() as f64;
+    let combined_error = first_error + second_error;
+
+    if combined_error == 0.0 {
+        //every run in both was identical, so either they're exactly the same or definitely different
+        let same = (first_mean - second_mean).abs() < f64::EPSILON;
+        return Some(WelchTest {
+            t: if same { 0.0 } else { (second_mean - first_mean).signum() * f64::INFINITY },
+            degrees_of_freedom: (first.len() + second.len() - 2) as f64,
+            p_value: if same { 1.0 } else { 0.0 },
+        });
+    }
+
+    let t = (second_mean - first_mean) / combined_error.sqrt();
+    let degrees_of_freedom = combined_error.powi(2)
+        / (first_error.powi(2) / (first.len() as f64 - 1.0)
+            + second_error.powi(2) / (second.len() as f64 - 1.0));
+    let p_value = regularized_incomplete_beta(
+        degrees_of_freedom / t.mul_add(t, degrees_of_freedom),
+        degrees_of_freedom / 2.0,
+        0.5,
+    ); //the two-tailed p value from the t distribution
+
+    Some(WelchTest {
+        t,
+        degrees_of_freedom,
+        p_value: p_value.clamp(0.0, 1.0),
+    })
+}
+
+///Compares two lists of microsecond run values, deciding whether the second is faster or slower at the given significance level.
+///
+/// Returns [`None`] if either list has fewer than two runs
+#[must_use]
+pub fn compare(first: &[u128], second: &[u128], significance_level: f64) -> Option<(Verdict, WelchTest)> {
+    let test = welch_t_test(first, second)?;
+    let (first_mean, _) = mean_variance(first);
+    let (second_mean, _) = mean_variance(second);
+
+    let verdict = if test.p_value >= significance_level || first_mean == 0.0 {
+        Verdict::NoDifference
+    } else if second_mean < first_mean {
+        Verdict::Faster((first_mean - second_mean) / first_mean)
+    } else {
+        Verdict::Slower((second_mean - first_mean) / first_mean)
+    };
+
+    Some((verdict, test))
+}
+
+///Compares several lists of microsecond run values by their means, eg. from comparing several commands at once.
+///
+/// Gives back which list is the fastest, and how many times slower each one is than it - so `2.0` is twice as slow, and the fastest is `1.0`. Lists without any runs get [`None`], as does everything if none of them have runs.
+///
+///```rust
+/// use benchmarker::compare::speedups;
+///
+/// let (fastest, relative) = speedups(&[&[200, 220], &[100, 110], &[]]).unwrap();
+/// assert_eq!(fastest, 1);
+/// assert_eq!(relative, [Some(2.0), Some(1.0), None]);
+///```
+#[allow(clippy::cast_precision_loss)]
+#[must_use]
+pub fn speedups(runs: &[&[u128]]) -> Option<(usize, Vec<Option<f64>>)> {
+    let means: Vec<Option<f64>> = runs
+        .iter()
+        .map(|runs| (!runs.is_empty()).then(|| mean_variance(runs).0))
+        .collect();
+    let (fastest, fastest_mean) = means
+        .iter()
+        .enumerate()
+        .filter_map(|(i, mean)| Some((i, (*mean)?)))
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))?;
+
+    let relative = means
+        .iter()
+        .enumerate()
+        .map(|(i, mean)| {
+            if i == fastest {
+                Some(1.0)
+            } else {
+                mean.filter(|_| fastest_mean > 0.0).map(|mean| mean / fastest_mean) //can't say how many times slower than instant something is
+            }
+        })
+        .collect();
+    Some((fastest, relative))
+}
+
+///Works out the effect size between two lists of microsecond run values. Returns [`None`] if either list has fewer than two runs
+#[allow(clippy::cast_precision_loss)]
+#[must_use]
+pub fn effect_size(first: &[u128], second: &[u128]) -> Option<EffectSize> {
+    if first.len() < 2 || second.len() < 2 {
+        return None;
+    }
+
+    let (first_mean, first_variance) = mean_variance(first);
+    let (second_mean, second_variance) = mean_variance(second);
+    let (first_len, second_len) = (first.len() as f64, second.len() as f64);
+    let pooled_standard_deviation = ((first_len - 1.0).mul_add(first_variance, (second_len - 1.0) * second_variance)
+        / (first_len + second_len - 2.0))
+        .sqrt();
+    let difference = second_mean - first_mean;
+    let cohens_d = if pooled_standard_deviation > 0.0 {
+        difference / pooled_standard_deviation
+    } else if difference == 0.0 {
+        0.0
+    } else {
+        difference.signum() * f64::INFINITY //every run in both was identical, but they're different
+    };
+
+    //for each run in the second set, count how many in the first are smaller and bigger - sorting makes that a binary search
+    let mut sorted_first = first.to_vec();
+    sorted_first.sort_unstable();
+    let dominance: i128 = second
+        .iter()
+        .map(|run| {
+            let smaller = sorted_first.partition_point(|first_run| first_run < run);
+            let bigger = sorted_first.len() - sorted_first.partition_point(|first_run| first_run <= run);
+            smaller as i128 - bigger as i128
+        })
+        .sum();
+    let cliffs_delta = dominance as f64 / (first_len * second_len);
+
+    Some(EffectSize {
+        cohens_d,
+        cliffs_delta,
+    })
+}
+
+///The natural log of the gamma function, using the Lanczos approximation
+fn ln_gamma(x: f64) -> f64 {
+    ///Coefficients for g = 7, n = 9
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+    ///`ln(sqrt(2 * pi))`
+    const HALF_LN_TWO_PI: f64 = 0.918_938_533_204_672_8;
+
+    if x < 0.5 {
+        //reflection formula, as the approximation is only good for x >= 0.5
+        return (std::f64::consts::PI / (std::f64::consts::PI * x).sin()).ln() - ln_gamma(1.0 - x);
+    }
+
+    let x = x - 1.0;
+    let mut sum = COEFFICIENTS[0];
+    for (i, c) in (1_u32..).zip(COEFFICIENTS.iter().skip(1)) {
+        sum += c / (x + f64::from(i));
+    }
+    let t = x + 7.5;
+    HALF_LN_TWO_PI + (x + 0.5).mul_add(t.ln(), -t) + sum.ln()
+}
+
+///The regularized incomplete beta function `I_x(a, b)`
+fn regularized_incomplete_beta(x: f64, a: f64, b: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    if x >= 1.0 {
+        return 1.0;
+    }
+
+    let front = b
+        .mul_add(
+            (1.0 - x).ln(),
+            a.mul_add(x.ln(), ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b)),
+        )
+        .exp();
+
+    //the continued fraction converges quickly for x < (a + 1) / (a + b + 2) - otherwise use the symmetry I_x(a, b) = 1 - I_{1-x}(b, a)
+    if x < (a + 1.0) / (a + b + 2.0) {
+        front * beta_continued_fraction(x, a, b) / a
+    } else {
+        1.0 - front * beta_continued_fraction(1.0 - x, b, a) / b
+    }
+}
+
+///Evaluates the continued fraction for the incomplete beta function, using the modified Lentz method
+#[allow(clippy::many_single_char_names)] //these match the usual names in the maths
+fn beta_continued_fraction(x: f64, a: f64, b: f64) -> f64 {
+    ///How close to 1 each step has to be before we stop
+    const EPSILON: f64 = 1e-14;
+    ///Stops us dividing by zero
+    const TINY: f64 = 1e-300;
+    ///The most steps to take - it normally converges in far fewer
+    const MAX_ITERATIONS: u32 = 300;
+
+    let mut c = 1.0;
+    let mut d = 1.0 - (a + b) * x / (a + 1.0);
+    if d.abs() < TINY {
+        d = TINY;
+    }
+    d = 1.0 / d;
+    let mut result = d;
+
+    for m in 1..=MAX_ITERATIONS {
+        let m = f64::from(m);
+        let m2 = 2.0 * m;
+
+        //even step
+        let numerator = m * (b - m) * x / ((a + m2 - 1.0) * (a + m2));
+        d = numerator.mul_add(d, 1.0);
+        if d.abs() < TINY {
+            d = TINY;
+        }
+        c = 1.0 + numerator / c;
+        if c.abs() < TINY {
+            c = TINY;
+        }
+        d = 1.0 / d;
+        result *= d * c;
+
+        //odd step
+        let numerator = -(a + m) * (a + b + m) * x / ((a + m2) * (a + m2 + 1.0));
+        d = numerator.mul_add(d, 1.0);
+        if d.abs() < TINY {
+            d = TINY;
+        }
+        c = 1.0 + numerator / c;
+        if c.abs() < TINY {
+            c = TINY;
+        }
+        d = 1.0 / d;
+        let delta = d * c;
+        result *= delta;
+
+        if (delta - 1.0).abs() < EPSILON {
+            break;
+        }
+    }
+
+    result
+}
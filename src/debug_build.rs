@@ -0,0 +1,132 @@
+//! Module to guess whether a binary is an unoptimised debug build, which is almost never what anyone means to benchmark.
+//!
+//! [`check_binary`] looks at where the binary is and what's inside it, and gives back every [`DebugBuildHint`] it finds - if there are any, it's probably a debug build. None of them are certain, so they only ever lead to a warning. Debug sections only count for binaries which weren't built by Rust, as the Rust standard library always brings its own.
+//!
+//! ## Example
+//! ```rust
+//! use benchmarker::debug_build::{check_binary, DebugBuildHint};
+//!
+//! assert_eq!(check_binary("target/debug/does_not_exist"), [DebugBuildHint::DebugDirectory]);
+//! assert_eq!(check_binary("target/x86_64-unknown-linux-gnu/debug/does_not_exist"), [DebugBuildHint::DebugDirectory]);
+//! assert!(check_binary("target/release/does_not_exist").is_empty());
+//! ```
+
+use memchr::memmem;
+use object::{Object, ObjectSection, ObjectSymbol};
+use std::{
+    fmt::{Display, Formatter},
+    fs::read,
+    path::{Component, Path},
+};
+
+///Part of the panic message for overflow checks, which are only on in Rust debug builds by default
+const OVERFLOW_CHECK_MESSAGE: &[u8] = b"attempt to add with overflow";
+
+///Part of the source paths in the panic locations of the Rust standard library, which every Rust binary has
+const RUST_STD_PATH: &[u8] = b"/rustc/";
+
+///Parts of the (mangled) names of tiny generic functions, which optimised builds always inline
+const UNOPTIMISED_SYMBOLS: &[&str] = &["8Iterator3map", "9into_iter", "4core3ops8function6FnOnce9call_once"];
+
+///How many of [`UNOPTIMISED_SYMBOLS`] a binary needs before it looks unoptimised, as the odd one can survive optimisation
+const UNOPTIMISED_SYMBOL_THRESHOLD: usize = 3;
+
+///Something about a binary which suggests it's a debug build
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DebugBuildHint {
+    ///It's in a `debug` folder inside a `target` folder, like Cargo's debug builds
+    DebugDirectory,
+    ///It has Rust's overflow checks, which are only on in debug builds by default
+    OverflowChecks,
+    ///It still has lots of tiny generic functions which optimised builds inline - stores how many
+    UnoptimisedSymbols(usize),
+    ///It has debug sections like `.debug_info`, and wasn't built by Rust
+    DebugSections,
+}
+
+impl Display for DebugBuildHint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::DebugDirectory => write!(f, "it is in a target/debug folder"),
+            Self::OverflowChecks => write!(f, "it has overflow checks"),
+            Self::UnoptimisedSymbols(count) => write!(f, "it has {count} functions which optimised builds would inline"),
+            Self::DebugSections => write!(f, "it has debug sections"),
+        }
+    }
+}
+
+///Checks a binary for anything suggesting it's a debug build. Anything which can't be read or parsed (eg. a binary on the `PATH`, or a script) just gets skipped
+#[must_use]
+#[instrument(skip(binary), fields(binary = %binary.as_ref().display()))]
+pub fn check_binary(binary: impl AsRef<Path>) -> Vec<DebugBuildHint> {
+    let binary = binary.as_ref();
+    let mut hints = vec![];
+
+    let folders: Vec<_> = binary
+        .parent()
+        .map(|parent| {
+            parent
+                .components()
+                .filter_map(|component| match component {
+                    Component::Normal(folder) => folder.to_str(),
+                    _ => None,
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    if folders.last() == Some(&"debug") && folders.contains(&"target") {
+        //target/debug, or target/<triple>/debug when cross-compiling
+        hints.push(DebugBuildHint::DebugDirectory);
+    }
+
+    let Ok(contents) = read(binary) else {
+        return hints;
+    };
+    let Ok(file) = object::File::parse(&*contents) else {
+        return hints; //not something we know how to read
+    };
+    let is_rust = memmem::find(&contents, RUST_STD_PATH).is_some();
+
+    if is_rust && memmem::find(&contents, OVERFLOW_CHECK_MESSAGE).is_some() {
+        hints.push(DebugBuildHint::OverflowChecks);
+    }
+
+    let unoptimised = file
+        .symbols()
+        .filter_map(|symbol| symbol.name().ok())
+        .filter(|name| UNOPTIMISED_SYMBOLS.iter().any(|unoptimised| name.contains(unoptimised)))
+        .count();
+    if unoptimised >= UNOPTIMISED_SYMBOL_THRESHOLD {
+        hints.push(DebugBuildHint::UnoptimisedSymbols(unoptimised));
+    }
+
+    if !is_rust
+        && file
+            .sections()
+            .filter_map(|section| section.name().ok())
+            .any(|name| name == ".debug_info" || name == "__debug_info")
+    {
+        hints.push(DebugBuildHint::DebugSections);
+    }
+
+    debug!(?hints, "Checked for a debug build");
+    hints
+}
+
+///Checks a binary with [`check_binary`], and gives back a warning to show if it looks like a debug build
+#[must_use]
+pub fn debug_build_warning(binary: impl AsRef<Path>) -> Option<String> {
+    let binary = binary.as_ref();
+    let hints = check_binary(binary);
+    if hints.is_empty() {
+        return None;
+    }
+
+    let mut reasons: Vec<String> = hints.iter().map(ToString::to_string).collect();
+    let last = reasons.pop().unwrap_or_default();
+    let reasons = if reasons.is_empty() { last } else { format!("{}, and {last}", reasons.join(", ")) };
+    Some(format!(
+        "{} looks like an unoptimised debug build, as {reasons} - did you mean to benchmark a release build?",
+        binary.display(),
+    ))
+}
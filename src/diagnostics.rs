@@ -0,0 +1,384 @@
+//! Module to check a finished trace for anything that makes the mean and standard deviation misleading, so frontends can warn about it rather than blindly averaging.
+//!
+//! Each check gives back a [`Diagnostic`], which displays as a warning for the user. Use [`diagnose`] to run all of them.
+//!
+//! ## Example
+//! ```rust
+//! use benchmarker::diagnostics::{diagnose, Diagnostic};
+//!
+//! let runs: Vec<u128> = (0..100).map(|i| if i < 50 { 1_000 + i % 7 } else { 2_000 + i % 5 }).collect();
+//! assert!(diagnose(&runs).iter().any(|d| matches!(d, Diagnostic::ChangePoint { index: 50, .. })));
+//!
+//! let drifting: Vec<u128> = (0..100).map(|i| 1_000 + i * 10 + i % 3).collect();
+//! assert!(diagnose(&drifting).iter().any(|d| matches!(d, Diagnostic::Drift { .. })));
+//!
+//! let bimodal: Vec<u128> = (0..100).map(|i| if i % 2 == 0 { 12_000 + i % 9 * 50 } else { 45_000 + i % 7 * 50 }).collect();
+//! assert!(diagnose(&bimodal).iter().any(|d| matches!(d, Diagnostic::Multimodal { modes } if modes.len() == 2)));
+//! ```
+
+use crate::compare::welch_t_test;
+use itertools::Itertools;
+use std::{
+    fmt::{Display, Formatter},
+    time::Duration,
+};
+
+///The fewest runs each side of a change point can have - any fewer and one outlier could look like a regime change
+pub const MIN_SEGMENT_LENGTH: usize = 10;
+
+///How unlikely a split has to be by chance before we call it a change point. This is stricter than a normal significance level, as we try every possible split and only test the best one
+const CHANGE_POINT_SIGNIFICANCE_LEVEL: f64 = 0.001;
+
+///How much the mean has to change by at a change point, as a fraction of the mean before, for it to be worth mentioning
+const MIN_CHANGE_POINT_SHIFT: f64 = 0.05;
+
+///The lag-1 autocorrelation above which runs count as strongly correlated
+const MAX_AUTOCORRELATION: f64 = 0.3;
+
+///How many standard errors the trend has to be away from flat before we believe it - about a 0.1% chance of happening by chance
+const MIN_TREND_T: f64 = 3.3;
+
+///How much the trend line has to move over the session, as a fraction of the mean, for it to be worth mentioning
+const MIN_DRIFT: f64 = 0.05;
+
+///How many points to estimate the density at when looking for modes
+const DENSITY_POINTS: usize = 256;
+
+///How tall a peak in the density has to be, as a fraction of the tallest peak, to count as a mode
+const MIN_MODE_HEIGHT: f64 = 0.1;
+
+///How deep the valley between two peaks has to be to count them as separate modes - the valley has to be below this fraction of the smaller peak
+const MAX_VALLEY_HEIGHT: f64 = 0.75;
+
+///Something about a trace that makes its summary statistics misleading
+#[derive(Clone, Debug)]
+pub enum Diagnostic {
+    ///The runs changed from one level to another part-way through, eg. when the CPU started throttling
+    ChangePoint {
+        ///The index of the first run after the change
+        index: usize,
+        ///The mean of the runs before the change
+        mean_before: Duration,
+        ///The mean of the runs from the change onwards
+        mean_after: Duration,
+    },
+    ///Each run is strongly correlated with the one before, so the runs aren't independent and the standard deviation understates how uncertain the mean is
+    Autocorrelated {
+        ///The lag-1 autocorrelation, from -1 to 1
+        coefficient: f64,
+    },
+    ///The runs steadily got slower or faster over the session, eg. as the machine heated up
+    Drift {
+        ///Where the trend line starts
+        start: Duration,
+        ///Where the trend line ends
+        end: Duration,
+    },
+    ///The runs are clustered around more than one value, eg. from something only sometimes being cached, so there isn't one typical run time
+    Multimodal {
+        ///Roughly where each cluster is, from fastest to slowest
+        modes: Vec<Duration>,
+    },
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ChangePoint {
+                index,
+                mean_before,
+                mean_after,
+            } => write!(
+                f,
+                "The runs changed from {mean_before:.3?} to {mean_after:.3?} at run {index}, so the mean mixes two different populations"
+            ),
+            Self::Autocorrelated { coefficient } => write!(
+                f,
+                "Each run is strongly correlated with the one before (lag-1 autocorrelation of {coefficient:.2}), so the standard deviation understates the uncertainty"
+            ),
+            Self::Drift { start, end } => write!(
+                f,
+                "The runs drifted from {start:.3?} to {end:.3?} over the session, so they aren't independent of when they ran"
+            ),
+            Self::Multimodal { modes } => {
+                let mut modes = modes.iter().map(|mode| format!("~{mode:.3?}")).collect_vec();
+                let last = modes.pop().unwrap_or_default();
+                write!(
+                    f,
+                    "The runs appear multimodal, with modes at {} and {last}, so a single mean is misleading",
+                    modes.join(", ")
+                )
+            }
+        }
+    }
+}
+
+///Runs every check on a list of microsecond run values, in the order they were run
+#[must_use]
+pub fn diagnose(runs: &[u128]) -> Vec<Diagnostic> {
+    [
+        find_change_point(runs),
+        find_autocorrelation(runs),
+        find_drift(runs),
+        find_modes(runs),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+///Finds the single split which best divides the runs into two levels (the one with the smallest total squared error around each side's mean), and gives it back if the two sides are clearly different
+#[allow(clippy::cast_precision_loss)]
+#[must_use]
+pub fn find_change_point(runs: &[u128]) -> Option<Diagnostic> {
+    if runs.len() < MIN_SEGMENT_LENGTH * 2 {
+        return None;
+    }
+
+    //prefix sums, so each split's error can be worked out without going over every run again
+    let mut sums = Vec::with_capacity(runs.len() + 1);
+    let mut sums_of_squares = Vec::with_capacity(runs.len() + 1);
+    let (mut running_total, mut running_squares) = (0.0, 0.0);
+    sums.push(running_total);
+    sums_of_squares.push(running_squares);
+    for run in runs {
+        let run = *run as f64;
+        running_total += run;
+        running_squares = run.mul_add(run, running_squares);
+        sums.push(running_total);
+        sums_of_squares.push(running_squares);
+    }
+
+    let squared_error = |start: usize, end: usize| {
+        let sum = sums[end] - sums[start];
+        let len = (end - start) as f64;
+        sums_of_squares[end] - sums_of_squares[start] - sum * sum / len
+    };
+
+    let (index, _) = (MIN_SEGMENT_LENGTH..=runs.len() - MIN_SEGMENT_LENGTH)
+        .map(|split| (split, squared_error(0, split) + squared_error(split, runs.len())))
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))?;
+
+    let (before, after) = runs.split_at(index);
+    let test = welch_t_test(before, after)?;
+    let mean_before = sums[index] / index as f64;
+    let mean_after = (sums[runs.len()] - sums[index]) / (runs.len() - index) as f64;
+
+    if test.p_value >= CHANGE_POINT_SIGNIFICANCE_LEVEL
+        || mean_before == 0.0
+        || ((mean_after - mean_before) / mean_before).abs() < MIN_CHANGE_POINT_SHIFT
+    {
+        return None;
+    }
+
+    Some(Diagnostic::ChangePoint {
+        index,
+        mean_before: Duration::from_secs_f64(mean_before / 1_000_000.0),
+        mean_after: Duration::from_secs_f64(mean_after / 1_000_000.0),
+    }) //divide by 1_000_000 to account for micros being stored
+}
+
+///Gets the mean of a list of microsecond run values
+#[allow(clippy::cast_precision_loss)]
+fn mean(runs: &[u128]) -> f64 {
+    runs.iter().map(|run| *run as f64).sum::<f64>() / runs.len() as f64
+}
+
+///Gives back the lag-1 autocorrelation if the runs are strongly correlated with the run before
+#[allow(clippy::cast_precision_loss)]
+#[must_use]
+pub fn find_autocorrelation(runs: &[u128]) -> Option<Diagnostic> {
+    if runs.len() < MIN_SEGMENT_LENGTH * 2 {
+        return None;
+    }
+
+    let mean = mean(runs);
+    let deviations: Vec<f64> = runs.iter().map(|run| *run as f64 - mean).collect();
+    let variance: f64 = deviations.iter().map(|d| d * d).sum();
+    if variance == 0.0 {
+        return None; //every run was the same
+    }
+    let covariance: f64 = deviations.windows(2).map(|pair| pair[0] * pair[1]).sum();
+    let coefficient = covariance / variance;
+
+    //with few runs, a big coefficient can happen by chance - 2/sqrt(n) is roughly the 95% bound for independent runs
+    let threshold = MAX_AUTOCORRELATION.max(2.0 / (runs.len() as f64).sqrt());
+    (coefficient.abs() > threshold).then_some(Diagnostic::Autocorrelated { coefficient })
+}
+
+///Fits a straight line to the runs against their index, and gives it back if it clearly isn't flat
+#[allow(clippy::cast_precision_loss)]
+#[must_use]
+pub fn find_drift(runs: &[u128]) -> Option<Diagnostic> {
+    if runs.len() < MIN_SEGMENT_LENGTH * 2 {
+        return None;
+    }
+
+    let len = runs.len() as f64;
+    let mean_index = (len - 1.0) / 2.0;
+    let mean = mean(runs);
+
+    let (mut index_variance, mut covariance) = (0.0, 0.0);
+    for (index, run) in runs.iter().enumerate() {
+        let index_deviation = index as f64 - mean_index;
+        index_variance = index_deviation.mul_add(index_deviation, index_variance);
+        covariance = index_deviation.mul_add(*run as f64 - mean, covariance);
+    }
+    let slope = covariance / index_variance;
+
+    let squared_error: f64 = runs
+        .iter()
+        .enumerate()
+        .map(|(index, run)| {
+            let fitted = slope.mul_add(index as f64 - mean_index, mean);
+            (*run as f64 - fitted).powi(2)
+        })
+        .sum();
+    let standard_error = (squared_error / (len - 2.0) / index_variance).sqrt();
+
+    let start = slope.mul_add(-mean_index, mean);
+    let end = slope.mul_add(mean_index, mean);
+    let clearly_sloped = standard_error == 0.0 || (slope / standard_error).abs() > MIN_TREND_T;
+    if slope == 0.0 || mean == 0.0 || !clearly_sloped || ((end - start) / mean).abs() < MIN_DRIFT {
+        return None;
+    }
+
+    Some(Diagnostic::Drift {
+        start: Duration::from_secs_f64(start.max(0.0) / 1_000_000.0),
+        end: Duration::from_secs_f64(end.max(0.0) / 1_000_000.0),
+    }) //divide by 1_000_000 to account for micros being stored
+}
+
+///Estimates the density of the runs, and gives back the modes if there is more than one clear peak
+#[allow(clippy::cast_precision_loss)]
+#[must_use]
+pub fn find_modes(runs: &[u128]) -> Option<Diagnostic> {
+    if runs.len() < MIN_SEGMENT_LENGTH * 2 {
+        return None;
+    }
+
+    let density = KernelDensity::new(runs)?;
+    let (low, high) = (density.percentile(0.01), density.percentile(0.99)); //ignore the far tails, so a few outliers don't squash everything else
+    if high <= low {
+        return None;
+    }
+    let step = (high - low) / (DENSITY_POINTS - 1) as f64;
+    let density: Vec<f64> = density
+        .curve(low, high, DENSITY_POINTS)
+        .into_iter()
+        .map(|(_, density)| density)
+        .collect();
+
+    let tallest = density.iter().copied().fold(0.0, f64::max);
+    let mut peaks: Vec<usize> = (1..DENSITY_POINTS - 1)
+        .filter(|&i| {
+            density[i] > density[i - 1]
+                && density[i] >= density[i + 1]
+                && density[i] >= tallest * MIN_MODE_HEIGHT
+        })
+        .collect();
+
+    //merge any neighbouring peaks without a deep enough valley between them, keeping the taller one
+    while let Some(shallow) = peaks.windows(2).position(|pair| {
+        let valley = density[pair[0]..=pair[1]].iter().copied().fold(f64::INFINITY, f64::min);
+        valley > density[pair[0]].min(density[pair[1]]) * MAX_VALLEY_HEIGHT
+    }) {
+        let smaller = if density[peaks[shallow]] < density[peaks[shallow + 1]] {
+            shallow
+        } else {
+            shallow + 1
+        };
+        peaks.remove(smaller);
+    }
+
+    (peaks.len() > 1).then(|| Diagnostic::Multimodal {
+        modes: peaks
+            .into_iter()
+            .map(|i| Duration::from_secs_f64((i as f64).mul_add(step, low) / 1_000_000.0))
+            .collect(),
+    }) //divide by 1_000_000 to account for micros being stored
+}
+
+///A smooth estimate of how the runs are distributed, made by putting a gaussian kernel on each run
+#[derive(Clone, Debug)]
+pub struct KernelDensity {
+    ///The runs, sorted
+    sorted: Vec<f64>,
+    ///How wide each kernel is
+    bandwidth: f64,
+}
+
+impl KernelDensity {
+    ///Makes an estimate for a list of microsecond run values, using Silverman's rule of thumb for the bandwidth - it errs on the side of smoothing, so noise doesn't look like extra peaks.
+    ///
+    /// Returns [`None`] if there are fewer than two runs, or they're all the same
+    #[allow(clippy::cast_precision_loss)]
+    #[must_use]
+    pub fn new(runs: &[u128]) -> Option<Self> {
+        if runs.len() < 2 {
+            return None;
+        }
+
+        let mut sorted: Vec<f64> = runs.iter().map(|run| *run as f64).collect();
+        sorted.sort_by(f64::total_cmp);
+        let mut density = Self {
+            sorted,
+            bandwidth: 0.0,
+        };
+
+        let len = density.sorted.len() as f64;
+        let mean = density.sorted.iter().sum::<f64>() / len;
+        let standard_deviation = (density.sorted.iter().map(|run| (run - mean).powi(2)).sum::<f64>() / len).sqrt();
+        let interquartile_range = density.percentile(0.75) - density.percentile(0.25);
+        let spread = if interquartile_range > 0.0 {
+            standard_deviation.min(interquartile_range / 1.34)
+        } else {
+            standard_deviation //if most runs are the same, the IQR can be 0 even though the rest are spread out
+        };
+        density.bandwidth = 0.9 * spread * len.powf(-0.2);
+
+        (density.bandwidth > 0.0).then_some(density)
+    }
+
+    ///Gets the run closest to a percentile, eg. `0.5` for the median
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    #[must_use]
+    pub fn percentile(&self, fraction: f64) -> f64 {
+        self.sorted[((self.sorted.len() - 1) as f64 * fraction.clamp(0.0, 1.0)).round() as usize]
+    }
+
+    ///How wide each kernel is, in microseconds
+    #[must_use]
+    pub const fn bandwidth(&self) -> f64 {
+        self.bandwidth
+    }
+
+    ///Estimates the density at a point - the whole curve integrates to 1, so traces with different numbers of runs can be compared
+    #[allow(clippy::cast_precision_loss)]
+    #[must_use]
+    pub fn estimate(&self, x: f64) -> f64 {
+        let sum: f64 = self
+            .sorted
+            .iter()
+            .map(|run| (-0.5 * ((x - run) / self.bandwidth).powi(2)).exp())
+            .sum();
+        sum / (self.sorted.len() as f64 * self.bandwidth * (2.0 * std::f64::consts::PI).sqrt())
+    }
+
+    ///Estimates the density at `points` evenly spaced points from `low` to `high`, giving back each point with its density
+    #[allow(clippy::cast_precision_loss)]
+    #[must_use]
+    pub fn curve(&self, low: f64, high: f64, points: usize) -> Vec<(f64, f64)> {
+        let step = (high - low) / points.saturating_sub(1).max(1) as f64;
+        (0..points)
+            .map(|i| {
+                let x = (i as f64).mul_add(step, low);
+                (x, self.estimate(x))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,141 @@
+//! Module to thin out huge traces before plotting them, as a plotly export with hundreds of thousands of points is unusably slow in a browser.
+//!
+//! This is only ever for display - the full runs still go into CSV exports and the raw data link in HTML exports. Histograms get a uniform random sample ([`reservoir_sample`]), which keeps the shape of the distribution, and run sequences get [`lttb`] (Largest Triangle Three Buckets), which keeps the spikes and steps that a random sample would miss.
+//!
+//! ## Example
+//! ```rust
+//! use benchmarker::downsample::{lttb, reservoir_sample};
+//!
+//! let runs: Vec<u128> = (0..100_000).map(|i| if i == 5_000 { 1_000_000 } else { 100 + i % 10 }).collect();
+//!
+//! let sampled = reservoir_sample(&runs, 1_000);
+//! assert_eq!(sampled.len(), 1_000);
+//!
+//! let sequence = lttb(&runs, 1_000);
+//! assert_eq!(sequence.len(), 1_000);
+//! assert!(sequence.contains(&(5_000, 1_000_000))); //the spike survives
+//! ```
+
+use rand::{rngs::StdRng, seq::index::sample, Rng, SeedableRng};
+
+///The most points to plot for each trace by default
+pub const DEFAULT_MAX_PLOT_POINTS: usize = 10_000;
+
+///Picks `size` of the runs uniformly at random, keeping them in their original order. If there aren't more than `size` runs, they all get given back.
+///
+///The sample is seeded, so exporting the same trace twice gives the same plot.
+#[must_use]
+pub fn reservoir_sample(runs: &[u128], size: usize) -> Vec<u128> {
+    if runs.len() <= size {
+        return runs.to_vec();
+    }
+
+    let mut indices = sample(&mut StdRng::seed_from_u64(0), runs.len(), size).into_vec();
+    indices.sort_unstable(); //keep them in order, in case anything cares about it
+    indices.into_iter().map(|i| runs[i]).collect()
+}
+
+///A uniform random sample of runs which get pushed one at a time, for when there are too many to keep them all in memory first - like [`reservoir_sample`], but without needing the whole trace.
+///
+///```rust
+/// use benchmarker::downsample::Reservoir;
+///
+/// let mut reservoir = Reservoir::new(100);
+/// for run in 0..1_000_000 {
+///     reservoir.push(run);
+/// }
+/// let sample = reservoir.into_sample();
+/// assert_eq!(sample.len(), 100);
+/// assert!(sample.windows(2).all(|pair| pair[0] < pair[1])); //still in their original order
+///```
+#[derive(Clone, Debug)]
+pub struct Reservoir {
+    ///How many runs to keep
+    size: usize,
+    ///How many runs have been pushed so far
+    seen: usize,
+    ///The runs we're keeping, with their indices so they can be put back in order
+    sample: Vec<(usize, u128)>,
+    ///Seeded like [`reservoir_sample`], so the same file always gives the same sample
+    rng: StdRng,
+}
+
+impl Reservoir {
+    ///Makes an empty reservoir which keeps `size` runs
+    #[must_use]
+    pub fn new(size: usize) -> Self {
+        Self {
+            size,
+            seen: 0,
+            sample: Vec::with_capacity(size.min(DEFAULT_MAX_PLOT_POINTS)),
+            rng: StdRng::seed_from_u64(0),
+        }
+    }
+
+    ///Adds a run, which replaces a random run already in the sample with the right probability once it's full
+    pub fn push(&mut self, run: u128) {
+        if self.sample.len() < self.size {
+            self.sample.push((self.seen, run));
+        } else {
+            let replace = self.rng.gen_range(0..=self.seen);
+            if replace < self.size {
+                self.sample[replace] = (self.seen, run);
+            }
+        }
+        self.seen += 1;
+    }
+
+    ///Gets the sampled runs, in the order they were pushed
+    #[must_use]
+    pub fn into_sample(mut self) -> Vec<u128> {
+        self.sample.sort_unstable_by_key(|(index, _)| *index);
+        self.sample.into_iter().map(|(_, run)| run).collect()
+    }
+}
+
+///Thins a run sequence down to `threshold` points with Largest Triangle Three Buckets, giving back each kept run with its index.
+///
+///The first and last runs are always kept, and from each bucket in between we keep the run which makes the biggest triangle with the last kept run and the average of the next bucket - so outliers and steps stay visible.
+///
+///If there aren't more than `threshold` runs (or `threshold` is less than 3), every run gets given back.
+#[must_use]
+#[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+pub fn lttb(runs: &[u128], threshold: usize) -> Vec<(usize, u128)> {
+    if runs.len() <= threshold || threshold < 3 {
+        return runs.iter().copied().enumerate().collect();
+    }
+
+    let point = |i: usize| (i as f64, runs[i] as f64);
+    let bucket_size = (runs.len() - 2) as f64 / (threshold - 2) as f64; //the first and last runs don't go in a bucket
+    let bucket_start = |bucket: usize| ((bucket as f64 * bucket_size) as usize + 1).min(runs.len() - 1);
+
+    let mut sampled = Vec::with_capacity(threshold);
+    sampled.push((0, runs[0]));
+    let mut last_kept = 0;
+
+    for bucket in 0..threshold - 2 {
+        //the average of the next bucket, or the last run if this is the last bucket
+        let next = bucket_start(bucket + 1)..bucket_start(bucket + 2).max(bucket_start(bucket + 1) + 1);
+        let next_len = next.len() as f64;
+        let (average_x, average_y) = next
+            .map(point)
+            .fold((0.0, 0.0), |(x, y), (px, py)| (x + px / next_len, y + py / next_len));
+
+        let (last_x, last_y) = point(last_kept);
+        let kept = (bucket_start(bucket)..bucket_start(bucket + 1))
+            .max_by(|a, b| {
+                let area = |i: usize| {
+                    let (x, y) = point(i);
+                    (last_x - average_x).mul_add(y - last_y, -((last_x - x) * (average_y - last_y))).abs() //twice the area, but we only compare them
+                };
+                area(*a).total_cmp(&area(*b))
+            })
+            .unwrap_or(last_kept); //buckets always have at least one run, as there are more runs than buckets
+
+        sampled.push((kept, runs[kept]));
+        last_kept = kept;
+    }
+
+    sampled.push((runs.len() - 1, runs[runs.len() - 1]));
+    sampled
+}
@@ -0,0 +1,283 @@
+//! Module to check whether or not the machine is in a fit state to be benchmarking on.
+//!
+//! Things like CPU frequency scaling, running on battery, or having lots of other stuff running can all make results noisy or just plain wrong, so [`check_environment`] looks for those and gives back a list of [`EnvironmentWarning`]s to show to the user before they start. The power state (being on battery, or in power-saver mode) is the one that's easiest to forget about, so [`power_tag`] turns it into a tag value to annotate the results with.
+//!
+//! It also collects a [`SystemInfo`] about what the machine is - the OS, CPU, cores, governor and memory - so results can be shown alongside the hardware they came from.
+//!
+//! Right now the checks only do anything on Linux (where everything is readable from `/sys` and `/proc`) - on other platforms you'll always get an empty list, and only the OS and the number of cores in the [`SystemInfo`].
+//!
+//! ## Example
+//! ```rust
+//! use benchmarker::environment::SystemInfo;
+//!
+//! let info = SystemInfo::collect();
+//! let rows = info.rows();
+//! assert_eq!(rows[0].0, "OS");
+//! assert!(rows.iter().any(|(label, _)| *label == "Cores"));
+//!
+//! use benchmarker::environment::{power_tag, EnvironmentWarning};
+//!
+//! assert_eq!(power_tag(&[EnvironmentWarning::TurboEnabled]), None);
+//! assert_eq!(power_tag(&[EnvironmentWarning::OnBattery]), Some("battery".to_string()));
+//! assert_eq!(
+//!     power_tag(&[EnvironmentWarning::OnBattery, EnvironmentWarning::PowerSaver("low-power".into())]),
+//!     Some("battery+power-saver".to_string())
+//! );
+//! ```
+
+use std::{
+    fmt::{Display, Formatter},
+    thread::available_parallelism,
+};
+
+///Something about the current machine that is likely to skew benchmark results
+#[derive(Clone, Debug, PartialEq)]
+pub enum EnvironmentWarning {
+    ///The CPU frequency governor isn't `performance`, so clock speeds will move around during the session
+    FrequencyScaling(String),
+    ///Turbo/boost is enabled, so clock speeds depend on temperature and how long we've been running
+    TurboEnabled,
+    ///We're running off a battery, which usually means power-saving kicks in
+    OnBattery,
+    ///The machine is in power-saver mode - stores the platform profile, like `low-power`
+    PowerSaver(String),
+    ///Something else is already keeping the CPUs busy - stores the 1 minute load average and the number of CPUs
+    HighLoad(f64, usize),
+    ///There's a debugger attached to us
+    DebuggerAttached,
+}
+
+impl Display for EnvironmentWarning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::FrequencyScaling(governor) => write!(
+                f,
+                "CPU frequency governor is \"{governor}\" rather than \"performance\""
+            ),
+            Self::TurboEnabled => write!(f, "CPU turbo/boost is enabled"),
+            Self::OnBattery => write!(f, "Running on battery power"),
+            Self::PowerSaver(profile) => write!(f, "Power-saver mode is on (platform profile is \"{profile}\")"),
+            Self::HighLoad(load, cpus) => write!(
+                f,
+                "High background load - load average is {load:.2} with {cpus} CPUs"
+            ),
+            Self::DebuggerAttached => write!(f, "A debugger is attached"),
+        }
+    }
+}
+
+impl EnvironmentWarning {
+    ///Whether this is about the power state - being on battery or in power-saver mode, which can make everything far slower
+    #[must_use]
+    pub const fn is_power_state(&self) -> bool {
+        matches!(self, Self::OnBattery | Self::PowerSaver(_))
+    }
+}
+
+///The tag to record the power state of a session under
+pub const POWER_TAG: &str = "power";
+
+///Gets the value for a [`POWER_TAG`] from a list of warnings, like `battery` or `battery+power-saver` - or [`None`] if none of them are about the power state
+#[must_use]
+pub fn power_tag(warnings: &[EnvironmentWarning]) -> Option<String> {
+    let states: Vec<_> = warnings
+        .iter()
+        .filter_map(|warning| match warning {
+            EnvironmentWarning::OnBattery => Some("battery"),
+            EnvironmentWarning::PowerSaver(_) => Some("power-saver"),
+            _ => None,
+        })
+        .collect();
+    (!states.is_empty()).then(|| states.join("+"))
+}
+
+///What the current machine is, for showing results with their hardware context. Anything which can't be found is [`None`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SystemInfo {
+    ///The name of the operating system, like `Ubuntu 22.04.3 LTS`, with the architecture
+    pub os: String,
+    ///The kernel version
+    pub kernel: Option<String>,
+    ///The CPU model, like `AMD Ryzen 7 5800X 8-Core Processor`
+    pub cpu_model: Option<String>,
+    ///The number of logical cores we can use
+    pub cores: Option<usize>,
+    ///The CPU frequency governor, like `performance`
+    pub governor: Option<String>,
+    ///The total memory in bytes
+    pub memory_bytes: Option<u64>,
+}
+
+impl SystemInfo {
+    ///Collects the details of the current machine
+    #[must_use]
+    #[instrument]
+    pub fn collect() -> Self {
+        let mut info = platform::system_info();
+        info.cores = available_parallelism().ok().map(std::num::NonZeroUsize::get);
+        info
+    }
+
+    ///Gets labelled rows to show, skipping anything which couldn't be found
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn rows(&self) -> Vec<(&'static str, String)> {
+        [
+            ("OS", Some(self.os.clone())),
+            ("Kernel", self.kernel.clone()),
+            ("CPU", self.cpu_model.clone()),
+            ("Cores", self.cores.map(|cores| cores.to_string())),
+            ("Governor", self.governor.clone()),
+            ("Memory", self.memory_bytes.map(|bytes| format!("{:.1} GiB", bytes as f64 / (1024.0 * 1024.0 * 1024.0)))),
+        ]
+        .into_iter()
+        .filter_map(|(label, value)| Some((label, value?)))
+        .collect()
+    }
+}
+
+///Checks the current machine for anything which could ruin a benchmark.
+///
+/// Any checks which can't be done (eg. missing files) are just skipped, rather than reported.
+#[must_use]
+#[instrument]
+pub fn check_environment() -> Vec<EnvironmentWarning> {
+    let warnings = platform::check();
+    for warning in &warnings {
+        warn!(%warning, "Environment warning");
+    }
+    warnings
+}
+
+#[cfg(target_os = "linux")]
+///Linux checks, using `/sys` and `/proc`
+mod platform {
+    use super::{EnvironmentWarning, SystemInfo};
+    use std::{fs::read_to_string, path::Path, thread::available_parallelism};
+
+    ///Fraction of the CPUs which can be busy before we count the load as too high
+    const HIGH_LOAD_FRACTION: f64 = 0.5;
+
+    ///Reads a file to a trimmed [`String`], or [`None`] if we can't
+    fn read_trimmed(path: impl AsRef<Path>) -> Option<String> {
+        read_to_string(path).ok().map(|s| s.trim().to_string())
+    }
+
+    ///Runs all of the Linux checks
+    pub fn check() -> Vec<EnvironmentWarning> {
+        let mut warnings = vec![];
+
+        if let Some(governor) =
+            read_trimmed("/sys/devices/system/cpu/cpu0/cpufreq/scaling_governor")
+        {
+            //we only check the first CPU, as they're almost always all set the same way
+            if governor != "performance" {
+                warnings.push(EnvironmentWarning::FrequencyScaling(governor));
+            }
+        }
+
+        let intel_turbo = read_trimmed("/sys/devices/system/cpu/intel_pstate/no_turbo")
+            .map(|no_turbo| no_turbo == "0"); //intel stores whether or not turbo is *disabled*
+        let other_boost =
+            read_trimmed("/sys/devices/system/cpu/cpufreq/boost").map(|boost| boost == "1"); //everyone else stores whether it is enabled
+        if intel_turbo.or(other_boost).unwrap_or(false) {
+            warnings.push(EnvironmentWarning::TurboEnabled);
+        }
+
+        if let Ok(supplies) = std::fs::read_dir("/sys/class/power_supply") {
+            let on_battery = supplies.filter_map(Result::ok).any(|supply| {
+                let path = supply.path();
+                read_trimmed(path.join("type")).as_deref() == Some("Battery")
+                    && read_trimmed(path.join("status")).as_deref() == Some("Discharging")
+            }); //if any batteries are discharging, then we aren't plugged in
+            if on_battery {
+                warnings.push(EnvironmentWarning::OnBattery);
+            }
+        }
+
+        if let Some(profile) = read_trimmed("/sys/firmware/acpi/platform_profile") {
+            //power-profiles-daemon's power-saver mode uses low-power, or quiet if there isn't one
+            if profile == "low-power" || profile == "quiet" {
+                warnings.push(EnvironmentWarning::PowerSaver(profile));
+            }
+        }
+
+        if let Some(load) = read_trimmed("/proc/loadavg")
+            .and_then(|s| s.split_whitespace().next().and_then(|l| l.parse::<f64>().ok()))
+        {
+            let cpus = available_parallelism().map_or(1, std::num::NonZeroUsize::get);
+            #[allow(clippy::cast_precision_loss)]
+            if load > cpus as f64 * HIGH_LOAD_FRACTION {
+                warnings.push(EnvironmentWarning::HighLoad(load, cpus));
+            }
+        }
+
+        if let Some(status) = read_trimmed("/proc/self/status") {
+            let tracer = status
+                .lines()
+                .find_map(|line| line.strip_prefix("TracerPid:"))
+                .map(str::trim); //TracerPid is the PID of whatever is tracing us, or 0 if nothing is
+            if tracer.is_some_and(|pid| pid != "0") {
+                warnings.push(EnvironmentWarning::DebuggerAttached);
+            }
+        }
+
+        warnings
+    }
+
+    ///Reads what the machine is from `/etc`, `/proc` and `/sys`
+    pub fn system_info() -> SystemInfo {
+        let distribution = read_trimmed("/etc/os-release").and_then(|os_release| {
+            os_release
+                .lines()
+                .find_map(|line| line.strip_prefix("PRETTY_NAME="))
+                .map(|name| name.trim_matches('"').to_string())
+        });
+        let cpu_model = read_trimmed("/proc/cpuinfo").and_then(|cpuinfo| {
+            cpuinfo
+                .lines()
+                .find(|line| line.starts_with("model name"))
+                .and_then(|line| line.split_once(':'))
+                .map(|(_, model)| model.trim().to_string())
+        });
+        let memory_bytes = read_trimmed("/proc/meminfo").and_then(|meminfo| {
+            meminfo
+                .lines()
+                .find_map(|line| line.strip_prefix("MemTotal:"))
+                .and_then(|total| total.trim().trim_end_matches("kB").trim().parse::<u64>().ok())
+                .map(|kibibytes| kibibytes * 1024)
+        });
+
+        SystemInfo {
+            os: format!(
+                "{} ({})",
+                distribution.unwrap_or_else(|| std::env::consts::OS.to_string()),
+                std::env::consts::ARCH
+            ),
+            kernel: read_trimmed("/proc/sys/kernel/osrelease"),
+            cpu_model,
+            cores: None, //filled in by SystemInfo::collect, as it works everywhere
+            governor: read_trimmed("/sys/devices/system/cpu/cpu0/cpufreq/scaling_governor"),
+            memory_bytes,
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+///Fallback for platforms where we don't have any checks yet
+mod platform {
+    use super::{EnvironmentWarning, SystemInfo};
+
+    ///We don't know how to check anything here, so we don't give any warnings
+    pub const fn check() -> Vec<EnvironmentWarning> {
+        vec![]
+    }
+
+    ///We only know the OS and architecture here
+    pub fn system_info() -> SystemInfo {
+        SystemInfo {
+            os: format!("{} ({})", std::env::consts::OS, std::env::consts::ARCH),
+            ..SystemInfo::default()
+        }
+    }
+}
@@ -0,0 +1,56 @@
+//! Module for the errors from starting runs, reading traces and writing exports - so callers (like the GUIs) can tell what went wrong and show it, rather than just getting an [`io::Error`].
+//!
+//! [`Error`] still turns back into an [`io::Error`] (keeping its [`io::ErrorKind`]), so code which only deals in [`io::Result`]s can keep using `?` on it.
+//!
+//! ## Example
+//! ```rust
+//! use benchmarker::{error::Error, io::import_csv};
+//! use std::io;
+//!
+//! let Err(e) = import_csv("/definitely/not/a/file.csv") else {
+//!     unreachable!();
+//! };
+//! assert!(matches!(e, Error::Io(_)));
+//! assert_eq!(io::Error::from(e).kind(), io::ErrorKind::NotFound);
+//!
+//! let csv = Error::Csv("unable to parse `abc` as a run".into());
+//! assert_eq!(csv.to_string(), "invalid CSV: unable to parse `abc` as a run");
+//! ```
+
+use crate::bencher::RunnerError;
+use std::{io, path::PathBuf};
+
+///Something which went wrong starting a run, reading traces, or writing an export
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    ///We couldn't start a thread to do the work on, like the [`crate::bencher::Runner`]'s
+    #[error("unable to start a thread: {0}")]
+    Spawn(#[source] io::Error),
+    ///Running the binary went wrong, eg. it exited with a non-zero exit code
+    #[error("{0}")]
+    Runner(#[from] RunnerError),
+    ///A CSV file of traces couldn't be parsed
+    #[error("invalid CSV: {0}")]
+    Csv(String),
+    ///An export couldn't be written to its file - including when it got cancelled, with [`io::ErrorKind::Interrupted`]
+    #[error("unable to write {}: {source}", file.display())]
+    Export {
+        ///The file we were writing to
+        file: PathBuf,
+        ///Why we couldn't write it
+        source: io::Error,
+    },
+    ///Anything else which went wrong reading or writing, eg. a file of traces not existing
+    #[error("{0}")]
+    Io(#[from] io::Error),
+}
+
+impl From<Error> for io::Error {
+    fn from(value: Error) -> Self {
+        match value {
+            Error::Spawn(e) | Error::Export { source: e, .. } | Error::Io(e) | Error::Runner(RunnerError::Io(e)) => e,
+            Error::Runner(e) => Self::other(e),
+            Error::Csv(e) => Self::new(io::ErrorKind::InvalidData, e),
+        }
+    }
+}
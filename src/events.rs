@@ -0,0 +1,289 @@
+//! Module for publishing a session's progress as it happens, so editor plugins or dashboards can follow a running benchmark without scraping the terminal.
+//!
+//! Each [`SessionEvent`] is written as one line of JSON, tagged with its `event` - `started` once the runner has started, `run_completed` after every measured run, and `finished` once the runs are done. An [`EventPublisher`] either writes them to a named pipe which already exists (eg. one made with `mkfifo`), or makes a Unix socket which any number of clients can connect to. Clients which connect part-way through still get the `started` event first.
+//!
+//! Publishing never stops or slows down the benchmark - clients which can't keep up get disconnected, and events get dropped if a pipe is full or nothing is reading it any more.
+//!
+//! ## Example
+//! ```rust
+//! use benchmarker::events::SessionEvent;
+//!
+//! let event = SessionEvent::RunCompleted {
+//!     index: 3,
+//!     duration_micros: 1_500,
+//!     exit_code: Some(0),
+//! };
+//! assert_eq!(
+//!     event.to_json_line().unwrap(),
+//!     r#"{"event":"run_completed","index":3,"duration_micros":1500,"exit_code":0}"#
+//! );
+//! ```
+
+use crate::bencher::RunMeasurement;
+use serde::Serialize;
+use std::{io, path::Path};
+
+///Something that happened in a session
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum SessionEvent {
+    ///The runner has started
+    Started {
+        ///The name of the session, which is also its trace name
+        name: String,
+        ///The command being benchmarked
+        command: String,
+        ///How many runs are going to be measured
+        runs: usize,
+        ///The session's ID, like in its provenance
+        session_id: String,
+    },
+    ///A measured run has finished
+    RunCompleted {
+        ///Which run this was, starting from 0
+        index: usize,
+        ///How long it took in microseconds
+        duration_micros: u128,
+        ///The exit code - [`None`] if it got killed by a signal
+        exit_code: Option<i32>,
+    },
+    ///All of the runs are done
+    Finished {
+        ///How many runs got measured
+        runs: usize,
+        ///The mean in microseconds, if there were any runs
+        mean_micros: Option<f64>,
+        ///The standard deviation in microseconds, if there were any runs
+        standard_deviation_micros: Option<f64>,
+        ///Whether the session got stopped early
+        stopped: bool,
+    },
+}
+
+impl SessionEvent {
+    ///Makes the event for a measured run
+    #[must_use]
+    pub const fn run_completed(measurement: &RunMeasurement) -> Self {
+        Self::RunCompleted {
+            index: measurement.index,
+            duration_micros: measurement.wall.as_micros(),
+            exit_code: measurement.exit_status,
+        }
+    }
+
+    ///Formats the event as one line of JSON, without a newline
+    ///
+    /// # Errors
+    ///
+    /// Can't really fail, but `serde_json` gives back a [`Result`]
+    pub fn to_json_line(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+}
+
+///Publishes [`SessionEvent`]s to a named pipe or a Unix socket - see [`crate::events`]
+#[derive(Debug)]
+pub struct EventPublisher {
+    ///Where the events go
+    sink: platform::Sink,
+}
+
+impl EventPublisher {
+    ///Starts publishing to `path`. If it is a named pipe, something has to already have it open for reading - otherwise, a Unix socket gets made there (replacing any old socket), and removed again when the publisher gets dropped
+    ///
+    /// # Errors
+    ///
+    /// Can fail if nothing is reading the pipe, we can't make the socket, or we aren't on Unix
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self {
+            sink: platform::Sink::open(path.as_ref())?,
+        })
+    }
+
+    ///Publishes an event to everything that is listening
+    pub fn publish(&mut self, event: &SessionEvent) {
+        match event.to_json_line() {
+            Ok(line) => self.sink.send(&(line + "\n"), matches!(event, SessionEvent::Started { .. })),
+            Err(e) => warn!(%e, "Unable to format event as JSON"),
+        }
+    }
+}
+
+#[cfg(unix)]
+mod platform {
+    //! Pipes and sockets, for Unix
+
+    use std::{
+        fs::{remove_file, File, OpenOptions},
+        io::{self, ErrorKind, Write},
+        os::unix::{
+            fs::{FileTypeExt, OpenOptionsExt},
+            net::{UnixListener, UnixStream},
+        },
+        path::{Path, PathBuf},
+        sync::{
+            mpsc::{channel, RecvTimeoutError, Sender},
+            Arc, Mutex,
+        },
+        thread::JoinHandle,
+        time::Duration,
+    };
+
+    ///How often to check for new clients on the socket
+    const ACCEPT_INTERVAL: Duration = Duration::from_millis(50);
+
+    ///How long a client gets to take an event before it gets disconnected
+    const CLIENT_WRITE_TIMEOUT: Duration = Duration::from_millis(100);
+
+    ///The clients connected to the socket, along with the `started` line to send to new ones
+    #[derive(Debug, Default)]
+    pub struct Clients {
+        ///The connected clients
+        streams: Vec<UnixStream>,
+        ///The `started` event, if it has been sent
+        started: Option<String>,
+    }
+
+    ///Somewhere to send events
+    #[derive(Debug)]
+    pub enum Sink {
+        ///A named pipe, until nothing is reading it any more
+        Pipe(Option<File>),
+        ///A Unix socket which clients can connect to
+        Socket {
+            ///Where the socket is, to remove it afterwards
+            path: PathBuf,
+            ///The connected clients
+            clients: Arc<Mutex<Clients>>,
+            ///Dropping or sending on this stops accepting new clients
+            stop_tx: Sender<()>,
+            ///The thread accepting new clients
+            handle: Option<JoinHandle<()>>,
+        },
+    }
+
+    impl Sink {
+        ///Opens the pipe at `path`, or makes a socket there if it isn't a pipe
+        pub fn open(path: &Path) -> io::Result<Self> {
+            match path.metadata() {
+                Ok(metadata) if metadata.file_type().is_fifo() => {
+                    //non-blocking, so a full pipe drops events rather than stalling the benchmark
+                    let pipe = OpenOptions::new()
+                        .write(true)
+                        .custom_flags(libc::O_NONBLOCK)
+                        .open(path)
+                        .map_err(|e| {
+                            if e.raw_os_error() == Some(libc::ENXIO) {
+                                io::Error::new(ErrorKind::NotConnected, format!("nothing is reading from the pipe at {}", path.display()))
+                            } else {
+                                e
+                            }
+                        })?;
+                    return Ok(Self::Pipe(Some(pipe)));
+                }
+                Ok(metadata) if metadata.file_type().is_socket() => remove_file(path)?, //left over from an old session
+                Ok(_) => {
+                    return Err(io::Error::new(
+                        ErrorKind::AlreadyExists,
+                        format!("{} already exists, and isn't a pipe or a socket", path.display()),
+                    ))
+                }
+                Err(_) => {}
+            }
+
+            let listener = UnixListener::bind(path)?;
+            listener.set_nonblocking(true)?;
+            let clients = Arc::new(Mutex::new(Clients::default()));
+            let (stop_tx, stop_rx) = channel();
+            let handle = {
+                let clients = clients.clone();
+                std::thread::Builder::new().name("event_publisher".into()).spawn(move || {
+                    while matches!(stop_rx.recv_timeout(ACCEPT_INTERVAL), Err(RecvTimeoutError::Timeout)) {
+                        while let Ok((mut stream, _)) = listener.accept() {
+                            if stream.set_nonblocking(false).and_then(|()| stream.set_write_timeout(Some(CLIENT_WRITE_TIMEOUT))).is_err() {
+                                continue;
+                            }
+                            let Ok(mut clients) = clients.lock() else {
+                                return;
+                            };
+                            if let Some(started) = &clients.started {
+                                if stream.write_all(started.as_bytes()).is_err() {
+                                    continue; //already gone
+                                }
+                            }
+                            clients.streams.push(stream);
+                        }
+                    }
+                })?
+            };
+
+            Ok(Self::Socket {
+                path: path.to_path_buf(),
+                clients,
+                stop_tx,
+                handle: Some(handle),
+            })
+        }
+
+        ///Sends a line to everything that is listening, remembering it for new clients if it is the `started` event
+        pub fn send(&mut self, line: &str, is_started: bool) {
+            match self {
+                Self::Pipe(pipe) => {
+                    if let Some(file) = pipe {
+                        match file.write_all(line.as_bytes()) {
+                            Ok(()) => {}
+                            Err(e) if e.kind() == ErrorKind::WouldBlock => warn!("Event pipe is full, so dropping an event"),
+                            Err(e) => {
+                                warn!(%e, "Event pipe closed, so not publishing any more events");
+                                *pipe = None;
+                            }
+                        }
+                    }
+                }
+                Self::Socket { clients, .. } => {
+                    if let Ok(mut clients) = clients.lock() {
+                        clients.streams.retain_mut(|stream| stream.write_all(line.as_bytes()).is_ok()); //disconnect anything which has gone or can't keep up
+                        if is_started {
+                            clients.started = Some(line.to_string());
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    impl Drop for Sink {
+        fn drop(&mut self) {
+            if let Self::Socket { path, stop_tx, handle, .. } = self {
+                let _ = stop_tx.send(());
+                if let Some(handle) = handle.take() {
+                    let _ = handle.join();
+                }
+                let _ = remove_file(path);
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod platform {
+    //! Placeholder for platforms without Unix sockets
+
+    use std::{io, path::Path};
+
+    ///Nowhere to send events
+    #[derive(Debug)]
+    pub enum Sink {}
+
+    impl Sink {
+        ///Always fails, as this needs Unix sockets or named pipes
+        pub fn open(_path: &Path) -> io::Result<Self> {
+            Err(io::Error::new(io::ErrorKind::Unsupported, "publishing events needs Unix sockets or named pipes"))
+        }
+
+        ///Can't be called, as there's never a [`Sink`]
+        pub fn send(&mut self, _line: &str, _is_started: bool) {
+            match *self {}
+        }
+    }
+}
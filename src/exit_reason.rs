@@ -0,0 +1,80 @@
+//! Module for the exit codes of the CLI programs, so scripts (eg. in CI) can tell *why* precipice failed rather than just that it did.
+//!
+//! Every [`ExitReason`] has its own code, which stays the same between versions. [`ExitReason::InvalidArguments`] is `2`, the same as clap uses when it can't parse the arguments, and [`ExitReason::Interrupted`] is `130`, the same as shells use for Ctrl-C. Anything which doesn't fit one of the other reasons is [`ExitReason::Error`], which is `1`.
+//!
+//! [`ExitReason`] is also an [`std::error::Error`], so it can go into an error's chain of causes (eg. with `eyre`'s `wrap_err`), and get found again with `downcast_ref` when it's time to exit.
+//!
+//! ## Example
+//! ```rust
+//! use benchmarker::exit_reason::ExitReason;
+//! use std::process::ExitCode;
+//!
+//! assert_eq!(ExitReason::Success.code(), 0);
+//! assert_eq!(ExitReason::BenchmarkFailed.code(), 3);
+//! assert_eq!(ExitReason::Interrupted.code(), 130);
+//! assert_eq!(ExitCode::from(ExitReason::ExportFailed), ExitCode::from(5));
+//! assert_eq!(ExitReason::AssertionFailed.to_string(), "an assertion failed");
+//! ```
+
+use std::{
+    error::Error,
+    fmt::{Display, Formatter},
+    process::ExitCode,
+};
+
+///Why one of the CLI programs exited, with a distinct exit code for each reason
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ExitReason {
+    ///Everything worked
+    Success,
+    ///Something went wrong which doesn't fit any of the other reasons, like being unable to get the benchmark lock
+    Error,
+    ///The arguments didn't make sense, either to clap or once we'd looked at them
+    InvalidArguments,
+    ///The binary being benchmarked failed - a run exited unsuccessfully or timed out, or the runner stopped early
+    BenchmarkFailed,
+    ///Something we were asked to check wasn't true, like `--abort-on-environment-warnings` finding warnings
+    AssertionFailed,
+    ///The results couldn't be exported
+    ExportFailed,
+    ///The user stopped the session with Ctrl-C
+    Interrupted,
+}
+
+impl ExitReason {
+    ///Gets the process exit code for this reason
+    #[must_use]
+    pub const fn code(self) -> u8 {
+        match self {
+            Self::Success => 0,
+            Self::Error => 1,
+            Self::InvalidArguments => 2, //the same as clap
+            Self::BenchmarkFailed => 3,
+            Self::AssertionFailed => 4,
+            Self::ExportFailed => 5,
+            Self::Interrupted => 130, //the same as shells, for SIGINT
+        }
+    }
+}
+
+impl Display for ExitReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Success => write!(f, "success"),
+            Self::Error => write!(f, "error"),
+            Self::InvalidArguments => write!(f, "invalid arguments"),
+            Self::BenchmarkFailed => write!(f, "the benchmark failed"),
+            Self::AssertionFailed => write!(f, "an assertion failed"),
+            Self::ExportFailed => write!(f, "the export failed"),
+            Self::Interrupted => write!(f, "interrupted by the user"),
+        }
+    }
+}
+
+impl Error for ExitReason {}
+
+impl From<ExitReason> for ExitCode {
+    fn from(reason: ExitReason) -> Self {
+        Self::from(reason.code())
+    }
+}
@@ -0,0 +1,117 @@
+//! Module for exporting sessions with external commands, so results can go to reporting systems Precipice doesn't know about without changing [`crate::io`].
+//!
+//! Each command gets run once the session is done, with a [`ResultDocument`] as JSON on its stdin - every run, the summary statistics, and the tags. Commands get split on spaces like `--pipe-into`, and their output goes straight to the terminal. The document has a [`ResultDocument::version`], which only changes if fields get removed or change meaning.
+//!
+//! ## Example
+//! ```rust
+//! use benchmarker::{export_command::ResultDocument, tags::Tags};
+//!
+//! let document = ResultDocument::new("sort".into(), "./sort --fast".into(), vec![90, 100, 110], Tags::new());
+//! assert_eq!(document.mean_micros, Some(100.0));
+//! assert_eq!((document.min_micros, document.max_micros), (Some(90), Some(110)));
+//!
+//! let json: serde_json::Value = serde_json::from_str(&document.to_json().unwrap()).unwrap();
+//! assert_eq!(json["runs"][2], 110);
+//! assert_eq!(json["command"], "./sort --fast");
+//! ```
+
+use crate::{
+    bencher::{calculate_mean_standard_deviation, calculate_median_mad_iqr},
+    tags::Tags,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    io::{self, ErrorKind, Write},
+    process::{Command, Stdio},
+};
+
+///The version of [`ResultDocument`] we write
+pub const RESULT_DOCUMENT_VERSION: u32 = 1;
+
+///Everything about a finished session, as it gets sent to export commands
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ResultDocument {
+    ///The version of the document, which is [`RESULT_DOCUMENT_VERSION`] for this version of Precipice
+    pub version: u32,
+    ///The name of the session, which is also its trace name
+    pub name: String,
+    ///A human-readable version of the command that got benchmarked
+    pub command: String,
+    ///When the session finished
+    pub finished_at: DateTime<Utc>,
+    ///The tags on the session, including where it came from
+    pub tags: Tags,
+    ///Every measured run in microseconds, in the order they ran
+    pub runs: Vec<u128>,
+    ///The mean run time in microseconds, if there were any runs
+    pub mean_micros: Option<f64>,
+    ///The population standard deviation in microseconds, if there were any runs
+    pub standard_deviation_micros: Option<f64>,
+    ///The median run time in microseconds, if there were any runs
+    pub median_micros: Option<f64>,
+    ///The fastest run in microseconds, if there were any runs
+    pub min_micros: Option<u128>,
+    ///The slowest run in microseconds, if there were any runs
+    pub max_micros: Option<u128>,
+}
+
+impl ResultDocument {
+    ///Makes the document for a session, timestamped now
+    #[must_use]
+    pub fn new(name: String, command: String, runs: Vec<u128>, tags: Tags) -> Self {
+        let mean_standard_deviation = calculate_mean_standard_deviation(&runs);
+        let median = calculate_median_mad_iqr(&runs).map(|(median, _, _)| median);
+
+        Self {
+            version: RESULT_DOCUMENT_VERSION,
+            name,
+            command,
+            finished_at: Utc::now(),
+            tags,
+            mean_micros: mean_standard_deviation.map(|(mean, _)| mean.as_secs_f64() * 1_000_000.0),
+            standard_deviation_micros: mean_standard_deviation.map(|(_, standard_deviation)| standard_deviation.as_secs_f64() * 1_000_000.0),
+            median_micros: median.map(|median| median.as_secs_f64() * 1_000_000.0),
+            min_micros: runs.iter().min().copied(),
+            max_micros: runs.iter().max().copied(),
+            runs,
+        }
+    }
+
+    ///Formats the document as JSON
+    ///
+    /// # Errors
+    ///
+    /// Can't really fail, but `serde_json` gives back a [`Result`]
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+}
+
+///Runs an export command (like `./my_exporter.sh --verbose`), sending it the document on its stdin and waiting for it to finish
+///
+/// # Errors
+///
+/// Can fail if the command is empty, can't be started, or exits unsuccessfully
+#[instrument(skip(document))]
+pub fn run_export_command(command: &str, document: &ResultDocument) -> io::Result<()> {
+    let mut parts = command.split(' ').filter(|part| !part.is_empty()); //split the same way as the CLI args
+    let Some(binary) = parts.next() else {
+        return Err(io::Error::new(ErrorKind::InvalidInput, "the export command is empty"));
+    };
+
+    let mut child = Command::new(binary).args(parts).stdin(Stdio::piped()).spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        match stdin.write_all(document.to_json()?.as_bytes()) {
+            Err(e) if e.kind() == ErrorKind::BrokenPipe => {} //the command doesn't have to read all of it
+            result => result?,
+        }
+    } //dropping stdin closes it, so the command knows the document is finished
+
+    let status = child.wait()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("{binary} exited unsuccessfully ({status})")))
+    }
+}
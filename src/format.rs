@@ -0,0 +1,113 @@
+//! Module for writing times out the same way everywhere - the CLI summaries, the GUI labels, and the reports and matrices.
+//!
+//! A [`DurationFormat`] either picks a unit for each time like `1.234ms` ([`TimeFormat::Human`]), or always writes a plain number of microseconds for scripts to read ([`TimeFormat::Raw`]). Both have a fixed number of decimal places, and can have their thousands grouped like `12,345.000s` - raw times only want grouping when they're for people to read.
+//!
+//! ## Example
+//! ```rust
+//! use benchmarker::format::{DurationFormat, TimeFormat};
+//! use std::time::Duration;
+//!
+//! let time = Duration::from_micros(1_234_567);
+//! assert_eq!(DurationFormat::default().duration(time), "1.235s");
+//! assert_eq!(DurationFormat::default().with_decimals(1).duration(Duration::from_micros(1_500)), "1.5ms");
+//! assert_eq!(DurationFormat::new(TimeFormat::Raw).duration(time), "1234567.000");
+//!
+//! let grouped = DurationFormat::default().with_thousands_separator(Some(','));
+//! assert_eq!(grouped.duration(Duration::from_secs(12_345)), "12,345.000s");
+//! assert_eq!(grouped.with_decimals(0).duration(Duration::from_micros(999)), "999µs");
+//! assert_eq!(DurationFormat::new(TimeFormat::Raw).with_thousands_separator(Some('_')).duration(time), "1_234_567.000");
+//! ```
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+///How many decimal places times get by default - the same as the summaries have always had
+pub const DEFAULT_DECIMALS: usize = 3;
+
+///Whether times get written for people or for scripts
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum, strum::Display, Serialize, Deserialize)]
+pub enum TimeFormat {
+    ///With whichever unit fits, like `1.234ms`
+    #[default]
+    Human,
+    ///As a plain number of microseconds without a unit, like `1234.000`
+    Raw,
+}
+
+///How to write times
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DurationFormat {
+    ///Whether times get written for people or for scripts
+    pub time_format: TimeFormat,
+    ///How many decimal places to write
+    pub decimals: usize,
+    ///What to group the thousands with, if anything
+    pub thousands_separator: Option<char>,
+}
+
+impl Default for DurationFormat {
+    fn default() -> Self {
+        Self::new(TimeFormat::Human)
+    }
+}
+
+impl DurationFormat {
+    ///Makes a format with [`DEFAULT_DECIMALS`] decimal places and no thousands separator
+    #[must_use]
+    pub const fn new(time_format: TimeFormat) -> Self {
+        Self {
+            time_format,
+            decimals: DEFAULT_DECIMALS,
+            thousands_separator: None,
+        }
+    }
+
+    ///Sets how many decimal places to write
+    #[must_use]
+    pub const fn with_decimals(mut self, decimals: usize) -> Self {
+        self.decimals = decimals;
+        self
+    }
+
+    ///Sets what to group the thousands with
+    #[must_use]
+    pub const fn with_thousands_separator(mut self, thousands_separator: Option<char>) -> Self {
+        self.thousands_separator = thousands_separator;
+        self
+    }
+
+    ///Writes a time
+    #[must_use]
+    pub fn duration(&self, duration: Duration) -> String {
+        let formatted = match self.time_format {
+            TimeFormat::Human => format!("{duration:.*?}", self.decimals),
+            TimeFormat::Raw => format!("{:.*}", self.decimals, duration.as_secs_f64() * 1_000_000.0),
+        };
+        match self.thousands_separator {
+            Some(separator) => group_thousands(&formatted, separator),
+            None => formatted,
+        }
+    }
+
+    ///Writes a time in microseconds, like the runs get stored
+    #[must_use]
+    pub fn micros(&self, micros: u128) -> String {
+        self.duration(Duration::from_micros(u64::try_from(micros).unwrap_or(u64::MAX)))
+    }
+}
+
+///Puts a separator between every three digits of the whole number at the start of `formatted`, leaving the decimals and unit alone
+fn group_thousands(formatted: &str, separator: char) -> String {
+    let digits = formatted.chars().take_while(char::is_ascii_digit).count();
+    let (whole, rest) = formatted.split_at(digits); //digits are all one byte, so this is a char boundary
+
+    let mut grouped = String::with_capacity(formatted.len() + digits / 3);
+    for (i, digit) in whole.chars().enumerate() {
+        if i > 0 && (digits - i) % 3 == 0 {
+            grouped.push(separator);
+        }
+        grouped.push(digit);
+    }
+    grouped + rest
+}
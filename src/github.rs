@@ -0,0 +1,104 @@
+//! Module to report sessions to GitHub Actions, so precipice can be dropped into a CI workflow as a benchmarking step.
+//!
+//! [`write_github_summary`] appends a Markdown table to the job summary (the file at `$GITHUB_STEP_SUMMARY`), and sets step outputs in `$GITHUB_OUTPUT` so later steps can use the numbers - eg. `steps.bench.outputs.mean_us`, or `steps.bench.outputs.delta_percent` to fail the job on a regression.
+//!
+//! The baseline is whichever session came before in the history for the same command, so a runner which keeps its history between jobs (eg. with `actions/cache`) gets a change against the last run.
+//!
+//! ## Example
+//! ```rust
+//! use benchmarker::{github::markdown_summary, history::HistoryEntry};
+//! use std::path::Path;
+//!
+//! let baseline = HistoryEntry::new(Path::new("sort"), &[], &[100, 100]).unwrap();
+//! let entry = HistoryEntry::new(Path::new("sort"), &[], &[110, 110]).unwrap();
+//! let summary = markdown_summary("sort", &entry, Some(&baseline));
+//! assert!(summary.contains("+10.00% (slower)"));
+//! ```
+
+use crate::history::HistoryEntry;
+use std::{
+    env,
+    fmt::Write as _,
+    fs::OpenOptions,
+    io::{self, ErrorKind, Write},
+    time::Duration,
+};
+
+///The change in the mean from the baseline to the entry, as a percentage of the baseline - positive means slower
+#[must_use]
+pub fn delta_percent(entry: &HistoryEntry, baseline: &HistoryEntry) -> Option<f64> {
+    (baseline.mean_micros > 0.0).then(|| (entry.mean_micros - baseline.mean_micros) / baseline.mean_micros * 100.0)
+}
+
+///Formats a number of microseconds like the rest of the output
+fn micros(value: f64) -> String {
+    format!("{:.3?}", Duration::from_secs_f64(value.max(0.0) / 1_000_000.0))
+}
+
+///Makes the Markdown for a session - a heading, and a table with the stats and the change from the baseline if there is one
+#[must_use]
+#[allow(clippy::cast_precision_loss)]
+pub fn markdown_summary(name: &str, entry: &HistoryEntry, baseline: Option<&HistoryEntry>) -> String {
+    let mut markdown = String::new();
+    let change = baseline
+        .and_then(|baseline| delta_percent(entry, baseline))
+        .map_or_else(
+            || "no baseline".to_string(),
+            |delta| format!("{delta:+.2}% ({})", if delta > 0.0 { "slower" } else { "faster" }),
+        );
+
+    //writing to a String can't fail
+    let _ = writeln!(markdown, "### Benchmark: {name}\n");
+    let _ = writeln!(markdown, "| Command | Runs | Mean ± Standard Deviation | Min … Max | Change |");
+    let _ = writeln!(markdown, "|---|---|---|---|---|");
+    let _ = writeln!(
+        markdown,
+        "| `{}` | {} | {} ± {} | {} … {} | {change} |\n",
+        entry.command.replace('|', "\\|").replace('`', "'"), //so the command can't break out of the table or code span
+        entry.runs,
+        micros(entry.mean_micros),
+        micros(entry.standard_deviation_micros),
+        micros(entry.min_micros as f64),
+        micros(entry.max_micros as f64)
+    );
+
+    markdown
+}
+
+///Appends to a file that GitHub Actions gave us, from an environment variable
+fn append_to_env_file(variable: &str, contents: &str) -> io::Result<()> {
+    let path = env::var_os(variable).ok_or_else(|| {
+        io::Error::new(
+            ErrorKind::NotFound,
+            format!("{variable} isn't set - is this running in GitHub Actions?"),
+        )
+    })?;
+
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(contents.as_bytes())
+}
+
+///Appends the Markdown summary to `$GITHUB_STEP_SUMMARY`, and sets the `mean_us`, `standard_deviation_us`, `runs`, and (if there is a baseline) `baseline_mean_us` and `delta_percent` outputs in `$GITHUB_OUTPUT`
+///
+/// # Errors
+///
+/// Can fail if either environment variable isn't set, or we fail to write to the files
+#[instrument(skip(entry, baseline))]
+pub fn write_github_summary(name: &str, entry: &HistoryEntry, baseline: Option<&HistoryEntry>) -> io::Result<()> {
+    append_to_env_file("GITHUB_STEP_SUMMARY", &markdown_summary(name, entry, baseline))?;
+
+    let mut outputs = String::new();
+    let _ = writeln!(outputs, "mean_us={:.3}", entry.mean_micros);
+    let _ = writeln!(outputs, "standard_deviation_us={:.3}", entry.standard_deviation_micros);
+    let _ = writeln!(outputs, "runs={}", entry.runs);
+    if let Some(baseline) = baseline {
+        let _ = writeln!(outputs, "baseline_mean_us={:.3}", baseline.mean_micros);
+        if let Some(delta) = delta_percent(entry, baseline) {
+            let _ = writeln!(outputs, "delta_percent={delta:.3}");
+        }
+    }
+    append_to_env_file("GITHUB_OUTPUT", &outputs)
+}
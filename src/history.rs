@@ -0,0 +1,237 @@
+//! Module to keep a per-user history of benchmark sessions, to be able to catch slow regressions over weeks.
+//!
+//! Every session gets summarised into a [`HistoryEntry`], and appended as one line of JSON to [`history_file`]. Entries are keyed by a hash of the binary and its arguments (see [`command_hash`]), so you can pull out every session of the same command with [`read_history`], and plot the mean over time with [`export_history_html`]. Sessions can also be tagged (see [`crate::tags`]), to compare eg. the sessions from different machines with [`group_by_tag`].
+
+use crate::{
+    bencher::{calculate_mean_standard_deviation, timing::TimingUnit},
+    error::Error,
+    io::write_plot,
+    tags::Tags,
+};
+use chrono::{DateTime, Utc};
+use directories_next::ProjectDirs;
+use plotly::{common::{ErrorData, ErrorType, Mode, Title}, layout::Axis, Layout, Plot, Scatter};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::Display,
+    fs::{create_dir_all, File, OpenOptions},
+    io::{self, BufRead, BufReader, ErrorKind, Write},
+    path::{Path, PathBuf},
+};
+
+///The summary of one benchmark session, as stored in the history file
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    ///The hash of the binary and arguments from [`command_hash`]
+    pub command_hash: u64,
+    ///A human-readable version of the command, for display
+    pub command: String,
+    ///When the session finished
+    pub time: DateTime<Utc>,
+    ///The number of runs in the session
+    pub runs: usize,
+    ///The mean run time in microseconds
+    pub mean_micros: f64,
+    ///The population standard deviation of the run times in microseconds
+    pub standard_deviation_micros: f64,
+    ///The fastest run in microseconds
+    pub min_micros: u128,
+    ///The slowest run in microseconds
+    pub max_micros: u128,
+    ///The tags on the session, like `machine=ryzen-box`. Older entries don't have any
+    #[serde(default, skip_serializing_if = "Tags::is_empty")]
+    pub tags: Tags,
+}
+
+impl HistoryEntry {
+    ///Summarises a session into a new entry, timestamped now. Gives back [`None`] if there weren't any runs.
+    #[must_use]
+    pub fn new(binary: &Path, cli_args: &[String], runs: &[u128]) -> Option<Self> {
+        let (mean, standard_deviation) = calculate_mean_standard_deviation(runs)?;
+        let command = std::iter::once(binary.display().to_string())
+            .chain(cli_args.iter().cloned())
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        Some(Self {
+            command_hash: command_hash(binary, cli_args),
+            command,
+            time: Utc::now(),
+            runs: runs.len(),
+            mean_micros: mean.as_secs_f64() * 1_000_000.0,
+            standard_deviation_micros: standard_deviation.as_secs_f64() * 1_000_000.0,
+            min_micros: runs.iter().min().copied().unwrap_or_default(),
+            max_micros: runs.iter().max().copied().unwrap_or_default(),
+            tags: Tags::new(),
+        })
+    }
+
+    ///Sets the tags on the session
+    #[must_use]
+    pub fn with_tags(mut self, tags: Tags) -> Self {
+        self.tags = tags;
+        self
+    }
+}
+
+///Hashes a binary and its arguments, to find sessions of the same command.
+///
+/// This uses FNV-1a rather than the [`std::hash::Hash`] machinery, as that isn't guaranteed to be stable between Rust versions, and the history file needs to outlive any one version of precipice. The binary path is canonicalised if possible, so that `./bin` and `/path/to/bin` count as the same command.
+#[must_use]
+pub fn command_hash(binary: &Path, cli_args: &[String]) -> u64 {
+    ///FNV-1a 64-bit offset basis
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    ///FNV-1a 64-bit prime
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    let binary = binary
+        .canonicalize()
+        .unwrap_or_else(|_| binary.to_path_buf());
+
+    std::iter::once(binary.to_string_lossy().into_owned())
+        .chain(cli_args.iter().cloned())
+        .flat_map(|part| part.into_bytes().into_iter().chain(std::iter::once(0))) //separate each part with a nul, so ["ab", "c"] and ["a", "bc"] hash differently
+        .fold(OFFSET_BASIS, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+        })
+}
+
+///Gets the location of the per-user history file, if we can find a data directory for this user
+#[must_use]
+pub fn history_file() -> Option<PathBuf> {
+    ProjectDirs::from("", "", "precipice").map(|dirs| dirs.data_dir().join("history.jsonl"))
+}
+
+///Appends an entry to the history file, creating it if needed
+///
+/// # Errors
+///
+/// Can fail if we can't find a data directory, or fail to create/write to the file
+#[instrument]
+pub fn append_to_history(entry: &HistoryEntry) -> io::Result<()> {
+    let file = history_file().ok_or_else(|| {
+        io::Error::new(ErrorKind::NotFound, "unable to find a data directory for the history file")
+    })?;
+    if let Some(parent) = file.parent() {
+        create_dir_all(parent)?;
+    }
+
+    let mut line = serde_json::to_string(entry)?;
+    line.push('\n');
+
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(file)?
+        .write_all(line.as_bytes())
+}
+
+///Reads every entry for a given [`command_hash`] from the history file, oldest first. Lines which can't be parsed are skipped.
+///
+/// # Errors
+///
+/// Can fail if we can't find a data directory, or fail to read the file. If the file doesn't exist yet, you just get an empty list.
+#[instrument]
+pub fn read_history(command_hash: u64) -> io::Result<Vec<HistoryEntry>> {
+    let file = history_file().ok_or_else(|| {
+        io::Error::new(ErrorKind::NotFound, "unable to find a data directory for the history file")
+    })?;
+    let file = match File::open(file) {
+        Ok(f) => f,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]), //no sessions yet
+        Err(e) => return Err(e),
+    };
+
+    let mut entries = vec![];
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<HistoryEntry>(&line) {
+            Ok(entry) if entry.command_hash == command_hash => entries.push(entry),
+            Ok(_) => {}
+            Err(e) => warn!(?e, "Skipping invalid history line"),
+        }
+    }
+
+    entries.sort_by_key(|entry| entry.time);
+    Ok(entries)
+}
+
+///Gets the most recent session of a command from the history, to compare a new session against. Gives back [`None`] if there isn't one, or the history can't be read
+#[must_use]
+pub fn last_session(command_hash: u64) -> Option<HistoryEntry> {
+    read_history(command_hash).ok()?.pop()
+}
+
+///Splits history entries up by the value of the tag `key`, in the order each value first turns up. Entries without that tag go in a group called `untagged`
+#[must_use]
+pub fn group_by_tag(entries: Vec<HistoryEntry>, key: &str) -> Vec<(String, Vec<HistoryEntry>)> {
+    let mut groups: Vec<(String, Vec<HistoryEntry>)> = vec![];
+    for entry in entries {
+        let name = entry
+            .tags
+            .get(key)
+            .map_or_else(|| "untagged".to_string(), |value| format!("{key}={value}"));
+        match groups.iter_mut().find(|(group, _)| group == &name) {
+            Some((_, group)) => group.push(entry),
+            None => groups.push((name, vec![entry])),
+        }
+    }
+    groups
+}
+
+///Exports a set of history entries to a plotly line chart of the mean (with standard deviation error bars) over time
+///
+/// # Errors
+///
+/// Can have errors if we fail to create a file or write to it
+pub fn export_history_html(
+    entries: &[HistoryEntry],
+    file_name_input: impl AsRef<Path> + Display,
+) -> Result<usize, Error> {
+    export_grouped_history_html(&[("Mean".to_string(), entries.to_vec())], file_name_input)
+}
+
+///Exports groups of history entries (eg. from [`group_by_tag`]) to a plotly line chart with one line of the mean (with standard deviation error bars) over time for each group
+///
+/// # Errors
+///
+/// Can have errors if we fail to create a file or write to it
+pub fn export_grouped_history_html(
+    groups: &[(String, Vec<HistoryEntry>)],
+    file_name_input: impl AsRef<Path> + Display,
+) -> Result<usize, Error> {
+    let title = groups
+        .iter()
+        .find_map(|(_, entries)| entries.first())
+        .map_or_else(|| "History".to_string(), |e| format!("History of {}", e.command));
+
+    let mut means: Vec<f64> = groups.iter().flat_map(|(_, entries)| entries.iter().map(|e| e.mean_micros)).collect();
+    means.sort_by(f64::total_cmp);
+    let unit = means.get(means.len() / 2).map_or(TimingUnit::Microseconds, |median| TimingUnit::for_micros(*median)); //so a slow command reads in seconds rather than millions of microseconds
+
+    let mut plot = Plot::new();
+    for (name, entries) in groups {
+        let trace = Scatter::new(
+            entries.iter().map(|e| e.time.to_rfc3339()).collect(),
+            entries.iter().map(|e| e.mean_micros / unit.micros()).collect(),
+        )
+        .mode(Mode::LinesMarkers)
+        .name(name)
+        .error_y(
+            ErrorData::new(ErrorType::Data)
+                .array(entries.iter().map(|e| e.standard_deviation_micros / unit.micros()).collect()),
+        );
+        plot.add_trace(trace);
+    }
+    plot.set_layout(
+        Layout::new()
+            .title(Title::new(&title))
+            .x_axis(Axis::new().title(Title::new("Session")))
+            .y_axis(Axis::new().title(Title::new(&format!("Mean ({})", unit.abbreviation())))),
+    );
+
+    write_plot(&plot, file_name_input)
+}
@@ -1,51 +1,957 @@
 //! Module to deal with imports and exports
 
+pub mod summary;
+
 use std::{
-    fmt::Display,
-    fs::{read_to_string, File},
-    io::{self, Write},
-    path::Path,
+    collections::{HashMap, HashSet, VecDeque},
+    fmt::{Display, Write as _},
+    fs::{read_dir, read_to_string, File},
+    io::{self, BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::mpsc::{Receiver, Sender, TryRecvError},
+};
+use crate::{
+    bencher::{calculate_mean_standard_deviation, calculate_median_mad_iqr, timing::TimingUnit},
+    diagnostics::KernelDensity,
+    error::Error,
+    downsample::{lttb, reservoir_sample, DEFAULT_MAX_PLOT_POINTS},
+    parameters::{parameter_tags, parameters, series_are_numeric, sweep_series, ParameterSeries},
+    report::escape_html,
+    tags::{parse_tag, TaggedTrace, Tags, TraceTags},
 };
+use chrono::{SecondsFormat, Utc};
 use clap::ValueEnum;
-use plotly::{Histogram, Plot};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use plotly::{
+    color::Rgba,
+    common::{ErrorData, ErrorType, Line, Marker, Mode, Title},
+    layout::{Axis, AxisType},
+    Histogram, Layout, Plot, Scatter,
+};
+
+///The start of a line in a CSV file which holds the tags for a trace, like `#tags,trace name,key=value,key=value`
+pub const CSV_TAGS_PREFIX: &str = "#tags";
+
+///The start of a line in a CSV file which holds metadata about the whole file rather than one trace, like `#meta,units=us,exported_at=2024-01-01T00:00:00Z` - see [`csv_metadata`]
+pub const CSV_METADATA_PREFIX: &str = "#meta";
+
+///The metadata key for which version of the CSV format a file uses - see [`CSV_FORMAT_VERSION`]
+pub const FORMAT_VERSION_METADATA_KEY: &str = "format_version";
+
+///The version of the CSV format that exports write, which gets bumped whenever the layout changes.
+///
+/// - Files without a version are either version 1 (one trace on each line, like `title,time1,time2`, maybe with [`CSV_TAGS_PREFIX`] lines), or the legacy column layout (a header line with a name for each trace, then one run from each trace on every line) which gets migrated on import.
+/// - Version 2 added [`CSV_METADATA_PREFIX`] lines.
+///
+/// Files from a newer version fail to import, rather than risk being read wrong.
+///
+///```rust
+/// use benchmarker::io::import_csv_iter;
+/// use std::io::Cursor;
+///
+/// let legacy = "before,after\n10,12\n11,oops\n,14\n";
+/// let mut iter = import_csv_iter(Cursor::new(legacy));
+/// let traces: Vec<_> = iter.by_ref().map(|trace| trace.unwrap()).collect();
+/// assert_eq!((traces[0].0.as_str(), &traces[0].1), ("before", &vec![10, 11]));
+/// assert_eq!((traces[1].0.as_str(), &traces[1].1), ("after", &vec![12, 14])); //only the broken cell gets skipped
+/// assert_eq!((iter.problems()[0].line, iter.problems()[0].column), (3, 2));
+///
+/// let newer = "#meta,format_version=99\nsort,10\n";
+/// assert!(import_csv_iter(Cursor::new(newer)).next().unwrap().is_err());
+///```
+pub const CSV_FORMAT_VERSION: u32 = 2;
+
+///The metadata key for the unit of the runs in a CSV file - one of `ns`, `us`, `ms` or `s`. Files without it are in microseconds
+pub const UNITS_METADATA_KEY: &str = "units";
+
+///The metadata key for when a CSV file was exported
+pub const EXPORTED_AT_METADATA_KEY: &str = "exported_at";
+
+///The metadata key for the version of precipice which exported a CSV file
+pub const VERSION_METADATA_KEY: &str = "precipice_version";
+
+///How many bytes an export writes at once, between reporting its progress and checking whether it has been cancelled
+pub const EXPORT_CHUNK_SIZE: usize = 64 * 1024;
+
+///Lets an export report how far it has got, and be cancelled part-way through - see [`export_csv_with_progress`] and [`export_html_with_progress`]
+#[derive(Debug)]
+pub struct ExportProgress {
+    ///Gets sent `(bytes written, total bytes)` after each chunk gets written. If nobody is listening, the export carries on anyway
+    pub progress_tx: Sender<(usize, usize)>,
+    ///If we receive anything on this (or it gets disconnected), the export stops and the half-written file gets removed
+    pub cancel_rx: Receiver<()>,
+}
+
+impl ExportProgress {
+    ///Whether the export has been cancelled
+    fn is_cancelled(&self) -> bool {
+        !matches!(self.cancel_rx.try_recv(), Err(TryRecvError::Empty))
+    }
+}
+
+///Writes an export to a file, a chunk at a time if we need to report progress. If it gets cancelled, the file gets removed
+///
+/// # Errors
+///
+/// Can have an [`Error::Export`] if we fail to create the file or write to it, or with [`io::ErrorKind::Interrupted`] if it got cancelled
+pub(crate) fn write_export(file_name: &str, contents: &[u8], progress: Option<&ExportProgress>) -> Result<usize, Error> {
+    let export_error = |source| Error::Export {
+        file: PathBuf::from(file_name),
+        source,
+    };
+
+    let mut file = File::create(file_name).map_err(export_error)?; //make a file
+    let Some(progress) = progress else {
+        file.write_all(contents).map_err(export_error)?; //if nobody is watching, write them all at once
+        return Ok(contents.len());
+    };
+
+    let mut written = 0;
+    let _ = progress.progress_tx.send((written, contents.len()));
+    for chunk in contents.chunks(EXPORT_CHUNK_SIZE) {
+        if progress.is_cancelled() {
+            drop(file);
+            std::fs::remove_file(file_name).map_err(export_error)?; //don't leave half a file lying around
+            return Err(export_error(io::Error::new(io::ErrorKind::Interrupted, "export cancelled")));
+        }
+
+        file.write_all(chunk).map_err(export_error)?;
+        written += chunk.len();
+        let _ = progress.progress_tx.send((written, contents.len()));
+    }
+
+    Ok(written)
+}
 
 ///Imports a set of traces from a CSV file
 ///
 /// # Errors
 ///
-/// Can fail if we fail to read the file using [`read_to_string`]
-pub fn import_csv(file: impl AsRef<Path>) -> io::Result<Vec<(String, Vec<u128>)>> {
-    let lines = read_to_string(file)?; //read in the csv file
-    if lines.trim().is_empty() {
-        //if it is empty (need to trim in case of extra newlines etc), just return an empty list
-        return Ok(vec![]);
+/// Can fail with an [`Error::Io`] if we fail to open or read the file, or an [`Error::Csv`] if it isn't valid
+pub fn import_csv(file: impl AsRef<Path>) -> Result<Vec<(String, Vec<u128>)>, Error> {
+    import_csv_with_tags(file).map(|(traces, _)| traces)
+}
+
+///Imports a set of traces from a CSV file, along with any tags stored for them on [`CSV_TAGS_PREFIX`] lines. Any other lines starting with `#` are comments, and get skipped
+///
+/// # Errors
+///
+/// Can fail with an [`Error::Io`] if we fail to open or read the file, or an [`Error::Csv`] if it isn't valid
+#[allow(clippy::type_complexity)] //the same traces as import_csv, plus the tags
+pub fn import_csv_with_tags(file: impl AsRef<Path>) -> Result<(Vec<(String, Vec<u128>)>, TraceTags), Error> {
+    import_csv_with_tags_from_reader(BufReader::new(File::open(file)?))
+}
+
+///Imports a set of traces from CSV in any reader, eg. stdin, along with their tags - see [`import_csv_with_tags`]
+///
+/// # Errors
+///
+/// Can fail with an [`Error::Io`] if we fail to read from the reader, or an [`Error::Csv`] if it isn't valid
+#[allow(clippy::type_complexity)] //the same as import_csv_with_tags
+pub fn import_csv_with_tags_from_reader(reader: impl BufRead) -> Result<(Vec<(String, Vec<u128>)>, TraceTags), Error> {
+    let mut traces = import_csv_iter(reader);
+    let trace_contents = traces
+        .by_ref()
+        .map(|trace| trace.map(|(name, runs, _)| (name, runs)))
+        .collect::<io::Result<_>>()
+        .map_err(|e| {
+            if e.kind() == io::ErrorKind::InvalidData {
+                Error::Csv(e.to_string()) //the iterator only gives back io::Errors, so anything it couldn't parse is InvalidData
+            } else {
+                Error::Io(e)
+            }
+        })?;
+
+    Ok((trace_contents, traces.into_tags())) //tags can come after their trace, so use every one in the file
+}
+
+///Lazily reads traces from a CSV file one line at a time, rather than reading the whole file in first - see [`import_csv_iter`]
+#[derive(Debug)]
+pub struct CsvTraces<R> {
+    ///Where the CSV comes from
+    reader: R,
+    ///The current line, kept between lines to avoid reallocating it
+    line: String,
+    ///Every tag we've read so far, by trace name
+    tags: TraceTags,
+    ///The metadata we've read so far, from [`CSV_METADATA_PREFIX`] lines
+    metadata: Tags,
+    ///What to multiply the runs by to get microseconds, if the file says they're in another unit
+    micros_per_unit: Option<f64>,
+    ///The [`FORMAT_VERSION_METADATA_KEY`] from the file, if it has one
+    format_version: Option<u32>,
+    ///Whether we've read the first line with runs on it yet
+    started: bool,
+    ///Traces we've already read, waiting to be given out - only used for the first two lines of unversioned files, and files we had to read all at once like the legacy column layout
+    pending: VecDeque<TaggedTrace>,
+    ///The line number of the current line, starting from 1
+    line_number: usize,
+    ///Every cell we've had to skip so far
+    problems: Vec<CsvProblem>,
+}
+
+///A cell in a CSV file which couldn't be read, so it got skipped - either a run (along with the rest of its line, or on its own in the legacy column layout), or a tag or metadata entry which isn't a `key=value` - see [`CsvTraces::problems`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CsvProblem {
+    ///The line the cell was on, starting from 1
+    pub line: usize,
+    ///The column the cell was in, starting from 1 - the trace names are in column 1, except in the legacy column layout
+    pub column: usize,
+    ///What was in the cell
+    pub value: String,
+    ///Why it couldn't be read
+    pub reason: String,
+}
+
+impl Display for CsvProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}, column {}: {:?} ({})", self.line, self.column, self.value, self.reason)
+    }
+}
+
+impl<R: BufRead> CsvTraces<R> {
+    ///The metadata about the whole file we've read so far, eg. the [`UNITS_METADATA_KEY`] - this usually all comes before the first trace
+    #[must_use]
+    pub const fn metadata(&self) -> &Tags {
+        &self.metadata
+    }
+
+    ///Every cell we've had to skip so far, because it wasn't a run or a valid tag - so frontends can say exactly what got left out
+    ///
+    ///```rust
+    /// use benchmarker::io::import_csv_iter;
+    /// use std::io::Cursor;
+    ///
+    /// let csv = "sort,10,11\nsearch,5,oops,x\nmerge,7\n";
+    /// let mut traces = import_csv_iter(Cursor::new(csv));
+    /// let names: Vec<String> = traces.by_ref().map(|trace| trace.unwrap().0).collect();
+    ///
+    /// assert_eq!(names, ["sort", "merge"]); //the broken line got skipped, rather than stopping the import
+    /// assert_eq!(traces.problems().len(), 2);
+    /// assert_eq!(traces.problems()[0].to_string(), r#"line 2, column 3: "oops" (invalid digit found in string)"#);
+    /// assert_eq!((traces.problems()[1].line, traces.problems()[1].column), (2, 4));
+    ///```
+    #[must_use]
+    pub fn problems(&self) -> &[CsvProblem] {
+        &self.problems
+    }
+
+    ///The tags we've read so far, by trace name
+    #[must_use]
+    pub const fn tags(&self) -> &TraceTags {
+        &self.tags
+    }
+
+    ///Gets the tags we've read, by trace name - once the iterator is done, this is every tag in the file
+    #[must_use]
+    pub fn into_tags(self) -> TraceTags {
+        self.tags
+    }
+
+    ///Reads lines until one with runs on it, which gets left in `line` without its line ending - dealing with any tags, metadata and comments on the way. Gives back `false` at the end of the file
+    ///
+    /// # Errors
+    ///
+    /// Can fail if we fail to read from the reader, or the metadata is invalid
+    fn next_data_line(&mut self) -> io::Result<bool> {
+        loop {
+            self.line.clear();
+            if self.reader.read_line(&mut self.line)? == 0 {
+                return Ok(false);
+            }
+            self.line_number += 1;
+            let len = self.line.trim_end_matches(['\n', '\r']).len();
+            self.line.truncate(len);
+
+            if self.line.trim().is_empty() {
+                continue;
+            }
+            if !self.line.starts_with('#') {
+                return Ok(true);
+            }
+            self.read_comment_line()?;
+        }
     }
-    let no_lines = lines.lines().count(); //have to get lines twice, as count consumes
-    let lines = lines.lines();
 
-    let mut trace_contents: Vec<(String, Vec<u128>)> = Vec::with_capacity(no_lines);
+    ///Deals with a line starting with `#` in `line` - either tags, metadata or a comment
+    ///
+    /// # Errors
+    ///
+    /// Can fail if the metadata is invalid
+    fn read_comment_line(&mut self) -> io::Result<()> {
+        if let Some(tags) = self.line.strip_prefix(CSV_TAGS_PREFIX).and_then(|tags| tags.strip_prefix(',')) {
+            let tags = tags.to_string(); //the line gets reused, so take it out first
+            self.read_tags(&tags);
+        } else if let Some(metadata) = self.line.strip_prefix(CSV_METADATA_PREFIX).and_then(|metadata| metadata.strip_prefix(',')) {
+            let metadata = metadata.to_string(); //the line gets reused, so take it out first
+            self.read_metadata(&metadata)?;
+        } //otherwise, it's a comment
+        Ok(())
+    }
+
+    ///Parses one run, converting it to microseconds if the file is in another unit
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    fn parse_run(&self, value: &str) -> Result<u128, String> {
+        self.micros_per_unit.map_or_else(
+            || value.parse().map_err(|e| format!("{e}")),
+            |micros_per_unit| {
+                value
+                    .parse::<f64>()
+                    .map(|value| (value * micros_per_unit).round().max(0.0) as u128) //rounded to the nearest microsecond, like the rest of precipice
+                    .map_err(|e| format!("{e}"))
+            },
+        )
+    }
 
-    for line in lines {
+    ///Parses a line like `title,time1,time2,time3`, giving back [`None`] if it can't be parsed - any cells which aren't runs get added to the problems
+    fn parse_row(&mut self, line: &str, line_number: usize) -> Option<TaggedTrace> {
         let mut values = line.split(',');
+        let title = values.next()?; //split always gives back at least one value
 
+        let mut contents = vec![];
+        let mut is_valid = true;
+        for (column, value) in values.enumerate() {
+            match self.parse_run(value) {
+                Ok(run) => contents.push(run),
+                Err(reason) => {
+                    is_valid = false; //keep going, so every problem on the line gets reported
+                    self.add_problem(line_number, column + 2, value, reason); //one-based, after the title
+                }
+            }
+        }
+
+        let tags = self.tags.get(title).cloned().unwrap_or_default();
+        is_valid.then(|| (title.to_string(), contents, tags))
+    }
+
+    ///Records a cell which couldn't be read, so got skipped
+    fn add_problem(&mut self, line: usize, column: usize, value: &str, reason: String) {
+        warn!(%line, %column, %value, %reason, "Skipping unreadable CSV cell");
+        self.problems.push(CsvProblem {
+            line,
+            column,
+            value: value.to_string(),
+            reason,
+        });
+    }
+
+    ///Makes the error for a metadata entry on the current line which stops us reading the file at all, saying where it is in the same way as a [`CsvProblem`]
+    fn metadata_error(&self, column: usize, entry: &str, reason: String) -> io::Error {
+        let problem = CsvProblem {
+            line: self.line_number,
+            column,
+            value: entry.to_string(),
+            reason,
+        };
+        io::Error::new(io::ErrorKind::InvalidData, problem.to_string())
+    }
+
+    ///Deals with an unversioned file whose first line (in `line`) has no runs on it, which is either the header of the legacy column layout (a trace name for each column, and then one run from each trace on every line), or a trace without any runs. We can only tell from the next line, so this reads that one too, putting any traces from them in `pending`.
+    ///
+    /// Only the legacy column layout needs the whole file read in, as every line has a run from every trace - otherwise the rest of the file gets read one line at a time as usual
+    ///
+    /// # Errors
+    ///
+    /// Can fail if we fail to read from the reader
+    fn read_ambiguous_start(&mut self) -> io::Result<()> {
+        let first = (self.line_number, std::mem::take(&mut self.line));
+        let second = self
+            .next_data_line()?
+            .then(|| (self.line_number, std::mem::take(&mut self.line)));
+
+        let is_columns = second
+            .as_ref()
+            .and_then(|(_, line)| line.split(',').next())
+            .is_some_and(|cell| cell.trim().parse::<f64>().is_ok());
+        if !is_columns {
+            for (line_number, line) in std::iter::once(&first).chain(&second) {
+                let trace = self.parse_row(line, *line_number);
+                self.pending.extend(trace);
+            }
+            return Ok(());
+        }
+
+        info!("Migrating a CSV file from the legacy column layout");
+        let mut rest: Vec<_> = second.into_iter().collect();
+        while self.next_data_line()? {
+            rest.push((self.line_number, std::mem::take(&mut self.line)));
+        }
+
+        let names: Vec<&str> = first.1.split(',').map(str::trim).collect();
+        let mut columns = vec![vec![]; names.len()];
+        for (line_number, line) in &rest {
+            for (column, (runs, value)) in columns.iter_mut().zip(line.split(',')).enumerate() {
+                let value = value.trim();
+                if value.is_empty() {
+                    continue; //traces can have different numbers of runs, so their columns can end early
+                }
+                match self.parse_run(value) {
+                    Ok(run) => runs.push(run),
+                    Err(reason) => self.add_problem(*line_number, column + 1, value, reason), //only this cell gets skipped, as the line has runs from every trace
+                }
+            }
+        }
+        self.pending = names
+            .into_iter()
+            .zip(columns)
+            .map(|(name, runs)| (name.to_string(), runs, self.tags.get(name).cloned().unwrap_or_default()))
+            .collect();
+        Ok(())
+    }
+
+    ///Reads a [`CSV_TAGS_PREFIX`] line into the tags - any tags which aren't a `key=value` get added to the problems
+    fn read_tags(&mut self, line: &str) {
+        let mut values = line.split(',');
         let Some(title) = values.next() else {
-            error!("Missing title");
+            return;
+        };
+        let mut tags = self.tags.remove(title).unwrap_or_default();
+        for (column, tag) in values.enumerate() {
+            match parse_tag(tag) {
+                Ok((key, value)) => {
+                    tags.insert(key, unescape_tag_value(&value));
+                }
+                Err(reason) => self.add_problem(self.line_number, column + 3, tag, reason), //one-based, after the prefix and the title
+            }
+        }
+        self.tags.insert(title.to_string(), tags);
+    }
+
+    ///Reads a [`CSV_METADATA_PREFIX`] line into the metadata
+    ///
+    /// # Errors
+    ///
+    /// Fails if the [`FORMAT_VERSION_METADATA_KEY`] is newer than we can read or the [`UNITS_METADATA_KEY`] isn't a unit we know, as then we'd read every run wrong. The error says where the entry is, like a [`CsvProblem`]. Any other entries which aren't a `key=value` get added to the problems
+    fn read_metadata(&mut self, line: &str) -> io::Result<()> {
+        for (column, entry) in line.split(',').enumerate() {
+            let column = column + 2; //one-based, after the prefix
+            let (key, value) = match parse_tag(entry) {
+                Ok((key, value)) => (key, unescape_tag_value(&value)),
+                Err(reason) => {
+                    self.add_problem(self.line_number, column, entry, reason);
+                    continue;
+                }
+            };
+            if key == FORMAT_VERSION_METADATA_KEY {
+                let format_version = value.parse().ok().filter(|version| *version > 0).ok_or_else(|| {
+                    self.metadata_error(column, entry, format!("invalid format version {value:?} in CSV metadata"))
+                })?;
+                if format_version > CSV_FORMAT_VERSION {
+                    return Err(self.metadata_error(
+                        column,
+                        entry,
+                        format!("this CSV file is format version {format_version}, but this version of precipice only reads up to {CSV_FORMAT_VERSION} - try updating precipice"),
+                    ));
+                }
+                self.format_version = Some(format_version);
+            } else if key == UNITS_METADATA_KEY {
+                let micros_per_unit = micros_per_unit(&value).ok_or_else(|| {
+                    self.metadata_error(column, entry, format!("unknown units {value:?} in CSV metadata"))
+                })?;
+                self.micros_per_unit = (value != "us").then_some(micros_per_unit); //microseconds need no converting, so they don't lose any precision
+            }
+            self.metadata.insert(key, value);
+        }
+        Ok(())
+    }
+}
+
+///How many microseconds there are in a unit - one of `ns`, `us` (or `µs`), `ms` or `s`
+fn micros_per_unit(unit: &str) -> Option<f64> {
+    match unit {
+        "ns" => Some(0.001),
+        "us" | "µs" => Some(1.0),
+        "ms" => Some(1_000.0),
+        "s" => Some(1_000_000.0),
+        _ => None,
+    }
+}
+
+impl<R: BufRead> Iterator for CsvTraces<R> {
+    type Item = io::Result<TaggedTrace>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(trace) = self.pending.pop_front() {
+            return Some(Ok(trace));
+        }
+
+        loop {
+            match self.next_data_line() {
+                Ok(true) => {}
+                Ok(false) => return None, //end of the file
+                Err(e) => return Some(Err(e)),
+            }
+
+            if !self.started {
+                self.started = true;
+                if self.format_version.is_none() && looks_like_header(&self.line) {
+                    //might be the legacy column layout, which we can only tell from the next line
+                    if let Err(e) = self.read_ambiguous_start() {
+                        return Some(Err(e));
+                    }
+                    if let Some(trace) = self.pending.pop_front() {
+                        return Some(Ok(trace));
+                    }
+                    continue; //neither line had a trace we could read, so carry on with the rest
+                }
+            }
+
+            let line = std::mem::take(&mut self.line);
+            let trace = self.parse_row(&line, self.line_number);
+            self.line = line; //give the buffer back, to avoid reallocating it
+            if let Some(trace) = trace {
+                return Some(Ok(trace));
+            }
+        }
+    }
+}
+
+///Whether the first line of an unversioned CSV file could be the header of the legacy column layout, ie. every cell is a name rather than a number
+fn looks_like_header(line: &str) -> bool {
+    line.split(',')
+        .all(|cell| !cell.trim().is_empty() && cell.trim().parse::<f64>().is_err())
+}
+
+///Lazily imports traces from a CSV reader, one trace at a time - so only a line or two of the file is ever in memory, rather than the whole thing like [`import_csv`].
+///
+///The exception is the legacy column layout (see [`CSV_FORMAT_VERSION`]), where every line has a run from every trace, so that gets read in all at once to be migrated.
+///
+///Each trace comes with the tags from any [`CSV_TAGS_PREFIX`] lines before it (which is where [`traces_to_csv_with_tags`] puts them). Lines which can't be parsed get skipped, like in [`import_csv`].
+///
+///```rust
+/// use benchmarker::io::import_csv_iter;
+/// use std::io::Cursor;
+///
+/// let csv = "#tags,sort,machine=ryzen-box\nsort,10,11\n# a comment\nsearch,5\n";
+/// let mut traces = import_csv_iter(Cursor::new(csv));
+///
+/// let (name, runs, tags) = traces.next().unwrap().unwrap();
+/// assert_eq!((name.as_str(), runs, tags["machine"].as_str()), ("sort", vec![10, 11], "ryzen-box"));
+/// let (name, runs, tags) = traces.next().unwrap().unwrap();
+/// assert_eq!((name.as_str(), runs, tags.is_empty()), ("search", vec![5], true));
+/// assert!(traces.next().is_none());
+///```
+pub fn import_csv_iter<R: BufRead>(reader: R) -> CsvTraces<R> {
+    CsvTraces {
+        reader,
+        line: String::new(),
+        tags: TraceTags::new(),
+        metadata: Tags::new(),
+        micros_per_unit: None,
+        format_version: None,
+        started: false,
+        pending: VecDeque::new(),
+        line_number: 0,
+        problems: vec![],
+    }
+}
+
+///A Google Benchmark JSON file, from `--benchmark_format=json` or `--benchmark_out`
+#[derive(Deserialize)]
+struct GoogleBenchmarkOutput {
+    ///Every benchmark run, including any aggregates
+    benchmarks: Vec<GoogleBenchmark>,
+}
+
+///One entry in a Google Benchmark JSON file
+#[derive(Deserialize)]
+struct GoogleBenchmark {
+    ///The full name, eg. `BM_Sort/1024`, which includes the aggregate name for aggregates
+    name: String,
+    ///The name without any aggregate, which older versions of Google Benchmark don't write
+    run_name: Option<String>,
+    ///Either `iteration` for a measured repetition, or `aggregate` for the likes of the mean and median
+    run_type: Option<String>,
+    ///The wall time per iteration, in `time_unit`
+    real_time: f64,
+    ///The unit for `real_time` - one of `ns`, `us`, `ms` or `s`
+    time_unit: Option<String>,
+}
+
+///Imports the traces from a Google Benchmark JSON file, with one trace per benchmark and one run per repetition.
+///
+/// Each run is the mean wall time per iteration of that repetition, rounded to the nearest microsecond like the rest of precipice - so run the benchmark with `--benchmark_repetitions` to get more than one run per trace. Aggregates (eg. the mean and standard deviation) are skipped, as precipice works those out itself.
+///
+/// # Errors
+///
+/// Can fail if we fail to read the file, or it isn't valid Google Benchmark JSON
+pub fn import_google_benchmark(file: impl AsRef<Path>) -> io::Result<Vec<(String, Vec<u128>)>> {
+    Ok(import_google_benchmark_output(serde_json::from_str(&read_to_string(file)?)?))
+}
+
+///Imports the traces from Google Benchmark JSON in any reader, eg. stdin - see [`import_google_benchmark`]
+///
+/// # Errors
+///
+/// Can fail if we fail to read from the reader, or it isn't valid Google Benchmark JSON
+pub fn import_google_benchmark_from_reader(reader: impl Read) -> io::Result<Vec<(String, Vec<u128>)>> {
+    Ok(import_google_benchmark_output(serde_json::from_reader(reader)?))
+}
+
+///Turns parsed Google Benchmark output into traces, skipping the aggregates
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss
+)]
+fn import_google_benchmark_output(output: GoogleBenchmarkOutput) -> Vec<(String, Vec<u128>)> {
+    let mut traces: Vec<(String, Vec<u128>)> = vec![];
+    for benchmark in output.benchmarks {
+        if benchmark.run_type.as_deref() == Some("aggregate") {
+            continue;
+        }
+
+        let unit = benchmark.time_unit.as_deref().unwrap_or("ns");
+        let Some(micros_per_unit) = micros_per_unit(unit) else {
+            error!(%unit, name=%benchmark.name, "Unknown Google Benchmark time unit");
             continue;
         };
-        let contents = match values.map(str::parse).collect() {
-            Ok(v) => v,
-            Err(e) => {
-                error!(?e, "Error parsing CSV file");
-                continue;
+        let run = (benchmark.real_time * micros_per_unit).round().max(0.0) as u128;
+
+        let name = benchmark.run_name.unwrap_or(benchmark.name);
+        match traces.iter_mut().find(|(trace_name, _)| trace_name == &name) {
+            Some((_, runs)) => runs.push(run),
+            None => traces.push((name, vec![run])), //keep the order from the file, rather than sorting by name
+        }
+    }
+
+    traces
+}
+
+///A hyperfine JSON file, from `--export-json`
+#[derive(Serialize, Deserialize)]
+struct HyperfineOutput {
+    ///One result for each command
+    results: Vec<HyperfineResult>,
+}
+
+///One command's result in a hyperfine JSON file. Everything is in seconds, and we don't write the `user` and `system` times or exit codes, as traces don't keep them
+#[derive(Serialize, Deserialize)]
+struct HyperfineResult {
+    ///The command, or its name if it was given one with `--command-name`
+    command: String,
+    ///The mean run time
+    #[serde(default)]
+    mean: f64,
+    ///The sample standard deviation of the run times, which is missing for a single run
+    #[serde(default)]
+    stddev: Option<f64>,
+    ///The median run time
+    #[serde(default)]
+    median: f64,
+    ///The fastest run
+    #[serde(default)]
+    min: f64,
+    ///The slowest run
+    #[serde(default)]
+    max: f64,
+    ///Every run time
+    #[serde(default)]
+    times: Vec<f64>,
+    ///The parameters from `--parameter-scan` or `--parameter-list`, by name
+    #[serde(default, skip_serializing_if = "Tags::is_empty")]
+    parameters: Tags,
+}
+
+///Imports the traces from a hyperfine JSON file (from `--export-json`), with one trace per command.
+///
+/// The runs get rounded to the nearest microsecond like the rest of precipice, and any parameters become [`crate::parameters::PARAMETER_TAG_PREFIX`] tags - so parameter scans can be plotted against their parameter.
+///
+/// # Errors
+///
+/// Can fail if we fail to read the file, or it isn't valid hyperfine JSON
+pub fn import_hyperfine_json(file: impl AsRef<Path>) -> io::Result<Vec<TaggedTrace>> {
+    Ok(import_hyperfine_output(serde_json::from_str(&read_to_string(file)?)?))
+}
+
+///Imports the traces from hyperfine JSON in any reader, eg. stdin - see [`import_hyperfine_json`]
+///
+/// # Errors
+///
+/// Can fail if we fail to read from the reader, or it isn't valid hyperfine JSON
+pub fn import_hyperfine_json_from_reader(reader: impl Read) -> io::Result<Vec<TaggedTrace>> {
+    Ok(import_hyperfine_output(serde_json::from_reader(reader)?))
+}
+
+///Turns parsed hyperfine output into traces
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss
+)]
+fn import_hyperfine_output(output: HyperfineOutput) -> Vec<TaggedTrace> {
+    output
+        .results
+        .into_iter()
+        .map(|result| {
+            let runs = result.times.iter().map(|time| (time * 1_000_000.0).round().max(0.0) as u128).collect();
+            (result.command, runs, parameter_tags(&result.parameters))
+        })
+        .collect()
+}
+
+///Writes a set of traces as hyperfine JSON, like from `--export-json`, with the mean, sample standard deviation, median, fastest and slowest run for each one - the times are all in seconds.
+///
+/// Each trace's name becomes its `command`, and any [`crate::parameters::PARAMETER_TAG_PREFIX`] tags become its `parameters`. The rest of the tags get left out, as hyperfine has nowhere to put them - as do the `user` and `system` times and the exit codes, which traces don't keep.
+///
+///```rust
+/// use benchmarker::{io::{import_hyperfine_json_from_reader, traces_to_hyperfine_json}, tags::parse_tags};
+/// use std::{collections::HashMap, io::Cursor};
+///
+/// let tags = HashMap::from([("sort".to_string(), parse_tags("param.threads=4, machine=ryzen-box").unwrap())]);
+/// let json = traces_to_hyperfine_json(&[("sort".to_string(), vec![1_000, 2_000, 6_000])], &tags).unwrap();
+/// let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+/// assert_eq!(value["results"][0]["command"], "sort");
+/// assert_eq!(value["results"][0]["times"], serde_json::json!([0.001, 0.002, 0.006]));
+/// assert_eq!((value["results"][0]["mean"].as_f64(), value["results"][0]["median"].as_f64()), (Some(0.003), Some(0.002)));
+/// assert_eq!(value["results"][0]["parameters"]["threads"], "4");
+///
+/// let traces = import_hyperfine_json_from_reader(Cursor::new(json)).unwrap();
+/// assert_eq!((traces[0].0.as_str(), &traces[0].1), ("sort", &vec![1_000, 2_000, 6_000]));
+/// assert_eq!(traces[0].2["param.threads"], "4");
+/// assert!(!traces[0].2.contains_key("machine"));
+///```
+///
+/// # Errors
+///
+/// Can only fail if serde does, which shouldn't happen
+#[allow(clippy::cast_precision_loss)]
+pub fn traces_to_hyperfine_json(traces: &[(String, Vec<u128>)], tags: &TraceTags) -> serde_json::Result<String> {
+    let results = traces
+        .iter()
+        .map(|(name, runs)| {
+            let (mean, standard_deviation) = calculate_mean_standard_deviation(runs).unwrap_or_default();
+            let (median, _, _) = calculate_median_mad_iqr(runs).unwrap_or_default();
+            let len = runs.len() as f64;
+            HyperfineResult {
+                command: name.clone(),
+                mean: mean.as_secs_f64(),
+                stddev: (runs.len() > 1).then(|| standard_deviation.as_secs_f64() * (len / (len - 1.0)).sqrt()), //hyperfine uses the sample standard deviation, rather than the population one
+                median: median.as_secs_f64(),
+                min: runs.iter().min().map_or(0.0, |min| *min as f64 / 1_000_000.0),
+                max: runs.iter().max().map_or(0.0, |max| *max as f64 / 1_000_000.0),
+                times: runs.iter().map(|run| *run as f64 / 1_000_000.0).collect(),
+                parameters: tags.get(name).map(parameters).unwrap_or_default(),
             }
+        })
+        .collect();
+    serde_json::to_string_pretty(&HyperfineOutput { results }) //pretty, like hyperfine writes it
+}
+
+///Exports a set of traces to a hyperfine JSON file, so they can go into anything which reads hyperfine's `--export-json` - see [`traces_to_hyperfine_json`]
+///
+/// # Errors
+///
+/// Can have errors if we fail to create a file or write to it
+#[allow(clippy::needless_pass_by_value)] //takes the traces the same way as the HTML export, so callers can swap between them
+pub fn export_hyperfine_json(
+    file_name_input: impl AsRef<Path> + Display,
+    traces: Vec<(String, Vec<u128>)>,
+    tags: &TraceTags,
+) -> Result<usize, Error> {
+    let to_be_written = traces_to_hyperfine_json(&traces, tags).map_err(io::Error::from)?;
+    write_export(&format!("{file_name_input}.json"), to_be_written.as_bytes(), None)
+}
+
+///Imports a set of traces from a file, picking the format from the extension.
+///
+/// `.json` files are read as our own JSON exports (see [`traces_to_json`]), hyperfine output or Google Benchmark output, and everything else as CSV
+///
+/// # Errors
+///
+/// Can fail if we fail to read the file, or it isn't in the right format
+pub fn import_traces(file: impl AsRef<Path>) -> io::Result<Vec<(String, Vec<u128>)>> {
+    if file.as_ref().extension().is_some_and(|extension| extension == "json") {
+        Ok(split_tags(import_traces_with_tags(file)?).0)
+    } else {
+        Ok(import_csv(file)?)
+    }
+}
+
+///Imports a set of traces from a file like [`import_traces`], with the tags for each one. Google Benchmark files don't have any tags, so their traces get empty ones
+///
+/// # Errors
+///
+/// Can fail if we fail to read the file, or it isn't in the right format
+pub fn import_traces_with_tags(file: impl AsRef<Path>) -> io::Result<Vec<TaggedTrace>> {
+    if file.as_ref().extension().is_some_and(|extension| extension == "json") {
+        return import_json(serde_json::from_str(&read_to_string(file)?)?);
+    }
+
+    let (traces, tags) = import_csv_with_tags(file)?;
+    Ok(traces
+        .into_iter()
+        .map(|(name, runs)| {
+            let tags = tags.get(&name).cloned().unwrap_or_default(); //clone rather than remove, as there can be more than one trace with a name
+            (name, runs, tags)
+        })
+        .collect())
+}
+
+///Imports a set of traces with their tags from any reader, eg. stdin.
+///
+/// There isn't an extension to go on, so anything starting with `{` (after any whitespace) gets read as JSON (either our own export, hyperfine output or Google Benchmark output), and everything else as CSV
+///
+///```rust
+/// use benchmarker::io::import_traces_with_tags_from_reader;
+/// use std::io::Cursor;
+///
+/// let traces = import_traces_with_tags_from_reader(Cursor::new("#tags,sort,machine=ryzen-box\nsort,10,11\n")).unwrap();
+/// assert_eq!((traces[0].0.as_str(), &traces[0].1, traces[0].2["machine"].as_str()), ("sort", &vec![10, 11], "ryzen-box"));
+///
+/// let json = r#"{"benchmarks": [{"name": "BM_Sort", "real_time": 2000, "time_unit": "ns"}]}"#;
+/// let traces = import_traces_with_tags_from_reader(Cursor::new(json)).unwrap();
+/// assert_eq!((traces[0].0.as_str(), &traces[0].1), ("BM_Sort", &vec![2]));
+///```
+///
+/// # Errors
+///
+/// Can fail if we fail to read from the reader, or it looks like JSON but isn't a valid export, hyperfine output or Google Benchmark output
+pub fn import_traces_with_tags_from_reader(reader: impl Read) -> io::Result<Vec<TaggedTrace>> {
+    let mut reader = BufReader::new(reader);
+    let is_json = loop {
+        let buffer = reader.fill_buf()?; //peek, so the parser still gets everything
+        let Some(&first) = buffer.first() else {
+            break false; //nothing there, which is an empty CSV
         };
-        trace_contents.push((title.to_string(), contents));
+        if first.is_ascii_whitespace() {
+            reader.consume(1);
+        } else {
+            break first == b'{';
+        }
+    };
+
+    if is_json {
+        return import_json(serde_json::from_reader(reader)?);
     }
 
-    Ok(trace_contents)
+    let (traces, tags) = import_csv_with_tags_from_reader(reader)?;
+    Ok(traces
+        .into_iter()
+        .map(|(name, runs)| {
+            let tags = tags.get(&name).cloned().unwrap_or_default();
+            (name, runs, tags)
+        })
+        .collect())
+}
+
+///Whether a file looks like a result file we can import, ie. it ends in `.csv` or `.json`
+fn is_result_file(file: &Path) -> bool {
+    file.extension().is_some_and(|extension| extension == "csv" || extension == "json")
+}
+
+///Expands a list of input paths and directories into the files to import.
+///
+/// Any input with glob characters (eg. `results/*.csv`) which isn't itself a file gets expanded, for shells (and scripts) which don't do it for us, and each directory adds every CSV and JSON file directly inside it. The matches from each glob and directory are sorted, and any file that comes up twice is only kept the first time.
+///
+/// # Errors
+///
+/// Can fail if a glob pattern is invalid or doesn't match anything, or if we fail to read a directory
+pub fn expand_inputs(inputs: &[PathBuf], directories: &[PathBuf]) -> io::Result<Vec<PathBuf>> {
+    let mut files = vec![];
+
+    for input in inputs {
+        let pattern = input.to_string_lossy();
+        if input.exists() || !pattern.contains(['*', '?', '[']) {
+            files.push(input.clone()); //a normal path - if it doesn't exist, we'll find out when importing it
+            continue;
+        }
+
+        let mut matches = glob::glob(&pattern)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, format!("invalid glob {pattern}: {e}")))?
+            .collect::<Result<Vec<_>, glob::GlobError>>()?;
+        if matches.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no files match {pattern}"),
+            ));
+        }
+        files.append(&mut matches); //glob already gives them in alphabetical order
+    }
+
+    for directory in directories {
+        let mut entries = read_dir(directory)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<io::Result<Vec<_>>>()?;
+        entries.retain(|file| file.is_file() && is_result_file(file));
+        entries.sort();
+        files.append(&mut entries);
+    }
+
+    let mut seen = HashSet::new();
+    files.retain(|file| seen.insert(file.clone())); //keep the first of each, so the order stays as given
+    Ok(files)
+}
+
+///What to do when two traces have the same name, eg. because they came from different files
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum, strum::Display)]
+pub enum DuplicatePolicy {
+    ///Add a suffix to the later ones, eg. `sort (2)`
+    #[default]
+    Suffix,
+    ///Merge the runs of the later ones into the first one
+    Merge,
+    ///Only keep the first one
+    Skip,
+    ///Fail, with the duplicated name
+    Error,
 }
 
-///Getting multiple traces from multiple files in CSV format
+///Deals with any traces which have the same name as an earlier one, using the `policy`. The traces keep their order, with merged runs going into the first trace of that name.
+///
+/// # Errors
+///
+/// Fails with [`io::ErrorKind::InvalidData`] if there are duplicates and the policy is [`DuplicatePolicy::Error`]
+///
+/// ## Example
+/// ```rust
+/// use benchmarker::io::{resolve_duplicates, DuplicatePolicy};
+///
+/// let traces = vec![("sort".to_string(), vec![1, 2]), ("sort".to_string(), vec![3])];
+/// let suffixed = resolve_duplicates(traces.clone(), DuplicatePolicy::Suffix).unwrap();
+/// assert_eq!(suffixed[1].0, "sort (2)");
+/// let merged = resolve_duplicates(traces.clone(), DuplicatePolicy::Merge).unwrap();
+/// assert_eq!(merged, vec![("sort".to_string(), vec![1, 2, 3])]);
+/// assert!(resolve_duplicates(traces, DuplicatePolicy::Error).is_err());
+/// ```
+pub fn resolve_duplicates(
+    traces: Vec<(String, Vec<u128>)>,
+    policy: DuplicatePolicy,
+) -> io::Result<Vec<(String, Vec<u128>)>> {
+    let mut resolved: Vec<(String, Vec<u128>)> = Vec::with_capacity(traces.len());
+
+    for (name, mut list) in traces {
+        let Some(existing) = resolved.iter().position(|(other, _)| other == &name) else {
+            resolved.push((name, list));
+            continue;
+        };
+
+        match policy {
+            DuplicatePolicy::Suffix => {
+                let mut suffix = 2;
+                while resolved.iter().any(|(other, _)| other == &format!("{name} ({suffix})")) {
+                    suffix += 1; //in case there's already a trace with that suffix
+                }
+                resolved.push((format!("{name} ({suffix})"), list));
+            }
+            DuplicatePolicy::Merge => resolved[existing].1.append(&mut list),
+            DuplicatePolicy::Skip => warn!(%name, "Skipping duplicate trace"),
+            DuplicatePolicy::Error => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("more than one trace is called {name}"),
+                ));
+            }
+        }
+    }
+
+    Ok(resolved)
+}
+
+///Getting multiple traces from multiple files, in CSV or JSON format. Any traces with the same name get suffixed - see [`get_traces_with_policy`] to do something else with them
 ///
 /// # Errors
 /// If we can't do something with the file
@@ -53,16 +959,57 @@ pub fn get_traces(
     trace_file_names: impl IntoIterator<Item = impl AsRef<Path>>,
     trace: Option<(String, Vec<u128>)>,
 ) -> io::Result<Vec<(String, Vec<u128>)>> {
-    Ok(trace_file_names
+    get_traces_with_policy(trace_file_names, trace, DuplicatePolicy::default())
+}
+
+///Getting multiple traces from multiple files, in CSV or JSON format, dealing with any traces with the same name using the `duplicates` policy
+///
+/// # Errors
+/// If we can't do something with the file, or there are duplicates and the policy is [`DuplicatePolicy::Error`]
+pub fn get_traces_with_policy(
+    trace_file_names: impl IntoIterator<Item = impl AsRef<Path>>,
+    trace: Option<(String, Vec<u128>)>,
+    duplicates: DuplicatePolicy,
+) -> io::Result<Vec<(String, Vec<u128>)>> {
+    let traces = trace_file_names
         .into_iter() //for each trace
-        .map(import_csv) //import it
+        .map(import_traces) //import it
         .collect::<io::Result<Vec<Vec<(String, Vec<u128>)>>>>()? //collect any results and bubble
         .into_iter() //make that back into an iterator
         .flatten() //flatten it - Vec<Vec<T>> to a flat Vec<T>
         .chain(trace)
+        .collect();
+    resolve_duplicates(traces, duplicates)
+}
+
+///Getting multiple traces from multiple files like [`get_traces`], along with their tags. Nothing is done about traces with the same name, so that they can be filtered or grouped by their tags first
+///
+/// # Errors
+/// If we can't do something with the file
+pub fn get_tagged_traces(trace_file_names: impl IntoIterator<Item = impl AsRef<Path>>) -> io::Result<Vec<TaggedTrace>> {
+    Ok(trace_file_names
+        .into_iter()
+        .map(import_traces_with_tags)
+        .collect::<io::Result<Vec<_>>>()?
+        .into_iter()
+        .flatten()
         .collect())
 }
 
+///Splits tagged traces into the traces and the tags for each trace name, for [`export_csv_with_tags`]. If more than one trace has the same name, the first one's tags are kept
+#[must_use]
+pub fn split_tags(traces: Vec<TaggedTrace>) -> (Vec<(String, Vec<u128>)>, TraceTags) {
+    let mut tags: TraceTags = HashMap::new();
+    let traces = traces
+        .into_iter()
+        .map(|(name, runs, trace_tags)| {
+            tags.entry(name.clone()).or_insert(trace_tags);
+            (name, runs)
+        })
+        .collect();
+    (traces, tags)
+}
+
 ///Exports a set of traces to a CSV file
 ///
 /// # Errors
@@ -72,7 +1019,7 @@ pub fn export_csv(
     trace: Option<(String, Vec<u128>)>,
     file_name_input: impl AsRef<Path> + Display,
     extra_trace_file_names: impl IntoIterator<Item = impl AsRef<Path>>,
-) -> io::Result<usize> {
+) -> Result<usize, Error> {
     let traces = get_traces(extra_trace_file_names, trace)?; //get the traces from the file and provided
     export_csv_no_file_input(file_name_input, traces) //export
 }
@@ -82,26 +1029,162 @@ pub fn export_csv(
 /// # Errors
 ///
 /// Can have errors if we fail to create a file or write to it
+#[allow(clippy::needless_pass_by_value)] //takes the traces the same way as the HTML export, so callers can swap between them
 pub fn export_csv_no_file_input(
     file_name_input: impl AsRef<Path> + Display,
     traces: Vec<(String, Vec<u128>)>,
-) -> io::Result<usize> {
+) -> Result<usize, Error> {
+    export_csv_with_tags(file_name_input, traces, &HashMap::new())
+}
+
+///Exports a set of traces to a CSV file, with the tags for each trace (by name)
+///
+/// # Errors
+///
+/// Can have errors if we fail to create a file or write to it
+#[allow(clippy::needless_pass_by_value)] //takes the traces the same way as the HTML export, so callers can swap between them
+pub fn export_csv_with_tags(
+    file_name_input: impl AsRef<Path> + Display,
+    traces: Vec<(String, Vec<u128>)>,
+    tags: &TraceTags,
+) -> Result<usize, Error> {
+    let to_be_written = traces_to_csv_with_metadata(&traces, tags, &csv_metadata());
+    write_export(&format!("{file_name_input}.csv"), to_be_written.as_bytes(), None)
+}
+
+///Exports a set of traces to a CSV file with their tags like [`export_csv_with_tags`], reporting the progress as it goes and stopping if it gets cancelled
+///
+///```rust
+/// use benchmarker::io::{export_csv_with_progress, ExportProgress};
+/// use std::{collections::HashMap, path::Path, sync::mpsc::channel};
+///
+/// let (progress_tx, progress_rx) = channel();
+/// let (cancel_tx, cancel_rx) = channel();
+/// let progress = ExportProgress { progress_tx, cancel_rx };
+/// let file = std::env::temp_dir().join("precipice_progress_doctest").display().to_string();
+///
+/// let n = export_csv_with_progress(&file, vec![("a".into(), vec![1, 2, 3])], &HashMap::new(), &progress).unwrap();
+/// assert_eq!(progress_rx.try_iter().last(), Some((n, n)));
+///
+/// cancel_tx.send(()).unwrap();
+/// assert!(export_csv_with_progress(&file, vec![("a".into(), vec![1, 2, 3])], &HashMap::new(), &progress).is_err());
+/// assert!(!Path::new(&format!("{file}.csv")).exists()); //the half-written file gets removed
+///```
+///
+/// # Errors
+///
+/// Can have an [`Error::Export`] if we fail to create a file or write to it, or with [`io::ErrorKind::Interrupted`] if it got cancelled
+#[allow(clippy::needless_pass_by_value)] //takes the traces the same way as the HTML export, so callers can swap between them
+pub fn export_csv_with_progress(
+    file_name_input: impl AsRef<Path> + Display,
+    traces: Vec<(String, Vec<u128>)>,
+    tags: &TraceTags,
+    progress: &ExportProgress,
+) -> Result<usize, Error> {
+    let to_be_written = traces_to_csv_with_metadata(&traces, tags, &csv_metadata());
+    write_export(&format!("{file_name_input}.csv"), to_be_written.as_bytes(), Some(progress))
+}
+
+///Writes a set of traces as CSV - each line is `title,time1,time2,time3` etc, which is what [`import_csv`] reads
+#[must_use]
+pub fn traces_to_csv(traces: &[(String, Vec<u128>)]) -> String {
+    traces_to_csv_with_tags(traces, &HashMap::new())
+}
+
+///Escapes the characters which would break a tag value in a CSV file (eg. the commas in a command line), like in a URL
+fn escape_tag_value(value: &str) -> String {
+    value
+        .replace('%', "%25") //has to go first, so we don't escape the escapes
+        .replace(',', "%2C")
+        .replace('\n', "%0A")
+        .replace('\r', "%0D")
+}
+
+///Undoes [`escape_tag_value`]
+fn unescape_tag_value(value: &str) -> String {
+    value
+        .replace("%2C", ",")
+        .replace("%0A", "\n")
+        .replace("%0D", "\r")
+        .replace("%25", "%") //has to go last, so `%252C` becomes `%2C` rather than `,`
+}
+
+///The metadata that exports put at the top of CSV files, so the raw files say what they are - the [`UNITS_METADATA_KEY`] (always `us`), when they were exported, and the version of precipice that exported them. Anything which doesn't know about [`CSV_METADATA_PREFIX`] lines can skip them like any other comment
+#[must_use]
+pub fn csv_metadata() -> Tags {
+    Tags::from([
+        (FORMAT_VERSION_METADATA_KEY.to_string(), CSV_FORMAT_VERSION.to_string()),
+        (UNITS_METADATA_KEY.to_string(), "us".to_string()),
+        (EXPORTED_AT_METADATA_KEY.to_string(), Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true)),
+        (VERSION_METADATA_KEY.to_string(), env!("CARGO_PKG_VERSION").to_string()),
+    ])
+}
+
+///Writes a set of traces as CSV like [`traces_to_csv_with_tags`], with a [`CSV_METADATA_PREFIX`] line for the whole file at the top (if there is any metadata)
+///
+///```rust
+/// use benchmarker::io::{import_csv_iter, traces_to_csv_with_metadata};
+/// use benchmarker::tags::Tags;
+/// use std::{collections::HashMap, io::Cursor};
+///
+/// let metadata = Tags::from([("units".to_string(), "ms".to_string())]);
+/// let csv = traces_to_csv_with_metadata(&[("sort".to_string(), vec![2])], &HashMap::new(), &metadata);
+/// assert_eq!(csv, "#meta,units=ms\nsort,2\n");
+///
+/// let mut traces = import_csv_iter(Cursor::new(csv));
+/// assert_eq!(traces.next().unwrap().unwrap().1, vec![2_000]); //read back in microseconds
+/// assert_eq!(traces.metadata()["units"], "ms");
+///```
+#[must_use]
+pub fn traces_to_csv_with_metadata(traces: &[(String, Vec<u128>)], tags: &TraceTags, metadata: &Tags) -> String {
+    let mut to_be_written = String::new();
+    if !metadata.is_empty() {
+        to_be_written += CSV_METADATA_PREFIX;
+        for (key, value) in metadata {
+            let _ = write!(to_be_written, ",{key}={}", escape_tag_value(value));
+        }
+        to_be_written += "\n";
+    }
+    to_be_written + &traces_to_csv_with_tags(traces, tags)
+}
+
+///Writes a set of traces as CSV like [`traces_to_csv`], with a [`CSV_TAGS_PREFIX`] line before each trace which has tags, which is what [`import_csv_with_tags`] reads.
+///
+///Commas, newlines and `%`s in the values get escaped like in a URL
+///
+///```rust
+/// use benchmarker::{io::traces_to_csv_with_tags, tags::parse_tags};
+/// use std::collections::HashMap;
+///
+/// let tags = HashMap::from([("sort".to_string(), parse_tags("commit=abc123").unwrap())]);
+/// let csv = traces_to_csv_with_tags(&[("sort".to_string(), vec![10, 11])], &tags);
+/// assert_eq!(csv, "#tags,sort,commit=abc123\nsort,10,11\n");
+///```
+#[must_use]
+pub fn traces_to_csv_with_tags(traces: &[(String, Vec<u128>)], tags: &TraceTags) -> String {
     let mut to_be_written = String::new(); //string with space to be written to
+    let mut tagged = HashSet::new(); //only write the tags once for each name
 
     for (name, times) in traces {
-        to_be_written += &name;
+        if let Some(tags) = tags.get(name).filter(|tags| !tags.is_empty() && tagged.insert(name)) {
+            to_be_written += CSV_TAGS_PREFIX;
+            to_be_written += ",";
+            to_be_written += name;
+            for (key, value) in tags {
+                let _ = write!(to_be_written, ",{key}={}", escape_tag_value(value));
+            }
+            to_be_written += "\n";
+        }
+
+        to_be_written += name;
         for time in times {
             to_be_written += ",";
             to_be_written += &time.to_string();
         }
         to_be_written += "\n";
-    } //manually write a csv - title,time1,time2,time3 etc
+    } //manually write a csv
 
-    let mut file = File::create(format!("{file_name_input}.csv"))?; //make a file
-    let to_be_written = to_be_written.as_bytes(); //get the bytes to be written
-    file.write_all(to_be_written)?; //write them all
-
-    Ok(to_be_written.len())
+    to_be_written
 }
 
 ///Exports a set of traces to a plotly plot
@@ -113,7 +1196,7 @@ pub fn export_html(
     trace: Option<(String, Vec<u128>)>,
     file_name_input: impl AsRef<Path> + Display,
     extra_trace_file_names: impl IntoIterator<Item = impl AsRef<Path>>,
-) -> io::Result<usize> {
+) -> Result<usize, Error> {
     let traces = get_traces(extra_trace_file_names, trace)?; //get the traces from the file and provided
     export_html_no_file_input(file_name_input, traces) //and export them
 }
@@ -126,31 +1209,723 @@ pub fn export_html(
 pub fn export_html_no_file_input(
     file_name_input: impl AsRef<Path> + Display,
     traces: Vec<(String, Vec<u128>)>,
-) -> io::Result<usize> {
+) -> Result<usize, Error> {
+    export_html_with_options(file_name_input, traces, &PlotOptions::default())
+}
+
+///Exports a set of traces to a HTML file, with options for how to plot them
+///
+/// # Errors
+///
+/// Can have errors if we fail to create a file or write to it
+pub fn export_html_with_options(
+    file_name_input: impl AsRef<Path> + Display,
+    traces: Vec<(String, Vec<u128>)>,
+    options: &PlotOptions,
+) -> Result<usize, Error> {
+    let html = traces_to_html(&file_name_input.to_string(), traces, options);
+    write_export(&format!("{file_name_input}.html"), html.as_bytes(), None)
+}
+
+///Exports a set of traces to a HTML file with options like [`export_html_with_options`], reporting the progress as it goes and stopping if it gets cancelled
+///
+/// # Errors
+///
+/// Can have an [`Error::Export`] if we fail to create a file or write to it, or with [`io::ErrorKind::Interrupted`] if it got cancelled
+pub fn export_html_with_progress(
+    file_name_input: impl AsRef<Path> + Display,
+    traces: Vec<(String, Vec<u128>)>,
+    options: &PlotOptions,
+    progress: &ExportProgress,
+) -> Result<usize, Error> {
+    let html = traces_to_html(&file_name_input.to_string(), traces, options);
+    write_export(&format!("{file_name_input}.html"), html.as_bytes(), Some(progress))
+}
+
+///Renders a set of traces to a standalone HTML document, with a link to download the raw runs as a CSV so the one file carries both the plot and the numbers. The `name` is used for the CSV's file name
+#[must_use]
+pub fn traces_to_html(name: &str, traces: Vec<(String, Vec<u128>)>, options: &PlotOptions) -> String {
+    let link = csv_download_link_with_tags(name, &traces, &options.tags);
+    plot_to_html(&plot_traces_with_options(traces, options)).replacen("</body>", &format!("{link}\n</body>"), 1)
+}
+
+///Makes a HTML link which downloads the traces as a CSV, with the CSV embedded in the link itself as a data URI
+#[must_use]
+pub fn csv_download_link(name: &str, traces: &[(String, Vec<u128>)]) -> String {
+    csv_download_link_with_tags(name, traces, &TraceTags::new())
+}
+
+///Makes a HTML link which downloads the traces as a CSV like [`csv_download_link`], with the tags for each trace
+#[must_use]
+pub fn csv_download_link_with_tags(name: &str, traces: &[(String, Vec<u128>)], tags: &TraceTags) -> String {
+    let csv = traces_to_csv_with_metadata(traces, tags, &csv_metadata());
+    let mut href = String::from("data:text/csv;charset=utf-8,");
+    for byte in csv.bytes() {
+        if byte.is_ascii_alphanumeric() || b",-_.".contains(&byte) {
+            href.push(char::from(byte));
+        } else {
+            let _ = write!(href, "%{byte:02X}"); //writing to a String can't fail
+        }
+    }
+
+    let file_name = Path::new(name)
+        .file_name()
+        .map_or_else(|| name.to_string(), |file_name| file_name.to_string_lossy().into_owned()); //the download goes wherever the browser puts it, so drop any directories
+    format!(
+        "<p style=\"font-family: sans-serif\"><a href=\"{href}\" download=\"{}.csv\">Download the raw data (CSV)</a></p>",
+        escape_html(&file_name)
+    )
+}
+
+///How to plot the traces in a HTML export
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum, strum::Display)]
+pub enum PlotKind {
+    ///Overlapping histograms of the run times
+    #[default]
+    Histogram,
+    ///A smooth density curve for each trace - each one has an area of 1, so traces with very different numbers of runs are still easy to compare
+    Density,
+    ///Each run against its index, so outliers and warm-up ramps can be picked out one by one
+    Scatter,
+}
+
+///A colour to draw a trace in, so the same trace is always the same colour
+///
+/// It can be parsed from hex like `#rrggbb`, or `#rrggbbaa` to make it see-through:
+/// ```rust
+/// use benchmarker::io::TraceColour;
+///
+/// let colour: TraceColour = "#ff800080".parse().unwrap();
+/// assert_eq!(colour, TraceColour { red: 255, green: 128, blue: 0, alpha: 128 });
+/// assert_eq!(colour.to_string(), "#ff800080");
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TraceColour {
+    ///The red component
+    pub red: u8,
+    ///The green component
+    pub green: u8,
+    ///The blue component
+    pub blue: u8,
+    ///How opaque it is - 255 is fully opaque
+    pub alpha: u8,
+}
+
+impl TraceColour {
+    ///Gets the colour for plotly
+    fn to_rgba(self) -> Rgba {
+        Rgba::new(self.red, self.green, self.blue, f64::from(self.alpha) / 255.0)
+    }
+}
+
+impl FromStr for TraceColour {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.strip_prefix('#').unwrap_or(s);
+        let component = |i: usize| {
+            hex.get(i..i + 2)
+                .and_then(|component| u8::from_str_radix(component, 16).ok())
+                .ok_or_else(|| format!("{s} isn't a colour like #rrggbb or #rrggbbaa"))
+        };
+
+        match hex.len() {
+            6 | 8 => Ok(Self {
+                red: component(0)?,
+                green: component(2)?,
+                blue: component(4)?,
+                alpha: if hex.len() == 8 { component(6)? } else { u8::MAX },
+            }),
+            _ => Err(format!("{s} isn't a colour like #rrggbb or #rrggbbaa")),
+        }
+    }
+}
+
+impl Display for TraceColour {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.red, self.green, self.blue)?;
+        if self.alpha != u8::MAX {
+            write!(f, "{:02x}", self.alpha)?;
+        }
+        Ok(())
+    }
+}
+
+///Parses a `name=#rrggbb` pair for picking a trace's colour on the command line. The name is everything before the last `=`, so it can have an `=` in it
+///
+/// # Errors
+///
+/// Fails if there isn't an `=`, or the colour isn't valid hex
+pub fn parse_trace_colour(s: &str) -> Result<(String, TraceColour), String> {
+    let (name, colour) = s
+        .rsplit_once('=')
+        .ok_or_else(|| format!("{s} should be like name=#rrggbb"))?;
+    Ok((name.to_string(), colour.parse()?))
+}
+
+///Options for plotting traces
+#[derive(Clone, Debug, Default)]
+pub struct PlotOptions {
+    ///How to plot each trace
+    pub kind: PlotKind,
+    ///Whether to use a logarithmic scale for the time axis - useful when the traces go from milliseconds to seconds
+    pub log_scale: bool,
+    ///Colours for the traces with these names - the rest get plotly's default colours
+    pub colours: HashMap<String, TraceColour>,
+    ///Plot every run, rather than thinning out traces with more than [`DEFAULT_MAX_PLOT_POINTS`] runs so the plot stays quick in a browser
+    pub full_resolution: bool,
+    ///The tags for each trace (including any [`crate::provenance::Provenance`]), which go into the raw data link in HTML exports and the `usermeta` of Vega-Lite exports
+    pub tags: TraceTags,
+    ///A parameter from a sweep (see [`crate::parameters`]) to put on the x-axis, with the mean and its 95% confidence interval for each trace, instead of plotting by [`Self::kind`]
+    pub parameter: Option<String>,
+}
+
+///How many points to draw each density curve with
+const DENSITY_CURVE_POINTS: usize = 200;
+
+///Makes a plotly histogram plot with all of the traces
+#[must_use]
+pub fn plot_traces(traces: Vec<(String, Vec<u128>)>) -> Plot {
+    plot_traces_with_options(traces, &PlotOptions::default())
+}
+
+///Makes a plotly plot with all of the traces, using the options to pick how. The time axis is in whichever unit suits the runs best, from nanoseconds to seconds
+#[allow(clippy::cast_precision_loss)]
+#[must_use]
+pub fn plot_traces_with_options(traces: Vec<(String, Vec<u128>)>, options: &PlotOptions) -> Plot {
+    let unit = display_unit(&traces);
+    if let Some(parameter) = &options.parameter {
+        return plot_sweep(&sweep_series(&traces, &options.tags, parameter), parameter, unit, options);
+    }
+
     let mut plot = Plot::new(); //make a new plotly plot
-    for (name, trace) in traces {
-        plot.add_trace(Histogram::new(trace).name(name)); //for each trace, add it to a plotly plot
+    let time_axis = || time_axis(options.log_scale, unit);
+    let in_unit = |runs: Vec<u128>| -> Vec<f64> { runs.into_iter().map(|run| run as f64 / unit.micros()).collect() };
+
+    match options.kind {
+        PlotKind::Histogram => {
+            for (name, trace) in traces {
+                if !options.full_resolution && trace.len() > DEFAULT_MAX_PLOT_POINTS {
+                    debug!(%name, runs = trace.len(), "Sampling trace for the histogram");
+                }
+                let trace = if options.full_resolution { trace } else { reservoir_sample(&trace, DEFAULT_MAX_PLOT_POINTS) };
+                let mut histogram = Histogram::new(in_unit(trace));
+                if let Some(colour) = options.colours.get(&name) {
+                    histogram = histogram.marker(Marker::new().color(colour.to_rgba()));
+                }
+                plot.add_trace(histogram.name(name)); //for each trace, add it to a plotly plot
+            }
+            plot.set_layout(Layout::new().x_axis(time_axis()));
+        }
+        PlotKind::Density => {
+            for (name, trace) in traces {
+                let Some(density) = KernelDensity::new(&trace) else {
+                    warn!(%name, "Not enough distinct runs to estimate a density, skipping trace");
+                    continue;
+                };
+                //go a few kernels past the ends so the curve gets back down to 0, but ignore the far tails so a few outliers don't stretch it out
+                let low = 3.0f64.mul_add(-density.bandwidth(), density.percentile(0.01)).max(0.0);
+                let high = 3.0f64.mul_add(density.bandwidth(), density.percentile(0.99));
+                let (xs, ys): (Vec<f64>, Vec<f64>) = density
+                    .curve(low, high, DENSITY_CURVE_POINTS)
+                    .into_iter()
+                    .map(|(x, y)| (x / unit.micros(), y * unit.micros())) //scale the density too, so the area stays at 1
+                    .unzip();
+                let mut curve = Scatter::new(xs, ys).mode(Mode::Lines);
+                if let Some(colour) = options.colours.get(&name) {
+                    curve = curve.line(Line::new().color(colour.to_rgba()));
+                }
+                plot.add_trace(curve.name(name));
+            }
+            plot.set_layout(
+                Layout::new()
+                    .x_axis(time_axis())
+                    .y_axis(Axis::new().title(Title::new("Density"))),
+            );
+        }
+        PlotKind::Scatter => {
+            for (name, trace) in traces {
+                let (indices, trace): (Vec<usize>, Vec<u128>) = if options.full_resolution {
+                    trace.into_iter().enumerate().unzip()
+                } else {
+                    lttb(&trace, DEFAULT_MAX_PLOT_POINTS).into_iter().unzip() //keeps the outliers, which are the point of this plot
+                };
+                let mut runs = Scatter::new(indices, in_unit(trace)).mode(Mode::Markers);
+                if let Some(colour) = options.colours.get(&name) {
+                    runs = runs.marker(Marker::new().color(colour.to_rgba()));
+                }
+                plot.add_trace(runs.name(name));
+            }
+            plot.set_layout(
+                Layout::new()
+                    .x_axis(Axis::new().title(Title::new("Run")))
+                    .y_axis(time_axis()),
+            );
+        }
+    }
+    plot
+}
+
+///Picks the unit to plot some traces in from the median of all of their runs, so the axis reads as eg. `1.2` seconds rather than `1200000` microseconds
+#[allow(clippy::cast_precision_loss)]
+fn display_unit(traces: &[(String, Vec<u128>)]) -> TimingUnit {
+    let mut runs: Vec<u128> = traces.iter().flat_map(|(_, runs)| runs.iter().copied()).collect();
+    if runs.is_empty() {
+        return TimingUnit::Microseconds;
+    }
+    let middle = runs.len() / 2;
+    let (_, median, _) = runs.select_nth_unstable(middle);
+    TimingUnit::for_micros(*median as f64)
+}
+
+///Makes the plotly axis for run times in `unit`
+fn time_axis(log_scale: bool, unit: TimingUnit) -> Axis {
+    let axis = Axis::new().title(Title::new(&format!("Time ({})", unit.abbreviation())));
+    if log_scale {
+        axis.type_(AxisType::Log)
+    } else {
+        axis
+    }
+}
+
+///Makes a plotly plot of a parameter sweep in `unit`, with a line of the means (and their confidence intervals as error bars) against the parameter for each series
+fn plot_sweep(series: &[ParameterSeries], parameter: &str, unit: TimingUnit, options: &PlotOptions) -> Plot {
+    let mut plot = Plot::new();
+    for series in series {
+        let mut line = Scatter::new(
+            series.points.iter().map(|point| point.value.clone()).collect(),
+            series.points.iter().map(|point| point.mean / unit.micros()).collect(),
+        )
+        .mode(Mode::LinesMarkers)
+        .error_y(
+            ErrorData::new(ErrorType::Data)
+                .array(series.points.iter().map(|point| point.confidence_interval / unit.micros()).collect()),
+        );
+        if let Some(colour) = options.colours.get(&series.name) {
+            line = line.line(Line::new().color(colour.to_rgba())).marker(Marker::new().color(colour.to_rgba()));
+        }
+        plot.add_trace(line.name(&series.name));
+    }
+
+    let parameter_axis = Axis::new().title(Title::new(parameter));
+    plot.set_layout(
+        Layout::new()
+            .x_axis(if series_are_numeric(series) { parameter_axis } else { parameter_axis.type_(AxisType::Category) }) //plotly guesses from the first few values, so a value like 10 among names would make it numeric
+            .y_axis(time_axis(options.log_scale, unit).title(Title::new(&format!("Mean time ({}), with 95% confidence interval", unit.abbreviation())))),
+    );
+    plot
+}
+
+///Where to load plotly.js from in HTML exports - the plotly cdn is currently down, so this is used instead
+pub const PLOTLY_JS_URL: &str = "https://cdnjs.cloudflare.com/ajax/libs/plotly.js/2.24.2/plotly.min.js";
+
+///Renders a plotly plot to a standalone HTML document
+#[must_use]
+pub fn plot_to_html(plot: &Plot) -> String {
+    plot.to_html().replace("https://cdn.plot.ly/plotly-2.12.1.min.js", PLOTLY_JS_URL)
+}
+
+///Writes a plotly plot to a HTML file, adding the extension
+///
+/// # Errors
+///
+/// Can have errors if we fail to create a file or write to it
+pub fn write_plot(plot: &Plot, file_name_input: impl AsRef<Path> + Display) -> Result<usize, Error> {
+    write_export(&format!("{file_name_input}.html"), plot_to_html(plot).as_bytes(), None)
+}
+
+///The schema for exported Vega-Lite specs
+pub const VEGA_LITE_SCHEMA: &str = "https://vega.github.io/schema/vega-lite/v5.json";
+
+///Makes a Vega-Lite spec for a set of traces, with the runs embedded as data. This plots the same way as [`plot_traces_with_options`], but is far lighter than the plotly bundle and renders natively in lots of dashboards and notebooks
+#[must_use]
+pub fn vega_lite_traces(traces: &[(String, Vec<u128>)], options: &PlotOptions) -> Value {
+    if let Some(parameter) = &options.parameter {
+        return vega_lite_sweep(traces, parameter, options);
+    }
+
+    let values: Vec<Value> = traces
+        .iter()
+        .flat_map(|(name, runs)| {
+            runs.iter()
+                .enumerate()
+                .map(move |(run, time)| json!({"trace": name, "run": run, "time_us": time}))
+        })
+        .collect();
+
+    let mut time_axis = json!({"type": "quantitative", "title": "Time (µs)"});
+    if options.log_scale {
+        time_axis["scale"] = json!({"type": "log"});
+    }
+    let colour = json!({"field": "trace", "type": "nominal", "title": "Trace"});
+
+    let mut spec = json!({
+        "$schema": VEGA_LITE_SCHEMA,
+        "data": {"values": values},
+    });
+    add_vega_lite_tags(&mut spec, traces, options);
+    match options.kind {
+        PlotKind::Histogram => {
+            time_axis["field"] = json!("time_us");
+            time_axis["bin"] = json!({"maxbins": 50});
+            spec["mark"] = json!({"type": "bar", "opacity": 0.6});
+            spec["encoding"] = json!({
+                "x": time_axis,
+                "y": {"aggregate": "count", "type": "quantitative", "stack": null, "title": "Runs"},
+                "color": colour,
+            }); //don't stack, so the bars overlap like the plotly histograms
+        }
+        PlotKind::Density => {
+            time_axis["field"] = json!("value");
+            spec["transform"] = json!([{"density": "time_us", "groupby": ["trace"]}]); //let Vega do the estimate, so the data stays as the raw runs
+            spec["mark"] = json!("line");
+            spec["encoding"] = json!({
+                "x": time_axis,
+                "y": {"field": "density", "type": "quantitative", "title": "Density"},
+                "color": colour,
+            });
+        }
+        PlotKind::Scatter => {
+            time_axis["field"] = json!("time_us");
+            spec["mark"] = json!("point");
+            spec["encoding"] = json!({
+                "x": {"field": "run", "type": "quantitative", "title": "Run"},
+                "y": time_axis,
+                "color": colour,
+            });
+        }
+    }
+    spec
+}
+
+///Adds the tags for each trace to a Vega-Lite spec, if any of them have tags
+fn add_vega_lite_tags(spec: &mut Value, traces: &[(String, Vec<u128>)], options: &PlotOptions) {
+    let tags: serde_json::Map<String, Value> = traces
+        .iter()
+        .filter_map(|(name, _)| Some((name.clone(), json!(options.tags.get(name).filter(|tags| !tags.is_empty())?))))
+        .collect();
+    if !tags.is_empty() {
+        spec["usermeta"] = json!({"tags": tags}); //Vega-Lite ignores usermeta, so it's the place for our own metadata
+    }
+}
+
+///Makes a Vega-Lite spec for a parameter sweep, like [`plot_traces_with_options`] does with [`PlotOptions::parameter`]. The data is the mean and confidence interval at each point, rather than the raw runs
+fn vega_lite_sweep(traces: &[(String, Vec<u128>)], parameter: &str, options: &PlotOptions) -> Value {
+    let series = sweep_series(traces, &options.tags, parameter);
+    let values: Vec<Value> = series
+        .iter()
+        .flat_map(|series| {
+            series.points.iter().map(|point| {
+                json!({
+                    "series": series.name,
+                    "value": point.value,
+                    "mean_us": point.mean,
+                    "low_us": point.mean - point.confidence_interval,
+                    "high_us": point.mean + point.confidence_interval,
+                    "runs": point.runs,
+                })
+            })
+        })
+        .collect();
+
+    let parameter_axis = if series_are_numeric(&series) {
+        json!({"field": "value", "type": "quantitative", "title": parameter})
+    } else {
+        json!({"field": "value", "type": "ordinal", "title": parameter, "sort": null}) //keep the order they turned up in
+    };
+    let mut mean_axis = json!({"field": "mean_us", "type": "quantitative", "title": "Mean time (µs), with 95% confidence interval"});
+    if options.log_scale {
+        mean_axis["scale"] = json!({"type": "log"});
+    }
+    let colour = json!({"field": "series", "type": "nominal", "title": "Series"});
+
+    let mut spec = json!({
+        "$schema": VEGA_LITE_SCHEMA,
+        "data": {"values": values},
+        "encoding": {"x": parameter_axis, "color": colour},
+        "layer": [
+            {"mark": {"type": "line", "point": true}, "encoding": {"y": mean_axis}},
+            {"mark": "errorbar", "encoding": {"y": {"field": "low_us", "type": "quantitative"}, "y2": {"field": "high_us"}}},
+        ],
+    });
+    add_vega_lite_tags(&mut spec, traces, options);
+    spec
+}
+
+///Writes a Vega-Lite spec to a file, adding the `.vl.json` extension
+///
+/// # Errors
+///
+/// Can have errors if we fail to create a file or write to it
+pub fn write_vega_lite(spec: &Value, file_name_input: impl AsRef<Path> + Display) -> Result<usize, Error> {
+    let json = serde_json::to_string(spec).map_err(io::Error::from)?; //not pretty, as the data can be thousands of runs
+    write_export(&format!("{file_name_input}.vl.json"), json.as_bytes(), None)
+}
+
+///Exports a set of traces to a Vega-Lite spec
+///
+/// # Errors
+///
+/// Can have errors if we fail to create a file or write to it
+#[allow(clippy::needless_pass_by_value)] //takes the traces the same way as the HTML export, so callers can swap between them
+pub fn export_vega_lite_no_file_input(
+    file_name_input: impl AsRef<Path> + Display,
+    traces: Vec<(String, Vec<u128>)>,
+    options: &PlotOptions,
+) -> Result<usize, Error> {
+    write_vega_lite(&vega_lite_traces(&traces, options), file_name_input)
+}
+
+///The version of the JSON export format, which gets bumped whenever the layout changes. Files from a newer version fail to import, rather than risk being read wrong
+pub const JSON_FORMAT_VERSION: u32 = 1;
+
+///A whole JSON export, as written by [`traces_to_json`]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct JsonExport {
+    ///See [`JSON_FORMAT_VERSION`]
+    format_version: u32,
+    ///The unit of the runs - one of `ns`, `us`, `ms` or `s`. Exports always use `us`
+    #[serde(default = "default_json_units")]
+    units: String,
+    ///When the file was exported
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    exported_at: Option<String>,
+    ///The version of precipice which exported the file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    precipice_version: Option<String>,
+    ///Every trace, in order
+    traces: Vec<JsonTrace>,
+}
+
+///The units for a JSON file which doesn't say, which is microseconds like everywhere else
+fn default_json_units() -> String {
+    "us".to_string()
+}
+
+///One trace in a JSON export
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct JsonTrace {
+    ///The name of the trace
+    name: String,
+    ///The tags for the trace
+    #[serde(default)]
+    tags: Tags,
+    ///Every run, in the file's units
+    runs: Vec<u128>,
+    ///The statistics for the runs, which only get written for other tools - they get worked out again on import
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    stats: Option<JsonStats>,
+}
+
+///The statistics for a trace in a JSON export, all in microseconds
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct JsonStats {
+    ///The mean run time
+    mean: f64,
+    ///The population standard deviation of the run times
+    standard_deviation: f64,
+    ///The fastest run
+    min: u128,
+    ///The slowest run
+    max: u128,
+    ///The median run time
+    median: f64,
+}
+
+impl JsonStats {
+    ///Works out the statistics for some microsecond runs, if there are any
+    fn new(runs: &[u128]) -> Option<Self> {
+        let (mean, standard_deviation) = calculate_mean_standard_deviation(runs)?;
+        let (median, _, _) = calculate_median_mad_iqr(runs)?;
+        Some(Self {
+            mean: mean.as_secs_f64() * 1_000_000.0,
+            standard_deviation: standard_deviation.as_secs_f64() * 1_000_000.0,
+            min: runs.iter().copied().min()?,
+            max: runs.iter().copied().max()?,
+            median: median.as_secs_f64() * 1_000_000.0,
+        })
+    }
+}
+
+///Writes a set of traces as JSON, with the tags for each trace (by name) and its mean, standard deviation, fastest, slowest and median run - everything is in microseconds.
+///
+/// It has the same metadata as the top of CSV exports (see [`csv_metadata`]), and [`import_traces_with_tags`] reads it back in, ignoring the statistics.
+///
+///```rust
+/// use benchmarker::{io::{import_traces_with_tags_from_reader, traces_to_json}, tags::parse_tags};
+/// use std::{collections::HashMap, io::Cursor};
+///
+/// let tags = HashMap::from([("sort".to_string(), parse_tags("commit=abc123").unwrap())]);
+/// let json = traces_to_json(&[("sort".to_string(), vec![10, 20, 60])], &tags).unwrap();
+/// let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+/// assert_eq!(value["units"], "us");
+/// assert_eq!(value["traces"][0]["runs"], serde_json::json!([10, 20, 60]));
+/// assert_eq!((value["traces"][0]["stats"]["mean"].as_f64(), value["traces"][0]["stats"]["median"].as_f64()), (Some(30.0), Some(20.0)));
+///
+/// let traces = import_traces_with_tags_from_reader(Cursor::new(json)).unwrap();
+/// assert_eq!((traces[0].0.as_str(), &traces[0].1, traces[0].2["commit"].as_str()), ("sort", &vec![10, 20, 60], "abc123"));
+///```
+///
+/// # Errors
+///
+/// Can only fail if serde does, which shouldn't happen
+pub fn traces_to_json(traces: &[(String, Vec<u128>)], tags: &TraceTags) -> serde_json::Result<String> {
+    let export = JsonExport {
+        format_version: JSON_FORMAT_VERSION,
+        units: default_json_units(),
+        exported_at: Some(Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true)),
+        precipice_version: Some(env!("CARGO_PKG_VERSION").to_string()),
+        traces: traces
+            .iter()
+            .map(|(name, runs)| JsonTrace {
+                name: name.clone(),
+                tags: tags.get(name).cloned().unwrap_or_default(),
+                runs: runs.clone(),
+                stats: JsonStats::new(runs),
+            })
+            .collect(),
+    };
+    serde_json::to_string(&export) //not pretty, as there can be thousands of runs
+}
+
+///Whether some parsed JSON is one of our own exports, rather than hyperfine or Google Benchmark output
+fn is_json_export(value: &Value) -> bool {
+    value.get("traces").is_some()
+}
+
+///Whether some parsed JSON is hyperfine output, rather than one of our own exports or Google Benchmark output
+fn is_hyperfine_json(value: &Value) -> bool {
+    value.get("results").is_some()
+}
+
+///Imports the traces from one of our own JSON exports (see [`traces_to_json`]), converting the runs to microseconds if they're in another unit
+///
+/// # Errors
+///
+/// Can fail if the JSON isn't in the right format, it's from a newer version of the format, or it has an unknown unit
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_precision_loss,
+    clippy::cast_sign_loss
+)]
+fn import_json_export(value: Value) -> io::Result<Vec<TaggedTrace>> {
+    let export: JsonExport = serde_json::from_value(value)?;
+    if export.format_version > JSON_FORMAT_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("the file is from version {} of the JSON format, but only up to version {JSON_FORMAT_VERSION} can be read - try a newer version of precipice", export.format_version),
+        ));
+    }
+    let Some(micros_per_unit) = micros_per_unit(&export.units) else {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("unknown units {}", export.units)));
+    };
+
+    Ok(export
+        .traces
+        .into_iter()
+        .map(|JsonTrace { name, tags, runs, .. }| {
+            let runs = if export.units == "us" {
+                runs
+            } else {
+                runs.into_iter().map(|run| (run as f64 * micros_per_unit).round() as u128).collect()
+            };
+            (name, runs, tags)
+        })
+        .collect())
+}
+
+///Imports the traces from some JSON, which can be one of our own exports, hyperfine output, or Google Benchmark output (whose traces get empty tags)
+///
+/// # Errors
+///
+/// Can fail if it isn't valid JSON in any of the formats
+fn import_json(value: Value) -> io::Result<Vec<TaggedTrace>> {
+    if is_json_export(&value) {
+        return import_json_export(value);
+    }
+    if is_hyperfine_json(&value) {
+        return Ok(import_hyperfine_output(serde_json::from_value(value)?));
     }
 
-    let mut file = File::create(format!("{file_name_input}.html"))?; //make a file
-    let mut html = plot.to_html(); //make the html
+    Ok(import_google_benchmark_output(serde_json::from_value(value)?)
+        .into_iter()
+        .map(|(name, runs)| (name, runs, Tags::new()))
+        .collect())
+}
+
+///Writes any JSON to a `.json` file, for exports which aren't traces (eg. the syscalls or metrics)
+///
+/// # Errors
+///
+/// Can have errors if we fail to create a file or write to it
+pub fn write_json(value: &Value, file_name_input: impl AsRef<Path> + Display) -> Result<usize, Error> {
+    let json = serde_json::to_string(value).map_err(io::Error::from)?;
+    write_export(&format!("{file_name_input}.json"), json.as_bytes(), None)
+}
 
-    html = html.replace("https://cdn.plot.ly/plotly-2.12.1.min.js", "https://cdnjs.cloudflare.com/ajax/libs/plotly.js/2.24.2/plotly.min.js"); //currently, cdn is down
+///Exports a set of traces to a JSON file with their statistics - see [`traces_to_json`]
+///
+/// # Errors
+///
+/// Can have errors if we fail to create a file or write to it
+#[allow(clippy::needless_pass_by_value)] //takes the traces the same way as the HTML export, so callers can swap between them
+pub fn export_json_no_file_input(
+    file_name_input: impl AsRef<Path> + Display,
+    traces: Vec<(String, Vec<u128>)>,
+) -> Result<usize, Error> {
+    export_json_with_tags(file_name_input, traces, &HashMap::new())
+}
 
-    let html = html.as_bytes(); //get the bytes - 2 steps to avoid dropping temporary value
-    file.write_all(html)?; //write all of the bytes
+///Exports a set of traces to a JSON file with their statistics, and the tags for each trace (by name)
+///
+/// # Errors
+///
+/// Can have errors if we fail to create a file or write to it
+#[allow(clippy::needless_pass_by_value)] //takes the traces the same way as the HTML export, so callers can swap between them
+pub fn export_json_with_tags(
+    file_name_input: impl AsRef<Path> + Display,
+    traces: Vec<(String, Vec<u128>)>,
+    tags: &TraceTags,
+) -> Result<usize, Error> {
+    let to_be_written = traces_to_json(&traces, tags).map_err(io::Error::from)?;
+    write_export(&format!("{file_name_input}.json"), to_be_written.as_bytes(), None)
+}
 
-    Ok(html.len())
+///Exports a set of traces to a JSON file with their tags like [`export_json_with_tags`], reporting the progress as it goes and stopping if it gets cancelled
+///
+/// # Errors
+///
+/// Can have an [`Error::Export`] if we fail to create a file or write to it, or with [`io::ErrorKind::Interrupted`] if it got cancelled
+#[allow(clippy::needless_pass_by_value)] //takes the traces the same way as the HTML export, so callers can swap between them
+pub fn export_json_with_progress(
+    file_name_input: impl AsRef<Path> + Display,
+    traces: Vec<(String, Vec<u128>)>,
+    tags: &TraceTags,
+    progress: &ExportProgress,
+) -> Result<usize, Error> {
+    let to_be_written = traces_to_json(&traces, tags).map_err(io::Error::from)?;
+    write_export(&format!("{file_name_input}.json"), to_be_written.as_bytes(), Some(progress))
 }
 
-#[derive(Copy, Clone, Debug, ValueEnum, strum::Display)]
+#[derive(Copy, Clone, Debug, ValueEnum, strum::Display, Serialize, Deserialize)]
 #[allow(clippy::upper_case_acronyms)]
 ///Any format
 pub enum ExportType {
     ///HTML graph
+    #[serde(rename = "html")]
     HTML,
     ///CSV file with everything
+    #[serde(rename = "csv")]
     CSV,
+    ///Vega-Lite JSON spec, with the data embedded
+    #[serde(rename = "vega-lite")]
+    VegaLite,
+    ///JSON file with every run, the tags, and the statistics for each trace
+    #[serde(rename = "json")]
+    JSON,
+    ///JSON file like hyperfine's `--export-json`, for tools which read hyperfine results
+    #[serde(rename = "hyperfine")]
+    Hyperfine,
 }
 
 impl ExportType {
@@ -164,18 +1939,60 @@ impl ExportType {
         trace_name: String,
         runs: Vec<u128>,
         export_file_name: String,
-    ) -> io::Result<usize> {
+    ) -> Result<usize, Error> {
+        self.export_with_tags(trace_name, runs, export_file_name, &Tags::new())
+    }
+
+    ///Export to the relevant format, with the session's tags (and provenance)
+    ///
+    /// # Errors
+    /// If we can't write to or create the file
+    #[instrument]
+    pub fn export_with_tags(
+        self,
+        trace_name: String,
+        runs: Vec<u128>,
+        export_file_name: String,
+        tags: &Tags,
+    ) -> Result<usize, Error> {
+        let tags = HashMap::from([(trace_name.clone(), tags.clone())]);
+        self.export_traces_with_tags(export_file_name, vec![(trace_name, runs)], &tags)
+    }
+
+    ///Export a set of traces to the relevant format, all in one file
+    ///
+    /// # Errors
+    /// If we can't write to or create the file
+    #[instrument(skip(traces))]
+    pub fn export_traces(
+        self,
+        export_file_name: String,
+        traces: Vec<(String, Vec<u128>)>,
+    ) -> Result<usize, Error> {
+        self.export_traces_with_tags(export_file_name, traces, &TraceTags::new())
+    }
+
+    ///Export a set of traces to the relevant format, all in one file, with the tags (and provenance) for each trace
+    ///
+    /// # Errors
+    /// If we can't write to or create the file
+    #[instrument(skip(traces, tags))]
+    pub fn export_traces_with_tags(
+        self,
+        export_file_name: String,
+        traces: Vec<(String, Vec<u128>)>,
+        tags: &TraceTags,
+    ) -> Result<usize, Error> {
+        let options = PlotOptions {
+            tags: tags.clone(),
+            ..PlotOptions::default()
+        };
         match self {
-            Self::HTML => export_html(
-                Some((trace_name, runs)),
-                export_file_name,
-                Vec::<String>::new(), //since we don't have any extra traces for here, we just give it an empty list. If we don't give it a type using the turbofish, then we get compiler errors on interpreting generics.
-            ),
-            Self::CSV => export_csv(
-                Some((trace_name, runs)),
-                export_file_name,
-                Vec::<String>::new(),
-            ),
+            Self::HTML => export_html_with_options(export_file_name, traces, &options),
+            Self::CSV => export_csv_with_tags(export_file_name, traces, tags),
+            Self::VegaLite => export_vega_lite_no_file_input(export_file_name, traces, &options),
+            Self::JSON => export_json_with_tags(export_file_name, traces, tags),
+            Self::Hyperfine => export_hyperfine_json(export_file_name, traces, tags),
         }
     }
 }
@@ -0,0 +1,303 @@
+//! Module for summarising huge CSV files without loading every run into memory.
+//!
+//! [`super::import_csv_iter`] only holds one line at a time, but with one trace per line a single trace with hundreds of millions of runs is still gigabytes. [`CsvSummaries`] reads each line a cell at a time instead, keeping a running mean and standard deviation and a [`Reservoir`] sample for plotting - so memory use stays the same however big the file is.
+//!
+//! The legacy column layout (see [`super::CSV_FORMAT_VERSION`]) can't be read like this, as each trace is spread over every line - import it normally and export it again to migrate it first.
+//!
+//! ## Example
+//! ```rust
+//! use benchmarker::io::{import_csv_iter, summary::TraceSummary};
+//! use std::io::Cursor;
+//!
+//! let csv = "#meta,units=ms\n#tags,sort,machine=ryzen-box\nsort,1,2,3\nsearch,5\n";
+//! let summaries: Vec<TraceSummary> = import_csv_iter(Cursor::new(csv))
+//!     .summaries(100)
+//!     .map(|summary| summary.unwrap())
+//!     .collect();
+//!
+//! assert_eq!((summaries[0].name.as_str(), summaries[0].runs), ("sort", 3));
+//! assert_eq!(summaries[0].mean_micros, 2_000.0);
+//! assert_eq!(summaries[0].tags["machine"], "ryzen-box");
+//! assert_eq!(summaries[0].sample, [1_000, 2_000, 3_000]); //everything fits in the sample
+//! assert_eq!((summaries[1].min_micros, summaries[1].max_micros), (5_000, 5_000));
+//! ```
+
+use super::{import_csv_iter, import_traces_with_tags, CsvTraces};
+use crate::{downsample::Reservoir, format::DurationFormat, tags::Tags};
+use std::{
+    fmt::{Display, Formatter},
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::Path,
+    time::Duration,
+};
+
+///Summarises the traces in a file, keeping a sample of up to `sample_size` runs from each.
+///
+/// CSV files get read a cell at a time with [`CsvSummaries`], and JSON files get read whole with [`super::import_traces_with_tags`]
+///
+/// # Errors
+///
+/// Can fail if we fail to read the file, or it isn't in the right format
+pub fn summarise_traces(file: impl AsRef<Path>, sample_size: usize) -> io::Result<Vec<TraceSummary>> {
+    if file.as_ref().extension().is_some_and(|extension| extension == "json") {
+        return Ok(import_traces_with_tags(file)?
+            .into_iter()
+            .map(|(name, runs, tags)| TraceSummary::new(name, &runs, tags, sample_size))
+            .collect());
+    }
+
+    import_csv_iter(BufReader::new(File::open(file)?))
+        .summaries(sample_size)
+        .collect()
+}
+
+///The summary of one trace, with a sample of its runs for plotting
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceSummary {
+    ///The name of the trace
+    pub name: String,
+    ///The tags for the trace, from any tags lines before it
+    pub tags: Tags,
+    ///How many runs the trace has
+    pub runs: usize,
+    ///The mean run time in microseconds
+    pub mean_micros: f64,
+    ///The population standard deviation of the run times in microseconds
+    pub standard_deviation_micros: f64,
+    ///The fastest run in microseconds
+    pub min_micros: u128,
+    ///The slowest run in microseconds
+    pub max_micros: u128,
+    ///A uniform random sample of the runs, in their original order - every run, if there weren't more than the sample size
+    pub sample: Vec<u128>,
+}
+
+impl TraceSummary {
+    ///Summarises a trace that's already in memory, eg. from a format which can't be read a cell at a time
+    #[must_use]
+    pub fn new(name: String, runs: &[u128], tags: Tags, sample_size: usize) -> Self {
+        let mut summariser = Summariser::new(sample_size);
+        for run in runs {
+            summariser.push(*run);
+        }
+        summariser.finish(name, tags)
+    }
+}
+
+impl TraceSummary {
+    ///Writes the summary on one line, with the times written in `format`
+    #[must_use]
+    pub fn format(&self, format: &DurationFormat) -> String {
+        let micros = |value: f64| format.duration(Duration::from_secs_f64(value.max(0.0) / 1_000_000.0));
+        format!(
+            "{}: {} ± {} ({} … {}) over {} runs",
+            self.name,
+            micros(self.mean_micros),
+            micros(self.standard_deviation_micros),
+            format.micros(self.min_micros),
+            format.micros(self.max_micros),
+            self.runs
+        )
+    }
+}
+
+impl Display for TraceSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.format(&DurationFormat::default()))
+    }
+}
+
+///Keeps running statistics for a trace as its runs get read, using Welford's algorithm so the mean and variance stay accurate over billions of runs
+#[derive(Clone, Debug)]
+struct Summariser {
+    ///How many runs so far
+    runs: usize,
+    ///The mean so far
+    mean: f64,
+    ///The sum of the squared differences from the mean so far
+    squared_differences: f64,
+    ///The fastest run so far
+    min: u128,
+    ///The slowest run so far
+    max: u128,
+    ///The sample for plotting
+    reservoir: Reservoir,
+}
+
+impl Summariser {
+    ///Makes a summariser with nothing in it yet, which keeps a sample of `sample_size` runs
+    fn new(sample_size: usize) -> Self {
+        Self {
+            runs: 0,
+            mean: 0.0,
+            squared_differences: 0.0,
+            min: u128::MAX,
+            max: 0,
+            reservoir: Reservoir::new(sample_size),
+        }
+    }
+
+    ///Adds a run
+    #[allow(clippy::cast_precision_loss)]
+    fn push(&mut self, run: u128) {
+        self.runs += 1;
+        let value = run as f64;
+        let delta = value - self.mean;
+        self.mean += delta / self.runs as f64;
+        self.squared_differences += delta * (value - self.mean);
+        self.min = self.min.min(run);
+        self.max = self.max.max(run);
+        self.reservoir.push(run);
+    }
+
+    ///Turns the statistics into a summary
+    #[allow(clippy::cast_precision_loss)]
+    fn finish(self, name: String, tags: Tags) -> TraceSummary {
+        TraceSummary {
+            name,
+            tags,
+            runs: self.runs,
+            mean_micros: self.mean,
+            standard_deviation_micros: if self.runs == 0 { 0.0 } else { (self.squared_differences / self.runs as f64).sqrt() },
+            min_micros: if self.runs == 0 { 0 } else { self.min },
+            max_micros: self.max,
+            sample: self.reservoir.into_sample(),
+        }
+    }
+}
+
+///Summarises the traces in a CSV file one at a time, reading each line a cell at a time - see [`CsvTraces::summaries`]
+#[derive(Debug)]
+pub struct CsvSummaries<R> {
+    ///The CSV reader, which deals with tags, metadata and problems like it does for whole traces
+    traces: CsvTraces<R>,
+    ///How many runs to keep for each trace's sample
+    sample_size: usize,
+    ///The current cell, kept between cells to avoid reallocating it
+    cell: Vec<u8>,
+}
+
+impl<R: BufRead> CsvTraces<R> {
+    ///Summarises each trace rather than giving back all of its runs, so a file can be bigger than memory - see [`crate::io::summary`].
+    ///
+    /// Each summary keeps a sample of up to `sample_size` runs for plotting. Tags, metadata and unreadable cells get dealt with just like when reading whole traces
+    #[must_use]
+    pub const fn summaries(self, sample_size: usize) -> CsvSummaries<R> {
+        CsvSummaries {
+            traces: self,
+            sample_size,
+            cell: Vec::new(),
+        }
+    }
+}
+
+impl<R: BufRead> CsvSummaries<R> {
+    ///The CSV reader underneath, eg. for its [`CsvTraces::problems`]
+    #[must_use]
+    pub const fn traces(&self) -> &CsvTraces<R> {
+        &self.traces
+    }
+
+    ///Reads the next cell into `cell` (without any line ending), giving back whether there are more cells on the line after it
+    ///
+    /// # Errors
+    ///
+    /// Can fail if we fail to read from the reader
+    fn read_cell(&mut self) -> io::Result<bool> {
+        self.cell.clear();
+        loop {
+            let buffer = self.traces.reader.fill_buf()?;
+            if buffer.is_empty() {
+                return Ok(false); //the file doesn't end with a newline
+            }
+            if let Some(end) = buffer.iter().position(|byte| matches!(byte, b',' | b'\n')) {
+                let more = buffer[end] == b',';
+                self.cell.extend_from_slice(&buffer[..end]);
+                self.traces.reader.consume(end + 1);
+                if self.cell.last() == Some(&b'\r') {
+                    self.cell.pop();
+                }
+                return Ok(more);
+            }
+            let len = buffer.len();
+            self.cell.extend_from_slice(buffer);
+            self.traces.reader.consume(len);
+        }
+    }
+
+    ///Reads the rest of a line of runs, after its title. Gives back [`None`] if any of the cells couldn't be read, like [`CsvTraces`] does
+    fn read_runs(&mut self, title: &str) -> io::Result<Option<TraceSummary>> {
+        let mut summariser = Summariser::new(self.sample_size);
+        let mut is_valid = true;
+        let mut more = true;
+        let mut column = 1;
+        while more {
+            more = self.read_cell()?;
+            column += 1;
+            let value = String::from_utf8_lossy(&self.cell);
+            match self.traces.parse_run(&value) {
+                Ok(run) => summariser.push(run),
+                Err(reason) => {
+                    is_valid = false; //keep going to the end of the line, so every problem gets reported
+                    let value = value.to_string();
+                    self.traces.add_problem(self.traces.line_number, column, &value, reason);
+                }
+            }
+        }
+
+        let tags = self.traces.tags.get(title).cloned().unwrap_or_default();
+        Ok(is_valid.then(|| summariser.finish(title.to_string(), tags)))
+    }
+}
+
+impl<R: BufRead> Iterator for CsvSummaries<R> {
+    type Item = io::Result<TraceSummary>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let first = match self.traces.reader.fill_buf() {
+                Ok([]) => return None, //end of the file
+                Ok(buffer) => buffer[0],
+                Err(e) => return Some(Err(e)),
+            };
+
+            if matches!(first, b'#' | b'\n' | b'\r') {
+                //tags, metadata, comments and blank lines are short, so they can be read whole
+                let traces = &mut self.traces;
+                traces.line.clear();
+                if let Err(e) = traces.reader.read_line(&mut traces.line) {
+                    return Some(Err(e));
+                }
+                traces.line_number += 1;
+                let len = traces.line.trim_end_matches(['\n', '\r']).len();
+                traces.line.truncate(len);
+                if traces.line.starts_with('#') {
+                    if let Err(e) = traces.read_comment_line() {
+                        return Some(Err(e));
+                    }
+                }
+                continue;
+            }
+
+            self.traces.line_number += 1;
+            let more = match self.read_cell() {
+                Ok(more) => more,
+                Err(e) => return Some(Err(e)),
+            };
+            let title = String::from_utf8_lossy(&self.cell).to_string();
+            if !more {
+                if title.trim().is_empty() {
+                    continue; //a line of whitespace
+                }
+                let tags = self.traces.tags.get(&title).cloned().unwrap_or_default();
+                return Some(Ok(TraceSummary::new(title, &[], tags, self.sample_size))); //a trace without any runs
+            }
+            match self.read_runs(&title) {
+                Ok(Some(summary)) => return Some(Ok(summary)),
+                Ok(None) => {} //skipped, with the problems recorded
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
@@ -8,15 +8,44 @@
 )]
 #![allow(clippy::too_many_lines)]
 
-//TODO: make some functions use color_eyre or custom error types, to remove the expects
-
 pub mod bencher;
+pub mod build_command;
+pub mod cargo;
+pub mod compare;
+pub mod debug_build;
+pub mod diagnostics;
+pub mod downsample;
+pub mod environment;
+pub mod error;
+pub mod events;
+pub mod exit_reason;
+pub mod export_command;
+pub mod format;
+pub mod github;
+pub mod history;
 pub mod io;
+pub mod lock;
+pub mod matrix;
+pub mod memory;
+pub mod metrics;
+pub mod outliers;
+pub mod parameters;
+pub mod profile;
+pub mod provenance;
+pub mod prometheus;
+pub mod report;
+pub mod stats;
+pub mod suite;
+pub mod syscalls;
+pub mod tags;
+pub mod terminal;
+pub mod upload;
+pub mod webhook;
 
 #[macro_use]
 extern crate tracing;
 
-///Separator to be used for storing lists in EGUI.
+///Separator that older versions used for storing lists in EGUI.
 ///
-///Since commas can reasonably appear, I'm instead using this, which theoretically shouldn't appear very often.
+///Since commas can reasonably appear, I was instead using this, which theoretically shouldn't appear very often. The GUIs now store their state as JSON, so this is only used to read what older versions stored.
 pub const EGUI_STORAGE_SEPARATOR: &str = "---,---";
@@ -0,0 +1,120 @@
+//! Module for making sure only one benchmark runs on a machine at a time, so two sessions don't silently inflate each other's numbers.
+//!
+//! A [`BenchmarkLock`] holds an exclusive lock on a file shared by every session (by default `precipice.lock` in the temporary directory) until it gets dropped - the lock goes away even if the session crashes. Whoever holds it writes their PID into the file, so anything waiting can say who it's waiting for.
+//!
+//! ## Example
+//! ```rust
+//! use benchmarker::lock::{lock_holder, BenchmarkLock};
+//!
+//! let path = std::env::temp_dir().join("precipice_lock_doctest.lock");
+//! let lock = BenchmarkLock::try_acquire(&path).unwrap().expect("nothing else has the lock");
+//! assert_eq!(lock_holder(&path), Some(std::process::id()));
+//!
+//! drop(lock);
+//! assert!(BenchmarkLock::try_acquire(&path).unwrap().is_some());
+//! # std::fs::remove_file(&path).unwrap();
+//! ```
+
+use clap::ValueEnum;
+use std::{
+    fs::{read_to_string, File, OpenOptions, TryLockError},
+    io::{self, ErrorKind, Write},
+    path::{Path, PathBuf},
+};
+
+///The name of the lock file in the temporary directory, if no other path is given
+pub const LOCK_FILE_NAME: &str = "precipice.lock";
+
+///Gets the lock file every session uses by default
+#[must_use]
+pub fn default_lock_path() -> PathBuf {
+    std::env::temp_dir().join(LOCK_FILE_NAME)
+}
+
+///What to do if another session already has the lock
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum, strum::Display)]
+pub enum LockMode {
+    ///Wait for the other session to finish
+    #[default]
+    Wait,
+    ///Stop straight away
+    Fail,
+}
+
+///An exclusive lock on the machine for benchmarking, which gets released when this is dropped
+#[derive(Debug)]
+pub struct BenchmarkLock {
+    ///The locked file - closing it releases the lock
+    file: File,
+    ///Where the lock file is
+    path: PathBuf,
+}
+
+impl BenchmarkLock {
+    ///Opens the lock file, without locking it
+    fn open(path: &Path) -> io::Result<File> {
+        match OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path) {
+            Err(e) if e.kind() == ErrorKind::PermissionDenied => File::open(path), //someone else made it, but we can still lock it - just without our PID
+            result => result,
+        }
+    }
+
+    ///Writes our PID into the lock file, now that we have it
+    fn locked(mut file: File, path: &Path) -> Self {
+        if file.set_len(0).is_ok() {
+            let _ = write!(file, "{}", std::process::id()); //only used for messages, so it doesn't matter if this fails
+        }
+        Self {
+            file,
+            path: path.to_path_buf(),
+        }
+    }
+
+    ///Takes the lock, waiting for any other session to finish first
+    ///
+    /// # Errors
+    ///
+    /// Can fail if we can't open or lock the file
+    #[instrument]
+    pub fn acquire(path: impl AsRef<Path> + std::fmt::Debug) -> io::Result<Self> {
+        let path = path.as_ref();
+        let file = Self::open(path)?;
+        file.lock()?;
+        Ok(Self::locked(file, path))
+    }
+
+    ///Takes the lock if nothing else has it, or gives back [`None`] if another session does
+    ///
+    /// # Errors
+    ///
+    /// Can fail if we can't open or lock the file
+    #[instrument]
+    pub fn try_acquire(path: impl AsRef<Path> + std::fmt::Debug) -> io::Result<Option<Self>> {
+        let path = path.as_ref();
+        let file = Self::open(path)?;
+        match file.try_lock() {
+            Ok(()) => Ok(Some(Self::locked(file, path))),
+            Err(TryLockError::WouldBlock) => Ok(None),
+            Err(TryLockError::Error(e)) => Err(e),
+        }
+    }
+
+    ///Gets where the lock file is
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for BenchmarkLock {
+    fn drop(&mut self) {
+        let _ = self.file.set_len(0); //so nothing thinks we still have it
+        let _ = self.file.unlock();
+    }
+}
+
+///Gets the PID of whichever session last took the lock at `path`, if it wrote one
+#[must_use]
+pub fn lock_holder(path: impl AsRef<Path>) -> Option<u32> {
+    read_to_string(path).ok()?.trim().parse().ok()
+}
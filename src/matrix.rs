@@ -0,0 +1,244 @@
+//! Module for comparison matrices - a table with a row for each command and a column for each set of parameters, which is how suite results normally get read.
+//!
+//! Each cell has the mean ± standard deviation of its runs, and how many times slower it is than the fastest command in the same column. Matrices can be written as Markdown (eg. for a PR comment) or HTML.
+//!
+//! ## Example
+//! ```rust
+//! use benchmarker::matrix::ComparisonMatrix;
+//!
+//! let matrix = ComparisonMatrix::new([
+//!     ("quicksort".to_string(), "threads=1".to_string(), vec![100, 100]),
+//!     ("mergesort".to_string(), "threads=1".to_string(), vec![150, 150]),
+//!     ("quicksort".to_string(), "threads=4".to_string(), vec![40, 40]),
+//! ]);
+//! assert_eq!(matrix.rows, ["quicksort", "mergesort"]);
+//! assert_eq!(matrix.columns, ["threads=1", "threads=4"]);
+//! assert!((matrix.cells[1][0].unwrap().relative - 1.5).abs() < 1e-9); //against quicksort, the fastest with 1 thread
+//! assert!(matrix.cells[1][1].is_none()); //mergesort wasn't run with 4 threads
+//!
+//! let markdown = matrix.to_markdown("sorting");
+//! assert!(markdown.contains("| mergesort | 150.000µs ± 0.000ns (1.50×) | - |"));
+//! ```
+
+use crate::{bencher::calculate_mean_standard_deviation, format::DurationFormat, report::escape_html};
+use clap::ValueEnum;
+use std::{
+    fmt::{Display, Write as _},
+    fs::File,
+    io::{self, Write},
+    path::Path,
+    time::Duration,
+};
+
+///How to write a [`ComparisonMatrix`]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum, strum::Display)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum MatrixFormat {
+    ///A Markdown table, eg. for a PR comment or a job summary
+    #[default]
+    Markdown,
+    ///A standalone HTML page
+    HTML,
+}
+
+impl MatrixFormat {
+    ///The file extension for this format, without the dot
+    #[must_use]
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Markdown => "md",
+            Self::HTML => "html",
+        }
+    }
+}
+
+///The results for one command with one set of parameters
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MatrixCell {
+    ///The mean run time
+    pub mean: Duration,
+    ///The standard deviation of the run times
+    pub standard_deviation: Duration,
+    ///How many times longer the mean is than the fastest mean in the same column - so the fastest is `1.0`
+    pub relative: f64,
+    ///How many runs there were
+    pub runs: usize,
+}
+
+///A table of results with a row for each command and a column for each set of parameters
+#[derive(Clone, Debug, PartialEq)]
+pub struct ComparisonMatrix {
+    ///The commands, in the order they first turned up
+    pub rows: Vec<String>,
+    ///The sets of parameters, in the order they first turned up
+    pub columns: Vec<String>,
+    ///The cells, indexed by row and then column. Commands which weren't run with a set of parameters have [`None`]
+    pub cells: Vec<Vec<Option<MatrixCell>>>,
+    ///How to write the times in each cell
+    pub duration_format: DurationFormat,
+}
+
+///The styles for the HTML matrix
+const STYLE: &str = "
+body { font-family: system-ui, sans-serif; margin: 2em auto; padding: 0 1em; color: #222; }
+table { border-collapse: collapse; }
+th, td { text-align: left; padding: 0.3em 1em; border: 1px solid #ddd; }
+th { font-weight: 600; background: #f8f8f8; }
+.relative { color: #666; font-size: 0.9em; }
+.fastest { font-weight: 600; }
+";
+
+impl ComparisonMatrix {
+    ///Makes a matrix from `(command, parameters, runs)` triples. Runs for the same command and parameters get merged, and cells without any runs get left empty
+    #[must_use]
+    pub fn new(results: impl IntoIterator<Item = (String, String, Vec<u128>)>) -> Self {
+        let mut rows: Vec<String> = vec![];
+        let mut columns: Vec<String> = vec![];
+        let mut runs: Vec<(usize, usize, Vec<u128>)> = vec![];
+        for (row, column, mut cell_runs) in results {
+            let row = index_of(&mut rows, row);
+            let column = index_of(&mut columns, column);
+            match runs.iter_mut().find(|(r, c, _)| *r == row && *c == column) {
+                Some((_, _, existing)) => existing.append(&mut cell_runs),
+                None => runs.push((row, column, cell_runs)),
+            }
+        }
+
+        let mut cells = vec![vec![None; columns.len()]; rows.len()];
+        for (row, column, cell_runs) in runs {
+            if let Some((mean, standard_deviation)) = calculate_mean_standard_deviation(&cell_runs) {
+                cells[row][column] = Some(MatrixCell {
+                    mean,
+                    standard_deviation,
+                    relative: 1.0,
+                    runs: cell_runs.len(),
+                });
+            }
+        }
+        for column in 0..columns.len() {
+            let fastest = cells
+                .iter()
+                .filter_map(|row| row[column].map(|cell| cell.mean))
+                .min()
+                .unwrap_or_default();
+            if fastest.is_zero() {
+                continue; //nothing to scale against, so leave them all at 1
+            }
+            for cell in cells.iter_mut().filter_map(|row| row[column].as_mut()) {
+                cell.relative = cell.mean.as_secs_f64() / fastest.as_secs_f64();
+            }
+        }
+
+        Self {
+            rows,
+            columns,
+            cells,
+            duration_format: DurationFormat::default(),
+        }
+    }
+
+    ///Sets how to write the times in each cell
+    #[must_use]
+    pub const fn with_duration_format(mut self, duration_format: DurationFormat) -> Self {
+        self.duration_format = duration_format;
+        self
+    }
+
+    ///Makes a Markdown table of the matrix, under a heading of `title`
+    #[must_use]
+    pub fn to_markdown(&self, title: &str) -> String {
+        let escape = |s: &str| s.replace('|', "\\|"); //so names can't break out of the table
+
+        //writing to a String can't fail
+        let mut markdown = String::new();
+        let _ = writeln!(markdown, "### {title}\n");
+        let _ = write!(markdown, "| Command |");
+        for column in &self.columns {
+            let _ = write!(markdown, " {} |", escape(column));
+        }
+        let _ = writeln!(markdown, "\n|---|{}", "---|".repeat(self.columns.len()));
+        for (row, cells) in self.rows.iter().zip(&self.cells) {
+            let _ = write!(markdown, "| {} |", escape(row));
+            for cell in cells {
+                match cell {
+                    Some(cell) => {
+                        let _ = write!(
+                            markdown,
+                            " {} ± {} ({:.2}×) |",
+                            self.duration_format.duration(cell.mean),
+                            self.duration_format.duration(cell.standard_deviation),
+                            cell.relative
+                        );
+                    }
+                    None => markdown += " - |",
+                }
+            }
+            markdown.push('\n');
+        }
+
+        markdown
+    }
+
+    ///Makes a standalone HTML page with the matrix as a table, with the fastest command in each column in bold
+    #[must_use]
+    pub fn to_html(&self, title: &str) -> String {
+        let title = escape_html(title);
+
+        //writing to a String can't fail
+        let mut html = String::new();
+        let _ = write!(
+            html,
+            "<!doctype html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\" />\n<title>{title}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n<h1>{title}</h1>\n<table>\n<tr><th>Command</th>"
+        );
+        for column in &self.columns {
+            let _ = write!(html, "<th>{}</th>", escape_html(column));
+        }
+        html += "</tr>\n";
+        for (row, cells) in self.rows.iter().zip(&self.cells) {
+            let _ = write!(html, "<tr><th>{}</th>", escape_html(row));
+            for cell in cells {
+                match cell {
+                    Some(cell) => {
+                        let class = if cell.relative <= 1.0 { " class=\"fastest\"" } else { "" };
+                        let _ = write!(
+                            html,
+                            "<td{class} title=\"{} runs\">{} ± {} <span class=\"relative\">({:.2}×)</span></td>",
+                            cell.runs,
+                            escape_html(&self.duration_format.duration(cell.mean)),
+                            escape_html(&self.duration_format.duration(cell.standard_deviation)),
+                            cell.relative
+                        );
+                    }
+                    None => html += "<td>-</td>",
+                }
+            }
+            html += "</tr>\n";
+        }
+        html += "</table>\n</body>\n</html>\n";
+
+        html
+    }
+
+    ///Writes the matrix to a file in `format`, adding the extension. Returns the number of bytes written
+    ///
+    /// # Errors
+    ///
+    /// Can have errors if we fail to create a file or write to it
+    pub fn export(&self, title: &str, format: MatrixFormat, file_name_input: impl AsRef<Path> + Display) -> io::Result<usize> {
+        let contents = match format {
+            MatrixFormat::Markdown => self.to_markdown(title),
+            MatrixFormat::HTML => self.to_html(title),
+        };
+        let mut file = File::create(format!("{file_name_input}.{}", format.extension()))?;
+        file.write_all(contents.as_bytes())?;
+        Ok(contents.len())
+    }
+}
+
+///Finds the index of a row or column, adding it to the end if it isn't there yet
+fn index_of(names: &mut Vec<String>, name: String) -> usize {
+    names.iter().position(|existing| existing == &name).unwrap_or_else(|| {
+        names.push(name);
+        names.len() - 1
+    })
+}
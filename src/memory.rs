@@ -0,0 +1,125 @@
+//! Module to export the peak memory of each run, so a change which trades memory for speed (or starts leaking) shows up next to the times.
+//!
+//! Every [`RunMeasurement`] already has the [`RunMeasurement::max_rss`] of its run - on Unix from `wait4`, and on Windows from `GetProcessMemoryInfo` (see [`crate::bencher::process`]). These exports put them in their own file next to the times, as bytes can't go on the same axis as microseconds.
+//!
+//! ## Example
+//! ```rust
+//! use benchmarker::{bencher::Runner, io::ExportType, memory::{export_memory, peak_memory}};
+//! use std::path::PathBuf;
+//!
+//! let (handle, rx, _) = Runner::new(PathBuf::from("/bin/echo"), vec!["Hello".into()], 3, None, 0, false)
+//!     .start()
+//!     .unwrap();
+//! handle.join().unwrap();
+//! let measurements: Vec<_> = rx.try_iter().collect();
+//!
+//! let memory = peak_memory(&measurements);
+//! assert_eq!(memory.iter().map(|(run, _)| *run).collect::<Vec<_>>(), [1, 2, 3]); //on Unix, every run has one
+//!
+//! let file = std::env::temp_dir().join("precipice_memory_example");
+//! export_memory(&memory, ExportType::CSV, file.display().to_string()).unwrap();
+//! let csv = std::fs::read_to_string(file.with_extension("csv")).unwrap();
+//! assert!(csv.starts_with("run,peak_rss_bytes\n1,"));
+//! ```
+
+use crate::{
+    bencher::RunMeasurement,
+    error::Error,
+    io::{write_export, write_json, write_plot, write_vega_lite, ExportType, VEGA_LITE_SCHEMA},
+};
+use plotly::{
+    common::{Mode, Title},
+    layout::Axis,
+    Layout, Plot, Scatter,
+};
+use serde_json::{json, Value};
+use std::fmt::Write as _;
+
+///Gets the peak memory of every run which has one, as its run number (starting from 1, like the GUI) and the most bytes any one command had resident - runs on platforms we can't read it on get skipped
+#[must_use]
+pub fn peak_memory(measurements: &[RunMeasurement]) -> Vec<(usize, u64)> {
+    measurements
+        .iter()
+        .filter_map(|measurement| measurement.max_rss.map(|max_rss| (measurement.index + 1, max_rss)))
+        .collect()
+}
+
+///Converts bytes to mebibytes for display
+#[must_use]
+#[allow(clippy::cast_precision_loss)]
+pub fn bytes_to_mebibytes(bytes: u64) -> f64 {
+    bytes as f64 / (1024.0 * 1024.0)
+}
+
+///Exports the peak memory of each run from [`peak_memory`] - either a CSV with one row per run, a HTML plot, or a Vega-Lite chart, both against the run number. The extension gets added to the file name
+///
+/// # Errors
+///
+/// Can have errors if we fail to create a file or write to it
+#[instrument(skip(memory))]
+pub fn export_memory(
+    memory: &[(usize, u64)],
+    export_ty: ExportType,
+    export_file_name: String,
+) -> Result<usize, Error> {
+    match export_ty {
+        ExportType::CSV => {
+            let mut to_be_written = String::from("run,peak_rss_bytes\n");
+            for (run, max_rss) in memory {
+                let _ = writeln!(to_be_written, "{run},{max_rss}"); //writing to a String can't fail
+            }
+
+            write_export(&format!("{export_file_name}.csv"), to_be_written.as_bytes(), None)
+        }
+        ExportType::HTML => write_plot(&plot_memory(memory), export_file_name),
+        ExportType::VegaLite => write_vega_lite(&vega_lite_memory(memory), export_file_name),
+        ExportType::JSON | ExportType::Hyperfine => {
+            //hyperfine's format is only for traces, so this gets the plain JSON
+            let rows: Vec<Value> = memory
+                .iter()
+                .map(|(run, max_rss)| json!({"run": run, "peak_rss_bytes": max_rss}))
+                .collect();
+            write_json(&Value::Array(rows), export_file_name)
+        }
+    }
+}
+
+///Makes a plot of the peak memory of each run in mebibytes, against the run number
+#[must_use]
+pub fn plot_memory(memory: &[(usize, u64)]) -> Plot {
+    let (runs, mebibytes): (Vec<usize>, Vec<f64>) = memory
+        .iter()
+        .map(|(run, max_rss)| (*run, bytes_to_mebibytes(*max_rss)))
+        .unzip();
+
+    let mut plot = Plot::new();
+    plot.add_trace(Scatter::new(runs, mebibytes).name("Peak memory").mode(Mode::LinesMarkers));
+    plot.set_layout(
+        Layout::new()
+            .title(Title::new("Peak Memory"))
+            .x_axis(Axis::new().title(Title::new("Run")))
+            .y_axis(Axis::new().title(Title::new("Peak RSS (MiB)"))),
+    );
+
+    plot
+}
+
+///Makes a Vega-Lite chart of the peak memory of each run, the same as [`plot_memory`]
+#[must_use]
+pub fn vega_lite_memory(memory: &[(usize, u64)]) -> Value {
+    let values: Vec<Value> = memory
+        .iter()
+        .map(|(run, max_rss)| json!({"run": run, "peak_rss_mib": bytes_to_mebibytes(*max_rss)}))
+        .collect();
+
+    json!({
+        "$schema": VEGA_LITE_SCHEMA,
+        "title": "Peak Memory",
+        "data": {"values": values},
+        "mark": {"type": "line", "point": true},
+        "encoding": {
+            "x": {"field": "run", "type": "quantitative", "title": "Run"},
+            "y": {"field": "peak_rss_mib", "type": "quantitative", "title": "Peak RSS (MiB)"},
+        },
+    })
+}
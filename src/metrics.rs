@@ -0,0 +1,341 @@
+//! Module to sample what the machine is doing in the background whilst a session runs, so spikes in run times can be lined up with things like CPU load or temperature.
+//!
+//! A [`MetricsSampler`] takes a [`SystemSample`] every interval on its own thread until it is finished. Each sample has the time since the sampler started, so the series can be plotted alongside the runs.
+//!
+//! Like [`crate::environment`], the metrics only get read on Linux (from `/proc` and `/sys`) - on other platforms every metric is [`None`].
+
+use crate::{
+    error::Error,
+    io::{write_export, write_json, write_plot, write_vega_lite, ExportType, VEGA_LITE_SCHEMA},
+};
+use plotly::{
+    common::{Mode, Title},
+    layout::Axis,
+    Layout, Plot, Scatter,
+};
+use serde_json::{json, Value};
+use std::{
+    fmt::Write as _,
+    io,
+    sync::mpsc::{channel, RecvTimeoutError, Sender},
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+///Default time between samples
+pub const DEFAULT_SAMPLE_INTERVAL: Duration = Duration::from_millis(100);
+
+///What the machine was doing at one point in the session. Anything we can't read on this platform is [`None`]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SystemSample {
+    ///How long after the sampler started this sample was taken
+    pub elapsed: Duration,
+    ///How busy all of the CPUs were since the last sample, as a percentage
+    pub cpu_utilisation: Option<f64>,
+    ///How much of the memory is in use, as a percentage
+    pub memory_used: Option<f64>,
+    ///The percentage of time in the last 10 seconds that something was stalled waiting for memory
+    pub memory_pressure: Option<f64>,
+    ///The CPU temperature in degrees celsius
+    pub cpu_temperature: Option<f64>,
+}
+
+///Samples the machine on a separate thread until it gets finished
+pub struct MetricsSampler {
+    ///Dropping or sending on this stops the sampler
+    stop_tx: Sender<()>,
+    ///The sampling thread, which gives back all of the samples
+    handle: JoinHandle<Vec<SystemSample>>,
+}
+
+impl MetricsSampler {
+    ///Starts sampling on a new thread, taking a sample every `interval`
+    ///
+    /// # Errors
+    ///
+    /// Can fail if we fail to spawn the thread
+    pub fn start(interval: Duration) -> io::Result<Self> {
+        let (stop_tx, stop_rx) = channel();
+        let handle = std::thread::Builder::new()
+            .name("metrics_sampler".into())
+            .spawn(move || {
+                let start = Instant::now();
+                let mut platform = platform::Sampler::new();
+                let _ = platform.sample(); //the first sample is just to get the CPU times to compare against
+                let mut samples = vec![];
+
+                while matches!(stop_rx.recv_timeout(interval), Err(RecvTimeoutError::Timeout)) {
+                    //keep on going until we get told to stop, or the sender gets dropped
+                    samples.push(SystemSample {
+                        elapsed: start.elapsed(),
+                        ..platform.sample()
+                    });
+                }
+
+                samples
+            })?;
+
+        Ok(Self { stop_tx, handle })
+    }
+
+    ///Stops sampling, and gives back all of the samples
+    #[must_use]
+    pub fn finish(self) -> Vec<SystemSample> {
+        let _ = self.stop_tx.send(()); //if the thread has gone, joining it will tell us
+        self.handle.join().unwrap_or_else(|_| {
+            error!("Metrics sampler panicked");
+            vec![]
+        })
+    }
+}
+
+///Exports a series of samples - either a CSV with one row per sample, a HTML line plot, or a Vega-Lite line chart. The extension gets added to the file name
+///
+/// # Errors
+///
+/// Can have errors if we fail to create a file or write to it
+#[instrument(skip(samples))]
+pub fn export_metrics(
+    samples: &[SystemSample],
+    export_ty: ExportType,
+    export_file_name: String,
+) -> Result<usize, Error> {
+    match export_ty {
+        ExportType::CSV => {
+            ///Formats a metric which might be missing as an empty cell
+            fn cell(metric: Option<f64>) -> String {
+                metric.map(|m| format!("{m:.2}")).unwrap_or_default()
+            }
+
+            let mut to_be_written =
+                String::from("elapsed_ms,cpu_utilisation,memory_used,memory_pressure,cpu_temperature\n");
+            for sample in samples {
+                let _ = writeln!(
+                    to_be_written,
+                    "{},{},{},{},{}",
+                    sample.elapsed.as_millis(),
+                    cell(sample.cpu_utilisation),
+                    cell(sample.memory_used),
+                    cell(sample.memory_pressure),
+                    cell(sample.cpu_temperature)
+                ); //writing to a String can't fail
+            }
+
+            write_export(&format!("{export_file_name}.csv"), to_be_written.as_bytes(), None)
+        }
+        ExportType::HTML => write_plot(&plot_metrics(samples), export_file_name),
+        ExportType::VegaLite => write_vega_lite(&vega_lite_metrics(samples), export_file_name),
+        ExportType::JSON | ExportType::Hyperfine => {
+            //hyperfine's format is only for traces, so this gets the plain JSON
+            let rows: Vec<Value> = samples
+                .iter()
+                .map(|sample| {
+                    json!({
+                        "elapsed_ms": sample.elapsed.as_millis(),
+                        "cpu_utilisation": sample.cpu_utilisation,
+                        "memory_used": sample.memory_used,
+                        "memory_pressure": sample.memory_pressure,
+                        "cpu_temperature": sample.cpu_temperature,
+                    })
+                })
+                .collect();
+            write_json(&Value::Array(rows), export_file_name)
+        }
+    }
+}
+
+///Gets one metric out of a sample
+type MetricGetter = fn(&SystemSample) -> Option<f64>;
+
+///Every metric we plot, with its name
+const METRICS: [(&str, MetricGetter); 4] = [
+    ("CPU utilisation (%)", |s| s.cpu_utilisation),
+    ("Memory used (%)", |s| s.memory_used),
+    ("Memory pressure (%)", |s| s.memory_pressure),
+    ("CPU temperature (°C)", |s| s.cpu_temperature),
+];
+
+///Makes a line plot of every metric over the session, skipping any which we never got
+#[must_use]
+pub fn plot_metrics(samples: &[SystemSample]) -> Plot {
+    let mut plot = Plot::new();
+    for (name, metric) in METRICS {
+        let (elapsed, values): (Vec<f64>, Vec<f64>) = samples
+            .iter()
+            .filter_map(|sample| metric(sample).map(|value| (sample.elapsed.as_secs_f64(), value)))
+            .unzip();
+        if !values.is_empty() {
+            plot.add_trace(Scatter::new(elapsed, values).name(name).mode(Mode::Lines));
+        }
+    }
+    plot.set_layout(
+        Layout::new()
+            .title(Title::new("System Metrics"))
+            .x_axis(Axis::new().title(Title::new("Time (s)"))),
+    );
+
+    plot
+}
+
+///Makes a Vega-Lite line chart of every metric over the session, the same as [`plot_metrics`]
+#[must_use]
+pub fn vega_lite_metrics(samples: &[SystemSample]) -> Value {
+    let values: Vec<Value> = METRICS
+        .iter()
+        .flat_map(|(name, metric)| {
+            samples.iter().filter_map(move |sample| {
+                metric(sample).map(|value| json!({"metric": name, "elapsed_s": sample.elapsed.as_secs_f64(), "value": value}))
+            })
+        })
+        .collect();
+
+    json!({
+        "$schema": VEGA_LITE_SCHEMA,
+        "title": "System Metrics",
+        "data": {"values": values},
+        "mark": "line",
+        "encoding": {
+            "x": {"field": "elapsed_s", "type": "quantitative", "title": "Time (s)"},
+            "y": {"field": "value", "type": "quantitative", "title": null},
+            "color": {"field": "metric", "type": "nominal", "title": "Metric"},
+        },
+    })
+}
+
+#[cfg(target_os = "linux")]
+///Linux sampling, using `/proc` and `/sys`
+mod platform {
+    use super::SystemSample;
+    use std::fs::{read_dir, read_to_string};
+
+    ///Keeps the CPU times from the last sample, as utilisation has to be worked out from the difference
+    pub struct Sampler {
+        ///The busy and total CPU time from the last sample, in clock ticks
+        last_cpu_times: Option<(u64, u64)>,
+    }
+
+    impl Sampler {
+        ///Constructor
+        pub const fn new() -> Self {
+            Self {
+                last_cpu_times: None,
+            }
+        }
+
+        ///Takes a sample - the elapsed time is left as the default
+        pub fn sample(&mut self) -> SystemSample {
+            let cpu_times = cpu_times();
+            let cpu_utilisation = self
+                .last_cpu_times
+                .zip(cpu_times)
+                .and_then(|((last_busy, last_total), (busy, total))| {
+                    let total = total.checked_sub(last_total).filter(|t| *t > 0)?;
+                    #[allow(clippy::cast_precision_loss)]
+                    Some(busy.saturating_sub(last_busy) as f64 / total as f64 * 100.0)
+                });
+            self.last_cpu_times = cpu_times;
+
+            SystemSample {
+                cpu_utilisation,
+                memory_used: memory_used(),
+                memory_pressure: memory_pressure(),
+                cpu_temperature: cpu_temperature(),
+                ..SystemSample::default()
+            }
+        }
+    }
+
+    ///Gets the busy and total CPU time across every CPU from the first line of `/proc/stat`
+    fn cpu_times() -> Option<(u64, u64)> {
+        let stat = read_to_string("/proc/stat").ok()?;
+        let times: Vec<u64> = stat
+            .lines()
+            .next()?
+            .strip_prefix("cpu ")?
+            .split_whitespace()
+            .filter_map(|t| t.parse().ok())
+            .collect(); //user nice system idle iowait irq softirq steal ...
+
+        let total: u64 = times.iter().sum();
+        let idle = times.get(3)? + times.get(4).unwrap_or(&0); //waiting on IO counts as idle
+        Some((total.saturating_sub(idle), total))
+    }
+
+    ///Gets the percentage of memory in use from `/proc/meminfo`
+    fn memory_used() -> Option<f64> {
+        let meminfo = read_to_string("/proc/meminfo").ok()?;
+        let find = |key: &str| -> Option<f64> {
+            meminfo
+                .lines()
+                .find_map(|line| line.strip_prefix(key))?
+                .trim()
+                .trim_end_matches("kB")
+                .trim()
+                .parse()
+                .ok()
+        };
+
+        let total = find("MemTotal:").filter(|t| *t > 0.0)?;
+        let available = find("MemAvailable:")?;
+        Some((1.0 - available / total) * 100.0)
+    }
+
+    ///Gets the 10 second average from the `some` line of `/proc/pressure/memory` - only there on kernels with PSI
+    fn memory_pressure() -> Option<f64> {
+        read_to_string("/proc/pressure/memory")
+            .ok()?
+            .lines()
+            .find(|line| line.starts_with("some"))?
+            .split_whitespace()
+            .find_map(|part| part.strip_prefix("avg10="))?
+            .parse()
+            .ok()
+    }
+
+    ///Gets the CPU temperature from the thermal zones, preferring ones that look like they're for the CPU package
+    fn cpu_temperature() -> Option<f64> {
+        let zones: Vec<_> = read_dir("/sys/class/thermal")
+            .ok()?
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with("thermal_zone"))
+            })
+            .collect();
+
+        let is_cpu = |kind: &str| kind.contains("pkg") || kind.contains("cpu") || kind.contains("k10temp");
+        let zone = zones
+            .iter()
+            .find(|zone| {
+                read_to_string(zone.join("type")).is_ok_and(|kind| is_cpu(&kind.to_lowercase()))
+            })
+            .or_else(|| zones.first())?;
+
+        let millidegrees: f64 = read_to_string(zone.join("temp")).ok()?.trim().parse().ok()?;
+        Some(millidegrees / 1_000.0)
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+///Fallback for platforms without any sampling yet
+mod platform {
+    use super::SystemSample;
+
+    ///Nothing to keep track of here
+    pub struct Sampler;
+
+    impl Sampler {
+        ///Constructor
+        pub const fn new() -> Self {
+            Self
+        }
+
+        ///Every metric is [`None`]
+        #[allow(clippy::unused_self, clippy::needless_pass_by_ref_mut)]
+        pub fn sample(&mut self) -> SystemSample {
+            SystemSample::default()
+        }
+    }
+}
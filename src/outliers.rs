@@ -0,0 +1,65 @@
+//! Module for picking out individual runs which are far away from the rest, eg. because something else woke up part-way through.
+//!
+//! This uses the modified z-score from Iglewicz and Hoaglin - how many standard deviations a run is from the median, estimating the standard deviation from the median absolute deviation (see [`crate::bencher::calculate_median_mad_iqr`]). Unlike a normal z-score, the outliers themselves barely move the median and MAD, so one huge run can't hide the others.
+//!
+//! ## Example
+//! ```rust
+//! use benchmarker::outliers::OutlierDetector;
+//!
+//! let runs: Vec<u128> = (0..50).map(|i| 1_000 + i % 10).chain([5_000]).collect();
+//! let detector = OutlierDetector::new(&runs).unwrap();
+//!
+//! assert!(detector.is_outlier(5_000));
+//! assert!(!detector.is_outlier(1_005));
+//! assert!(detector.z_score(5_000) > 100.0);
+//! ```
+
+use crate::bencher::calculate_median_mad_iqr;
+
+///How many modified z-scores a run has to be from the median to count as an outlier - the usual cut-off from Iglewicz and Hoaglin
+pub const OUTLIER_Z_SCORE: f64 = 3.5;
+
+///The fewest runs we look for outliers in - with fewer, the median and MAD move too much with each new run to trust
+pub const MIN_OUTLIER_RUNS: usize = 10;
+
+///Turns a median absolute deviation into an estimate of the standard deviation, for normally distributed runs
+const MAD_TO_STANDARD_DEVIATION: f64 = 1.4826;
+
+///Works out how far runs are from the rest of a set of runs
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct OutlierDetector {
+    ///The median run, in microseconds
+    median: f64,
+    ///The estimated standard deviation, in microseconds
+    scale: f64,
+}
+
+impl OutlierDetector {
+    ///Makes a detector from a list of microsecond run values. Gives back [`None`] if there are fewer than [`MIN_OUTLIER_RUNS`], or if at least half of the runs took exactly the same time (as then there is no spread to compare against)
+    #[must_use]
+    pub fn new(runs: &[u128]) -> Option<Self> {
+        if runs.len() < MIN_OUTLIER_RUNS {
+            return None;
+        }
+
+        let (median, median_absolute_deviation, _) = calculate_median_mad_iqr(runs)?;
+        let scale = median_absolute_deviation.as_secs_f64() * 1_000_000.0 * MAD_TO_STANDARD_DEVIATION;
+        (scale > 0.0).then_some(Self {
+            median: median.as_secs_f64() * 1_000_000.0,
+            scale,
+        })
+    }
+
+    ///The modified z-score of a run in microseconds - positive if it was slower than the median, and negative if it was faster
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn z_score(&self, run: u128) -> f64 {
+        (run as f64 - self.median) / self.scale
+    }
+
+    ///Whether a run in microseconds is an outlier, either way
+    #[must_use]
+    pub fn is_outlier(&self, run: u128) -> bool {
+        self.z_score(run).abs() > OUTLIER_Z_SCORE
+    }
+}
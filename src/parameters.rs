@@ -0,0 +1,163 @@
+//! Module for traces from parameter sweeps - the same benchmark run with eg. 1, 2, 4 and 8 threads.
+//!
+//! Each trace records its parameters as tags starting with [`PARAMETER_TAG_PREFIX`], like `param.threads=4` (suites do this from each entry's `parameters`, and the runner CLI from `--param`). Exports can then put one parameter on the x-axis, with the mean and its 95% confidence interval at each value, rather than a flat list of similarly-named traces.
+//!
+//! ## Example
+//! ```rust
+//! use benchmarker::{parameters::{parameter_tags, sweep_series}, tags::{parse_tags, TraceTags}};
+//!
+//! let traces = vec![
+//!     ("sort_4".to_string(), vec![50, 52, 54]),
+//!     ("sort_1".to_string(), vec![200, 210, 190]),
+//!     ("search_1".to_string(), vec![30, 31]),
+//! ];
+//! let tags: TraceTags = [
+//!     ("sort_4", "algorithm=sort, threads=4"),
+//!     ("sort_1", "algorithm=sort, threads=1"),
+//!     ("search_1", "algorithm=search, threads=1"),
+//! ]
+//! .into_iter()
+//! .map(|(name, parameters)| (name.to_string(), parameter_tags(&parse_tags(parameters).unwrap())))
+//! .collect();
+//!
+//! let series = sweep_series(&traces, &tags, "threads");
+//! assert_eq!(series.len(), 2); //one line for each value of the other parameters
+//! assert_eq!(series[0].name, "algorithm=sort");
+//! let values: Vec<&str> = series[0].points.iter().map(|point| point.value.as_str()).collect();
+//! assert_eq!(values, ["1", "4"]); //numeric values get sorted
+//! assert_eq!(series[0].points[0].mean, 200.0);
+//! ```
+
+use crate::tags::{tags_to_string, Tags, TraceTags};
+
+///The start of the tag keys which hold a trace's parameters, so `param.threads=4` is the parameter `threads` with a value of `4`
+pub const PARAMETER_TAG_PREFIX: &str = "param.";
+
+///How many standard errors either side of the mean the 95% confidence interval goes, using the normal approximation
+const CONFIDENCE_Z: f64 = 1.96;
+
+///Turns a set of parameters (like `threads=4`) into the tags which record them on a trace (like `param.threads=4`)
+#[must_use]
+pub fn parameter_tags(parameters: &Tags) -> Tags {
+    parameters
+        .iter()
+        .map(|(name, value)| (format!("{PARAMETER_TAG_PREFIX}{name}"), value.clone()))
+        .collect()
+}
+
+///Gets the parameters back out of a trace's tags, without the [`PARAMETER_TAG_PREFIX`]
+#[must_use]
+pub fn parameters(tags: &Tags) -> Tags {
+    tags.iter()
+        .filter_map(|(key, value)| Some((key.strip_prefix(PARAMETER_TAG_PREFIX)?.to_string(), value.clone())))
+        .collect()
+}
+
+///Gets the mean of some microsecond runs, with how far either side of it the 95% confidence interval goes. A single run has an interval of 0, and there's nothing for no runs
+#[allow(clippy::cast_precision_loss)]
+#[must_use]
+pub fn mean_confidence_interval(runs: &[u128]) -> Option<(f64, f64)> {
+    if runs.is_empty() {
+        return None;
+    }
+
+    let len = runs.len() as f64;
+    let mean = runs.iter().map(|run| *run as f64).sum::<f64>() / len;
+    if runs.len() == 1 {
+        return Some((mean, 0.0));
+    }
+    let variance = runs.iter().map(|run| (*run as f64 - mean).powi(2)).sum::<f64>() / (len - 1.0);
+
+    Some((mean, CONFIDENCE_Z * (variance / len).sqrt()))
+}
+
+///The runs at one value of the swept parameter
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParameterPoint {
+    ///The value of the parameter
+    pub value: String,
+    ///The mean run time in microseconds
+    pub mean: f64,
+    ///How far either side of the mean the 95% confidence interval goes, in microseconds
+    pub confidence_interval: f64,
+    ///How many runs went into the mean
+    pub runs: usize,
+}
+
+///One line in a parameter sweep - the traces which have the same values for every other parameter
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParameterSeries {
+    ///The other parameters, like `algorithm=sort`, or the swept parameter's name if there aren't any
+    pub name: String,
+    ///The points, sorted by value if every value is a number and in the order they first turn up otherwise
+    pub points: Vec<ParameterPoint>,
+}
+
+///Splits the traces with the parameter `parameter` into series, with a point for each of its values.
+///
+/// Traces are put in the same series if the rest of their parameters match, and traces with the same value in a series get merged (eg. the same sweep run twice). Traces without the parameter get left out
+#[allow(clippy::type_complexity)] //only for the grouping in here
+#[must_use]
+pub fn sweep_series(traces: &[(String, Vec<u128>)], tags: &TraceTags, parameter: &str) -> Vec<ParameterSeries> {
+    let mut grouped: Vec<(String, Vec<(String, Vec<u128>)>)> = vec![]; //the series names, with the runs for each value
+    for (name, runs) in traces {
+        let mut trace_parameters = tags.get(name).map(parameters).unwrap_or_default();
+        let Some(value) = trace_parameters.remove(parameter) else {
+            continue;
+        };
+        let series_name = if trace_parameters.is_empty() {
+            parameter.to_string()
+        } else {
+            tags_to_string(&trace_parameters)
+        };
+
+        let index = grouped.iter().position(|(name, _)| name == &series_name).unwrap_or_else(|| {
+            grouped.push((series_name, vec![]));
+            grouped.len() - 1
+        });
+        let series = &mut grouped[index].1;
+        match series.iter_mut().find(|(existing, _)| existing == &value) {
+            Some((_, existing_runs)) => existing_runs.extend_from_slice(runs),
+            None => series.push((value, runs.clone())),
+        }
+    }
+
+    let numeric = values_are_numeric(grouped.iter().flat_map(|(_, points)| points.iter().map(|(value, _)| value.as_str())));
+    grouped
+        .into_iter()
+        .map(|(name, mut points)| {
+            if numeric {
+                points.sort_by(|(a, _), (b, _)| parse_value(a).total_cmp(&parse_value(b)));
+            }
+            let points = points
+                .into_iter()
+                .filter_map(|(value, runs)| {
+                    let (mean, confidence_interval) = mean_confidence_interval(&runs)?;
+                    Some(ParameterPoint {
+                        value,
+                        mean,
+                        confidence_interval,
+                        runs: runs.len(),
+                    })
+                })
+                .collect();
+            ParameterSeries { name, points }
+        })
+        .collect()
+}
+
+///Whether every value of the swept parameter is a number, so it can go on a numeric axis
+#[must_use]
+pub fn series_are_numeric(series: &[ParameterSeries]) -> bool {
+    values_are_numeric(series.iter().flat_map(|series| series.points.iter().map(|point| point.value.as_str())))
+}
+
+///Whether every one of the values is a number
+fn values_are_numeric<'a>(mut values: impl Iterator<Item = &'a str>) -> bool {
+    values.all(|value| value.trim().parse::<f64>().is_ok())
+}
+
+///Parses a value which [`values_are_numeric`] has already checked
+fn parse_value(value: &str) -> f64 {
+    value.trim().parse().unwrap_or(f64::NAN)
+}
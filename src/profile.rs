@@ -0,0 +1,377 @@
+//! Module to profile a few extra runs of a binary and draw them as a flamegraph, so when a regression shows up you can see where the time went.
+//!
+//! The profiled runs are separate from the measured runs, as the profiler slows the binary down. On Linux, each run is wrapped in `perf record`, and on macOS in `dtrace` - other platforms aren't supported yet. Only the binary itself gets profiled, not anything it gets piped into.
+//!
+//! The stacks from every run get folded together (like the `stackcollapse` scripts from Brendan Gregg's [FlameGraph](https://github.com/brendangregg/FlameGraph)) and drawn as an SVG, with the callers at the bottom and each frame as wide as the share of samples it was on the stack for.
+//!
+//! ## Example
+//! ```rust
+//! use benchmarker::profile::{fold_perf_script, flamegraph_svg};
+//! use std::collections::HashMap;
+//!
+//! let script = "app 123 1.0: 1 cycles:\n\t1 work+0x10 (/bin/app)\n\t2 main+0x20 (/bin/app)\n";
+//! let mut stacks = HashMap::new();
+//! fold_perf_script(script, &mut stacks);
+//! assert_eq!(stacks.get("app;main;work"), Some(&1));
+//!
+//! assert!(flamegraph_svg(&stacks, "Flamegraph").contains("main"));
+//! ```
+
+use crate::bencher::template::{needs_directory, RunScope};
+use rand::Rng;
+use std::{
+    collections::{BTreeMap, HashMap},
+    env,
+    fmt::Write as _,
+    fs::{create_dir, read_to_string, remove_dir_all, File},
+    io::{self, Write},
+    path::Path,
+    process::{Command, Stdio},
+};
+
+///Default number of samples the profiler takes every second
+pub const DEFAULT_SAMPLE_FREQUENCY: u32 = 999;
+
+///Folded stacks - each stack is the frames from the outermost caller in, joined with `;`, and maps to how many samples had that stack
+pub type FoldedStacks = HashMap<String, u64>;
+
+///The width of the flamegraph SVG in pixels
+const IMAGE_WIDTH: f64 = 1200.0;
+///The height of each frame in pixels
+const FRAME_HEIGHT: f64 = 16.0;
+///The space above the frames for the title
+const TITLE_HEIGHT: f64 = 32.0;
+///Roughly how wide each character of a frame name is, to know how much fits in a frame
+const CHARACTER_WIDTH: f64 = 7.0;
+///Frames thinner than this don't get drawn
+const MIN_FRAME_WIDTH: f64 = 0.1;
+
+///Runs the binary `runs` times under the profiler for this platform, folding all of the stacks together. The CLI args can use the same placeholders as a [`crate::bencher::Runner`]
+///
+/// # Errors
+///
+/// Can fail if there isn't a profiler for this platform, the profiler isn't installed or fails, or we fail to make or read its output
+#[instrument]
+pub fn profile_runs(binary: &Path, cli_args: &[String], runs: usize, frequency: u32) -> io::Result<FoldedStacks> {
+    let directory = env::temp_dir().join(format!(
+        "precipice_profile_{}_{:x}",
+        std::process::id(),
+        rand::thread_rng().gen::<u32>()
+    ));
+    create_dir(&directory)?;
+
+    let result = (|| {
+        let needs_directory = cli_args.iter().any(|arg| needs_directory(arg));
+        let mut stacks = FoldedStacks::new();
+
+        for run_index in 0..runs {
+            let scope = RunScope::new(run_index, needs_directory)?;
+            let cli_args: Vec<String> = cli_args.iter().map(|arg| scope.expand(arg)).collect();
+            let output_file = directory.join(format!("run_{run_index}.out"));
+
+            if cfg!(target_os = "linux") {
+                record_perf(binary, &cli_args, frequency, &output_file, &mut stacks)?;
+            } else if cfg!(target_os = "macos") {
+                record_dtrace(binary, &cli_args, frequency, &output_file, &mut stacks)?;
+            } else {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "profiling is only supported on Linux (with perf) and macOS (with dtrace)",
+                ));
+            }
+            trace!(%run_index, stacks = %stacks.len(), "Profiled run");
+        }
+
+        Ok(stacks)
+    })();
+
+    if let Err(e) = remove_dir_all(&directory) {
+        warn!(%e, ?directory, "Unable to clean up profiler output");
+    }
+    result
+}
+
+///Runs a command, turning a failed exit status into an error with whatever it wrote to stderr
+fn run_profiler(command: &mut Command) -> io::Result<Vec<u8>> {
+    let output = command.stdin(Stdio::null()).output().map_err(|e| {
+        if e.kind() == io::ErrorKind::NotFound {
+            io::Error::new(
+                e.kind(),
+                format!("unable to find {} - is it installed?", command.get_program().to_string_lossy()),
+            )
+        } else {
+            e
+        }
+    })?;
+    if output.status.success() {
+        Ok(output.stdout)
+    } else {
+        Err(io::Error::other(format!(
+            "profiler failed with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )))
+    }
+}
+
+///Records one run with `perf record`, and folds the output of `perf script`
+fn record_perf(
+    binary: &Path,
+    cli_args: &[String],
+    frequency: u32,
+    output_file: &Path,
+    stacks: &mut FoldedStacks,
+) -> io::Result<()> {
+    run_profiler(
+        Command::new("perf")
+            .args(["record", "--quiet", "-g", "-F", &frequency.to_string(), "-o"])
+            .arg(output_file)
+            .arg("--")
+            .arg(binary)
+            .args(cli_args)
+            .stdout(Stdio::null()), //the binary's output would just get in the way
+    )?;
+
+    let script = run_profiler(Command::new("perf").arg("script").arg("-i").arg(output_file))?;
+    fold_perf_script(&String::from_utf8_lossy(&script), stacks);
+    Ok(())
+}
+
+///Records one run with `dtrace`, and folds the aggregated user stacks it prints
+fn record_dtrace(
+    binary: &Path,
+    cli_args: &[String],
+    frequency: u32,
+    output_file: &Path,
+    stacks: &mut FoldedStacks,
+) -> io::Result<()> {
+    let command = shell_words::join(
+        std::iter::once(binary.to_string_lossy().into_owned()).chain(cli_args.iter().cloned()),
+    ); //dtrace takes the command as one string
+
+    run_profiler(
+        Command::new("dtrace")
+            .args(["-x", "ustackframes=100", "-n"])
+            .arg(format!("profile-{frequency} /pid == $target/ {{ @[ustack()] = count(); }}"))
+            .arg("-o")
+            .arg(output_file)
+            .arg("-c")
+            .arg(command)
+            .stdout(Stdio::null()),
+    )?;
+
+    fold_dtrace(&read_to_string(output_file)?, stacks);
+    Ok(())
+}
+
+///Removes the `+0x1f` offset from the end of a frame, so every sample in a function gets merged together
+fn strip_offset(frame: &str) -> &str {
+    frame
+        .rsplit_once("+0x")
+        .map_or(frame, |(function, _)| function)
+}
+
+///Folds the output of `perf script` into `stacks`.
+///
+/// Each sample is a header line starting with the command name, then one line per frame from the innermost out, then a blank line.
+pub fn fold_perf_script(script: &str, stacks: &mut FoldedStacks) {
+    let mut command: Option<&str> = None;
+    let mut frames: Vec<String> = vec![];
+
+    let mut finish_sample = |command: Option<&str>, frames: &mut Vec<String>| {
+        if let Some(command) = command {
+            let stack = std::iter::once(command.to_string())
+                .chain(frames.drain(..).rev())
+                .collect::<Vec<_>>()
+                .join(";");
+            *stacks.entry(stack).or_default() += 1;
+        }
+        frames.clear();
+    };
+
+    for line in script.lines() {
+        if line.trim().is_empty() {
+            finish_sample(command.take(), &mut frames);
+        } else if line.starts_with(char::is_whitespace) {
+            //a frame, like `	55d0c2 main+0x12 (/path/to/binary)` - C++ symbols can have spaces, so split off the address and library instead of splitting on whitespace
+            let rest = line.trim().split_once(' ').map_or("", |(_address, rest)| rest);
+            let (symbol, library) = rest
+                .strip_suffix(')')
+                .and_then(|rest| rest.rsplit_once(" ("))
+                .unwrap_or((rest, ""));
+            let symbol = if symbol.is_empty() || symbol == "[unknown]" {
+                //use the library if we don't know the function
+                library
+                    .rsplit('/')
+                    .next()
+                    .filter(|library| !library.is_empty())
+                    .map_or_else(|| "[unknown]".to_string(), |library| format!("[{library}]"))
+            } else {
+                strip_offset(symbol).replace(';', ":")
+            };
+            frames.push(symbol);
+        } else if command.is_none() {
+            command = line.split_whitespace().next(); //the header
+        }
+    }
+    finish_sample(command, &mut frames);
+}
+
+///Folds the output of a `dtrace` aggregation of `ustack()` into `stacks`.
+///
+/// Each stack is one line per frame from the innermost out, like `binary``main+0x12`, followed by the count on its own line.
+pub fn fold_dtrace(output: &str, stacks: &mut FoldedStacks) {
+    let mut frames: Vec<String> = vec![];
+
+    for line in output.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Ok(count) = line.parse::<u64>() {
+            if !frames.is_empty() {
+                let stack = std::mem::take(&mut frames).into_iter().rev().collect::<Vec<_>>().join(";");
+                *stacks.entry(stack).or_default() += count;
+            }
+        } else {
+            let function = line.split_once('`').map_or(line, |(_, function)| function); //drop the module
+            frames.push(strip_offset(function).replace(';', ":"));
+        }
+    }
+}
+
+///One frame in the flamegraph, with everything it called
+#[derive(Default)]
+struct Frame {
+    ///How many samples were in this frame or something it called
+    samples: u64,
+    ///The frames called from this one, sorted by name like the original flamegraphs
+    children: BTreeMap<String, Self>,
+}
+
+impl Frame {
+    ///Adds a folded stack to the tree
+    fn add<'a>(&mut self, mut stack: impl Iterator<Item = &'a str>, samples: u64) {
+        self.samples += samples;
+        if let Some(name) = stack.next() {
+            self.children
+                .entry(name.to_string())
+                .or_default()
+                .add(stack, samples);
+        }
+    }
+
+    ///How many frames deep this goes, not including this one
+    fn depth(&self) -> usize {
+        self.children
+            .values()
+            .map(|child| child.depth() + 1)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+///Escapes text to go inside SVG
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+///Gets a warm colour for a frame from its name, so the same function is always the same colour
+fn colour(name: &str) -> (u8, u8, u8) {
+    let hash = name
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        }); //FNV-1a
+    let [a, b, c, ..] = hash.to_le_bytes();
+    (205 + a % 50, b % 230, c % 55)
+}
+
+///Draws the folded stacks as an SVG flamegraph, with the callers at the bottom
+#[must_use]
+#[allow(
+    clippy::cast_precision_loss,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss
+)]
+pub fn flamegraph_svg(stacks: &FoldedStacks, title: &str) -> String {
+    ///Draws a frame and then everything it called above it
+    fn draw(svg: &mut String, name: &str, frame: &Frame, x: f64, depth: usize, total: u64, image_height: f64) {
+        let width = frame.samples as f64 / total as f64 * IMAGE_WIDTH;
+        if width < MIN_FRAME_WIDTH {
+            return;
+        }
+
+        let top = (depth as f64).mul_add(-FRAME_HEIGHT, image_height - FRAME_HEIGHT);
+        let (red, green, blue) = colour(name);
+        let percentage = frame.samples as f64 / total as f64 * 100.0;
+        let fitting_characters = ((width - 6.0) / CHARACTER_WIDTH).max(0.0) as usize;
+        let label = if name.chars().count() <= fitting_characters {
+            name.to_string()
+        } else if fitting_characters > 2 {
+            name.chars().take(fitting_characters - 2).collect::<String>() + ".."
+        } else {
+            String::new()
+        };
+
+        let _ = writeln!(
+            svg,
+            r#"<g><title>{} ({} samples, {percentage:.2}%)</title><rect x="{x:.1}" y="{top:.1}" width="{width:.1}" height="{}" fill="rgb({red},{green},{blue})" rx="2" ry="2"/><text x="{:.1}" y="{:.1}">{}</text></g>"#,
+            escape(name),
+            frame.samples,
+            FRAME_HEIGHT - 1.0,
+            x + 3.0,
+            top + FRAME_HEIGHT - 4.0,
+            escape(&label)
+        ); //writing to a String can't fail
+
+        let mut child_x = x;
+        for (child_name, child) in &frame.children {
+            draw(svg, child_name, child, child_x, depth + 1, total, image_height);
+            child_x += child.samples as f64 / total as f64 * IMAGE_WIDTH;
+        }
+    }
+
+    let mut root = Frame::default();
+    for (stack, samples) in stacks {
+        root.add(stack.split(';').filter(|frame| !frame.is_empty()), *samples);
+    }
+
+    let depth = root.depth() + 1; //everything, plus the root
+    let image_height = (depth as f64).mul_add(FRAME_HEIGHT, TITLE_HEIGHT);
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{IMAGE_WIDTH}" height="{image_height}" viewBox="0 0 {IMAGE_WIDTH} {image_height}" font-family="monospace" font-size="12">"#
+    );
+    let _ = writeln!(
+        svg,
+        r##"<rect width="100%" height="100%" fill="#f8f8f8"/><text x="{}" y="20" text-anchor="middle" font-size="16">{}</text>"##,
+        IMAGE_WIDTH / 2.0,
+        escape(title)
+    );
+    if root.samples > 0 {
+        draw(&mut svg, "all", &root, 0.0, 0, root.samples, image_height);
+    }
+    svg.push_str("</svg>\n");
+
+    svg
+}
+
+///Draws the folded stacks as a flamegraph, and writes it to a file. The `.svg` extension gets added to the file name
+///
+/// # Errors
+///
+/// Can have errors if we fail to create a file or write to it
+#[instrument(skip(stacks))]
+pub fn export_flamegraph(stacks: &FoldedStacks, title: &str, export_file_name: &str) -> io::Result<usize> {
+    let svg = flamegraph_svg(stacks, title);
+    let mut file = File::create(format!("{export_file_name}.svg"))?;
+    file.write_all(svg.as_bytes())?;
+    Ok(svg.len())
+}
@@ -0,0 +1,105 @@
+//! Module to export session summaries for Prometheus, so nightly benchmark numbers can land on existing Grafana dashboards.
+//!
+//! Everything is in the [text exposition format](https://prometheus.io/docs/instrumenting/exposition_formats/). The run times become a summary metric (`precipice_run_duration_seconds`, with quantiles, a sum and a count), alongside gauges for the mean, standard deviation, and min and max. Every metric has a `benchmark` label with the trace name and a `command` label with what got run.
+//!
+//! The text can be written to a file for the node exporter's textfile collector with [`export_prometheus`], or pushed to a Pushgateway with [`push_to_gateway`].
+//!
+//! ## Example
+//! ```rust
+//! use benchmarker::prometheus::prometheus_text;
+//!
+//! let text = prometheus_text("sort", "./sort --fast", &[1_000, 2_000, 3_000]).unwrap();
+//! assert!(text.contains("precipice_run_duration_seconds_count{benchmark=\"sort\",command=\"./sort --fast\"} 3"));
+//! assert!(text.contains("precipice_mean_seconds{benchmark=\"sort\",command=\"./sort --fast\"} 0.002"));
+//! ```
+
+use crate::{
+    bencher::stats::{calculate_mean_standard_deviation, quantile},
+    upload::curl_post,
+};
+use std::{
+    fmt::{Display, Write as _},
+    fs::File,
+    io::{self, Write},
+    path::Path,
+};
+
+///The quantiles to give for the run duration summary
+const QUANTILES: [f64; 5] = [0.1, 0.25, 0.5, 0.75, 0.9];
+
+///Escapes a label value - backslashes, quotes and newlines all need escaping
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+///Makes the Prometheus text for a session, from a list of microsecond run values. Gives back [`None`] if there weren't any runs.
+#[must_use]
+#[allow(clippy::cast_precision_loss)]
+pub fn prometheus_text(benchmark_name: &str, command: &str, runs: &[u128]) -> Option<String> {
+    let (mean, standard_deviation) = calculate_mean_standard_deviation(runs)?;
+    let mut sorted: Vec<f64> = runs.iter().map(|run| *run as f64 / 1_000_000.0).collect();
+    sorted.sort_by(f64::total_cmp);
+
+    let labels = format!(
+        "benchmark=\"{}\",command=\"{}\"",
+        escape_label(benchmark_name),
+        escape_label(command)
+    );
+    let mut text = String::new();
+
+    //writing to a String can't fail
+    let _ = writeln!(text, "# HELP precipice_run_duration_seconds How long each run took.");
+    let _ = writeln!(text, "# TYPE precipice_run_duration_seconds summary");
+    for quantile_to_give in QUANTILES {
+        let _ = writeln!(
+            text,
+            "precipice_run_duration_seconds{{{labels},quantile=\"{quantile_to_give}\"}} {}",
+            quantile(&sorted, quantile_to_give)
+        );
+    }
+    let _ = writeln!(text, "precipice_run_duration_seconds_sum{{{labels}}} {}", sorted.iter().sum::<f64>());
+    let _ = writeln!(text, "precipice_run_duration_seconds_count{{{labels}}} {}", sorted.len());
+
+    for (name, help, value) in [
+        ("mean", "The mean run time.", mean.as_secs_f64()),
+        ("standard_deviation", "The population standard deviation of the run times.", standard_deviation.as_secs_f64()),
+        ("min", "The fastest run.", sorted[0]),
+        ("max", "The slowest run.", sorted[sorted.len() - 1]),
+    ] {
+        let _ = writeln!(text, "# HELP precipice_{name}_seconds {help}");
+        let _ = writeln!(text, "# TYPE precipice_{name}_seconds gauge");
+        let _ = writeln!(text, "precipice_{name}_seconds{{{labels}}} {value}");
+    }
+
+    Some(text)
+}
+
+///Writes the Prometheus text to a file, adding the `.prom` extension that the node exporter's textfile collector looks for
+///
+/// # Errors
+///
+/// Can have errors if we fail to create a file or write to it
+pub fn export_prometheus(text: &str, file_name_input: impl AsRef<Path> + Display) -> io::Result<usize> {
+    let mut file = File::create(format!("{file_name_input}.prom"))?;
+    file.write_all(text.as_bytes())?;
+    Ok(text.len())
+}
+
+///Pushes the Prometheus text to a Pushgateway, under the `precipice` job, replacing anything from an earlier push of the same benchmark. This needs `curl`
+///
+/// # Errors
+///
+/// Can fail if `curl` isn't installed, or the push fails
+#[instrument(skip(text))]
+pub fn push_to_gateway(gateway: &str, benchmark_name: &str, text: &str) -> io::Result<()> {
+    let mut endpoint = format!("{}/metrics/job/precipice/benchmark/", gateway.trim_end_matches('/'));
+    for byte in benchmark_name.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-_.".contains(&byte) {
+            endpoint.push(char::from(byte));
+        } else {
+            let _ = write!(endpoint, "%{byte:02X}"); //writing to a String can't fail
+        }
+    } //the benchmark name is part of the path, so it needs encoding
+
+    curl_post(&endpoint, None, "text/plain; version=0.0.4", text)
+}
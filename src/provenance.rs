@@ -0,0 +1,143 @@
+//! Module for recording where a session's runs came from, so traces can always be traced back to how they were produced - even after being merged with others in the exporter.
+//!
+//! Each session gets a random [`Provenance::session_id`], along with the exact command line, a SHA-256 hash of the binary, and when it started and finished. These get stored as tags (see [`crate::tags`]) so they go into every export alongside the user's own tags, and so traces can be filtered by them like any other tag - eg. `--filter-tag session_id=...`.
+//!
+//! ## Example
+//! ```rust
+//! use benchmarker::provenance::{quote_command, Provenance};
+//! use std::path::Path;
+//!
+//! let binary = Path::new("/definitely/not/a/binary");
+//! let provenance = Provenance::start(quote_command(binary, &["--size".into(), "big file.txt".into()]))
+//!     .with_binary_hash(binary)
+//!     .finish();
+//! assert_eq!(provenance.command, "/definitely/not/a/binary --size 'big file.txt'");
+//! assert_eq!(provenance.binary_hash, None); //we couldn't read the binary
+//!
+//! let tags = provenance.to_tags();
+//! assert_eq!(Provenance::from_tags(&tags), Some(provenance));
+//! ```
+
+use crate::tags::Tags;
+use chrono::{DateTime, SecondsFormat, Utc};
+use sha2::{Digest, Sha256};
+use std::{
+    fmt::Write as _,
+    fs::File,
+    io::{self, BufReader},
+    path::Path,
+};
+use uuid::Uuid;
+
+///The tag for [`Provenance::session_id`]
+pub const SESSION_ID_TAG: &str = "session_id";
+///The tag for [`Provenance::command`]
+pub const COMMAND_TAG: &str = "command";
+///The tag for [`Provenance::binary_hash`]
+pub const BINARY_HASH_TAG: &str = "binary_sha256";
+///The tag for [`Provenance::started_at`]
+pub const STARTED_AT_TAG: &str = "started_at";
+///The tag for [`Provenance::finished_at`]
+pub const FINISHED_AT_TAG: &str = "finished_at";
+
+///Where a session's runs came from
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Provenance {
+    ///A random UUID for the session, so it can be told apart from every other session of the same command
+    pub session_id: String,
+    ///The exact command line, quoted so it can be pasted back into a shell
+    pub command: String,
+    ///The SHA-256 hash of the binary in hex - [`None`] if we couldn't read it, eg. when it runs on a remote agent
+    pub binary_hash: Option<String>,
+    ///When the session started
+    pub started_at: DateTime<Utc>,
+    ///When the session finished
+    pub finished_at: DateTime<Utc>,
+}
+
+impl Provenance {
+    ///Starts recording a new session of a command (eg. from [`quote_command`]) now. Call [`Provenance::finish`] once the runs are done
+    #[must_use]
+    pub fn start(command: String) -> Self {
+        let now = Utc::now();
+
+        Self {
+            session_id: Uuid::new_v4().to_string(),
+            command,
+            binary_hash: None,
+            started_at: now,
+            finished_at: now,
+        }
+    }
+
+    ///Hashes the binary that got run. This is separate from [`Provenance::start`], as the binary isn't on this machine when running on a remote agent
+    #[must_use]
+    pub fn with_binary_hash(mut self, binary: &Path) -> Self {
+        self.binary_hash = hash_file(binary)
+            .map_err(|e| warn!(%e, ?binary, "Unable to hash binary"))
+            .ok();
+        self
+    }
+
+    ///Marks the session as finished now
+    #[must_use]
+    pub fn finish(mut self) -> Self {
+        self.finished_at = Utc::now();
+        self
+    }
+
+    ///Turns the provenance into tags, to go into exports with the session's other tags
+    #[must_use]
+    pub fn to_tags(&self) -> Tags {
+        let mut tags = Tags::from([
+            (SESSION_ID_TAG.to_string(), self.session_id.clone()),
+            (COMMAND_TAG.to_string(), self.command.clone()),
+            (STARTED_AT_TAG.to_string(), self.started_at.to_rfc3339_opts(SecondsFormat::AutoSi, true)),
+            (FINISHED_AT_TAG.to_string(), self.finished_at.to_rfc3339_opts(SecondsFormat::AutoSi, true)),
+        ]);
+        if let Some(binary_hash) = &self.binary_hash {
+            tags.insert(BINARY_HASH_TAG.to_string(), binary_hash.clone());
+        }
+        tags
+    }
+
+    ///Reads the provenance back out of a trace's tags, if they have it
+    #[must_use]
+    pub fn from_tags(tags: &Tags) -> Option<Self> {
+        let time = |tag: &str| {
+            DateTime::parse_from_rfc3339(tags.get(tag)?)
+                .ok()
+                .map(|time| time.with_timezone(&Utc))
+        };
+
+        Some(Self {
+            session_id: tags.get(SESSION_ID_TAG)?.clone(),
+            command: tags.get(COMMAND_TAG)?.clone(),
+            binary_hash: tags.get(BINARY_HASH_TAG).cloned(),
+            started_at: time(STARTED_AT_TAG)?,
+            finished_at: time(FINISHED_AT_TAG)?,
+        })
+    }
+}
+
+///Quotes a binary and its arguments into one command line, which can be pasted back into a shell
+#[must_use]
+pub fn quote_command(binary: &Path, cli_args: &[String]) -> String {
+    shell_words::join(std::iter::once(binary.to_string_lossy().as_ref()).chain(cli_args.iter().map(String::as_str)))
+}
+
+///Hashes a file with SHA-256, giving back the hash in lowercase hex
+///
+/// # Errors
+///
+/// Can fail if we fail to open or read the file
+pub fn hash_file(file: impl AsRef<Path>) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut BufReader::new(File::open(file)?), &mut hasher)?; //stream it in, as binaries can be big
+
+    let mut hash = String::with_capacity(64);
+    for byte in hasher.finalize() {
+        let _ = write!(hash, "{byte:02x}"); //writing to a String can't fail
+    }
+    Ok(hash)
+}
@@ -0,0 +1,461 @@
+//! Module to make a full HTML report for one benchmark session.
+//!
+//! The normal HTML export is just a histogram, which is fine for a quick look but doesn't say what got run, where, or whether the numbers can be trusted. A [`SessionReport`] puts the command and machine details, a table of summary statistics, any [`Diagnostic`]s and [`EnvironmentWarning`]s, a histogram, and a run-sequence chart all into one styled page, along with a download link for the raw runs.
+//!
+//! If the runs measured more than their wall time (eg. CPU time or peak memory), each metric gets its own tab with its own histogram and run-sequence chart, so one page still has everything about the session.
+//!
+//! ## Example
+//! ```rust
+//! use benchmarker::report::escape_html;
+//!
+//! assert_eq!(escape_html("grep \"<a>\" & more"), "grep &quot;&lt;a&gt;&quot; &amp; more");
+//! ```
+
+use crate::{
+    bencher::{calculate_mean_standard_deviation, calculate_median_mad_iqr, calculate_trimmed_mean, calculate_winsorized_mean_standard_deviation, RunMeasurement},
+    diagnostics::{diagnose, Diagnostic},
+    environment::EnvironmentWarning,
+    format::DurationFormat,
+    io::{csv_download_link_with_tags, plot_traces_with_options, PlotKind, PlotOptions, PLOTLY_JS_URL},
+    memory::bytes_to_mebibytes,
+    provenance::Provenance,
+    stats::Statistic,
+    tags::{tags_to_string, Tags},
+};
+use chrono::{DateTime, Utc};
+use plotly::{
+    common::{Mode, Title},
+    layout::Axis,
+    Histogram, Layout, Plot, Scatter,
+};
+use std::{
+    collections::HashMap,
+    fmt::{Display, Write as _},
+    fs::File,
+    io::{self, Write},
+    path::Path,
+    time::Duration,
+};
+
+///Everything needed to make a report for one session
+#[derive(Clone, Debug)]
+pub struct SessionReport {
+    ///The name of the session - used as the title and the trace name
+    pub name: String,
+    ///A human-readable version of the command that got benchmarked
+    pub command: String,
+    ///Every measured run
+    pub measurements: Vec<RunMeasurement>,
+    ///Anything about the machine that could have skewed the results, from when the session started
+    pub environment_warnings: Vec<EnvironmentWarning>,
+    ///When the session finished
+    pub finished_at: DateTime<Utc>,
+    ///The tags on the session, like `machine=ryzen-box`
+    pub tags: Tags,
+    ///Where the session's runs came from, if we know
+    pub provenance: Option<Provenance>,
+    ///How much of each end to trim for the trimmed and winsorized means in the summary, if they should be shown at all
+    pub trim_fraction: Option<f64>,
+    ///The statistics to put in the summary instead of the usual ones, if any have been chosen
+    pub statistics: Vec<Statistic>,
+    ///How to write the times in the summary
+    pub duration_format: DurationFormat,
+}
+
+///The styles for the report - kept small so the file stays readable
+const STYLE: &str = "
+body { font-family: system-ui, sans-serif; max-width: 1100px; margin: 2em auto; padding: 0 1em; color: #222; }
+h1 { margin-bottom: 0; }
+h2 { border-bottom: 1px solid #ddd; padding-bottom: 0.2em; margin-top: 1.5em; }
+table { border-collapse: collapse; }
+th, td { text-align: left; padding: 0.3em 1em 0.3em 0; border-bottom: 1px solid #eee; }
+th { font-weight: 600; }
+code { background: #f4f4f4; padding: 0.1em 0.3em; border-radius: 3px; }
+.subtitle { color: #666; margin-top: 0.2em; }
+.warning { color: #8a5a00; }
+.chart { height: 450px; }
+.tabs { display: flex; gap: 0.3em; border-bottom: 1px solid #ddd; margin-top: 1em; }
+.tab { font: inherit; padding: 0.4em 1em; border: 1px solid #ddd; border-bottom: none; border-radius: 4px 4px 0 0; background: #f4f4f4; cursor: pointer; }
+.tab.active { background: white; font-weight: 600; }
+";
+
+///Shows one metric's tab and hides the rest, resizing its charts as plotly can't size them whilst they're hidden
+const TAB_SCRIPT: &str = "
+function showTab(id) {
+    document.querySelectorAll('.tab-panel').forEach(panel => panel.hidden = panel.id !== 'tab_' + id);
+    document.querySelectorAll('.tab').forEach(tab => tab.classList.toggle('active', tab.dataset.tab === id));
+    document.querySelectorAll('#tab_' + id + ' .js-plotly-plot').forEach(plot => Plotly.Plots.resize(plot));
+}
+";
+
+///The values of one metric for every run
+enum MetricValues {
+    ///Times in microseconds, which get plotted in whichever unit suits them
+    Time(Vec<u128>),
+    ///Amounts of memory in mebibytes
+    Mebibytes(Vec<f64>),
+}
+
+///One metric of the runs, which gets its own tab when there's more than one
+struct Metric {
+    ///The name on the tab and the axes
+    title: &'static str,
+    ///What to suffix the chart IDs with, so they stay unique
+    id: &'static str,
+    ///The values for every run which measured it
+    values: MetricValues,
+}
+
+impl Metric {
+    ///Makes the histogram and run-sequence charts for this metric, with headings at `heading` level (eg. `h2`)
+    fn charts(&self, name: &str, heading: &str, id_suffix: &str) -> String {
+        let mut html = String::new();
+        for (title, id, kind) in [
+            ("Distribution", "histogram", PlotKind::Histogram),
+            ("Run Sequence", "sequence", PlotKind::Scatter),
+        ] {
+            let plot = match &self.values {
+                MetricValues::Time(runs) => plot_traces_with_options(vec![(name.to_string(), runs.clone())], &PlotOptions {
+                    kind,
+                    ..PlotOptions::default()
+                }),
+                MetricValues::Mebibytes(values) => plot_mebibytes(name, self.title, values, kind),
+            };
+            let _ = writeln!(
+                html,
+                "<{heading}>{title}</{heading}>\n<div class=\"chart\">{}</div>",
+                plot.to_inline_html(Some(&format!("{id}{id_suffix}")))
+            );
+        }
+        html
+    }
+}
+
+///Plots amounts of memory as a histogram, or against the run index for [`PlotKind::Scatter`]
+fn plot_mebibytes(name: &str, title: &str, values: &[f64], kind: PlotKind) -> Plot {
+    let mut plot = Plot::new();
+    let axis = || Axis::new().title(Title::new(&format!("{title} (MiB)")));
+    if matches!(kind, PlotKind::Scatter) {
+        plot.add_trace(Scatter::new((0..values.len()).collect(), values.to_vec()).mode(Mode::Markers).name(name));
+        plot.set_layout(Layout::new().x_axis(Axis::new().title(Title::new("Run"))).y_axis(axis()));
+    } else {
+        plot.add_trace(Histogram::new(values.to_vec()).name(name));
+        plot.set_layout(Layout::new().x_axis(axis()));
+    }
+    plot
+}
+
+impl SessionReport {
+    ///Makes a new report, timestamped now
+    #[must_use]
+    pub fn new(
+        name: String,
+        command: String,
+        measurements: Vec<RunMeasurement>,
+        environment_warnings: Vec<EnvironmentWarning>,
+    ) -> Self {
+        Self {
+            name,
+            command,
+            measurements,
+            environment_warnings,
+            finished_at: Utc::now(),
+            tags: Tags::new(),
+            provenance: None,
+            trim_fraction: None,
+            statistics: vec![],
+            duration_format: DurationFormat::default(),
+        }
+    }
+
+    ///Sets the tags on the session
+    #[must_use]
+    pub fn with_tags(mut self, tags: Tags) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    ///Sets where the session's runs came from, which also goes into the raw data link
+    #[must_use]
+    pub fn with_provenance(mut self, provenance: Provenance) -> Self {
+        self.provenance = Some(provenance);
+        self
+    }
+
+    ///Sets how much of each end to trim (eg. `0.05` for 5%) for the trimmed and winsorized means in the summary, or [`None`] to leave them out
+    #[must_use]
+    pub const fn with_trim_fraction(mut self, trim_fraction: Option<f64>) -> Self {
+        self.trim_fraction = trim_fraction;
+        self
+    }
+
+    ///Sets the statistics to put in the summary instead of the usual ones - leaving it empty keeps the usual ones
+    #[must_use]
+    pub fn with_statistics(mut self, statistics: Vec<Statistic>) -> Self {
+        self.statistics = statistics;
+        self
+    }
+
+    ///Sets how to write the times in the summary
+    #[must_use]
+    pub const fn with_duration_format(mut self, duration_format: DurationFormat) -> Self {
+        self.duration_format = duration_format;
+        self
+    }
+
+    ///Renders the report to a standalone HTML document
+    #[must_use]
+    pub fn to_html(&self) -> String {
+        let runs: Vec<u128> = self.measurements.iter().map(|measurement| measurement.wall.as_micros()).collect();
+        let name = escape_html(&self.name);
+
+        let mut html = String::new();
+        let _ = write!(
+            html,
+            "<!doctype html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\" />\n<title>{name}</title>\n<style>{STYLE}</style>\n<script src=\"{PLOTLY_JS_URL}\"></script>\n</head>\n<body>\n<h1>{name}</h1>\n<p class=\"subtitle\">Precipice report, generated {}</p>\n",
+            self.finished_at.format("%Y-%m-%d %H:%M:%S UTC")
+        ); //writing to a String can't fail
+
+        html += "<h2>Session</h2>\n<table>\n";
+        for (label, value) in self.metadata() {
+            let _ = writeln!(html, "<tr><th>{label}</th><td>{value}</td></tr>");
+        }
+        html += "</table>\n";
+
+        html += "<h2>Summary</h2>\n<table>\n";
+        for (label, value) in self.summary(&runs) {
+            let _ = writeln!(html, "<tr><th>{label}</th><td>{value}</td></tr>");
+        }
+        html += "</table>\n";
+
+        let diagnostics = diagnose(&runs);
+        if !diagnostics.is_empty() || !self.environment_warnings.is_empty() {
+            html += "<h2>Warnings</h2>\n<ul>\n";
+            for warning in warnings(&diagnostics, &self.environment_warnings) {
+                let _ = writeln!(html, "<li class=\"warning\">{}</li>", escape_html(&warning));
+            }
+            html += "</ul>\n";
+        }
+
+        let metrics = self.metrics(&runs);
+        if let [wall] = metrics.as_slice() {
+            html += &wall.charts(&self.name, "h2", "");
+        } else {
+            html += "<h2>Metrics</h2>\n<div class=\"tabs\">\n";
+            for (i, metric) in metrics.iter().enumerate() {
+                let _ = writeln!(
+                    html,
+                    "<button class=\"tab{}\" data-tab=\"{}\" onclick=\"showTab('{}')\">{}</button>",
+                    if i == 0 { " active" } else { "" },
+                    metric.id,
+                    metric.id,
+                    metric.title
+                );
+            }
+            html += "</div>\n";
+            for (i, metric) in metrics.iter().enumerate() {
+                let suffix = if i == 0 { String::new() } else { format!("_{}", metric.id) }; //the wall time charts keep their usual IDs
+                let _ = writeln!(
+                    html,
+                    "<div class=\"tab-panel\" id=\"tab_{}\"{}>\n{}</div>",
+                    metric.id,
+                    if i == 0 { "" } else { " hidden" },
+                    metric.charts(&self.name, "h3", &suffix)
+                );
+            }
+            let _ = writeln!(html, "<script>{TAB_SCRIPT}</script>");
+        }
+
+        let mut tags = self.tags.clone();
+        tags.extend(self.provenance.iter().flat_map(Provenance::to_tags));
+        let tags = HashMap::from([(self.name.clone(), tags)]);
+        let _ = writeln!(
+            html,
+            "<h2>Raw Data</h2>\n{}",
+            csv_download_link_with_tags(&self.name, &[(self.name.clone(), runs)], &tags)
+        );
+
+        html += "</body>\n</html>\n";
+        html
+    }
+
+    ///Writes the report to a HTML file, adding the extension
+    ///
+    /// # Errors
+    ///
+    /// Can have errors if we fail to create a file or write to it
+    pub fn export(&self, file_name_input: impl AsRef<Path> + Display) -> io::Result<usize> {
+        let mut file = File::create(format!("{file_name_input}.html"))?;
+        let html = self.to_html();
+        file.write_all(html.as_bytes())?;
+        Ok(html.len())
+    }
+
+    ///Every metric that the runs measured, starting with the wall time
+    fn metrics(&self, runs: &[u128]) -> Vec<Metric> {
+        let mut metrics = vec![Metric {
+            title: "Wall Time",
+            id: "wall",
+            values: MetricValues::Time(runs.to_vec()),
+        }];
+
+        let times = |value: fn(&RunMeasurement) -> Option<Duration>| -> Vec<u128> {
+            self.measurements.iter().filter_map(value).map(|time| time.as_micros()).collect()
+        };
+        for (title, id, values) in [
+            ("User CPU Time", "cpu_user", times(|measurement| measurement.cpu_user)),
+            ("System CPU Time", "cpu_sys", times(|measurement| measurement.cpu_sys)),
+        ] {
+            if !values.is_empty() {
+                metrics.push(Metric {
+                    title,
+                    id,
+                    values: MetricValues::Time(values),
+                });
+            }
+        }
+
+        let memory: Vec<f64> = self
+            .measurements
+            .iter()
+            .filter_map(|measurement| measurement.max_rss)
+            .map(bytes_to_mebibytes)
+            .collect();
+        if !memory.is_empty() {
+            metrics.push(Metric {
+                title: "Peak Memory",
+                id: "memory",
+                values: MetricValues::Mebibytes(memory),
+            });
+        }
+
+        metrics
+    }
+
+    ///The rows for the session table - what got run, when, and where. Values are already escaped
+    fn metadata(&self) -> Vec<(&'static str, String)> {
+        let started_at = self.measurements.iter().map(|measurement| measurement.started_at).min();
+        let cpus = std::thread::available_parallelism().map_or_else(|_| "unknown".to_string(), |cpus| cpus.to_string());
+
+        let mut rows = vec![("Command", format!("<code>{}</code>", escape_html(&self.command)))];
+        if let Some(started_at) = started_at {
+            rows.push(("Started", started_at.format("%Y-%m-%d %H:%M:%S UTC").to_string()));
+        }
+        rows.push(("Finished", self.finished_at.format("%Y-%m-%d %H:%M:%S UTC").to_string()));
+        if let Some(provenance) = &self.provenance {
+            rows.push(("Session ID", format!("<code>{}</code>", escape_html(&provenance.session_id))));
+            if let Some(binary_hash) = &provenance.binary_hash {
+                rows.push(("Binary SHA-256", format!("<code>{}</code>", escape_html(binary_hash))));
+            }
+        }
+        if !self.tags.is_empty() {
+            rows.push(("Tags", escape_html(&tags_to_string(&self.tags))));
+        }
+        rows.push(("Platform", format!("{} ({})", std::env::consts::OS, std::env::consts::ARCH)));
+        rows.push(("CPUs", cpus));
+        rows.push(("Precipice Version", env!("CARGO_PKG_VERSION").to_string()));
+        rows
+    }
+
+    ///The usual rows for the summary table, when no statistics have been chosen
+    fn default_statistics(&self, runs: &[u128]) -> Vec<(String, String)> {
+        let time = |duration: Duration| escape_html(&self.duration_format.duration(duration));
+        let mut rows = vec![("Runs".to_string(), runs.len().to_string())];
+
+        if let Some((mean, standard_deviation)) = calculate_mean_standard_deviation(runs) {
+            rows.push(("Mean ± Standard Deviation".to_string(), format!("{} ± {}", time(mean), time(standard_deviation))));
+        }
+        if let Some((median, median_absolute_deviation, interquartile_range)) = calculate_median_mad_iqr(runs) {
+            rows.push(("Median ± MAD".to_string(), format!("{} ± {}", time(median), time(median_absolute_deviation))));
+            rows.push(("Interquartile Range".to_string(), time(interquartile_range)));
+        }
+        if let Some(fraction) = self.trim_fraction {
+            let percent = (fraction * 100_000.0).round() / 1_000.0; //so eg. 7% doesn't come out as 7.000000000000001%
+            if let Some(trimmed_mean) = calculate_trimmed_mean(runs, fraction) {
+                rows.push(("Trimmed Mean".to_string(), format!("{} (without the fastest and slowest {percent}%)", time(trimmed_mean))));
+            }
+            if let Some((mean, standard_deviation)) = calculate_winsorized_mean_standard_deviation(runs, fraction) {
+                rows.push((
+                    "Winsorized Mean ± SD".to_string(),
+                    format!("{} ± {} (clamping the fastest and slowest {percent}%)", time(mean), time(standard_deviation)),
+                ));
+            }
+        }
+        if let Some((min, max)) = runs.iter().min().zip(runs.iter().max()) {
+            rows.push((
+                "Min … Max".to_string(),
+                format!(
+                    "{} … {}",
+                    escape_html(&self.duration_format.micros(*min)),
+                    escape_html(&self.duration_format.micros(*max))
+                ),
+            ));
+        }
+
+        rows
+    }
+
+    ///The rows for the summary table. Values are already escaped
+    #[allow(clippy::cast_possible_truncation)]
+    fn summary(&self, runs: &[u128]) -> Vec<(String, String)> {
+        let mut rows = if self.statistics.is_empty() {
+            self.default_statistics(runs)
+        } else {
+            self.statistics
+                .iter()
+                .filter_map(|statistic| Some((statistic.label(), escape_html(&statistic.calculate(runs)?.format(&self.duration_format)))))
+                .collect()
+        };
+
+        let mean_of = |value: fn(&RunMeasurement) -> Option<Duration>| {
+            let values: Vec<Duration> = self.measurements.iter().filter_map(value).collect();
+            (!values.is_empty()).then(|| values.iter().sum::<Duration>() / values.len() as u32)
+        };
+        if let Some(cpu_user) = mean_of(|measurement| measurement.cpu_user) {
+            rows.push(("Mean User CPU Time".to_string(), escape_html(&self.duration_format.duration(cpu_user))));
+        }
+        if let Some(cpu_sys) = mean_of(|measurement| measurement.cpu_sys) {
+            rows.push(("Mean System CPU Time".to_string(), escape_html(&self.duration_format.duration(cpu_sys))));
+        }
+        if let Some(max_rss) = self.measurements.iter().filter_map(|measurement| measurement.max_rss).max() {
+            rows.push(("Peak Memory".to_string(), format!("{:.1} MiB", bytes_to_mebibytes(max_rss))));
+        }
+
+        let failed = self
+            .measurements
+            .iter()
+            .filter(|measurement| measurement.exit_status != Some(0))
+            .count();
+        if failed > 0 {
+            rows.push(("Failed Runs".to_string(), failed.to_string()));
+        }
+
+        rows
+    }
+}
+
+///Gets every warning for the report as a string - the diagnostics first, as they're about the results themselves
+fn warnings(diagnostics: &[Diagnostic], environment_warnings: &[EnvironmentWarning]) -> Vec<String> {
+    diagnostics
+        .iter()
+        .map(ToString::to_string)
+        .chain(environment_warnings.iter().map(ToString::to_string))
+        .collect()
+}
+
+///Escapes text so it can go inside HTML
+#[must_use]
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped += "&amp;",
+            '<' => escaped += "&lt;",
+            '>' => escaped += "&gt;",
+            '"' => escaped += "&quot;",
+            '\'' => escaped += "&#39;",
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
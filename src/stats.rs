@@ -0,0 +1,208 @@
+//! Module for choosing which statistics go in a summary, like `--stats mean,median,p99,max`.
+//!
+//! Each [`Statistic`] knows its name, how to parse it, and how to work itself out from some microsecond runs using the calculations in [`crate::bencher::stats`] - so the CLI summaries and the report all agree on what eg. `p99` means. CI logs can stay terse with just a mean, while reports for analysis can have every statistic.
+//!
+//! ## Example
+//! ```rust
+//! use benchmarker::stats::{parse_statistics, Statistic};
+//! use std::time::Duration;
+//!
+//! let statistics = parse_statistics("mean, p90, max, runs").unwrap();
+//! assert_eq!(statistics[1], Statistic::Percentile(90.0));
+//!
+//! let runs: Vec<u128> = (1..=10).map(|run| run * 1_000).collect();
+//! let values: Vec<String> = statistics
+//!     .iter()
+//!     .map(|statistic| format!("{}: {}", statistic.label(), statistic.calculate(&runs).unwrap()))
+//!     .collect();
+//! assert_eq!(values, ["Mean: 5.500ms", "P90: 9.100ms", "Max: 10.000ms", "Runs: 10"]);
+//!
+//! assert!(parse_statistics("mean, p101").is_err());
+//! ```
+
+use crate::{
+    bencher::stats::{
+        calculate_mean_standard_deviation, calculate_median_mad_iqr, calculate_percentiles, calculate_trimmed_mean,
+        calculate_winsorized_mean_standard_deviation, DEFAULT_TRIM_FRACTION,
+    },
+    format::DurationFormat,
+};
+use std::{
+    fmt::{Display, Formatter},
+    str::FromStr,
+    time::Duration,
+};
+
+///One statistic which can go in a summary
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Statistic {
+    ///How many runs there were - `runs`
+    Runs,
+    ///The mean - `mean`
+    Mean,
+    ///The population standard deviation - `sd`
+    StandardDeviation,
+    ///The median - `median`
+    Median,
+    ///The median absolute deviation - `mad`
+    MedianAbsoluteDeviation,
+    ///The interquartile range - `iqr`
+    InterquartileRange,
+    ///The fastest run - `min`
+    Min,
+    ///The slowest run - `max`
+    Max,
+    ///A percentile from 0 to 100, interpolating between runs - `p99`, or `p99.9`
+    Percentile(f64),
+    ///The mean without this fraction of the fastest and slowest runs - `trimmed` for the default 5%, or eg. `trimmed10`
+    TrimmedMean(f64),
+    ///The mean with this fraction of the fastest and slowest runs clamped to the ones left - `winsorized` for the default 5%, or eg. `winsorized10`
+    WinsorizedMean(f64),
+}
+
+///The value of a [`Statistic`] for some runs
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StatisticValue {
+    ///A number of runs
+    Count(usize),
+    ///A time
+    Time(Duration),
+}
+
+impl StatisticValue {
+    ///Writes the value, with times written in `format`
+    #[must_use]
+    pub fn format(&self, format: &DurationFormat) -> String {
+        match self {
+            Self::Count(count) => count.to_string(),
+            Self::Time(time) => format.duration(*time),
+        }
+    }
+}
+
+impl Display for StatisticValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.format(&DurationFormat::default()))
+    }
+}
+
+///Turns a fraction into a percentage to show, so eg. `0.07` doesn't come out as `7.000000000000001`
+fn percent(fraction: f64) -> f64 {
+    (fraction * 100_000.0).round() / 1_000.0
+}
+
+impl Statistic {
+    ///The name to show next to the statistic in a summary, like `Trimmed Mean (5%)`
+    #[must_use]
+    pub fn label(&self) -> String {
+        match self {
+            Self::Runs => "Runs".to_string(),
+            Self::Mean => "Mean".to_string(),
+            Self::StandardDeviation => "Standard Deviation".to_string(),
+            Self::Median => "Median".to_string(),
+            Self::MedianAbsoluteDeviation => "MAD".to_string(),
+            Self::InterquartileRange => "IQR".to_string(),
+            Self::Min => "Min".to_string(),
+            Self::Max => "Max".to_string(),
+            Self::Percentile(percentile) => format!("P{percentile}"),
+            Self::TrimmedMean(fraction) => format!("Trimmed Mean ({}%)", percent(*fraction)),
+            Self::WinsorizedMean(fraction) => format!("Winsorized Mean ({}%)", percent(*fraction)),
+        }
+    }
+
+    ///Works out the statistic from some microsecond runs, or [`None`] if there aren't any
+    #[allow(clippy::cast_precision_loss)]
+    #[must_use]
+    pub fn calculate(&self, runs: &[u128]) -> Option<StatisticValue> {
+        let time = match self {
+            Self::Runs => return Some(StatisticValue::Count(runs.len())),
+            Self::Mean => calculate_mean_standard_deviation(runs)?.0,
+            Self::StandardDeviation => calculate_mean_standard_deviation(runs)?.1,
+            Self::Median => calculate_median_mad_iqr(runs)?.0,
+            Self::MedianAbsoluteDeviation => calculate_median_mad_iqr(runs)?.1,
+            Self::InterquartileRange => calculate_median_mad_iqr(runs)?.2,
+            Self::Min => Duration::from_micros(u64::try_from(*runs.iter().min()?).unwrap_or(u64::MAX)),
+            Self::Max => Duration::from_micros(u64::try_from(*runs.iter().max()?).unwrap_or(u64::MAX)),
+            Self::Percentile(percentile) => calculate_percentiles(runs, &[*percentile])?[0],
+            Self::TrimmedMean(fraction) => calculate_trimmed_mean(runs, *fraction)?,
+            Self::WinsorizedMean(fraction) => calculate_winsorized_mean_standard_deviation(runs, *fraction)?.0,
+        };
+        Some(StatisticValue::Time(time))
+    }
+}
+
+impl Display for Statistic {
+    ///Writes the statistic the same way it gets parsed
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Runs => write!(f, "runs"),
+            Self::Mean => write!(f, "mean"),
+            Self::StandardDeviation => write!(f, "sd"),
+            Self::Median => write!(f, "median"),
+            Self::MedianAbsoluteDeviation => write!(f, "mad"),
+            Self::InterquartileRange => write!(f, "iqr"),
+            Self::Min => write!(f, "min"),
+            Self::Max => write!(f, "max"),
+            Self::Percentile(percentile) => write!(f, "p{percentile}"),
+            Self::TrimmedMean(fraction) => write!(f, "trimmed{}", percent(*fraction)),
+            Self::WinsorizedMean(fraction) => write!(f, "winsorized{}", percent(*fraction)),
+        }
+    }
+}
+
+///Parses the percentage after `trimmed` or `winsorized`, which has to leave some runs in the middle
+fn parse_trim(s: &str, percentage: &str) -> Result<f64, String> {
+    if percentage.is_empty() {
+        return Ok(DEFAULT_TRIM_FRACTION);
+    }
+    let percentage: f64 = percentage.parse().map_err(|e| format!("{s} should end in a percentage: {e}"))?;
+    if (0.0..50.0).contains(&percentage) {
+        Ok(percentage / 100.0)
+    } else {
+        Err(format!("{s} should trim at least 0% and less than 50%, as it gets trimmed from both ends"))
+    }
+}
+
+impl FromStr for Statistic {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.trim().to_lowercase();
+        Ok(match lower.as_str() {
+            "runs" | "n" => Self::Runs,
+            "mean" | "avg" => Self::Mean,
+            "sd" | "stddev" | "std" => Self::StandardDeviation,
+            "median" => Self::Median,
+            "mad" => Self::MedianAbsoluteDeviation,
+            "iqr" => Self::InterquartileRange,
+            "min" => Self::Min,
+            "max" => Self::Max,
+            _ => {
+                if let Some(percentage) = lower.strip_prefix("trimmed") {
+                    Self::TrimmedMean(parse_trim(s, percentage)?)
+                } else if let Some(percentage) = lower.strip_prefix("winsorized") {
+                    Self::WinsorizedMean(parse_trim(s, percentage)?)
+                } else if let Some(percentile) = lower.strip_prefix('p') {
+                    let percentile: f64 = percentile.parse().map_err(|e| format!("{s} should be like p99: {e}"))?;
+                    if !(0.0..=100.0).contains(&percentile) {
+                        return Err(format!("{s} should be a percentile from p0 to p100"));
+                    }
+                    Self::Percentile(percentile)
+                } else {
+                    return Err(format!(
+                        "{s} isn't a statistic - try runs, mean, sd, median, mad, iqr, min, max, a percentile like p99, trimmed or winsorized"
+                    ));
+                }
+            }
+        })
+    }
+}
+
+///Parses a comma-separated list of statistics, like `mean,median,p99,max`
+///
+/// # Errors
+///
+/// Fails if any of them isn't a statistic
+pub fn parse_statistics(s: &str) -> Result<Vec<Statistic>, String> {
+    s.split(',').filter(|statistic| !statistic.trim().is_empty()).map(str::parse).collect()
+}
@@ -0,0 +1,506 @@
+//! Module to deal with benchmark suites - a list of named benchmarks with some shared defaults, read from one TOML file.
+//!
+//! A [`Suite`] doesn't run anything itself - each [`SuiteEntry`] gives you a [`Runner`] which you start and read from however you like (eg. with a progress bar in a CLI, or from a queue in a GUI), then you hand all of the results back to [`Suite::export`].
+//!
+//! ## Example File
+//! ```toml
+//! name = "my_project"
+//!
+//! [defaults]
+//! runs = 500
+//! warmup = 2
+//! env = { RUST_LOG = "warn" }
+//!
+//! [profile.quick] # picked with --profile quick
+//! runs = 20
+//! warmup = 0
+//!
+//! [profile.ci]
+//! inherits = "quick" # starts from the quick profile rather than just the defaults
+//! runs = 100
+//! export = "html"
+//!
+//! [[entry]]
+//! name = "hello"
+//! binary = "/bin/echo"
+//! cli_args = ["hello", "world"]
+//! env = { RUST_LOG = "debug", LANG = "C" } # on top of the defaults
+//!
+//! [[entry]]
+//! name = "sleep"
+//! binary = "/bin/sleep"
+//! cli_args = ["0.01"]
+//! runs = 50
+//! parameters = { seconds = 0.01 }
+//!
+//! [[entry]]
+//! name = "sleep_longer"
+//! binary = "/bin/sleep"
+//! cli_args = ["0.02"]
+//! runs = 50
+//! parameters = { seconds = 0.02 } # recorded as tags, so exports can plot both entries against it
+//! ```
+//!
+//! ## Example
+//! ```rust
+//! use benchmarker::suite::Suite;
+//!
+//! let suite = Suite::from_toml(r#"
+//!     [defaults]
+//!     runs = 500
+//!     warmup = 2
+//!     env = { LANG = "C", RUST_LOG = "warn" }
+//!
+//!     [profile.quick]
+//!     runs = 20
+//!
+//!     [profile.ci]
+//!     inherits = "quick"
+//!     export = "html"
+//!
+//!     [[entry]]
+//!     name = "hello"
+//!     binary = "/bin/echo"
+//!     env = { RUST_LOG = "debug" }
+//! "#).unwrap();
+//!
+//! let env = suite.entries[0].env(&suite.defaults);
+//! assert_eq!((env["LANG"].as_str(), env["RUST_LOG"].as_str()), ("C", "debug")); //the entry wins
+//!
+//! let ci = suite.clone().with_profile("ci").unwrap();
+//! assert_eq!((ci.defaults.runs, ci.defaults.warmup), (20, 2));
+//! assert!(ci.defaults.export.is_some());
+//!
+//! assert!(suite.with_profile("thorough").is_err());
+//! ```
+
+use crate::{
+    bencher::{ramdisk::RamCopyOptions, PipelineStage, Runner, DEFAULT_RUNS},
+    io::ExportType,
+    matrix::ComparisonMatrix,
+    tags::{tags_to_string, Tags, TraceTags},
+};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt::{Display, Formatter},
+    fs::read_to_string,
+    io,
+    path::{Path, PathBuf},
+    sync::mpsc::Receiver,
+    time::Duration,
+};
+
+///Default name for a suite if the file doesn't provide one - used as the file name for the combined export
+pub const DEFAULT_SUITE_NAME: &str = "precipice_suite";
+
+///Settings shared between every entry in a [`Suite`], which each entry can override
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SuiteDefaults {
+    ///The number of runs for each entry
+    pub runs: usize,
+    ///The number of warmup runs for each entry
+    pub warmup: u8,
+    ///Whether or not to print the initial run of each entry
+    pub print_initial: bool,
+    ///How long a single run of each entry can take in milliseconds, before it gets killed and skipped
+    pub timeout_ms: Option<u64>,
+    ///Whether or not to randomise the size of the environment for each run of each entry
+    pub randomize_environment: bool,
+    ///Whether or not to copy each binary to a RAM-backed directory and run it from there
+    pub copy_to_ram: bool,
+    ///The RAM-backed directory to copy to, if the platform doesn't have a default
+    pub ram_dir: Option<PathBuf>,
+    ///How to export the results, if not given on the command line
+    pub export: Option<ExportType>,
+    ///Environment variables to set for every run of each entry
+    pub env: BTreeMap<String, String>,
+}
+
+impl Default for SuiteDefaults {
+    fn default() -> Self {
+        Self {
+            runs: DEFAULT_RUNS,
+            warmup: 0,
+            print_initial: false,
+            timeout_ms: None,
+            randomize_environment: false,
+            copy_to_ram: false,
+            ram_dir: None,
+            export: None,
+            env: BTreeMap::new(),
+        }
+    }
+}
+
+///Named settings in a [`Suite`] which override its [`SuiteDefaults`] when picked with [`Suite::with_profile`] - eg. a `quick` profile with fewer runs for local checks, and a `thorough` one for releases. Entries still override them like they override the defaults
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SuiteProfile {
+    ///Another profile to start from, rather than just the defaults
+    pub inherits: Option<String>,
+    ///The number of runs for each entry
+    pub runs: Option<usize>,
+    ///The number of warmup runs for each entry
+    pub warmup: Option<u8>,
+    ///Whether or not to print the initial run of each entry
+    pub print_initial: Option<bool>,
+    ///How long a single run of each entry can take in milliseconds, before it gets killed and skipped
+    pub timeout_ms: Option<u64>,
+    ///Whether or not to randomise the size of the environment for each run of each entry
+    pub randomize_environment: Option<bool>,
+    ///Whether or not to copy each binary to a RAM-backed directory and run it from there
+    pub copy_to_ram: Option<bool>,
+    ///The RAM-backed directory to copy to, if the platform doesn't have a default
+    pub ram_dir: Option<PathBuf>,
+    ///How to export the results, if not given on the command line
+    pub export: Option<ExportType>,
+}
+
+impl SuiteProfile {
+    ///Overrides the defaults with anything this profile sets
+    fn apply(&self, defaults: &mut SuiteDefaults) {
+        if let Some(runs) = self.runs {
+            defaults.runs = runs;
+        }
+        if let Some(warmup) = self.warmup {
+            defaults.warmup = warmup;
+        }
+        if let Some(print_initial) = self.print_initial {
+            defaults.print_initial = print_initial;
+        }
+        if let Some(timeout_ms) = self.timeout_ms {
+            defaults.timeout_ms = Some(timeout_ms);
+        }
+        if let Some(randomize_environment) = self.randomize_environment {
+            defaults.randomize_environment = randomize_environment;
+        }
+        if let Some(copy_to_ram) = self.copy_to_ram {
+            defaults.copy_to_ram = copy_to_ram;
+        }
+        if let Some(ram_dir) = &self.ram_dir {
+            defaults.ram_dir = Some(ram_dir.clone());
+        }
+        if let Some(export) = self.export {
+            defaults.export = Some(export);
+        }
+    }
+}
+
+///One named benchmark in a [`Suite`]. Any settings which aren't provided come from the [`SuiteDefaults`]
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SuiteEntry {
+    ///The label for the entry - this is used as the trace name, and as part of the file name for the per-entry export
+    pub name: String,
+    ///The binary to run
+    pub binary: PathBuf,
+    ///The args to pass to the binary
+    #[serde(default)]
+    pub cli_args: Vec<String>,
+    ///The number of runs, if different to the default
+    pub runs: Option<usize>,
+    ///The number of warmup runs, if different to the default
+    pub warmup: Option<u8>,
+    ///Whether or not to print the initial run, if different to the default
+    pub print_initial: Option<bool>,
+    ///The timeout for a single run in milliseconds, if different to the default
+    pub timeout_ms: Option<u64>,
+    ///Whether or not to randomise the size of the environment, if different to the default
+    pub randomize_environment: Option<bool>,
+    ///Commands to pipe the output of the binary through, in order
+    #[serde(default)]
+    pub pipe_into: Vec<PipelineStage>,
+    ///Whether or not to copy the binary to a RAM-backed directory, if different to the default
+    pub copy_to_ram: Option<bool>,
+    ///Data files to copy next to the binary, if it gets copied to a RAM-backed directory
+    #[serde(default)]
+    pub ram_data_files: Vec<PathBuf>,
+    ///Environment variables to set for every run, on top of the default ones - if both set the same variable, this one wins
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    ///The parameters of this entry, if it's part of a sweep - like `{ threads = 4 }`. These get recorded on the trace with [`crate::parameters::parameter_tags`]
+    #[serde(default, deserialize_with = "deserialize_parameters")]
+    pub parameters: Tags,
+    ///The row for this entry in the comparison matrix (see [`Suite::comparison_matrix`]), so entries which run the same thing with different parameters share a row. Defaults to the binary's file name
+    pub command: Option<String>,
+}
+
+///Reads the parameters of a [`SuiteEntry`], turning numbers and booleans into strings so they don't need quoting in the TOML
+fn deserialize_parameters<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Tags, D::Error> {
+    let parameters = std::collections::BTreeMap::<String, toml::Value>::deserialize(deserializer)?;
+    Ok(parameters
+        .into_iter()
+        .map(|(name, value)| {
+            let value = match value {
+                toml::Value::String(value) => value,
+                value => value.to_string(),
+            };
+            (name, value)
+        })
+        .collect())
+}
+
+impl SuiteEntry {
+    ///Gets the number of runs for this entry, falling back to the defaults
+    #[must_use]
+    pub fn runs(&self, defaults: &SuiteDefaults) -> usize {
+        self.runs.unwrap_or(defaults.runs)
+    }
+
+    ///Gets the row for this entry in the comparison matrix - the `command` if there is one, or else the binary's file name
+    #[must_use]
+    pub fn command(&self) -> String {
+        self.command.clone().unwrap_or_else(|| {
+            self.binary
+                .file_name()
+                .map_or_else(|| self.binary.display().to_string(), |name| name.to_string_lossy().to_string())
+        })
+    }
+
+    ///Gets the column for this entry in the comparison matrix - its parameters, or `default` if it doesn't have any
+    #[must_use]
+    pub fn matrix_column(&self) -> String {
+        if self.parameters.is_empty() {
+            "default".to_string()
+        } else {
+            tags_to_string(&self.parameters)
+        }
+    }
+
+    ///Makes a [`Runner`] for this entry, filling in anything missing from the defaults
+    #[must_use]
+    pub fn runner(&self, defaults: &SuiteDefaults, stop_rx: Option<Receiver<()>>) -> Runner {
+        Runner::new(
+            self.binary.clone(),
+            self.cli_args.clone(),
+            self.runs(defaults),
+            stop_rx,
+            self.warmup.unwrap_or(defaults.warmup),
+            self.print_initial.unwrap_or(defaults.print_initial),
+        )
+        .timeout(self.timeout_ms.or(defaults.timeout_ms).map(Duration::from_millis))
+        .randomize_environment(
+            self.randomize_environment
+                .unwrap_or(defaults.randomize_environment),
+        )
+        .pipeline(self.pipe_into.clone())
+        .ram_copy(
+            self.copy_to_ram
+                .unwrap_or(defaults.copy_to_ram)
+                .then(|| RamCopyOptions {
+                    directory: defaults.ram_dir.clone(),
+                    data_files: self.ram_data_files.clone(),
+                }),
+        )
+        .env(self.env(defaults))
+    }
+
+    ///Gets the environment variables for this entry - the defaults, with any this entry sets on top
+    #[must_use]
+    pub fn env(&self, defaults: &SuiteDefaults) -> BTreeMap<String, String> {
+        let mut env = defaults.env.clone();
+        env.extend(self.env.clone());
+        env
+    }
+}
+
+///A list of named benchmarks with shared defaults, normally read from a TOML file using [`Suite::from_file`]
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Suite {
+    ///The name of the suite - used as the file name for the combined export
+    #[serde(default = "default_suite_name")]
+    pub name: String,
+    ///Settings shared between all of the entries
+    #[serde(default)]
+    pub defaults: SuiteDefaults,
+    ///Named profiles which can override the defaults, like `[profile.quick]`
+    #[serde(rename = "profile", default)]
+    pub profiles: BTreeMap<String, SuiteProfile>,
+    ///All of the benchmarks to run
+    #[serde(rename = "entry", default)]
+    pub entries: Vec<SuiteEntry>,
+}
+
+///Gets the default suite name as an owned [`String`] for `serde`
+fn default_suite_name() -> String {
+    DEFAULT_SUITE_NAME.to_string()
+}
+
+///Anything that can go wrong reading a [`Suite`] file
+#[derive(Debug)]
+pub enum SuiteError {
+    ///We couldn't read the file
+    Io(io::Error),
+    ///The file wasn't valid TOML, or didn't match the suite layout
+    Parse(toml::de::Error),
+    ///Two entries have the same name, so their traces and files would clash
+    DuplicateEntry(String),
+    ///A profile which doesn't exist got picked or inherited from
+    UnknownProfile(String),
+    ///A profile ends up inheriting from itself
+    ProfileCycle(String),
+}
+
+impl Display for SuiteError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "Unable to read suite file: {e}"),
+            Self::Parse(e) => write!(f, "Unable to parse suite file: {e}"),
+            Self::DuplicateEntry(name) => write!(f, "Suite has more than one entry called {name:?}"),
+            Self::UnknownProfile(name) => write!(f, "Suite doesn't have a profile called {name:?}"),
+            Self::ProfileCycle(name) => write!(f, "Profile {name:?} ends up inheriting from itself"),
+        }
+    }
+}
+
+impl std::error::Error for SuiteError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::Parse(e) => Some(e),
+            Self::DuplicateEntry(_) | Self::UnknownProfile(_) | Self::ProfileCycle(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for SuiteError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<toml::de::Error> for SuiteError {
+    fn from(value: toml::de::Error) -> Self {
+        Self::Parse(value)
+    }
+}
+
+impl Suite {
+    ///Parses a suite from a TOML string
+    ///
+    /// # Errors
+    ///
+    /// Can fail if the TOML is invalid, or if two entries share a name
+    pub fn from_toml(contents: &str) -> Result<Self, SuiteError> {
+        let suite: Self = toml::from_str(contents)?;
+
+        let mut seen = vec![];
+        for entry in &suite.entries {
+            if seen.contains(&&entry.name) {
+                return Err(SuiteError::DuplicateEntry(entry.name.clone()));
+            }
+            seen.push(&entry.name);
+        }
+
+        Ok(suite)
+    }
+
+    ///Reads a suite from a TOML file
+    ///
+    /// # Errors
+    ///
+    /// Can fail if we fail to read the file, or if [`Suite::from_toml`] fails
+    #[instrument]
+    pub fn from_file(file: impl AsRef<Path> + std::fmt::Debug) -> Result<Self, SuiteError> {
+        Self::from_toml(&read_to_string(file)?)
+    }
+
+    ///Applies a profile (and any profiles it inherits from) on top of the defaults
+    ///
+    /// # Errors
+    ///
+    /// Can fail if the profile (or one it inherits from) doesn't exist, or if it ends up inheriting from itself
+    pub fn with_profile(mut self, name: &str) -> Result<Self, SuiteError> {
+        let mut chain: Vec<&SuiteProfile> = vec![];
+        let mut seen: Vec<&str> = vec![];
+        let mut next = Some(name);
+        while let Some(name) = next {
+            if seen.contains(&name) {
+                return Err(SuiteError::ProfileCycle(name.to_string()));
+            }
+            let profile = self
+                .profiles
+                .get(name)
+                .ok_or_else(|| SuiteError::UnknownProfile(name.to_string()))?;
+            seen.push(name);
+            chain.push(profile);
+            next = profile.inherits.as_deref();
+        }
+
+        let mut defaults = self.defaults.clone();
+        for profile in chain.into_iter().rev() {
+            //start from the furthest ancestor, so closer profiles win
+            profile.apply(&mut defaults);
+        }
+        self.defaults = defaults;
+
+        Ok(self)
+    }
+
+    ///Gets the file name (without extension) for the per-entry export of an entry
+    #[must_use]
+    pub fn entry_file_name(&self, entry_name: &str) -> String {
+        format!("{}_{entry_name}", self.name)
+    }
+
+    ///Makes a comparison matrix of the results of a suite, with a row for each command and a column for each set of parameters (see [`SuiteEntry::command`] and [`SuiteEntry::matrix_column`]).
+    ///
+    /// `results` should be pairs of entry names and their microsecond timings, like for [`Suite::export`]. Results which don't match an entry get skipped
+    #[must_use]
+    pub fn comparison_matrix(&self, results: &[(String, Vec<u128>)]) -> ComparisonMatrix {
+        ComparisonMatrix::new(results.iter().filter_map(|(name, runs)| {
+            let entry = self.entries.iter().find(|entry| &entry.name == name)?;
+            Some((entry.command(), entry.matrix_column(), runs.clone()))
+        }))
+    }
+
+    ///Exports the results of a suite - one file per entry, and then one combined file with every trace. Entries without any results are skipped.
+    ///
+    /// `results` should be pairs of entry names and their microsecond timings. Returns the total number of bytes written.
+    ///
+    /// # Errors
+    ///
+    /// Can have errors if we fail to create a file or write to it
+    #[instrument(skip(self, results))]
+    pub fn export(
+        &self,
+        results: Vec<(String, Vec<u128>)>,
+        export_ty: ExportType,
+        combined_file_name: Option<String>,
+    ) -> io::Result<usize> {
+        self.export_with_tags(results, &TraceTags::new(), export_ty, combined_file_name)
+    }
+
+    ///Exports the results of a suite like [`Suite::export`], with the tags (and provenance) for each entry by name
+    ///
+    /// # Errors
+    ///
+    /// Can have errors if we fail to create a file or write to it
+    #[instrument(skip(self, results, tags))]
+    pub fn export_with_tags(
+        &self,
+        results: Vec<(String, Vec<u128>)>,
+        tags: &TraceTags,
+        export_ty: ExportType,
+        combined_file_name: Option<String>,
+    ) -> io::Result<usize> {
+        let results: Vec<_> = results
+            .into_iter()
+            .filter(|(_, runs)| !runs.is_empty())
+            .collect(); //no point exporting an empty trace
+
+        let mut written = 0;
+        for (name, runs) in &results {
+            let entry_tags = tags.get(name).cloned().unwrap_or_default();
+            written += export_ty.export_with_tags(name.clone(), runs.clone(), self.entry_file_name(name), &entry_tags)?; //per-entry files
+        }
+        written += export_ty.export_traces_with_tags(
+            combined_file_name.unwrap_or_else(|| self.name.clone()),
+            results,
+            tags,
+        )?; //and the combined file
+
+        Ok(written)
+    }
+}
@@ -0,0 +1,246 @@
+//! Module to count the syscalls a binary makes, by running a few extra runs under `strace -c`.
+//!
+//! For I/O-heavy binaries the wall time can be too noisy to tell two builds apart, but the number of syscalls (and how long was spent in them) is much steadier. The counted runs are separate from the measured runs, as `strace` slows the binary down a lot. Anything the binary spawns gets counted too.
+//!
+//! This needs `strace`, so it only works on Linux.
+//!
+//! ## Example
+//! ```rust
+//! use benchmarker::syscalls::SyscallSummary;
+//!
+//! let strace = "\
+//! % time     seconds  usecs/call     calls    errors syscall
+//! ------ ----------- ----------- --------- --------- ----------------
+//!  60.00    0.000060          15         4           read
+//!  40.00    0.000040          10         4         2 openat
+//! ------ ----------- ----------- --------- --------- ----------------
+//! 100.00    0.000100          12         8         2 total
+//! ";
+//! let mut summary = SyscallSummary::default();
+//! summary.add_strace_output(strace);
+//! assert_eq!(summary.syscalls["openat"].errors, 2);
+//! assert_eq!(summary.total_calls(), 8);
+//! ```
+
+use crate::{
+    bencher::template::{needs_directory, RunScope},
+    error::Error,
+    io::{write_export, write_json, write_plot, write_vega_lite, ExportType, VEGA_LITE_SCHEMA},
+};
+use plotly::{
+    common::Title,
+    layout::Axis,
+    Bar, Layout, Plot,
+};
+use rand::Rng;
+use serde_json::json;
+use std::{
+    collections::BTreeMap,
+    env,
+    fmt::Write as _,
+    fs::{read_to_string, remove_file},
+    io,
+    path::Path,
+    process::{Command, Stdio},
+    time::Duration,
+};
+
+///How many times one syscall got made, summed over every counted run
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SyscallStats {
+    ///How many times it got called
+    pub calls: u64,
+    ///How many of those calls failed
+    pub errors: u64,
+    ///How long was spent in it
+    pub time: Duration,
+}
+
+///The syscalls from every counted run
+#[derive(Clone, Debug, Default)]
+pub struct SyscallSummary {
+    ///How many runs got counted
+    pub runs: usize,
+    ///The stats for each syscall, by name
+    pub syscalls: BTreeMap<String, SyscallStats>,
+}
+
+impl SyscallSummary {
+    ///Adds the table printed by `strace -c`. This doesn't count as a run, as the table could be from anywhere
+    pub fn add_strace_output(&mut self, output: &str) {
+        //the rows are between the two lines of dashes, and the total is after the second one
+        let rows = output
+            .lines()
+            .skip_while(|line| !line.starts_with("---"))
+            .skip(1)
+            .take_while(|line| !line.starts_with("---"));
+
+        for row in rows {
+            //% time, seconds, usecs/call, calls, errors (which can be empty), syscall
+            let columns: Vec<&str> = row.split_whitespace().collect();
+            let (Some(name), Some(seconds), Some(calls)) = (columns.last(), columns.get(1), columns.get(3)) else {
+                continue;
+            };
+            let (Ok(seconds), Ok(calls)) = (seconds.parse::<f64>(), calls.parse::<u64>()) else {
+                warn!(%row, "Unable to parse strace row");
+                continue;
+            };
+            let errors = if columns.len() >= 6 {
+                columns[4].parse().unwrap_or_default()
+            } else {
+                0
+            };
+
+            let stats = self.syscalls.entry((*name).to_string()).or_default();
+            stats.calls += calls;
+            stats.errors += errors;
+            stats.time += Duration::try_from_secs_f64(seconds).unwrap_or_default();
+        }
+    }
+
+    ///The number of calls to every syscall, summed over every counted run
+    #[must_use]
+    pub fn total_calls(&self) -> u64 {
+        self.syscalls.values().map(|stats| stats.calls).sum()
+    }
+
+    ///Divides by the number of runs, to get a per-run value - or gives back the value if we don't have any runs
+    #[allow(clippy::cast_precision_loss)]
+    fn per_run(&self, value: f64) -> f64 {
+        value / self.runs.max(1) as f64
+    }
+}
+
+///Runs the binary `runs` times under `strace -c`, adding up the syscalls. The CLI args can use the same placeholders as a [`crate::bencher::Runner`]
+///
+/// # Errors
+///
+/// Can fail if `strace` isn't installed or fails, or we fail to read its output
+#[instrument]
+pub fn count_syscalls(binary: &Path, cli_args: &[String], runs: usize) -> io::Result<SyscallSummary> {
+    let needs_directory = cli_args.iter().any(|arg| needs_directory(arg));
+    let mut summary = SyscallSummary::default();
+
+    for run_index in 0..runs {
+        let scope = RunScope::new(run_index, needs_directory)?;
+        let output_file = env::temp_dir().join(format!(
+            "precipice_strace_{}_{:x}.txt",
+            std::process::id(),
+            rand::thread_rng().gen::<u32>()
+        ));
+
+        let output = Command::new("strace")
+            .args(["-c", "-f", "-o"]) //count, and follow anything it spawns
+            .arg(&output_file)
+            .arg("--")
+            .arg(binary)
+            .args(cli_args.iter().map(|arg| scope.expand(arg)))
+            .stdin(Stdio::null())
+            .stdout(Stdio::null()) //the binary's output would just get in the way
+            .output()
+            .map_err(|e| {
+                if e.kind() == io::ErrorKind::NotFound {
+                    io::Error::new(e.kind(), "unable to find strace - is it installed?")
+                } else {
+                    e
+                }
+            })?;
+
+        let table = read_to_string(&output_file);
+        let _ = remove_file(&output_file); //if it isn't there, there's nothing to clean up
+        if !output.status.success() {
+            return Err(io::Error::other(format!(
+                "strace failed with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        summary.add_strace_output(&table?);
+        summary.runs += 1;
+        trace!(%run_index, "Counted syscalls");
+    }
+
+    Ok(summary)
+}
+
+///Exports the syscalls - either a CSV with one row per syscall, or a HTML or Vega-Lite bar chart of the calls. Everything is per run, and the extension gets added to the file name
+///
+/// # Errors
+///
+/// Can have errors if we fail to create a file or write to it
+#[instrument(skip(summary))]
+#[allow(clippy::cast_precision_loss)]
+pub fn export_syscalls(
+    summary: &SyscallSummary,
+    export_ty: ExportType,
+    export_file_name: String,
+) -> Result<usize, Error> {
+    let mut syscalls: Vec<(&String, &SyscallStats)> = summary.syscalls.iter().collect();
+    syscalls.sort_by_key(|(_, stats)| std::cmp::Reverse(stats.calls)); //most called first
+
+    match export_ty {
+        ExportType::CSV => {
+            let mut to_be_written = String::from("syscall,calls_per_run,errors_per_run,time_per_run_us\n");
+            for (name, stats) in syscalls {
+                let _ = writeln!(
+                    to_be_written,
+                    "{name},{:.2},{:.2},{:.2}",
+                    summary.per_run(stats.calls as f64),
+                    summary.per_run(stats.errors as f64),
+                    summary.per_run(stats.time.as_secs_f64() * 1_000_000.0)
+                ); //writing to a String can't fail
+            }
+
+            write_export(&format!("{export_file_name}.csv"), to_be_written.as_bytes(), None)
+        }
+        ExportType::HTML => {
+            let (names, calls): (Vec<String>, Vec<f64>) = syscalls
+                .into_iter()
+                .map(|(name, stats)| (name.clone(), summary.per_run(stats.calls as f64)))
+                .unzip();
+
+            let mut plot = Plot::new();
+            plot.add_trace(Bar::new(names, calls).name("Calls per run"));
+            plot.set_layout(
+                Layout::new()
+                    .title(Title::new("Syscalls"))
+                    .y_axis(Axis::new().title(Title::new("Calls per run"))),
+            );
+            write_plot(&plot, export_file_name)
+        }
+        ExportType::VegaLite => {
+            let values: Vec<_> = syscalls
+                .into_iter()
+                .map(|(name, stats)| json!({"syscall": name, "calls_per_run": summary.per_run(stats.calls as f64)}))
+                .collect();
+
+            let spec = json!({
+                "$schema": VEGA_LITE_SCHEMA,
+                "title": "Syscalls",
+                "data": {"values": values},
+                "mark": "bar",
+                "encoding": {
+                    "x": {"field": "syscall", "type": "nominal", "sort": "-y", "title": "Syscall"},
+                    "y": {"field": "calls_per_run", "type": "quantitative", "title": "Calls per run"},
+                },
+            });
+            write_vega_lite(&spec, export_file_name)
+        }
+        ExportType::JSON | ExportType::Hyperfine => {
+            //hyperfine's format is only for traces, so this gets the plain JSON
+            let rows: Vec<_> = syscalls
+                .into_iter()
+                .map(|(name, stats)| {
+                    json!({
+                        "syscall": name,
+                        "calls_per_run": summary.per_run(stats.calls as f64),
+                        "errors_per_run": summary.per_run(stats.errors as f64),
+                        "time_per_run_us": summary.per_run(stats.time.as_secs_f64() * 1_000_000.0),
+                    })
+                })
+                .collect();
+            write_json(&json!({"runs": summary.runs, "syscalls": rows}), export_file_name)
+        }
+    }
+}
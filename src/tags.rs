@@ -0,0 +1,98 @@
+//! Module for free-form tags on benchmark sessions, like `commit=abc123` or `machine=ryzen-box`.
+//!
+//! Tags get stored with the session in the history and in CSV exports (see [`crate::io::traces_to_csv_with_tags`]), so sessions can be filtered and grouped by them later - eg. only exporting the traces from one machine, or merging the traces from each commit.
+//!
+//! ## Example
+//! ```rust
+//! use benchmarker::tags::{group_by_tag, matches_filter, parse_tag, parse_tags, tags_to_string};
+//!
+//! let tags = parse_tags("commit=abc123, machine=ryzen-box").unwrap();
+//! assert_eq!(tags_to_string(&tags), "commit=abc123, machine=ryzen-box");
+//!
+//! assert!(matches_filter(&tags, &[parse_tag("machine=ryzen-box").unwrap()]));
+//! assert!(!matches_filter(&tags, &[parse_tag("commit=def456").unwrap()]));
+//!
+//! let other_tags = parse_tags("commit=def456, machine=ryzen-box").unwrap();
+//! let grouped = group_by_tag(vec![("a".into(), vec![1], tags), ("b".into(), vec![2], other_tags)], "machine");
+//! assert_eq!(grouped.len(), 1);
+//! assert_eq!((grouped[0].0.as_str(), grouped[0].1.as_slice()), ("machine=ryzen-box", [1, 2].as_slice()));
+//! ```
+
+use std::collections::{BTreeMap, HashMap};
+
+///The tags on a session, sorted by key. Each key only has one value
+pub type Tags = BTreeMap<String, String>;
+
+///The tags for each trace, by trace name
+pub type TraceTags = HashMap<String, Tags>;
+
+///A trace with its tags
+pub type TaggedTrace = (String, Vec<u128>, Tags);
+
+///Parses one `key=value` tag. The key is everything before the first `=`, so the value can have an `=` in it
+///
+/// # Errors
+///
+/// Fails if there isn't an `=`, the key is empty, or there is a comma or newline in it (as tags get stored in CSV files)
+pub fn parse_tag(s: &str) -> Result<(String, String), String> {
+    let (key, value) = s.split_once('=').ok_or_else(|| format!("{s} should be like key=value"))?;
+    let key = key.trim();
+    if key.is_empty() {
+        return Err(format!("{s} needs a key before the ="));
+    }
+    if s.contains([',', '\n', '\r']) {
+        return Err(format!("{s} can't have a comma or a newline in it"));
+    }
+    Ok((key.to_string(), value.trim().to_string()))
+}
+
+///Parses a list of `key=value` tags separated by commas, eg. from a GUI text field. Empty entries get skipped, so an empty string is no tags
+///
+/// # Errors
+///
+/// Fails if any of the tags isn't a valid `key=value`
+pub fn parse_tags(s: &str) -> Result<Tags, String> {
+    s.split(',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(parse_tag)
+        .collect()
+}
+
+///Formats the tags as a comma-separated list of `key=value`, which [`parse_tags`] can read back
+#[must_use]
+pub fn tags_to_string(tags: &Tags) -> String {
+    tags.iter()
+        .map(|(key, value)| format!("{key}={value}"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+///Whether the tags have every `key=value` in the filter
+#[must_use]
+pub fn matches_filter(tags: &Tags, filter: &[(String, String)]) -> bool {
+    filter.iter().all(|(key, value)| tags.get(key) == Some(value))
+}
+
+///Merges the traces which have the same value for the tag `key` into one trace each, called `key=value`, in the order each value first turns up. Traces without that tag are left as they are
+#[must_use]
+pub fn group_by_tag(traces: Vec<TaggedTrace>, key: &str) -> Vec<TaggedTrace> {
+    let mut grouped: Vec<TaggedTrace> = Vec::with_capacity(traces.len());
+
+    for (name, mut runs, tags) in traces {
+        let Some(value) = tags.get(key) else {
+            grouped.push((name, runs, tags));
+            continue;
+        };
+
+        let group_name = format!("{key}={value}");
+        if let Some((_, group_runs, _)) = grouped.iter_mut().find(|(name, _, _)| name == &group_name) {
+            group_runs.append(&mut runs);
+        } else {
+            let group_tags = Tags::from([(key.to_string(), value.clone())]); //the other tags could be different between the traces
+            grouped.push((group_name, runs, group_tags));
+        }
+    }
+
+    grouped
+}
@@ -0,0 +1,108 @@
+//! Module for drawing runs straight in the terminal, to get a feel for their shape without exporting anything.
+//!
+//! ## Example
+//! ```rust
+//! use benchmarker::terminal::histogram_lines;
+//!
+//! let runs = [1_000, 1_000, 1_000, 1_200, 2_000];
+//! let lines = histogram_lines(&runs, 2, 10);
+//!
+//! assert_eq!(lines.len(), 2);
+//! assert!(lines[0].ends_with("██████████ 4")); //the biggest bin always gets the whole width
+//! assert!(lines[1].ends_with("██▌ 1"));
+//! ```
+
+use std::time::Duration;
+
+///How many bins to split the runs into for the CLI summary
+pub const DEFAULT_HISTOGRAM_BINS: usize = 12;
+
+///How many characters wide the biggest bar is in the CLI summary
+pub const DEFAULT_HISTOGRAM_WIDTH: usize = 40;
+
+///The characters for a bar which is partly filled in, from an eighth up to seven eighths of a character
+const PARTIAL_BLOCKS: [char; 7] = ['▏', '▎', '▍', '▌', '▋', '▊', '▉'];
+
+///The character for a whole character of bar
+const FULL_BLOCK: char = '█';
+
+///Draws a histogram of some microsecond runs, with one line for each of `bins` evenly sized bins from the fastest run to the slowest.
+///
+/// Each line has where its bin starts, a bar made of unicode blocks (so bars can be an eighth of a character apart), and how many runs were in it. The biggest bin gets a bar `width` characters long. If every run took the same time, there's only one line, and if there aren't any runs there aren't any lines
+#[allow(
+    clippy::cast_precision_loss,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss
+)]
+#[must_use]
+pub fn histogram_lines(runs: &[u128], bins: usize, width: usize) -> Vec<String> {
+    let (Some(min), Some(max)) = (runs.iter().min().copied(), runs.iter().max().copied()) else {
+        return vec![];
+    };
+    let bins = if min == max { 1 } else { bins.max(1) };
+    let bin_width = (max - min) as f64 / bins as f64;
+
+    let mut counts = vec![0_usize; bins];
+    for run in runs {
+        let bin = (((run - min) as f64 / bin_width) as usize).min(bins - 1); //the slowest run goes in the last bin, rather than one past it
+        counts[bin] += 1;
+    }
+
+    let biggest = counts.iter().max().copied().unwrap_or(1);
+    let labels: Vec<String> = (0..bins)
+        .map(|bin| {
+            let start = (bin as f64).mul_add(bin_width, min as f64).round() as u64;
+            format!("{:.3?}", Duration::from_micros(start))
+        })
+        .collect();
+    let label_width = labels.iter().map(|label| label.chars().count()).max().unwrap_or(0);
+
+    labels
+        .into_iter()
+        .zip(counts)
+        .map(|(label, count)| {
+            let eighths = (count * width * 8 + biggest / 2) / biggest; //round to the nearest eighth of a character
+            let mut bar = FULL_BLOCK.to_string().repeat(eighths / 8);
+            let partial = eighths % 8;
+            if partial > 0 {
+                bar.push(PARTIAL_BLOCKS[partial - 1]);
+            }
+            format!("{label:>label_width$} │{bar} {count}")
+        })
+        .collect()
+}
+
+///How many of the most recent runs to draw in the CLI progress bar's sparkline
+pub const DEFAULT_SPARKLINE_RUNS: usize = 30;
+
+///The characters for a sparkline, from the fastest run to the slowest
+const SPARKLINE_BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+///Draws some microsecond runs as a sparkline, with one character for each run.
+///
+/// The fastest run gets the lowest block and the slowest gets the highest, so a session slowing down shows up as the line climbing
+///
+///```rust
+/// use benchmarker::terminal::sparkline;
+///
+/// assert_eq!(sparkline(&[100, 200, 800, 100]), "▁▂█▁");
+/// assert_eq!(sparkline(&[5, 5]), "▁▁"); //nothing to scale against
+/// assert_eq!(sparkline(&[]), "");
+///```
+#[allow(
+    clippy::cast_precision_loss,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss
+)]
+#[must_use]
+pub fn sparkline(runs: &[u128]) -> String {
+    let (Some(min), Some(max)) = (runs.iter().min().copied(), runs.iter().max().copied()) else {
+        return String::new();
+    };
+    let range = (max - min).max(1) as f64;
+    let top = (SPARKLINE_BLOCKS.len() - 1) as f64;
+
+    runs.iter()
+        .map(|run| SPARKLINE_BLOCKS[((run - min) as f64 / range * top).round() as usize])
+        .collect()
+}
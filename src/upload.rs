@@ -0,0 +1,137 @@
+//! Module to post session summaries to a continuous-benchmarking service, eg. [bencher.dev](https://bencher.dev).
+//!
+//! The summary gets sent as JSON in a `POST` to whatever endpoint is configured, with an optional bearer token. It can either be the same [`HistoryEntry`] that goes into the local history, or the [Bencher Metric Format](https://bencher.dev/docs/reference/bencher-metric-format/) which bencher.dev (and anything else which speaks it) understands.
+//!
+//! The request is made with `curl`, as it is everywhere and handles TLS for us. The token is passed to `curl` on its stdin rather than as an argument, so it doesn't show up in the process list.
+//!
+//! ## Example
+//! ```rust
+//! use benchmarker::{history::HistoryEntry, upload::bencher_metric_format};
+//! use std::path::Path;
+//!
+//! let entry = HistoryEntry::new(Path::new("sort"), &[], &[90, 100, 110]).unwrap();
+//! let bmf = bencher_metric_format("sort", &entry);
+//! assert_eq!(bmf["sort"]["latency"]["value"], 100_000.0); //in nanoseconds
+//! ```
+
+use crate::history::HistoryEntry;
+use clap::ValueEnum;
+use rand::Rng;
+use serde_json::{json, Value};
+use std::{
+    env,
+    fs::{remove_file, write},
+    io::{self, Write},
+    process::{Command, Stdio},
+};
+
+///What to send to the service
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum, strum::Display)]
+pub enum UploadFormat {
+    ///The [`HistoryEntry`] for the session, as JSON
+    #[default]
+    Summary,
+    ///The Bencher Metric Format, with the mean latency and one standard deviation either side, in nanoseconds
+    Bmf,
+}
+
+///Where to send the session summaries
+#[derive(Clone, Debug)]
+pub struct UploadTarget {
+    ///The URL to `POST` to
+    pub endpoint: String,
+    ///A token to send as `Authorization: Bearer <token>`, if the service needs one
+    pub token: Option<String>,
+    ///What to send
+    pub format: UploadFormat,
+}
+
+///Makes the Bencher Metric Format JSON for a session, with one benchmark called `benchmark_name`
+#[must_use]
+pub fn bencher_metric_format(benchmark_name: &str, entry: &HistoryEntry) -> Value {
+    let mean = entry.mean_micros * 1_000.0;
+    let standard_deviation = entry.standard_deviation_micros * 1_000.0;
+
+    json!({
+        benchmark_name: {
+            "latency": {
+                "value": mean,
+                "lower_value": (mean - standard_deviation).max(0.0),
+                "upper_value": mean + standard_deviation,
+            }
+        }
+    })
+}
+
+///Posts a session summary to the target, with `benchmark_name` as the name of the benchmark if using [`UploadFormat::Bmf`]
+///
+/// # Errors
+///
+/// Can fail if `curl` isn't installed, or the request fails (including if the service responds with an error)
+#[instrument(skip(target), fields(endpoint = %target.endpoint))]
+pub fn upload_session(target: &UploadTarget, benchmark_name: &str, entry: &HistoryEntry) -> io::Result<()> {
+    let body = match target.format {
+        UploadFormat::Summary => serde_json::to_string(entry)?,
+        UploadFormat::Bmf => bencher_metric_format(benchmark_name, entry).to_string(),
+    };
+
+    curl_post(&target.endpoint, target.token.as_deref(), "application/json", &body)
+}
+
+///Posts a body to an endpoint with `curl`, with an optional bearer token
+///
+/// # Errors
+///
+/// Can fail if `curl` isn't installed, or the request fails (including if the endpoint responds with an error)
+pub(crate) fn curl_post(endpoint: &str, token: Option<&str>, content_type: &str, body: &str) -> io::Result<()> {
+    //the body goes in a file, as curl's stdin is used for the config with the token
+    let body_file = env::temp_dir().join(format!(
+        "precipice_upload_{}_{:x}",
+        std::process::id(),
+        rand::thread_rng().gen::<u32>()
+    ));
+    write(&body_file, body)?;
+
+    let result = run_curl(endpoint, token, content_type, &body_file.display().to_string());
+    let _ = remove_file(&body_file); //if it isn't there, there's nothing to clean up
+    result
+}
+
+///Runs `curl` to post the body file to the endpoint
+fn run_curl(endpoint: &str, token: Option<&str>, content_type: &str, body_file: &str) -> io::Result<()> {
+    let mut child = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--request", "POST"])
+        .args(["--header", &format!("Content-Type: {content_type}")])
+        .args(["--data-binary", &format!("@{body_file}")])
+        .args(["--config", "-"]) //read the rest of the config (ie. the token) from stdin
+        .arg(endpoint)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| {
+            if e.kind() == io::ErrorKind::NotFound {
+                io::Error::new(e.kind(), "unable to find curl - is it installed?")
+            } else {
+                e
+            }
+        })?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        if let Some(token) = token {
+            let token = token.replace('\\', "\\\\").replace('"', "\\\""); //curl config strings are in quotes, with backslash escapes
+            writeln!(stdin, "header = \"Authorization: Bearer {token}\"")?;
+        }
+    } //drop stdin, so curl knows the config is done
+
+    let output = child.wait_with_output()?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "upload failed with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )))
+    }
+}
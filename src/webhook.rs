@@ -0,0 +1,119 @@
+//! Module to fire a webhook when a session finishes or finds a regression, so a Slack/Discord/Matrix channel gets told without needing a wrapper script.
+//!
+//! The payload comes from a JSON template, with placeholders which get swapped for the details of the session:
+//!
+//! |Placeholder|Value|
+//! |---|---|
+//! |`{event}`|`finished`, or `regression` if the session found one|
+//! |`{name}`|The name of the session|
+//! |`{message}`|A one-line, human-readable summary|
+//! |`{runs}`|The number of runs|
+//! |`{mean_us}`|The mean run time in microseconds|
+//! |`{standard_deviation_us}`|The standard deviation of the run times in microseconds|
+//!
+//! The text values are escaped for use inside JSON strings, so those placeholders should go inside quotes - the numbers can go anywhere a number can. The default template ([`DEFAULT_TEMPLATE`]) sets both `text` and `content` to the message, which covers Slack, Discord and Matrix hookshot webhooks.
+//!
+//! ## Example
+//! ```rust
+//! use benchmarker::webhook::{render_payload, WebhookEvent, DEFAULT_TEMPLATE};
+//!
+//! let payload = render_payload(DEFAULT_TEMPLATE, WebhookEvent::Finished, "sort", "sort: \"done\"", &[100, 200]);
+//! assert_eq!(payload, r#"{"text": "sort: \"done\"", "content": "sort: \"done\""}"#);
+//! ```
+
+use crate::{bencher::calculate_mean_standard_deviation, upload::curl_post};
+use std::io;
+
+///The default payload template, which works for Slack, Discord and Matrix hookshot webhooks
+pub const DEFAULT_TEMPLATE: &str = r#"{"text": "{message}", "content": "{message}"}"#;
+
+///The placeholder for the event - `finished` or `regression`
+pub const EVENT_PLACEHOLDER: &str = "{event}";
+///The placeholder for the name of the session
+pub const NAME_PLACEHOLDER: &str = "{name}";
+///The placeholder for a one-line summary
+pub const MESSAGE_PLACEHOLDER: &str = "{message}";
+///The placeholder for the number of runs
+pub const RUNS_PLACEHOLDER: &str = "{runs}";
+///The placeholder for the mean in microseconds
+pub const MEAN_PLACEHOLDER: &str = "{mean_us}";
+///The placeholder for the standard deviation in microseconds
+pub const STANDARD_DEVIATION_PLACEHOLDER: &str = "{standard_deviation_us}";
+
+///Why the webhook got fired
+#[derive(Copy, Clone, Debug, PartialEq, Eq, strum::Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum WebhookEvent {
+    ///The session finished
+    Finished,
+    ///The session found a regression
+    Regression,
+}
+
+///Where to send notifications, and what to send
+#[derive(Clone, Debug)]
+pub struct Webhook {
+    ///The URL to `POST` the payload to
+    pub url: String,
+    ///The JSON template for the payload - see the module docs for the placeholders
+    pub template: String,
+}
+
+impl Webhook {
+    ///Constructor - uses [`DEFAULT_TEMPLATE`] if there isn't a template
+    #[must_use]
+    pub fn new(url: String, template: Option<String>) -> Self {
+        Self {
+            url,
+            template: template.unwrap_or_else(|| DEFAULT_TEMPLATE.to_string()),
+        }
+    }
+
+    ///Fills in the template, and posts it to the URL. This needs `curl`
+    ///
+    /// # Errors
+    ///
+    /// Can fail if `curl` isn't installed, or the request fails
+    #[instrument(skip(self, runs), fields(url = %self.url))]
+    pub fn fire(&self, event: WebhookEvent, name: &str, message: &str, runs: &[u128]) -> io::Result<()> {
+        let payload = render_payload(&self.template, event, name, message, runs);
+        curl_post(&self.url, None, "application/json", &payload)
+    }
+}
+
+///Escapes a value so it can go inside a JSON string
+fn escape_json(value: &str) -> String {
+    let quoted = serde_json::Value::from(value).to_string();
+    quoted[1..quoted.len() - 1].to_string() //take off the quotes, as the template has its own
+}
+
+///Fills in the placeholders in a payload template. This is done in one pass, so a placeholder inside a value (eg. a name with `{message}` in it) doesn't get filled in
+#[must_use]
+pub fn render_payload(template: &str, event: WebhookEvent, name: &str, message: &str, runs: &[u128]) -> String {
+    let (mean, standard_deviation) = calculate_mean_standard_deviation(runs).unwrap_or_default();
+    let values = [
+        (EVENT_PLACEHOLDER, event.to_string()),
+        (NAME_PLACEHOLDER, escape_json(name)),
+        (MESSAGE_PLACEHOLDER, escape_json(message)),
+        (RUNS_PLACEHOLDER, runs.len().to_string()),
+        (MEAN_PLACEHOLDER, format!("{:.3}", mean.as_secs_f64() * 1_000_000.0)),
+        (STANDARD_DEVIATION_PLACEHOLDER, format!("{:.3}", standard_deviation.as_secs_f64() * 1_000_000.0)),
+    ];
+
+    let mut payload = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        payload += &rest[..start];
+        rest = &rest[start..];
+        if let Some((placeholder, value)) = values.iter().find(|(placeholder, _)| rest.starts_with(placeholder)) {
+            payload += value;
+            rest = &rest[placeholder.len()..];
+        } else {
+            payload.push('{'); //just a normal brace from the JSON
+            rest = &rest[1..];
+        }
+    }
+    payload += rest;
+
+    payload
+}
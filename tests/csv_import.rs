@@ -0,0 +1,149 @@
+//! Imports the malformed CSV files in `tests/fixtures`, and checks exactly what gets reported as skipped - and that everything around it still gets imported.
+
+use benchmarker::io::{import_csv_iter, CsvProblem};
+use std::{
+    cell::Cell,
+    fs::File,
+    io::{BufReader, Cursor, Read},
+    path::PathBuf,
+    rc::Rc,
+};
+
+///A trace from a fixture, as its name and runs - the tags get checked separately
+type Trace = (String, Vec<u128>);
+
+///Gets the path to a fixture
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name)
+}
+
+///Imports every trace from a fixture, failing the test if the file can't be read at all, along with everything which got skipped as `(line, column, value)`
+fn import(name: &str) -> (Vec<Trace>, Vec<(usize, usize, String)>) {
+    let mut traces = import_csv_iter(BufReader::new(File::open(fixture(name)).unwrap()));
+    let contents = traces
+        .by_ref()
+        .map(|trace| trace.map(|(name, runs, _)| (name, runs)))
+        .collect::<Result<_, _>>()
+        .unwrap_or_else(|e| panic!("unable to read {name}: {e}"));
+    let problems = traces
+        .problems()
+        .iter()
+        .map(|CsvProblem { line, column, value, .. }| (*line, *column, value.clone()))
+        .collect();
+    (contents, problems)
+}
+
+///Imports a fixture which can't be read at all, giving back the error
+fn import_error(name: &str) -> String {
+    import_csv_iter(BufReader::new(File::open(fixture(name)).unwrap()))
+        .find_map(Result::err)
+        .unwrap_or_else(|| panic!("{name} should fail to import"))
+        .to_string()
+}
+
+///Makes the expected traces, without all of the `to_string`s
+fn traces<const N: usize>(traces: [(&str, &[u128]); N]) -> Vec<Trace> {
+    traces.into_iter().map(|(name, runs)| (name.to_string(), runs.to_vec())).collect()
+}
+
+///Makes the expected problems, without all of the `to_string`s
+fn problems<const N: usize>(problems: [(usize, usize, &str); N]) -> Vec<(usize, usize, String)> {
+    problems
+        .into_iter()
+        .map(|(line, column, value)| (line, column, value.to_string()))
+        .collect()
+}
+
+#[test]
+fn non_numeric_cells_skip_their_line() {
+    let (contents, skipped) = import("non_numeric.csv");
+    assert_eq!(contents, traces([("sort", &[10, 11, 12]), ("merge", &[7, 8])]));
+    assert_eq!(skipped, problems([(2, 3, "oops"), (2, 5, "x")]));
+}
+
+#[test]
+fn bad_tags_are_skipped_on_their_own() {
+    let (contents, skipped) = import("bad_tags.csv");
+    assert_eq!(contents, traces([("sort", &[10, 11]), ("search", &[5, 6])]));
+    assert_eq!(skipped, problems([(1, 4, "nokey"), (1, 5, "=empty")]));
+
+    let mut iter = import_csv_iter(BufReader::new(File::open(fixture("bad_tags.csv")).unwrap()));
+    let (_, _, sort_tags) = iter.next().unwrap().unwrap();
+    assert_eq!(sort_tags.into_iter().collect::<Vec<_>>(), [("machine".to_string(), "ryzen-box".to_string())]);
+    let (_, _, search_tags) = iter.next().unwrap().unwrap();
+    assert_eq!(search_tags.into_iter().collect::<Vec<_>>(), [("os".to_string(), "linux".to_string())]);
+}
+
+#[test]
+fn bad_metadata_entries_are_skipped_on_their_own() {
+    let (contents, skipped) = import("bad_meta.csv");
+    assert_eq!(contents, traces([("sort", &[1500, 2000]), ("merge", &[3000])])); //the units after the bad entry still get used
+    assert_eq!(skipped, problems([(1, 3, "oops"), (3, 3, "?")]));
+}
+
+#[test]
+fn unreadable_metadata_stops_the_import() {
+    assert_eq!(
+        import_error("newer_version.csv"),
+        r#"line 2, column 3: "format_version=3" (this CSV file is format version 3, but this version of precipice only reads up to 2 - try updating precipice)"#
+    );
+    assert_eq!(
+        import_error("invalid_version.csv"),
+        r#"line 1, column 2: "format_version=zero" (invalid format version "zero" in CSV metadata)"#
+    );
+    assert_eq!(
+        import_error("unknown_units.csv"),
+        r#"line 1, column 2: "units=fortnights" (unknown units "fortnights" in CSV metadata)"#
+    );
+}
+
+#[test]
+fn legacy_columns_skip_single_cells() {
+    let (contents, skipped) = import("legacy_columns.csv");
+    assert_eq!(
+        contents,
+        traces([("before", &[10, 11, 13]), ("after", &[12, 14]), ("third", &[1, 2])])
+    );
+    assert_eq!(skipped, problems([(3, 2, "oops"), (4, 3, "x")]));
+}
+
+///Counts how many bytes have been read from the reader inside it
+struct CountingReader<R> {
+    ///Where the bytes come from
+    inner: R,
+    ///How many bytes have been read so far, shared with the test
+    read: Rc<Cell<usize>>,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.read.set(self.read.get() + n);
+        Ok(n)
+    }
+}
+
+#[test]
+fn unversioned_files_stream() {
+    let mut csv = String::from("empty\n");
+    for i in 0..10_000 {
+        csv += &format!("trace{i},1,2,3\n");
+    }
+    let read = Rc::new(Cell::new(0));
+    let mut traces = import_csv_iter(BufReader::new(CountingReader {
+        inner: Cursor::new(csv.clone()),
+        read: read.clone(),
+    }));
+
+    assert_eq!(traces.next().unwrap().unwrap().0, "empty"); //a trace without any runs, rather than a legacy header
+    assert!(read.get() < csv.len() / 10, "read {} of {} bytes for the first trace", read.get(), csv.len());
+    assert_eq!(traces.count(), 10_000);
+}
+
+#[test]
+fn unreadable_first_lines_dont_end_the_import() {
+    let mut traces = import_csv_iter(Cursor::new("sort,oops\nsearch,x\nmerge,1\n"));
+    let names: Vec<String> = traces.by_ref().map(|trace| trace.unwrap().0).collect();
+    assert_eq!(names, ["merge"]);
+    assert_eq!(traces.problems().len(), 2);
+}
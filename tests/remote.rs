@@ -0,0 +1,84 @@
+//! Runs a real agent on an ephemeral port, and checks that a `--remote` session against it finishes by itself.
+#![cfg(unix)]
+
+use std::{
+    io::{self, BufRead, BufReader},
+    net::TcpListener,
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+    time::{Duration, Instant},
+};
+
+///The binary under test
+const PRECIPICE: &str = env!("CARGO_BIN_EXE_precipice");
+
+///How long the session gets before we call it hung - it only has 5 runs of `/bin/true`
+const SESSION_TIMEOUT: Duration = Duration::from_secs(30);
+
+///Kills the child when it goes out of scope, so a failing test doesn't leave an agent running
+struct KillOnDrop(Child);
+
+impl Drop for KillOnDrop {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+///Finds a port nothing is listening on, by letting the OS pick one
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port()
+}
+
+///Makes an empty directory for the session to export into and keep its history in
+fn scratch_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("precipice_remote_test_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn remote_session_finishes() {
+    let port = free_port();
+    let mut agent = KillOnDrop(
+        Command::new(PRECIPICE)
+            .args(["agent", "-p", &port.to_string()])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap(),
+    );
+    let mut agent_output = BufReader::new(agent.0.stdout.take().unwrap());
+    let mut line = String::new();
+    agent_output.read_line(&mut line).unwrap(); //wait for it to be listening
+    assert!(line.contains("Agent listening"), "unexpected agent output: {line:?}");
+    std::thread::spawn(move || io::copy(&mut agent_output, &mut io::sink())); //keep reading, as the agent fails to print if the pipe gets closed
+
+    let dir = scratch_dir();
+    let mut client = KillOnDrop(
+        Command::new(PRECIPICE)
+            .args(["runner-cli", "--remote", &format!("127.0.0.1:{port}"), "-b", "/bin/true", "-r", "5", "--no-history"])
+            .current_dir(&dir)
+            .env("XDG_DATA_HOME", &dir)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap(),
+    );
+
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = client.0.try_wait().unwrap() {
+            break status;
+        }
+        assert!(started.elapsed() < SESSION_TIMEOUT, "the remote session didn't finish within {SESSION_TIMEOUT:?}");
+        std::thread::sleep(Duration::from_millis(50));
+    };
+
+    assert!(status.success(), "the remote session failed with {status}");
+    let export = std::fs::read_to_string(dir.join("true_5.csv")).unwrap();
+    let trace = export.lines().find(|line| line.starts_with("true_5,")).expect("the export should have the trace");
+    assert_eq!(trace.split(',').count(), 6); //the name, then one time per run
+    let _ = std::fs::remove_dir_all(&dir);
+}